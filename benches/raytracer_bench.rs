@@ -1,6 +1,19 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use image::{ImageBuffer, Rgba};
-use raytracer::render_context::RenderContext;
+use raytracer::scalar::consts::PI;
+use raytracer::{
+    camera::Camera,
+    colour::Colour,
+    light::Light,
+    matrix::Matrix,
+    ray::Ray,
+    render_context::RenderContext,
+    scalar::Float,
+    shape::{plane::Plane, sphere::Sphere, Shape},
+    transformations::view_transform,
+    tuple::Tuple,
+    world::World,
+};
 use std::fs;
 use std::time::Duration;
 
@@ -90,8 +103,148 @@ fn benchmark_render_large(c: &mut Criterion) {
     save_render_to_png(&ctx, "render_200x200_sample.png");
 }
 
+fn benchmark_matrix_ops(c: &mut Criterion) {
+    let mut group = c.benchmark_group("matrix_ops");
+
+    let a = &(&Matrix::translation(1.0, -2.0, 3.0) * &Matrix::rotation_y(0.7))
+        * &Matrix::scaling(2.0, 0.5, 1.5);
+    let b = Matrix::shearing(0.1, 0.2, 0.3, 0.4, 0.5, 0.6);
+    let point = Tuple::point(1.0, 2.0, 3.0);
+
+    group.bench_function("multiply", |bench| {
+        bench.iter(|| black_box(&a) * black_box(&b))
+    });
+    group.bench_function("multiply_tuple", |bench| {
+        bench.iter(|| black_box(&a) * black_box(point))
+    });
+    group.bench_function("inverse", |bench| bench.iter(|| black_box(&a).inverse()));
+
+    group.finish();
+}
+
+// count spheres on a square grid over a floor, with a light above. The grid
+// always covers the same area, so more spheres means smaller ones. There's no
+// BVH until build_bvh is called.
+fn sphere_grid(count: usize, reflective: Float) -> World {
+    let mut world = World::new();
+    world.lights = vec![Light::point_light(
+        Tuple::point(-10.0, 10.0, -10.0),
+        Colour::new(1.0, 1.0, 1.0),
+    )];
+    world.add_object(Plane::new());
+
+    let side = (count as Float).sqrt().ceil() as usize;
+    let spacing = 8.0 / side as Float;
+    for i in 0..count {
+        let (x, z) = ((i % side) as Float, (i / side) as Float);
+        let mut sphere = Sphere::new();
+        sphere.set_transform(
+            &Matrix::translation(
+                (x + 0.5) * spacing - 4.0,
+                spacing * 0.4,
+                (z + 0.5) * spacing - 4.0,
+            ) * &Matrix::scaling(spacing * 0.4, spacing * 0.4, spacing * 0.4),
+        );
+        sphere.data.material.colour = Colour::new(0.2 + 0.6 * x / side as Float, 0.5, 0.8);
+        sphere.data.material.reflective = reflective;
+        world.add_object(sphere);
+    }
+    world
+}
+
+fn grid_camera(size: usize) -> Camera {
+    let mut camera = Camera::new(size, size, PI / 3.0);
+    camera.set_transform(view_transform(
+        Tuple::point(0.0, 6.0, -9.0),
+        Tuple::point(0.0, 0.0, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    ));
+    camera
+}
+
+fn benchmark_scene_complexity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scene_complexity");
+    group.sample_size(10);
+    let camera = grid_camera(64);
+
+    for count in [1, 16, 64, 256] {
+        let mut world = sphere_grid(count, 0.0);
+        world.build_bvh();
+        group.bench_with_input(BenchmarkId::new("spheres", count), &world, |b, world| {
+            b.iter(|| camera.render(black_box(world)))
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_reflection_depth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reflection_depth");
+    group.sample_size(10);
+    let camera = grid_camera(64);
+    let mut world = sphere_grid(16, 0.8);
+    world.build_bvh();
+
+    for depth in [0, 1, 2, 4, 8] {
+        world.settings.max_bounces = depth;
+        group.bench_with_input(BenchmarkId::new("bounces", depth), &world, |b, world| {
+            b.iter(|| camera.render(black_box(world)))
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_shadows(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shadows");
+    group.sample_size(10);
+    let camera = grid_camera(64);
+    let mut world = sphere_grid(64, 0.0);
+    world.build_bvh();
+
+    for shadows in [true, false] {
+        world.settings.shadows = shadows;
+        let name = if shadows { "on" } else { "off" };
+        group.bench_with_input(BenchmarkId::new("shadows", name), &world, |b, world| {
+            b.iter(|| camera.render(black_box(world)))
+        });
+    }
+    group.finish();
+}
+
+// Intersections alone, for the same rays through a world with and without its
+// BVH
+fn benchmark_bvh(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh_vs_linear");
+    let camera = grid_camera(16);
+    let rays: Vec<Ray> = (0..16)
+        .flat_map(|y| (0..16).map(move |x| (x, y)))
+        .map(|(x, y)| camera.ray_for_pixel(x, y))
+        .collect();
+
+    for count in [16, 256, 1024] {
+        let linear = sphere_grid(count, 0.0);
+        let mut with_bvh = sphere_grid(count, 0.0);
+        with_bvh.build_bvh();
+
+        for (name, world) in [("bvh", &with_bvh), ("linear", &linear)] {
+            group.bench_with_input(BenchmarkId::new(name, count), world, |b, world| {
+                b.iter(|| {
+                    rays.iter()
+                        .map(|ray| world.intersect_world(black_box(ray)).len())
+                        .sum::<usize>()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
+    benchmark_matrix_ops,
+    benchmark_scene_complexity,
+    benchmark_reflection_depth,
+    benchmark_shadows,
+    benchmark_bvh,
     benchmark_render_small,
     benchmark_render_medium,
     benchmark_render_large,
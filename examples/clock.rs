@@ -0,0 +1,49 @@
+// The chapter 4 exercise: the twelve hour marks of a clock face, found by
+// rotating the twelve o'clock position about the y axis and drawn looking down
+// on the xz plane.
+//
+//   cargo run --example clock [output.png]
+
+use raytracer::scalar::consts::PI;
+use raytracer::{camera::Canvas, colour::Colour, matrix::Matrix, scalar::Float, tuple::Tuple};
+
+const SIZE: usize = 400;
+
+fn main() {
+    let output = std::env::args().nth(1).unwrap_or("clock.png".to_string());
+    let mut canvas = Canvas::new(SIZE, SIZE);
+    let radius = SIZE as Float * 3.0 / 8.0;
+    let centre = SIZE as Float / 2.0;
+    let to_pixel = |p: Tuple| {
+        (
+            (centre + p.x * radius).round() as i64,
+            (centre - p.z * radius).round() as i64,
+        )
+    };
+
+    let twelve = Tuple::point(0.0, 0.0, 1.0);
+    for hour in 0..12 {
+        let mark = Matrix::rotation_y(hour as Float * PI / 6.0) * twelve;
+        canvas.draw_circle(to_pixel(mark), 6, Colour::WHITE);
+    }
+    // The hands at a quarter past three
+    let minute = Matrix::rotation_y(PI / 2.0) * Tuple::point(0.0, 0.0, 0.8);
+    let hour = Matrix::rotation_y(3.25 * PI / 6.0) * Tuple::point(0.0, 0.0, 0.5);
+    for hand in [minute, hour] {
+        canvas.draw_line(
+            to_pixel(Tuple::point(0.0, 0.0, 0.0)),
+            to_pixel(hand),
+            Colour::YELLOW,
+        );
+    }
+
+    save_png(&canvas, &output);
+}
+
+fn save_png(canvas: &Canvas, path: &str) {
+    let image = image::RgbaImage::from_fn(canvas.width as u32, canvas.height as u32, |x, y| {
+        image::Rgba(canvas.pixel_at(x as usize, y as usize).to_rgba8())
+    });
+    image.save(path).expect("Failed to save image");
+    println!("Saved {}", path);
+}
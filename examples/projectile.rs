@@ -0,0 +1,146 @@
+// The chapter 2 exercise: a projectile fired up and to the right, pulled down
+// by gravity and slowed by a headwind, with its path plotted until it lands.
+// With --render the same flight is ray traced instead, one frame per tick
+// with a ball at the projectile's position, numbered after the output, e.g.
+// projectile_0001.png.
+//
+//   cargo run --example projectile [output.png]
+//   cargo run --release --example projectile -- --render [output]
+
+use raytracer::scalar::consts::PI;
+use raytracer::{
+    camera::{Camera, Canvas},
+    colour::Colour,
+    environment::Environment,
+    light::Light,
+    materials::Material,
+    matrix::Matrix,
+    pattern::{checkered::Checkered, Pattern, PatternType},
+    projectile::Projectile,
+    scalar::Float,
+    shape::{plane::Plane, sphere::Sphere, Shape},
+    simulation::Simulation,
+    transformations::view_transform,
+    tuple::Tuple,
+    world::World,
+};
+
+const WIDTH: usize = 900;
+const HEIGHT: usize = 550;
+
+// Rendered frames are smaller, as there are a couple of hundred of them
+const FRAME_WIDTH: usize = 360;
+const FRAME_HEIGHT: usize = 220;
+const BALL_RADIUS: Float = 0.12;
+// Scene units per plot unit, keeping the scene a few units across so surface
+// offsets stay small next to it
+const SCALE: Float = 0.01;
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let render = args.iter().any(|arg| arg == "--render");
+    args.retain(|arg| arg != "--render");
+
+    let start = Tuple::point(0.0, 1.0, 0.0);
+    let velocity = Tuple::vector(1.0, 1.8, 0.0).normalise() * 11.25;
+    let environment = Environment::new(
+        Tuple::vector(0.0, -0.1, 0.0),
+        Tuple::vector(-0.01, 0.0, 0.0),
+    );
+    let simulation = Simulation::new(environment, vec![Projectile::new(start, velocity)]);
+
+    if render {
+        let prefix = args.first().map_or("projectile", String::as_str);
+        render_frames(simulation, prefix);
+    } else {
+        let output = args.first().map_or("projectile.png", String::as_str);
+        plot(simulation, output);
+    }
+}
+
+fn plot(mut simulation: Simulation, output: &str) {
+    // y is up in the world but down the canvas
+    let to_pixel = |p: Tuple| (p.x.round() as i64, HEIGHT as i64 - p.y.round() as i64);
+    let mut canvas = Canvas::new(WIDTH, HEIGHT);
+    let mut ticks = 0;
+    let mut last = to_pixel(simulation.get_projectiles()[0].pos);
+    loop {
+        simulation.tick();
+        ticks += 1;
+        let position = simulation.get_projectiles()[0].pos;
+        if position.y <= 0.0 {
+            break;
+        }
+        let next = to_pixel(position);
+        canvas.draw_line(last, next, Colour::new(1.0, 0.5, 0.2));
+        canvas.draw_circle(next, 1, Colour::WHITE);
+        last = next;
+    }
+    canvas.fill_rect(0, HEIGHT as i64 - 1, WIDTH, 1, Colour::GREY);
+
+    println!("Landed after {} ticks", ticks);
+    save_png(&canvas, output);
+    println!("Saved {}", output);
+}
+
+// The flight is about 9 units across and 5 up once scaled, seen side on from
+// in front
+fn render_frames(mut simulation: Simulation, prefix: &str) {
+    let mut world = World::new();
+    world.lights = vec![Light::point_light(
+        Tuple::point(-2.0, 15.0, -10.0),
+        Colour::WHITE,
+    )];
+
+    let mut floor = Plane::new();
+    let mut checkers = Checkered::new(Colour::new(0.9, 0.9, 0.9), Colour::new(0.6, 0.6, 0.6));
+    // Off the cell boundary at y = 0, where rounding would pick cells at random
+    checkers.set_transform(Matrix::translation(0.0, 0.5, 0.0));
+    let mut material = Material::new();
+    material.specular = 0.0;
+    material.set_pattern(Some(PatternType::Checkered(checkers)));
+    floor.set_material(material);
+    world.add_object(floor);
+
+    let mut ball = Sphere::new();
+    let mut material = Material::new();
+    material.colour = Colour::new(1.0, 0.5, 0.2);
+    ball.set_material(material);
+    let ball = world.add_object(ball);
+
+    let mut camera = Camera::new(FRAME_WIDTH, FRAME_HEIGHT, PI / 3.0);
+    camera.set_transform(view_transform(
+        Tuple::point(4.5, 3.5, -10.0),
+        Tuple::point(4.5, 2.2, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    ));
+
+    let mut frames = 0;
+    loop {
+        let position = simulation.get_projectiles()[0].pos * SCALE;
+        if position.y <= 0.0 {
+            break;
+        }
+        if let Some(shape) = world.registry.get_mut(ball) {
+            shape.set_transform(
+                Matrix::translation(position.x, position.y, position.z)
+                    * Matrix::scaling(BALL_RADIUS, BALL_RADIUS, BALL_RADIUS),
+            );
+        }
+        save_png(
+            &camera.render(&world),
+            &format!("{}_{:04}.png", prefix, frames),
+        );
+        simulation.tick();
+        frames += 1;
+    }
+
+    println!("Rendered {} frames to {}_NNNN.png", frames, prefix);
+}
+
+fn save_png(canvas: &Canvas, path: &str) {
+    let image = image::RgbaImage::from_fn(canvas.width as u32, canvas.height as u32, |x, y| {
+        image::Rgba(canvas.pixel_at(x as usize, y as usize).to_rgba8())
+    });
+    image.save(path).expect("Failed to save image");
+}
@@ -0,0 +1,89 @@
+use crate::{camera::Canvas, colour::Colour, scalar::Float};
+
+// Running colour sums and sample counts per pixel, for images refined over
+// several passes: progressive rendering in the browser, or path traced frames
+// that keep gaining samples. Each pass only traces its new samples; the
+// average of everything so far is resolved on demand.
+pub struct AccumulationBuffer {
+    pub width: usize,
+    pub height: usize,
+    sums: Vec<Colour>,
+    counts: Vec<u32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: usize, height: usize) -> AccumulationBuffer {
+        AccumulationBuffer {
+            width,
+            height,
+            sums: vec![Colour::black(); width * height],
+            counts: vec![0; width * height],
+        }
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, colour: Colour) {
+        let i = y * self.width + x;
+        self.sums[i] = if self.counts[i] == 0 {
+            colour
+        } else {
+            self.sums[i] + colour
+        };
+        self.counts[i] += 1;
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.counts[y * self.width + x]
+    }
+
+    // The mean of a pixel's samples; black before it has any
+    pub fn colour_at(&self, x: usize, y: usize) -> Colour {
+        let i = y * self.width + x;
+        match self.counts[i] {
+            0 => Colour::black(),
+            count => self.sums[i] * (1.0 / count as Float),
+        }
+    }
+
+    pub fn resolve(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                canvas.write_pixel(x, y, self.colour_at(x, y));
+            }
+        }
+        canvas
+    }
+
+    // Forgets every sample, e.g. when the scene or camera changes
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_resolve_to_the_mean_of_their_samples() {
+        let mut buffer = AccumulationBuffer::new(2, 1);
+        buffer.add_sample(0, 0, Colour::new(1.0, 0.0, 0.5));
+        buffer.add_sample(0, 0, Colour::new(0.0, 0.0, 0.5));
+
+        assert_eq!(buffer.sample_count(0, 0), 2);
+        assert_eq!(buffer.sample_count(1, 0), 0);
+        let canvas = buffer.resolve();
+        assert_eq!(canvas.pixel_at(0, 0), Colour::new(0.5, 0.0, 0.5));
+        assert_eq!(canvas.pixel_at(1, 0), Colour::black());
+    }
+
+    #[test]
+    fn clearing_starts_every_pixel_afresh() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+        buffer.add_sample(0, 0, Colour::white());
+        buffer.clear();
+        buffer.add_sample(0, 0, Colour::new(0.2, 0.2, 0.2));
+
+        assert_eq!(buffer.colour_at(0, 0), Colour::new(0.2, 0.2, 0.2));
+    }
+}
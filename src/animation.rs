@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+use std::ops::{Add, Mul, Sub};
+
+use crate::{
+    camera::Camera, colour::Colour, matrix::Matrix, scalar::Float, sweep::SweepParameter,
+    transformations::view_transform, tuple::Tuple, world::World,
+};
+
+// How values are filled in between keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    // A Catmull-Rom spline through the keys, so motion eases through each key
+    // instead of turning sharply at it
+    Cubic,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Interpolation> {
+        match name.to_ascii_lowercase().as_str() {
+            "linear" => Some(Interpolation::Linear),
+            "cubic" => Some(Interpolation::Cubic),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Linear => "linear",
+            Interpolation::Cubic => "cubic",
+        }
+    }
+}
+
+// Values that can be keyed: numbers, points and vectors, and colours
+pub trait Animatable:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Float, Output = Self>
+{
+}
+
+impl<T> Animatable for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Float, Output = T> {}
+
+// Keyed values of one property over time, in seconds
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<(Float, T)>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Track<T> {
+        Track {
+            keys: Vec::new(),
+            interpolation,
+        }
+    }
+
+    // Keys can be given in any order. A key at the same time as an earlier
+    // one replaces it.
+    pub fn key(mut self, time: Float, value: T) -> Track<T> {
+        match self.keys.binary_search_by(|(t, _)| t.total_cmp(&time)) {
+            Ok(index) => self.keys[index].1 = value,
+            Err(index) => self.keys.insert(index, (time, value)),
+        }
+        self
+    }
+
+    pub fn keys(&self) -> &[(Float, T)] {
+        &self.keys
+    }
+
+    // The first and last keys hold before and after the keyed range. None
+    // when there are no keys.
+    pub fn value_at(&self, time: Float) -> Option<T> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+
+        let next = self.keys.partition_point(|(t, _)| *t <= time);
+        let (t0, b) = self.keys[next - 1];
+        let (t1, c) = self.keys[next];
+        let t = (time - t0) / (t1 - t0);
+        Some(match self.interpolation {
+            Interpolation::Linear => b + (c - b) * t,
+            Interpolation::Cubic => {
+                // The ends are extended by repeating the end keys
+                let a = self.keys[next.saturating_sub(2)].1;
+                let d = self.keys[(next + 1).min(self.keys.len() - 1)].1;
+                catmull_rom(a, b, c, d, t)
+            }
+        })
+    }
+
+    // Time of the last key, or 0 with none
+    pub fn end(&self) -> Float {
+        self.keys.last().map_or(0.0, |(t, _)| *t)
+    }
+}
+
+// Passes through b at t = 0 and c at t = 1, heading from a and towards d
+fn catmull_rom<T: Animatable>(a: T, b: T, c: T, d: T, t: Float) -> T {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (b * 2.0 + (c - a) * t + (a * 2.0 - b * 5.0 + c * 4.0 - d) * t2 + ((b - c) * 3.0 + d - a) * t3)
+        * 0.5
+}
+
+// Keyed changes to one shape. Any of translation, rotation and scale being
+// keyed replaces the shape's transform with translate * rotate * scale, taking
+// the others as none. Rotations are angles in radians about x, then y, then z.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectAnimation {
+    pub translation: Option<Track<Tuple>>,
+    pub rotation: Option<Track<Tuple>>,
+    pub scale: Option<Track<Tuple>>,
+    pub colour: Option<Track<Colour>>,
+    pub fields: Vec<(SweepParameter, Track<Float>)>,
+}
+
+impl ObjectAnimation {
+    pub fn transform_at(&self, time: Float) -> Option<Matrix> {
+        if self.translation.is_none() && self.rotation.is_none() && self.scale.is_none() {
+            return None;
+        }
+        let value = |track: &Option<Track<Tuple>>, default: Tuple| {
+            track
+                .as_ref()
+                .and_then(|track| track.value_at(time))
+                .unwrap_or(default)
+        };
+        let t = value(&self.translation, Tuple::vector(0.0, 0.0, 0.0));
+        let r = value(&self.rotation, Tuple::vector(0.0, 0.0, 0.0));
+        let s = value(&self.scale, Tuple::vector(1.0, 1.0, 1.0));
+        Some(
+            Matrix::identity()
+                .scale(s.x, s.y, s.z)
+                .rotate_x(r.x)
+                .rotate_y(r.y)
+                .rotate_z(r.z)
+                .translate(t.x, t.y, t.z),
+        )
+    }
+
+    // A shape on a scene graph node is moved through the node, taking
+    // whatever hangs off it along. Keys whose scales pass through zero on the
+    // way between them leave the shape where it was at that instant.
+    fn apply(&self, world: &mut World, id: u32, time: Float) {
+        let transform = self
+            .transform_at(time)
+            .filter(|transform| transform.determinant() != 0.0);
+        if let Some(transform) = transform {
+            match world.scene_graph.node_of(id) {
+                Some(node) => world.scene_graph.set_local_transform(node, transform),
+                None => {
+                    world.registry.set_transform(id, transform);
+                }
+            }
+        }
+        if self.colour.is_none() && self.fields.is_empty() {
+            return;
+        }
+        let Some(shape) = world.registry.get(id) else {
+            return;
+        };
+        let mut material = shape.material().clone();
+        if let Some(colour) = self.colour.as_ref().and_then(|c| c.value_at(time)) {
+            material.colour = colour;
+        }
+        for (field, track) in &self.fields {
+            if let Some(value) = track.value_at(time) {
+                field.apply(&mut material, value);
+            }
+        }
+        world.registry.set_material(id, material);
+    }
+
+    fn end(&self) -> Float {
+        [&self.translation, &self.rotation, &self.scale]
+            .into_iter()
+            .flatten()
+            .map(Track::end)
+            .chain(self.colour.iter().map(Track::end))
+            .chain(self.fields.iter().map(|(_, track)| track.end()))
+            .fold(0.0, Float::max)
+    }
+}
+
+// Where the camera is and what it looks at, with y up
+#[derive(Debug, Clone)]
+pub struct CameraAnimation {
+    pub from: Track<Tuple>,
+    pub to: Track<Tuple>,
+    pub field_of_view: Option<Track<Float>>,
+}
+
+// Keyed changes to a world's top-level shapes, found by name, and to the camera
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub objects: BTreeMap<String, ObjectAnimation>,
+    pub camera: Option<CameraAnimation>,
+}
+
+impl Animation {
+    // Sets the animated shapes as they are at `time`. Names that aren't
+    // top-level shapes in the world are skipped.
+    pub fn apply(&self, world: &mut World, time: Float) {
+        for (name, object) in &self.objects {
+            let Some(id) = world.get_by_name(name).map(|shape| shape.id()) else {
+                continue;
+            };
+            if world.registry.root_id(id) == id {
+                object.apply(world, id, time);
+            }
+        }
+        world.update_scene_graph();
+    }
+
+    // The camera as it is at `time`, or unchanged if the camera isn't animated
+    pub fn camera_at(&self, camera: &Camera, time: Float) -> Camera {
+        let mut camera = camera.clone();
+        let Some(animation) = &self.camera else {
+            return camera;
+        };
+        if let (Some(from), Some(to)) = (animation.from.value_at(time), animation.to.value_at(time))
+        {
+            camera.set_transform(view_transform(from, to, Tuple::vector(0.0, 1.0, 0.0)));
+        }
+        if let Some(fov) = animation
+            .field_of_view
+            .as_ref()
+            .and_then(|track| track.value_at(time))
+        {
+            camera.set_field_of_view(fov);
+        }
+        camera
+    }
+
+    // Time of the last key of anything
+    pub fn duration(&self) -> Float {
+        let camera = self.camera.iter().flat_map(|camera| {
+            [camera.from.end(), camera.to.end()]
+                .into_iter()
+                .chain(camera.field_of_view.as_ref().map(Track::end))
+        });
+        self.objects
+            .values()
+            .map(ObjectAnimation::end)
+            .chain(camera)
+            .fold(0.0, Float::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use crate::shape::{sphere::Sphere, Shape};
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn linear_tracks_hold_their_ends_and_blend_between_keys() {
+        let track = Track::new(Interpolation::Linear)
+            .key(2.0, 10.0)
+            .key(0.0, 0.0)
+            .key(3.0, 4.0);
+
+        assert_eq!(track.value_at(-1.0), Some(0.0));
+        assert_eq!(track.value_at(1.0), Some(5.0));
+        assert_eq!(track.value_at(2.5), Some(7.0));
+        assert_eq!(track.value_at(9.0), Some(4.0));
+        assert_eq!(
+            Track::<Float>::new(Interpolation::Linear).value_at(0.0),
+            None
+        );
+    }
+
+    #[test]
+    fn cubic_tracks_pass_through_keys_smoothly() {
+        let track = Track::new(Interpolation::Cubic)
+            .key(0.0, 0.0)
+            .key(1.0, 1.0)
+            .key(2.0, 0.0);
+
+        assert_abs_diff_eq!(track.value_at(1.0).unwrap(), 1.0, epsilon = TEST_EPSILON);
+        // Rounded over the peak rather than a straight line up to it
+        assert!(track.value_at(0.9).unwrap() > 0.9);
+        assert_abs_diff_eq!(
+            track.value_at(0.5).unwrap(),
+            track.value_at(1.5).unwrap(),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn animations_move_and_recolour_shapes_by_name() {
+        let mut world = World::new();
+        let mut ball = Sphere::new();
+        ball.set_name(Some("ball".to_string()));
+        let id = world.add_object(ball);
+        let mut animation = Animation::default();
+        animation.objects.insert(
+            "ball".to_string(),
+            ObjectAnimation {
+                translation: Some(
+                    Track::new(Interpolation::Linear)
+                        .key(0.0, Tuple::vector(0.0, 0.0, 0.0))
+                        .key(2.0, Tuple::vector(0.0, 4.0, 0.0)),
+                ),
+                colour: Some(
+                    Track::new(Interpolation::Linear)
+                        .key(0.0, Colour::black())
+                        .key(2.0, Colour::white()),
+                ),
+                fields: vec![(
+                    SweepParameter::Reflective,
+                    Track::new(Interpolation::Linear).key(1.0, 0.5),
+                )],
+                ..ObjectAnimation::default()
+            },
+        );
+
+        animation.apply(&mut world, 1.0);
+
+        let ball = world.registry.get(id).unwrap();
+        assert_abs_diff_eq!(
+            ball.transform() * Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, 2.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            ball.material().colour,
+            Colour::new(0.5, 0.5, 0.5),
+            epsilon = TEST_EPSILON
+        );
+        assert_eq!(ball.material().reflective, 0.5);
+        assert_eq!(animation.duration(), 2.0);
+    }
+
+    #[test]
+    fn scales_passing_through_zero_leave_the_shape_in_place() {
+        let mut world = World::new();
+        let mut ball = Sphere::new();
+        ball.set_name(Some("ball".to_string()));
+        let id = world.add_object(ball);
+        let mut animation = Animation::default();
+        animation.objects.insert(
+            "ball".to_string(),
+            ObjectAnimation {
+                scale: Some(
+                    Track::new(Interpolation::Linear)
+                        .key(0.0, Tuple::vector(1.0, 1.0, 1.0))
+                        .key(2.0, Tuple::vector(-1.0, 1.0, 1.0)),
+                ),
+                ..ObjectAnimation::default()
+            },
+        );
+
+        animation.apply(&mut world, 0.0);
+        animation.apply(&mut world, 1.0);
+
+        assert_eq!(
+            *world.registry.get(id).unwrap().transform(),
+            Matrix::identity()
+        );
+    }
+
+    #[test]
+    fn the_camera_follows_its_keys() {
+        let camera = Camera::new(11, 11, 1.0);
+        let animation = Animation {
+            camera: Some(CameraAnimation {
+                from: Track::new(Interpolation::Linear)
+                    .key(0.0, Tuple::point(0.0, 0.0, -5.0))
+                    .key(1.0, Tuple::point(0.0, 0.0, -10.0)),
+                to: Track::new(Interpolation::Linear).key(0.0, Tuple::point(0.0, 0.0, 0.0)),
+                field_of_view: Some(Track::new(Interpolation::Linear).key(0.0, 0.5)),
+            }),
+            ..Animation::default()
+        };
+
+        let moved = animation.camera_at(&camera, 0.5);
+
+        let ray = moved.ray_for_pixel(5, 5);
+        assert_abs_diff_eq!(
+            ray.origin,
+            Tuple::point(0.0, 0.0, -7.5),
+            epsilon = TEST_EPSILON
+        );
+        assert_eq!(moved.field_of_view, 0.5);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    camera::{Camera, Canvas},
+    colour::Colour,
+    intersection::{prepare_computations, Intersection, PreComputedData},
+    ray::Ray,
+    scalar::Float,
+    world::World,
+};
+
+// Debugging views of a scene, rendered in place of its shaded colours. Each
+// pixel is traced once through its centre, whatever the camera's samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    // Distance to the nearest visible surface, white for the nearest in the
+    // image fading to black for the furthest. Misses are black.
+    Depth,
+    // World space surface normals, with x, y and z from -1 to 1 as red, green
+    // and blue from 0 to 1
+    Normal,
+    // A colour for each top-level shape, so the parts of a group or mesh
+    // match
+    ObjectId,
+    // How much of the light reaches each surface: white when lit, black in
+    // shadow. With several lights, it's the share of those lighting the
+    // surface that gets through.
+    ShadowMask,
+    // A heatmap of reflection, refraction and path bounces per pixel, from
+    // blue for none through green and yellow to red for the most in the image
+    Bounces,
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Aov> {
+        match name.to_ascii_lowercase().as_str() {
+            "depth" => Some(Aov::Depth),
+            "normal" | "normals" => Some(Aov::Normal),
+            "object_id" | "id" => Some(Aov::ObjectId),
+            "shadow" | "shadow_mask" => Some(Aov::ShadowMask),
+            "bounces" => Some(Aov::Bounces),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::ObjectId => "object_id",
+            Aov::ShadowMask => "shadow_mask",
+            Aov::Bounces => "bounces",
+        }
+    }
+
+    pub fn render(&self, world: &World, camera: &Camera) -> Canvas {
+        let rays = (0..camera.vsize)
+            .flat_map(|y| (0..camera.hsize).map(move |x| camera.ray_for_pixel(x, y)));
+        // Depth and bounces are scaled to the range the image holds, so every
+        // pixel's value is found before any are coloured
+        let colours: Vec<Colour> = match self {
+            Aov::Depth => {
+                let depths: Vec<Option<Float>> = rays
+                    .map(|ray| visible_hit(world, &ray, |hit, _| hit.t))
+                    .collect();
+                let near = depths
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .fold(Float::MAX, Float::min);
+                let far = depths.iter().flatten().copied().fold(0.0, Float::max);
+                let range = (far - near).max(Float::EPSILON);
+                depths
+                    .iter()
+                    .map(|depth| {
+                        depth.map_or(Colour::black(), |t| {
+                            let v = 1.0 - (t - near) / range;
+                            Colour::new(v, v, v)
+                        })
+                    })
+                    .collect()
+            }
+            Aov::Bounces => {
+                let counts: Vec<u64> = rays.map(|ray| world.bounce_count(&ray)).collect();
+                let most = counts.iter().copied().max().unwrap_or(0).max(1);
+                counts
+                    .iter()
+                    .map(|&count| heat(count as Float / most as Float))
+                    .collect()
+            }
+            _ => rays
+                .map(|ray| self.colour(world, &ray).unwrap_or(Colour::black()))
+                .collect(),
+        };
+
+        let mut canvas = Canvas::new(camera.hsize, camera.vsize);
+        for (i, colour) in colours.into_iter().enumerate() {
+            canvas.write_pixel(i % camera.hsize, i / camera.hsize, colour);
+        }
+        canvas
+    }
+
+    // The views that don't depend on the rest of the image, or None for a miss
+    fn colour(&self, world: &World, ray: &Ray) -> Option<Colour> {
+        visible_hit(world, ray, |hit, comps| match self {
+            Aov::Normal => {
+                let n = comps.normalv;
+                Colour::new(n.x + 1.0, n.y + 1.0, n.z + 1.0) * 0.5
+            }
+            Aov::ObjectId => id_colour(world.registry.root_id(hit.object_id)),
+            Aov::ShadowMask => shadow_mask(world, comps),
+            Aov::Depth | Aov::Bounces => unreachable!("scaled to the whole image in render"),
+        })
+    }
+}
+
+// The lights' combined intensity at a hit that isn't blocked, as a share of
+// the intensity without shadows, per channel
+fn shadow_mask(world: &World, comps: &PreComputedData) -> Colour {
+    let mut lit = Colour::black();
+    let mut total = Colour::black();
+    for light in world.lights_for(comps.object) {
+        let transmission =
+            world.light_transmission_from(light.position, comps.over_point, comps.time, comps.eye);
+        lit = lit + light.intensity * transmission;
+        total = total + light.intensity;
+    }
+    let share = |lit: Float, total: Float| if total > 0.0 { lit / total } else { 1.0 };
+    Colour::new(
+        share(lit.r, total.r),
+        share(lit.g, total.g),
+        share(lit.b, total.b),
+    )
+}
+
+// Calls f with the nearest hit the camera can see, skipping shapes hidden
+// from it as shading does
+fn visible_hit<T>(
+    world: &World,
+    ray: &Ray,
+    f: impl FnOnce(&Intersection, &PreComputedData) -> T,
+) -> Option<T> {
+    let xs = world.intersect_world(ray);
+    let hit = xs.iter().find(|x| {
+        x.t >= 0.0
+            && world
+                .registry
+                .get(x.object_id)
+                .is_some_and(|shape| shape.visibility().camera_visible)
+    })?;
+    let comps = prepare_computations(hit, ray, &world.registry, Some(&xs))?;
+    Some(f(hit, &comps))
+}
+
+// Spreads ids around the colour wheel by the golden ratio, so neighbouring ids
+// get very different hues
+fn id_colour(id: u32) -> Colour {
+    let hue = (id as Float * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Colour::new(0.2 + 0.7 * r, 0.2 + 0.7 * g, 0.2 + 0.7 * b)
+}
+
+// 0 is blue, then green, yellow and red at 1
+fn heat(v: Float) -> Colour {
+    let v = v.clamp(0.0, 1.0) * 3.0;
+    match v {
+        v if v < 1.0 => Colour::new(0.0, v, 1.0 - v),
+        v if v < 2.0 => Colour::new(v - 1.0, 1.0, 0.0),
+        v => Colour::new(1.0, 3.0 - v, 0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::light::Light;
+    use crate::scalar::TEST_EPSILON;
+    use crate::transformations::view_transform;
+    use crate::tuple::Tuple;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(11, 11, crate::scalar::consts::PI / 2.0);
+        camera.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+        camera
+    }
+
+    #[test]
+    fn aovs_are_found_by_name() {
+        for aov in [
+            Aov::Depth,
+            Aov::Normal,
+            Aov::ObjectId,
+            Aov::ShadowMask,
+            Aov::Bounces,
+        ] {
+            assert_eq!(Aov::from_name(aov.name()), Some(aov));
+        }
+        assert_eq!(Aov::from_name("beauty"), None);
+    }
+
+    #[test]
+    fn normals_and_depth_of_the_default_world() {
+        let world = World::default_world();
+
+        let normals = Aov::Normal.render(&world, &camera());
+        let depth = Aov::Depth.render(&world, &camera());
+
+        // Straight at the outer sphere, whose normal faces back at the camera
+        assert_abs_diff_eq!(
+            normals.pixel_at(5, 5),
+            Colour::new(0.5, 0.5, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_eq!(depth.pixel_at(5, 5), Colour::white());
+        assert_eq!(depth.pixel_at(0, 0), Colour::black());
+        assert!(depth.pixel_at(3, 5).r < 1.0);
+    }
+
+    #[test]
+    fn bounces_light_up_reflective_surfaces() {
+        let mut world = World::default_world();
+        let id = world.registry.iter().next().unwrap().id();
+        let mut material = world.registry.get(id).unwrap().material().clone();
+        material.reflective = 0.5;
+        world.registry.set_material(id, material);
+
+        let heatmap = Aov::Bounces.render(&world, &camera());
+
+        assert_eq!(heatmap.pixel_at(5, 5), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(heatmap.pixel_at(0, 0), Colour::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn the_shadow_mask_is_black_where_the_light_is_blocked() {
+        let mut world = World::default_world();
+        world.lights = vec![Light::point_light(
+            Tuple::point(0.0, 0.0, -10.0),
+            Colour::white(),
+        )];
+        let front = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let back = Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, -1.0));
+
+        assert_eq!(
+            Aov::ShadowMask.colour(&world, &front),
+            Some(Colour::white())
+        );
+        assert_eq!(Aov::ShadowMask.colour(&world, &back), Some(Colour::black()));
+        assert_eq!(
+            Aov::ObjectId.colour(&world, &front),
+            Aov::ObjectId.colour(&world, &back)
+        );
+
+        // A second light as bright behind the spheres lights half of each side
+        world.lights.push(Light::point_light(
+            Tuple::point(0.0, 0.0, 10.0),
+            Colour::white(),
+        ));
+        let half = Colour::new(0.5, 0.5, 0.5);
+        assert_eq!(Aov::ShadowMask.colour(&world, &front), Some(half));
+        assert_eq!(Aov::ShadowMask.colour(&world, &back), Some(half));
+    }
+}
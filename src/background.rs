@@ -0,0 +1,191 @@
+use crate::{
+    colour::Colour,
+    pattern::uv_pattern::{cubic_map, UvImage},
+    ray::Ray,
+    tuple::Tuple,
+};
+
+#[derive(Clone)]
+pub enum Background {
+    Solid(Colour),
+    // Vertical blend from the horizon colour (level rays) up to the zenith
+    // colour (straight up). Rays pointing below the horizon get the horizon
+    // colour.
+    Gradient { horizon: Colour, zenith: Colour },
+    // Six images around the scene at infinity, looked up by ray direction only
+    Skybox(Box<Skybox>),
+}
+
+// Cube map faces in CubeFace order (+x, -x, +y, -y, +z, -z), each as seen from
+// inside the cube; see cubic_map for which way is up on each face
+#[derive(Clone)]
+pub struct Skybox {
+    pub faces: [UvImage; 6],
+}
+
+impl Skybox {
+    pub fn new(faces: [UvImage; 6]) -> Skybox {
+        Skybox { faces }
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(paths: [P; 6]) -> Result<Skybox, String> {
+        let [right, left, up, down, front, back] = paths;
+        Ok(Skybox::new([
+            UvImage::load(right)?,
+            UvImage::load(left)?,
+            UvImage::load(up)?,
+            UvImage::load(down)?,
+            UvImage::load(front)?,
+            UvImage::load(back)?,
+        ]))
+    }
+
+    pub fn colour_at(&self, direction: Tuple) -> Colour {
+        // Push the direction out to the surface of the unit cube
+        let largest = direction
+            .x
+            .abs()
+            .max(direction.y.abs())
+            .max(direction.z.abs());
+        if largest == 0.0 {
+            return Colour::black();
+        }
+        let on_cube = Tuple::point(
+            direction.x / largest,
+            direction.y / largest,
+            direction.z / largest,
+        );
+        let (face, u, v) = cubic_map(on_cube);
+        self.faces[face as usize].colour_at(u, v)
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Colour::black())
+    }
+}
+
+impl Background {
+    pub fn solid(colour: Colour) -> Background {
+        Background::Solid(colour)
+    }
+
+    pub fn gradient(horizon: Colour, zenith: Colour) -> Background {
+        Background::Gradient { horizon, zenith }
+    }
+
+    pub fn skybox(skybox: Skybox) -> Background {
+        Background::Skybox(Box::new(skybox))
+    }
+
+    pub fn colour_at(&self, ray: &Ray) -> Colour {
+        match self {
+            Background::Solid(colour) => *colour,
+            Background::Gradient { horizon, zenith } => {
+                let frac = ray.direction.normalise().y.clamp(0.0, 1.0);
+                *horizon + (*zenith - *horizon) * frac
+            }
+            Background::Skybox(skybox) => skybox.colour_at(ray.direction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Float;
+    use crate::tuple::Tuple;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn default_background_is_black() {
+        let bg = Background::default();
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        assert_eq!(bg.colour_at(&r), Colour::black());
+    }
+
+    #[test]
+    fn solid_background_ignores_ray_direction() {
+        let bg = Background::solid(Colour::new(0.2, 0.3, 0.4));
+        let up = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let level = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+
+        assert_eq!(bg.colour_at(&up), Colour::new(0.2, 0.3, 0.4));
+        assert_eq!(bg.colour_at(&level), Colour::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn gradient_background_blends_from_horizon_to_zenith() {
+        let horizon = Colour::white();
+        let zenith = Colour::new(0.0, 0.0, 1.0);
+        let bg = Background::gradient(horizon, zenith);
+        let origin = Tuple::point(0.0, 0.0, 0.0);
+
+        let level = Ray::new(origin, Tuple::vector(0.0, 0.0, 1.0));
+        let up = Ray::new(origin, Tuple::vector(0.0, 1.0, 0.0));
+        let halfway = Ray::new(origin, Tuple::vector(0.0, 0.5, Float::sqrt(3.0) / 2.0));
+
+        assert_eq!(bg.colour_at(&level), horizon);
+        assert_eq!(bg.colour_at(&up), zenith);
+        assert_abs_diff_eq!(
+            bg.colour_at(&halfway),
+            Colour::new(0.5, 0.5, 1.0),
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn gradient_background_below_horizon_uses_horizon_colour() {
+        let horizon = Colour::new(0.8, 0.8, 0.8);
+        let bg = Background::gradient(horizon, Colour::new(0.1, 0.2, 0.9));
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, -1.0, 1.0));
+
+        assert_eq!(bg.colour_at(&r), horizon);
+    }
+
+    #[test]
+    fn gradient_background_handles_unnormalised_directions() {
+        let bg = Background::gradient(Colour::black(), Colour::white());
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 5.0, 0.0));
+
+        assert_eq!(bg.colour_at(&r), Colour::white());
+    }
+
+    fn solid_image(colour: Colour) -> UvImage {
+        let mut canvas = crate::camera::Canvas::new(2, 2);
+        for y in 0..2 {
+            for x in 0..2 {
+                canvas.write_pixel(x, y, colour);
+            }
+        }
+        UvImage::from_canvas(&canvas)
+    }
+
+    #[test]
+    fn skybox_picks_the_face_the_ray_points_at() {
+        let face_colours = [
+            Colour::new(1.0, 0.0, 0.0),
+            Colour::new(0.0, 1.0, 0.0),
+            Colour::new(0.0, 0.0, 1.0),
+            Colour::new(1.0, 1.0, 0.0),
+            Colour::new(0.0, 1.0, 1.0),
+            Colour::new(1.0, 0.0, 1.0),
+        ];
+        let bg = Background::skybox(Skybox::new(face_colours.map(solid_image)));
+        let origin = Tuple::point(3.0, -2.0, 7.0);
+        let directions = [
+            Tuple::vector(1.0, 0.2, 0.1),
+            Tuple::vector(-5.0, 0.0, 0.0),
+            Tuple::vector(0.1, 1.0, -0.3),
+            Tuple::vector(0.0, -1.0, 0.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+            Tuple::vector(0.4, -0.4, -0.9),
+        ];
+
+        for (direction, expected) in directions.iter().zip(face_colours) {
+            assert_eq!(bg.colour_at(&Ray::new(origin, *direction)), expected);
+        }
+    }
+}
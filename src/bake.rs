@@ -0,0 +1,208 @@
+use crate::{
+    camera::Canvas,
+    colour::Colour,
+    light::Light,
+    materials::{lighting, Material},
+    matrix::Matrix,
+    pattern::{
+        uv_pattern::{TextureMap, UvImage, UvMapping},
+        Pattern, PatternType,
+    },
+    scalar::Float,
+    tuple::Tuple,
+    world::World,
+};
+
+// Experimental: precomputes the light falling on a static surface so that
+// animations with fixed lighting can skip shading it every frame.
+
+// Rectangle of object-space x/z coordinates to bake, on the shape's y = 0
+// plane. That plane is the surface itself for a Plane.
+#[derive(Debug, Clone, Copy)]
+pub struct BakeRegion {
+    pub min_x: Float,
+    pub min_z: Float,
+    pub max_x: Float,
+    pub max_z: Float,
+}
+
+impl BakeRegion {
+    pub fn new(min_x: Float, min_z: Float, max_x: Float, max_z: Float) -> BakeRegion {
+        BakeRegion {
+            min_x,
+            min_z,
+            max_x,
+            max_z,
+        }
+    }
+
+    // Stretches a planar-mapped texture's unit square over the region
+    pub fn texture_transform(&self) -> Matrix {
+        &Matrix::translation(self.min_x, 0.0, self.min_z)
+            * &Matrix::scaling(self.max_x - self.min_x, 1.0, self.max_z - self.min_z)
+    }
+}
+
+// Shades the region of a top-level shape into a texture, one texel per grid
+// point. Only ambient and diffuse light with shadows are baked; specular and
+// reflections depend on the viewer and are left out.
+pub fn bake_irradiance(
+    world: &World,
+    id: u32,
+    region: BakeRegion,
+    width: usize,
+    height: usize,
+) -> Result<Canvas, String> {
+    if width < 2 || height < 2 {
+        return Err(format!(
+            "bake texture must be at least 2x2, got {}x{}",
+            width, height
+        ));
+    }
+    let shape = world
+        .registry
+        .get(id)
+        .ok_or_else(|| format!("no shape with id {}", id))?;
+    if !shape.has_surface() {
+        return Err(format!(
+            "cannot bake a composite shape (id {}); bake one of its parts",
+            id
+        ));
+    }
+
+    let mut material = shape.material().clone();
+    material.specular = 0.0;
+
+    let mut canvas = Canvas::new(width, height);
+    let lights: Vec<Light> = world
+        .lights_for(shape)
+        .map(|l| l.at_time(world.time))
+        .collect();
+    if lights.is_empty() {
+        return Ok(canvas);
+    }
+
+    for y in 0..height {
+        // Matches UvImage, which puts v = 0 on the bottom row
+        let v = 1.0 - y as Float / (height - 1) as Float;
+        let z = region.min_z + v * (region.max_z - region.min_z);
+        for x in 0..width {
+            let u = x as Float / (width - 1) as Float;
+            let object_point =
+                Tuple::point(region.min_x + u * (region.max_x - region.min_x), 0.0, z);
+            let point = shape.transform() * object_point;
+            let normal = shape.normal_at(&point);
+            let over_point = point + normal * world.shadow_bias();
+
+            let colour = lights.iter().fold(Colour::black(), |colour, light| {
+                colour
+                    + lighting(
+                        &material,
+                        shape,
+                        light,
+                        point,
+                        normal,
+                        normal,
+                        world.light_transmission_from(light.position, over_point, 0.0, point),
+                    )
+            });
+            canvas.write_pixel(x, y, colour);
+        }
+    }
+
+    Ok(canvas)
+}
+
+// Copy of base that shows the baked texture as-is, ignoring scene lights
+pub fn baked_material(base: &Material, texture: &Canvas, region: BakeRegion) -> Material {
+    let mut pattern = TextureMap::image(UvImage::from_canvas(texture), UvMapping::Planar);
+    pattern.set_transform(region.texture_transform());
+
+    let mut material = base.clone();
+    material.colour = Colour::white();
+    material.ambient = 1.0;
+    material.diffuse = 0.0;
+    material.specular = 0.0;
+    material.pattern = Some(PatternType::TextureMap(pattern));
+    material
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::{
+        light::Light,
+        ray::Ray,
+        shape::{group::Group, plane::Plane, sphere::Sphere, Shape},
+    };
+
+    fn floor_world() -> (World, u32) {
+        let mut world = World::new();
+        world.lights = vec![Light::point_light(
+            Tuple::point(0.0, 10.0, 0.0),
+            Colour::white(),
+        )];
+        let floor = world.add_object(Plane::new());
+
+        let mut blocker = Sphere::new();
+        blocker.set_transform(Matrix::translation(0.0, 5.0, 0.0));
+        world.add_object(blocker);
+        (world, floor)
+    }
+
+    #[test]
+    fn baking_captures_shadows_on_the_floor() {
+        let (world, floor) = floor_world();
+        let region = BakeRegion::new(-4.0, -4.0, 4.0, 4.0);
+        let texture = bake_irradiance(&world, floor, region, 9, 9).unwrap();
+
+        // The sphere hangs straight between the light and the middle texel
+        let ambient = Colour::new(0.1, 0.1, 0.1);
+        assert_abs_diff_eq!(texture.pixel_at(4, 4), ambient, epsilon = TEST_EPSILON);
+        assert!(texture.pixel_at(0, 0).r > ambient.r);
+    }
+
+    #[test]
+    fn baked_material_reproduces_the_lit_floor() {
+        let (mut world, floor) = floor_world();
+        let region = BakeRegion::new(-4.0, -4.0, 4.0, 4.0);
+        let texture = bake_irradiance(&world, floor, region, 33, 33).unwrap();
+
+        let ray = Ray::new(Tuple::point(2.0, 3.0, -1.0), Tuple::vector(0.0, -1.0, 0.0));
+        let mut lit = world.registry.get(floor).unwrap().material().clone();
+        lit.specular = 0.0;
+        world
+            .registry
+            .get_mut(floor)
+            .unwrap()
+            .set_material(lit.clone());
+        let live = world.colour_at(&ray, 0);
+
+        let baked = baked_material(&lit, &texture, region);
+        world.registry.get_mut(floor).unwrap().set_material(baked);
+        assert_abs_diff_eq!(world.colour_at(&ray, 0), live, epsilon = 0.01);
+    }
+
+    #[test]
+    fn baking_an_unknown_shape_fails() {
+        let (world, _) = floor_world();
+        let region = BakeRegion::new(0.0, 0.0, 1.0, 1.0);
+        assert!(bake_irradiance(&world, 999, region, 4, 4).is_err());
+    }
+
+    #[test]
+    fn baking_a_composite_shape_fails() {
+        let (mut world, _) = floor_world();
+        let mut group = Group::new();
+        group.add_child(Box::new(Plane::new()));
+        let id = world.add_object(group);
+        let region = BakeRegion::new(0.0, 0.0, 1.0, 1.0);
+
+        let err = bake_irradiance(&world, id, region, 4, 4).err().unwrap();
+
+        assert!(err.contains("composite"), "{}", err);
+    }
+}
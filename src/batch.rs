@@ -0,0 +1,221 @@
+use crate::scalar::Float;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A list of renders to run in one go, read from JSON:
+//
+// {
+//   "renders": [
+//     { "scene": "default", "output": "images/default.png", "width": 400, "height": 300 },
+//     { "scene": "scenes/room.json", "output": "images/room.png", "samples": 4, "tonemap": "aces" }
+//   ]
+// }
+//
+// Each entry takes the same options as the CLI. Relative scene, mesh and output
+// paths are resolved against the manifest's directory, so a manifest can sit
+// next to the scenes it renders.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchManifest {
+    pub renders: Vec<BatchEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchEntry {
+    // Built-in scene name or path to a JSON scene file
+    pub scene: String,
+    pub output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obj: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tonemap: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exposure: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamma: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lut: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fov: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_pos: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_target: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_up: Option<[Float; 3]>,
+}
+
+impl BatchManifest {
+    pub fn from_json(json: &str) -> Result<BatchManifest, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid batch manifest: {}", e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<BatchManifest, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        BatchManifest::from_json(&contents)
+    }
+}
+
+impl BatchEntry {
+    // Command line arguments (without the program name) that render this entry
+    pub fn to_args(&self, base_dir: &Path) -> Vec<String> {
+        let resolve = |path: &str| base_dir.join(path).to_string_lossy().into_owned();
+        let xyz = |v: &[Float; 3]| format!("{},{},{}", v[0], v[1], v[2]);
+
+        // Built-in scene names are passed through; anything that exists relative
+        // to the manifest is treated as a scene file
+        let scene = if base_dir.join(&self.scene).is_file() {
+            resolve(&self.scene)
+        } else {
+            self.scene.clone()
+        };
+
+        let mut args = vec![
+            "--scene".to_string(),
+            scene,
+            "--output".to_string(),
+            resolve(&self.output),
+        ];
+        let mut push = |flag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{}", flag));
+                args.push(value);
+            }
+        };
+        push("width", self.width.map(|v| v.to_string()));
+        push("height", self.height.map(|v| v.to_string()));
+        push("obj", self.obj.as_deref().map(resolve));
+        push("samples", self.samples.map(|v| v.to_string()));
+        push("sampling", self.sampling.clone());
+        push("tonemap", self.tonemap.clone());
+        push("exposure", self.exposure.map(|v| v.to_string()));
+        push("gamma", self.gamma.map(|v| v.to_string()));
+        push("lut", self.lut.as_deref().map(resolve));
+        push("fov", self.fov.map(|v| v.to_string()));
+        push("camera-pos", self.camera_pos.as_ref().map(xyz));
+        push("camera-target", self.camera_target.as_ref().map(xyz));
+        push("camera-up", self.camera_up.as_ref().map(xyz));
+        args
+    }
+}
+
+// Outcome of one batch entry, for the summary printed at the end
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+    pub output: String,
+    pub seconds: f64,
+    pub error: Option<String>,
+    // See World::scene_hash; missing for failed renders and scenes that can't
+    // be described, such as those with generated textures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scene_hash: Option<String>,
+}
+
+pub fn summary(results: &[BatchResult]) -> String {
+    let mut report = String::new();
+    for result in results {
+        match &result.error {
+            None => report.push_str(&format!(
+                "  ok      {:>8.2}s  {}{}\n",
+                result.seconds,
+                result.output,
+                result
+                    .scene_hash
+                    .as_ref()
+                    .map_or(String::new(), |hash| format!("  (scene {})", hash))
+            )),
+            Some(e) => report.push_str(&format!(
+                "  FAILED  {:>8.2}s  {}: {}\n",
+                result.seconds, result.output, e
+            )),
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    let total: f64 = results.iter().map(|r| r.seconds).sum();
+    report.push_str(&format!(
+        "{} rendered, {} failed, {:.2}s total\n",
+        results.len() - failed,
+        failed,
+        total
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_entries_only_need_scene_and_output() {
+        let manifest = BatchManifest::from_json(
+            r#"{ "renders": [
+                { "scene": "default", "output": "a.png" },
+                { "scene": "third", "output": "b.png", "width": 40, "camera_pos": [0, 1, -5] }
+            ] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.renders.len(), 2);
+        assert_eq!(manifest.renders[0].width, None);
+        assert_eq!(manifest.renders[1].width, Some(40));
+        assert!(BatchManifest::from_json(r#"{ "renders": [{ "scene": "x" }] }"#).is_err());
+    }
+
+    #[test]
+    fn entries_become_cli_arguments() {
+        let entry = BatchEntry {
+            scene: "default".to_string(),
+            output: "out/a.png".to_string(),
+            width: Some(40),
+            camera_pos: Some([0.0, 1.5, -5.0]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.to_args(Path::new("docs")),
+            [
+                "--scene",
+                "default",
+                "--output",
+                "docs/out/a.png",
+                "--width",
+                "40",
+                "--camera-pos",
+                "0,1.5,-5"
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_counts_failures() {
+        let results = [
+            BatchResult {
+                output: "a.png".to_string(),
+                seconds: 1.0,
+                error: None,
+                scene_hash: Some("0123456789abcdef".to_string()),
+            },
+            BatchResult {
+                output: "b.png".to_string(),
+                seconds: 0.5,
+                error: Some("Unknown scene 'x'".to_string()),
+                scene_hash: None,
+            },
+        ];
+
+        let report = summary(&results);
+        assert!(report.contains("FAILED"));
+        assert!(report.contains("a.png  (scene 0123456789abcdef)"));
+        assert!(report.ends_with("1 rendered, 1 failed, 1.50s total\n"));
+    }
+}
@@ -1,19 +1,86 @@
-use clap::Parser;
+use clap::{ArgAction, Parser, Subcommand};
 use image::{ImageBuffer, Rgba};
-use raytracer::{camera::Camera, transformations::view_transform, tuple::Tuple, world::World};
+use raytracer::{
+    aov::Aov,
+    batch::{summary, BatchManifest, BatchResult},
+    blueprint::{render_blueprint, BlueprintView},
+    camera::{is_hdr_path, Camera, Canvas, CanvasStorage, Projection, SamplingMode},
+    camera_path::{frame_path, CameraPath},
+    checkpoint::Checkpoint,
+    environment::Environment,
+    lut::ColourLut,
+    materials::Material,
+    matrix::Matrix,
+    obj_parser::parse_obj_file,
+    projectile::Projectile,
+    render_stats::RenderStats,
+    scalar::Float,
+    scene::load_scene_file,
+    shape::{group::Group, lod::Lod, sphere::Sphere},
+    simulation::Simulation,
+    sweep::{render_sweep, Sweep},
+    tonemap::{ToneMapOperator, ToneMapping},
+    transformations::view_transform,
+    tuple::Tuple,
+    world::{Integrator, World, DEFAULT_MAX_BOUNCES, DEFAULT_ROULETTE_THRESHOLD},
+};
+use serde::Serialize;
 use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::Path;
-use std::time::Instant;
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "raytracer-cli")]
 #[command(about = "A CLI raytracer for rendering single frames")]
 #[command(version = "0.1.0")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+
+    // Without a subcommand, the options are those of render
+    #[command(flatten)]
+    render: Box<Args>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Render a scene (the default)
+    Render(Box<Args>),
+    /// Quick look: direct lighting and hard shadows only, one sample per
+    /// pixel. Takes the same options as render.
+    Preview(Box<Args>),
+    /// Load scene files and report any errors, without rendering
+    Validate(ValidateArgs),
+}
+
+#[derive(clap::Args)]
+struct ValidateArgs {
+    /// Scene files, or built-in scene names
+    #[arg(required = true)]
+    scenes: Vec<String>,
+
+    /// Print one JSON object describing the results
+    #[arg(long)]
+    json_output: bool,
+}
+
+#[derive(Parser)]
 struct Args {
-    /// Output filename (PNG format)
+    /// Output filename (PNG, or .hdr/.exr to keep the full dynamic range
+    /// without tone mapping)
     #[arg(short, long, default_value = "output.png")]
     output: String,
 
+    /// Hold a .hdr/.exr frame in half floats while it renders, for a quarter
+    /// of the memory; each channel keeps 11 significant bits
+    #[arg(long)]
+    half_precision: bool,
+
     /// Image width in pixels
     #[arg(short, long, default_value = "800")]
     width: usize,
@@ -22,48 +89,558 @@ struct Args {
     #[arg(short = 'H', long, default_value = "600")]
     height: usize,
 
-    /// Scene to render (default, test, third)
+    /// Scene to render (default, test, third, or a JSON scene file)
     #[arg(short, long, default_value = "third")]
     scene: String,
 
+    /// Wavefront OBJ mesh to add to the scene
+    #[arg(long)]
+    obj: Option<String>,
+
+    /// Simplified version of the --obj mesh to use while the mesh is smaller
+    /// than the given size on screen, as PATH:PIXELS (repeatable)
+    #[arg(long)]
+    obj_lod: Vec<String>,
+
+    /// Draw the --obj mesh as its bounding sphere when it is smaller than this
+    /// many pixels across
+    #[arg(long)]
+    obj_impostor_pixels: Option<Float>,
+
+    /// Samples per pixel for anti-aliasing; the most per pixel with --adaptive
+    #[arg(long, default_value = "1")]
+    samples: usize,
+
+    /// Sample placement within each pixel (grid, jittered)
+    #[arg(long, default_value = "grid")]
+    sampling: String,
+
+    /// Seconds the shutter stays open, blurring shapes that have a velocity.
+    /// Use several --samples, as each one sees a different moment.
+    #[arg(long, default_value = "0.0")]
+    shutter: Float,
+
+    /// Camera projection (perspective, orthographic, fisheye, equirectangular).
+    /// Fisheye covers --fov across the shorter edge; equirectangular is a full
+    /// 360 degree panorama best rendered at 2:1.
+    #[arg(long, default_value = "perspective")]
+    projection: String,
+
+    /// Width of an orthographic view in scene units
+    #[arg(long, default_value = "10.0")]
+    ortho_width: Float,
+
+    /// Trace one ray per pixel and use --samples only where a pixel differs
+    /// from a neighbour by more than this in any colour channel
+    #[arg(long)]
+    adaptive: Option<Float>,
+
+    /// Tone mapping operator (clamp, reinhard, aces)
+    #[arg(long, default_value = "clamp")]
+    tonemap: String,
+
+    /// Exposure multiplier applied before tone mapping
+    #[arg(long, default_value = "1.0")]
+    exposure: Float,
+
+    /// Display gamma (1.0 leaves colours linear, 2.2 for sRGB-like output)
+    #[arg(long, default_value = "1.0")]
+    gamma: Float,
+
+    /// Encode output with the sRGB curve (overrides --gamma)
+    #[arg(long)]
+    srgb: bool,
+
+    /// End reflection chains dimmer than this at random (0 always recurses)
+    #[arg(long, default_value_t = DEFAULT_ROULETTE_THRESHOLD)]
+    roulette_threshold: Float,
+
+    /// From this many bounces deep, end each reflection or refraction at random
+    /// by its reflectivity or transparency; raise --max-bounces along with it
+    #[arg(long)]
+    roulette_depth: Option<u32>,
+
+    /// Reflections followed from each camera ray
+    #[arg(long, default_value_t = DEFAULT_MAX_BOUNCES)]
+    max_bounces: u32,
+
+    /// Light every surface as if nothing blocked the light
+    #[arg(long)]
+    no_shadows: bool,
+
+    /// Ignore hits closer than this along shadow and reflection rays, in scene
+    /// units (default 0.1mm, scaled by the scene's unit_scale)
+    #[arg(long)]
+    epsilon: Option<Float>,
+
+    /// Lift hit points this far off surfaces before tracing shadow and
+    /// reflection rays from them, in scene units (default scaled by the
+    /// scene's unit_scale)
+    #[arg(long)]
+    shadow_bias: Option<Float>,
+
+    /// Draw every shape's bounding box over the image
+    #[arg(long)]
+    show_bounds: bool,
+
+    /// Quick look: direct lighting and hard shadows only, one sample per pixel
+    #[arg(long)]
+    preview: bool,
+
+    /// Light transport: full (reflections, the default), preview, or path
+    /// (Monte Carlo global illumination; use plenty of --samples)
+    #[arg(long, default_value = "full")]
+    integrator: String,
+
+    /// Let light through transparent materials, tinted by their colour
+    #[arg(long)]
+    transparent_shadows: bool,
+
+    /// Render an orthographic overview instead (top, front, side)
+    #[arg(long)]
+    blueprint: Option<String>,
+
+    /// Render a debug view instead: depth, normal, object_id, shadow_mask, or
+    /// bounces (a heatmap of reflection and refraction rays per pixel)
+    #[arg(long)]
+    aov: Option<String>,
+
+    /// Colour grade the output with a .cube 3D LUT
+    #[arg(long)]
+    lut: Option<String>,
+
+    /// Keep each pixel's brightness when applying the LUT
+    #[arg(long)]
+    lut_preserve_luminance: bool,
+
+    /// Render a material contact sheet instead, sweeping a property as
+    /// name=from:to:steps (give twice for a grid); width and height are per cell
+    #[arg(long)]
+    sweep: Vec<String>,
+
     /// Field of view in degrees
     #[arg(short, long, default_value = "60")]
-    fov: f64,
+    fov: Float,
 
     /// Camera position (x,y,z)
-    #[arg(long, value_delimiter = ',', num_args = 3)]
-    camera_pos: Option<Vec<f64>>,
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    camera_pos: Option<Vec<Float>>,
 
     /// Camera look-at point (x,y,z)
-    #[arg(long, value_delimiter = ',', num_args = 3)]
-    camera_target: Option<Vec<f64>>,
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    camera_target: Option<Vec<Float>>,
 
     /// Camera up vector (x,y,z)
-    #[arg(long, value_delimiter = ',', num_args = 3)]
-    camera_up: Option<Vec<f64>>,
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    camera_up: Option<Vec<Float>>,
+
+    /// Render every entry of a JSON batch manifest instead of a single frame
+    #[arg(long)]
+    batch: Option<String>,
+
+    /// Number of batch entries to render at once, each in its own process
+    #[arg(long, default_value = "1")]
+    jobs: usize,
+
+    /// Render this many frames of an animation instead, orbiting the camera
+    /// around its target, or moving it to --camera-pos-end/--camera-target-end.
+    /// Keys in the scene file's "animation" are followed too.
+    /// Frames are numbered after the output, e.g. output_0001.png
+    #[arg(long)]
+    animate: Option<usize>,
+
+    /// Frames per second, setting the scene time of each animation frame
+    #[arg(long, default_value = "24")]
+    fps: Float,
+
+    /// Drop a ball into the animation that falls and bounces off the scene,
+    /// from X,Y,Z with an optional starting velocity VX,VY,VZ in units per
+    /// second (repeatable)
+    #[arg(long, allow_hyphen_values = true)]
+    ball: Vec<String>,
+
+    /// Radius of --ball balls
+    #[arg(long, default_value = "0.25")]
+    ball_radius: Float,
+
+    /// Fraction of a --ball ball's speed kept after each bounce
+    #[arg(long, default_value = "0.8")]
+    ball_restitution: Float,
+
+    /// Camera position on the last animation frame (x,y,z)
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    camera_pos_end: Option<Vec<Float>>,
+
+    /// Camera look-at point on the last animation frame (x,y,z)
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    camera_target_end: Option<Vec<Float>>,
+
+    /// Render in square tiles of this size, showing a progress bar
+    #[arg(long)]
+    tile_size: Option<usize>,
+
+    /// Save the rows rendered so far to this file every --checkpoint-interval
+    /// seconds, so an interrupted render can be finished with --resume
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value = "60")]
+    checkpoint_interval: f64,
+
+    /// Finish a render from a checkpoint file, with the same scene and
+    /// options it was started with. Checkpoints keep being saved to it.
+    #[arg(long)]
+    resume: Option<String>,
+
+    /// Keep running, and render again whenever the scene file changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Print more detail (camera setup, ray counts)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Print one JSON object describing the result instead of progress text
+    #[arg(long)]
+    json_output: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+// Progress text for people. Warnings and errors go to stderr regardless, so
+// stdout stays clean for --json-output.
+#[derive(Clone, Copy)]
+struct Log {
+    verbosity: Verbosity,
+}
+
+impl Log {
+    fn from_args(args: &Args) -> Log {
+        let verbosity = if args.quiet || args.json_output {
+            Verbosity::Quiet
+        } else if args.verbose > 0 {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+        Log { verbosity }
+    }
+
+    fn info(&self, message: impl std::fmt::Display) {
+        if self.verbosity >= Verbosity::Normal {
+            println!("{}", message);
+        }
+    }
+
+    fn detail(&self, message: impl std::fmt::Display) {
+        if self.verbosity >= Verbosity::Verbose {
+            println!("{}", message);
+        }
+    }
+
+    // Redraws a progress bar in place on stderr
+    fn progress(&self, done: usize, total: usize) {
+        if self.verbosity < Verbosity::Normal || total == 0 {
+            return;
+        }
+        const WIDTH: usize = 40;
+        let filled = done * WIDTH / total;
+        eprint!(
+            "\r[{}{}] {:>3}%",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            done * 100 / total
+        );
+        if done == total {
+            eprintln!();
+        }
+    }
+}
+
+// What --json-output prints for a single render
+#[derive(Serialize)]
+struct RenderReport {
+    status: &'static str,
+    output: String,
+    scene: String,
+    frames: usize,
+    width: usize,
+    height: usize,
+    seconds: f64,
+    rays_traced: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scene_hash: Option<String>,
+    // Only collected in builds with the "stats" feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<RenderStats>,
 }
 
 fn main() {
-    let args = Args::parse();
+    let cli = Cli::parse();
+    match cli.command {
+        Some(CliCommand::Render(args)) => run(&args),
+        Some(CliCommand::Preview(mut args)) => {
+            args.preview = true;
+            run(&args)
+        }
+        Some(CliCommand::Validate(validate_args)) => validate(&validate_args),
+        None => run(&cli.render),
+    }
+}
+
+fn run(args: &Args) {
+    if args.watch {
+        if let Err(e) = watch(args) {
+            report_error(args, &e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    println!("Starting raytracer...");
-    println!("Resolution: {}x{}", args.width, args.height);
-    println!("Scene: {}", args.scene);
-    println!("Output: {}", args.output);
+    let result = match &args.batch {
+        Some(manifest) => run_batch(manifest, args).and_then(|results| {
+            let failed = results.iter().any(|r| r.error.is_some());
+            if args.json_output {
+                let status = if failed { "error" } else { "ok" };
+                let report = serde_json::json!({ "status": status, "renders": results });
+                println!("{}", to_json(&report));
+            } else if !args.quiet {
+                print!("{}", summary(&results));
+            }
+            if failed && args.json_output {
+                // The report already says what failed
+                std::process::exit(1);
+            }
+            if failed {
+                return Err("Some renders failed".to_string());
+            }
+            Ok(())
+        }),
+        None => render(args).map(|report| {
+            if args.json_output {
+                println!("{}", to_json(&report));
+            }
+        }),
+    };
+    if let Err(e) = result {
+        report_error(args, &e);
+        std::process::exit(1);
+    }
+}
+
+fn report_error(args: &Args, e: &str) {
+    if args.json_output {
+        println!(
+            "{}",
+            to_json(&serde_json::json!({ "status": "error", "error": e }))
+        );
+    } else {
+        eprintln!("{}", e);
+    }
+}
+
+// Renders, then renders again each time the scene file is saved, until
+// killed. Failed renders are reported without stopping, since a scene file
+// that's part way through being edited often doesn't parse.
+fn watch(args: &Args) -> Result<(), String> {
+    if args.batch.is_some() || args.resume.is_some() {
+        return Err("--watch can't be used with --batch or --resume".to_string());
+    }
+    let scene = Path::new(&args.scene)
+        .canonicalize()
+        .ok()
+        .filter(|path| path.is_file())
+        .ok_or_else(|| format!("--watch needs a scene file, not '{}'", args.scene))?;
+
+    // Editors often save by writing a new file over the old one, which a watch
+    // on the file itself would lose track of, so its directory is watched
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| format!("Failed to watch {}: {}", args.scene, e))?;
+    let directory = scene.parent().unwrap_or(Path::new("/"));
+    notify::Watcher::watch(&mut watcher, directory, notify::RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", args.scene, e))?;
+
+    let log = Log::from_args(args);
+    loop {
+        match render(args) {
+            Ok(report) if args.json_output => println!("{}", to_json(&report)),
+            Ok(_) => {}
+            Err(e) => report_error(args, &e),
+        }
+        log.info(format!("Watching {} for changes...", args.scene));
+        wait_for_change(&receiver, &scene)?;
+    }
+}
+
+// Blocks until the file is created or modified. A save is often several
+// events in quick succession, so they're let settle before returning.
+fn wait_for_change(
+    receiver: &Receiver<notify::Result<notify::Event>>,
+    file: &Path,
+) -> Result<(), String> {
+    let changed = |event: notify::Result<notify::Event>| {
+        event.is_ok_and(|event| {
+            (event.kind.is_create() || event.kind.is_modify())
+                && event.paths.iter().any(|path| path == file)
+        })
+    };
+    loop {
+        let event = receiver
+            .recv()
+            .map_err(|_| "Stopped watching for changes".to_string())?;
+        if changed(event) {
+            break;
+        }
+    }
+    while receiver.recv_timeout(Duration::from_millis(100)).is_ok() {}
+    Ok(())
+}
+
+// What validate prints for each scene with --json-output
+#[derive(Serialize)]
+struct ValidationReport {
+    scene: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    objects: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// Loads each scene the way render would, textures and all, and exits with an
+// error if any fail
+fn validate(args: &ValidateArgs) {
+    let reports: Vec<ValidationReport> = args
+        .scenes
+        .iter()
+        .map(|scene| {
+            let world = if Path::new(scene).is_file() {
+                load_scene_file(scene)
+            } else {
+                World::from_name(scene).ok_or_else(|| "No such file or built-in scene".to_string())
+            };
+            ValidationReport {
+                scene: scene.clone(),
+                objects: world.as_ref().ok().map(|world| world.registry.len()),
+                error: world.err(),
+            }
+        })
+        .collect();
+    let failed = reports.iter().any(|report| report.error.is_some());
+
+    if args.json_output {
+        let status = if failed { "error" } else { "ok" };
+        let report = serde_json::json!({ "status": status, "scenes": reports });
+        println!("{}", to_json(&report));
+    } else {
+        for report in &reports {
+            match (&report.error, report.objects) {
+                (Some(e), _) => eprintln!("{}: {}", report.scene, e),
+                (None, Some(objects)) => println!("{}: OK, {} objects", report.scene, objects),
+                (None, None) => println!("{}: OK", report.scene),
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("Reports always serialise")
+}
+
+fn render(args: &Args) -> Result<RenderReport, String> {
+    let log = Log::from_args(args);
+    log.info("Starting raytracer...");
+    log.info(format!("Resolution: {}x{}", args.width, args.height));
+    log.info(format!("Scene: {}", args.scene));
+    log.info(format!("Output: {}", args.output));
 
     // Create the world based on the scene parameter
-    let world = match args.scene.as_str() {
-        "default" => World::default_world(),
-        "test" => World::test_world(),
-        "third" => World::third_world(),
-        _ => {
+    let mut world = if Path::new(&args.scene).is_file() {
+        load_scene_file(&args.scene)?
+    } else {
+        World::from_name(&args.scene).unwrap_or_else(|| {
             eprintln!("Unknown scene '{}'. Using 'third' scene.", args.scene);
             World::third_world()
-        }
+        })
     };
 
+    if let Some(obj_path) = &args.obj {
+        log.detail(format!("Loading mesh: {}", obj_path));
+        let mesh = parse_obj_file(obj_path)?;
+        if args.obj_lod.is_empty() && args.obj_impostor_pixels.is_none() {
+            world.add_object(mesh);
+        } else {
+            world.add_object(mesh_lod(args, mesh, &log)?);
+        }
+        world.build_bvh();
+    }
+    world.settings.show_bounds = args.show_bounds;
+    world.settings.transparent_shadows = args.transparent_shadows;
+    world.settings.roulette_threshold = args.roulette_threshold;
+    world.settings.roulette_depth = args.roulette_depth;
+    world.settings.max_bounces = args.max_bounces;
+    world.settings.shadows = !args.no_shadows;
+    if let Some(epsilon) = args.epsilon {
+        world.settings.secondary_t_min = Some(epsilon);
+    }
+    if let Some(bias) = args.shadow_bias {
+        world.settings.shadow_bias = Some(bias);
+    }
+
+    let tone_operator = ToneMapOperator::from_name(&args.tonemap).unwrap_or_else(|| {
+        eprintln!("Unknown tone mapping '{}'. Using 'clamp'.", args.tonemap);
+        ToneMapOperator::Clamp
+    });
+    let mut tone_mapping = ToneMapping::new(tone_operator, args.exposure, args.gamma);
+    tone_mapping.srgb = args.srgb;
+    if let Some(lut_path) = &args.lut {
+        log.detail(format!("Loading LUT: {}", lut_path));
+        let mut lut = ColourLut::load(lut_path)?;
+        lut.preserve_luminance = args.lut_preserve_luminance;
+        tone_mapping.lut = Some(Rc::new(lut));
+    }
+
     // Create camera
     let mut camera = Camera::new(args.width, args.height, args.fov.to_radians());
+    let sampling = SamplingMode::from_name(&args.sampling).unwrap_or_else(|| {
+        eprintln!("Unknown sampling mode '{}'. Using 'grid'.", args.sampling);
+        SamplingMode::Grid
+    });
+    camera.shutter = args.shutter.max(0.0);
+    camera.projection = match Projection::from_name(&args.projection) {
+        Some(Projection::Orthographic { .. }) => Projection::Orthographic {
+            view_width: args.ortho_width,
+        },
+        Some(projection) => projection,
+        None => {
+            eprintln!(
+                "Unknown projection '{}'. Using 'perspective'.",
+                args.projection
+            );
+            Projection::Perspective
+        }
+    };
+    set_sampling(&mut world, &mut camera, args, sampling);
+    world.settings.integrator = Integrator::from_name(&args.integrator).unwrap_or_else(|| {
+        eprintln!("Unknown integrator '{}'. Using 'full'.", args.integrator);
+        Integrator::Full
+    });
+    if args.preview {
+        world.settings.integrator = Integrator::Preview;
+    }
 
     // Set up camera position and orientation
     let camera_pos = args
@@ -105,59 +682,854 @@ fn main() {
         })
         .unwrap_or_else(|| Tuple::vector(0.0, 1.0, 0.0));
 
-    println!(
+    log.detail(format!(
         "Camera position: ({:.2}, {:.2}, {:.2})",
         camera_pos.x, camera_pos.y, camera_pos.z
-    );
-    println!(
+    ));
+    log.detail(format!(
         "Camera target: ({:.2}, {:.2}, {:.2})",
         camera_target.x, camera_target.y, camera_target.z
-    );
-    println!(
+    ));
+    log.detail(format!(
         "Camera up: ({:.2}, {:.2}, {:.2})",
         camera_up.x, camera_up.y, camera_up.z
-    );
+    ));
 
     camera.set_transform(view_transform(camera_pos, camera_target, camera_up));
 
+    let settings = HashedSettings::new(args, &camera, [camera_pos, camera_target, camera_up]);
+    let scene_hash = match world.scene_hash(&settings) {
+        Ok(hash) => {
+            log.info(format!("Scene hash: {}", hash));
+            Some(hash)
+        }
+        Err(e) => {
+            log.detail(format!("No scene hash: {}", e));
+            None
+        }
+    };
+    let hash = scene_hash.as_deref();
+    let storage = match args.half_precision {
+        true => CanvasStorage::Half,
+        false => CanvasStorage::Full,
+    };
+
+    // Create output directory if it doesn't exist
+    if let Some(parent) = Path::new(&args.output).parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create output directory: {}", e))?;
+        }
+    }
+
+    let checkpoint_path = args.checkpoint.as_ref().or(args.resume.as_ref());
+    if checkpoint_path.is_some()
+        && (args.blueprint.is_some()
+            || args.aov.is_some()
+            || !args.sweep.is_empty()
+            || args.tile_size.is_some()
+            || args.animate.is_some())
+    {
+        return Err("--checkpoint and --resume only work for single-frame renders".to_string());
+    }
+
+    if let Some(view_name) = &args.blueprint {
+        let view = BlueprintView::from_name(view_name).unwrap_or_else(|| {
+            eprintln!("Unknown blueprint view '{}'. Using 'top'.", view_name);
+            BlueprintView::Top
+        });
+        log.info(format!("Rendering {:?} blueprint...", view));
+        let start_time = Instant::now();
+        let canvas = render_blueprint(&world, Some(&camera), view, args.width, args.height);
+        save_canvas(&canvas, &args.output, &tone_mapping, hash)?;
+        log.info("Image saved successfully!");
+        return Ok(report(
+            args,
+            canvas.width,
+            canvas.height,
+            start_time,
+            &world,
+            scene_hash,
+        ));
+    }
+
+    if let Some(name) = &args.aov {
+        let aov = Aov::from_name(name).ok_or_else(|| format!("Unknown AOV '{}'", name))?;
+        log.info(format!("Rendering {} AOV...", aov.name()));
+        let start_time = Instant::now();
+        let canvas = aov.render(&world, &camera);
+        // The values are the point, so they're saved without tone mapping
+        save_canvas(&canvas, &args.output, &ToneMapping::default(), hash)?;
+        log.info("Image saved successfully!");
+        return Ok(report(
+            args,
+            canvas.width,
+            canvas.height,
+            start_time,
+            &world,
+            scene_hash,
+        ));
+    }
+
+    if !args.sweep.is_empty() {
+        let sweeps = args
+            .sweep
+            .iter()
+            .map(|spec| Sweep::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if sweeps.len() > 2 {
+            return Err("At most two --sweep parameters can be given".to_string());
+        }
+        log.info("Rendering material sweep...");
+        let start_time = Instant::now();
+        let sheet = render_sweep(&camera, &Material::new(), &sweeps[0], sweeps.get(1));
+        save_canvas(&sheet, &args.output, &tone_mapping, None)?;
+        log.info("Image saved successfully!");
+        // The sweep renders its own preview worlds, so there's no ray count or
+        // scene hash
+        return Ok(report(
+            args,
+            sheet.width,
+            sheet.height,
+            start_time,
+            &World::new(),
+            None,
+        ));
+    }
+
+    if let Some(tile_size) = args.tile_size {
+        log.info("Rendering...");
+        let start_time = Instant::now();
+        let mut canvas = Canvas::new(args.width, args.height);
+        let total = camera.tiles(tile_size).len();
+        let mut done = 0;
+        camera.render_tiled(&world, tile_size, |tile, pixels| {
+            for (i, colour) in pixels.iter().enumerate() {
+                canvas.write_pixel(tile.x + i % tile.width, tile.y + i / tile.width, *colour);
+            }
+            done += 1;
+            log.progress(done, total);
+        });
+        save_canvas(&canvas, &args.output, &tone_mapping, hash)?;
+        log.info(format!(
+            "Total time: {:.2}s",
+            start_time.elapsed().as_secs_f64()
+        ));
+        log.info("Image saved successfully!");
+        return Ok(report(
+            args,
+            canvas.width,
+            canvas.height,
+            start_time,
+            &world,
+            scene_hash,
+        ));
+    }
+
+    if let Some(frames) = args.animate {
+        if !(args.fps > 0.0 && args.fps.is_finite()) {
+            return Err(format!("--fps must be positive, got {}", args.fps));
+        }
+        // Checked as --camera-pos and --camera-target are, falling back to
+        // the start of the path
+        let end_point = |values: &Option<Vec<Float>>, what: &str, start: Tuple| match values {
+            Some(values) if values.len() == 3 => xyz(values, Tuple::point),
+            Some(_) => {
+                eprintln!(
+                    "Camera {} must have exactly 3 values (x,y,z). Using the start.",
+                    what
+                );
+                start
+            }
+            None => start,
+        };
+        let path = match (&args.camera_pos_end, &args.camera_target_end) {
+            (None, None) => CameraPath::Orbit {
+                from: camera_pos,
+                to: camera_target,
+            },
+            (end_pos, end_target) => CameraPath::Between {
+                from: camera_pos,
+                to: camera_target,
+                end_from: end_point(end_pos, "end position", camera_pos),
+                end_to: end_point(end_target, "end target", camera_target),
+            },
+        };
+        // Simulated against the scene before the balls themselves join it
+        let ball_paths = simulate_balls(args, &world, frames)?;
+        let ball_ids: Vec<u32> = ball_paths
+            .iter()
+            .map(|_| world.add_object(Sphere::new()))
+            .collect();
+        let rebuild_bvh = world.has_bvh();
+
+        log.info(format!("Rendering {} frames...", frames));
+        let start_time = Instant::now();
+        for frame in 0..frames {
+            let (from, to) = path.pose(frame, frames);
+            camera.set_transform(view_transform(from, to, camera_up));
+            world.time = frame as Float / args.fps;
+            for (id, path) in ball_ids.iter().zip(&ball_paths) {
+                let (p, r) = (path[frame], args.ball_radius);
+                // Blurred along the way to the next frame's position
+                let velocity = path
+                    .get(frame + 1)
+                    .filter(|_| camera.shutter > 0.0)
+                    .map(|next| (*next - p) * args.fps);
+                if let Some(ball) = world.registry.get_mut(*id) {
+                    ball.set_transform(
+                        &Matrix::translation(p.x, p.y, p.z) * &Matrix::scaling(r, r, r),
+                    );
+                    ball.set_velocity(velocity);
+                }
+            }
+            if rebuild_bvh && !ball_ids.is_empty() {
+                world.build_bvh();
+            }
+            // Keys from the scene file, including any camera keys, which take
+            // over from the camera path
+            let (keyed_world, keyed_camera) = match &world.animation {
+                Some(animation) => (
+                    Some(world.at_time(world.time)),
+                    animation.camera_at(&camera, world.time),
+                ),
+                None => (None, camera.clone()),
+            };
+            let output = frame_path(&args.output, frame);
+            render_to_file(
+                &keyed_camera.at_time(world.time),
+                keyed_world.as_ref().unwrap_or(&world),
+                &output,
+                &tone_mapping,
+                storage,
+                hash,
+            )?;
+            log.progress(frame + 1, frames);
+        }
+        let total_time = start_time.elapsed();
+        log.info(format!("Total time: {:.2}s", total_time.as_secs_f64()));
+        log.info("Frames saved successfully!");
+        return Ok(RenderReport {
+            output: frame_path(&args.output, 0).display().to_string(),
+            frames,
+            ..report(
+                args,
+                args.width,
+                args.height,
+                start_time,
+                &world,
+                scene_hash,
+            )
+        });
+    }
+
     // Render the scene
-    println!("Rendering...");
+    log.info("Rendering...");
     let start_time = Instant::now();
+    match checkpoint_path {
+        Some(path) => {
+            let canvas = render_with_checkpoints(&camera, &world, args, path, hash, &log)?;
+            save_canvas(&canvas, &args.output, &tone_mapping, hash)?;
+            // The image is safely saved, so the checkpoint isn't needed. There
+            // won't be one if the render took less than an interval.
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    eprintln!("Warning: failed to remove checkpoint: {}", e)
+                }
+                _ => {}
+            }
+        }
+        None => render_to_file(
+            &camera,
+            &world,
+            Path::new(&args.output),
+            &tone_mapping,
+            storage,
+            hash,
+        )?,
+    }
 
-    let canvas = camera.render(&world);
+    let total_time = start_time.elapsed();
+    log.info(format!("Total time: {:.2}s", total_time.as_secs_f64()));
+    log.detail(format!("Rays traced: {}", world.rays_traced()));
+    if let Some(stats) = render_stats(&world) {
+        log.detail(format!("Intersection tests: {}", stats.intersection_tests));
+        log.detail(format!("Shadow rays: {}", stats.shadow_rays));
+        log.detail(format!(
+            "Reflection rays by depth: {:?}",
+            stats.reflection_depths
+        ));
+    }
+    log.info("Image saved successfully!");
+    Ok(report(
+        args,
+        args.width,
+        args.height,
+        start_time,
+        &world,
+        scene_hash,
+    ))
+}
 
-    let render_time = start_time.elapsed();
-    println!("Render completed in {:.2}s", render_time.as_secs_f64());
+// Samples per pixel from --samples, or the most per pixel under --adaptive.
+// --preview overrides both with one sample.
+fn set_sampling(world: &mut World, camera: &mut Camera, args: &Args, sampling: SamplingMode) {
+    camera.sampling = sampling;
+    world.settings.samples = match args.adaptive {
+        Some(threshold) if !args.preview => {
+            camera.set_adaptive(threshold, args.samples);
+            1
+        }
+        _ if args.preview => 1,
+        _ => args.samples.max(1),
+    };
+}
+
+// Render options that change the image, hashed along with the world and its
+// RenderSettings. Sampling is taken from the camera and the world, after
+// --preview and --adaptive have had their say.
+#[derive(Serialize)]
+struct HashedSettings<'a> {
+    width: usize,
+    height: usize,
+    fov: Float,
+    // Position, target and up
+    camera: [[Float; 3]; 3],
+    camera_pos_end: Option<&'a [Float]>,
+    camera_target_end: Option<&'a [Float]>,
+    sampling: &'a str,
+    // Threshold and the most samples per pixel
+    adaptive: Option<(Float, usize)>,
+    shutter: Float,
+    projection: &'a str,
+    ortho_width: Float,
+    tonemap: &'a str,
+    exposure: Float,
+    gamma: Float,
+    srgb: bool,
+    lut: Option<&'a str>,
+    lut_preserve_luminance: bool,
+    half_precision: bool,
+    blueprint: Option<&'a str>,
+    aov: Option<&'a str>,
+    sweep: &'a [String],
+    animate: Option<usize>,
+    fps: Float,
+    balls: &'a [String],
+    ball_radius: Float,
+    ball_restitution: Float,
+}
+
+impl<'a> HashedSettings<'a> {
+    fn new(args: &'a Args, camera: &Camera, view: [Tuple; 3]) -> HashedSettings<'a> {
+        HashedSettings {
+            width: args.width,
+            height: args.height,
+            fov: args.fov,
+            camera: view.map(|t| [t.x, t.y, t.z]),
+            camera_pos_end: args.camera_pos_end.as_deref(),
+            camera_target_end: args.camera_target_end.as_deref(),
+            sampling: &args.sampling,
+            adaptive: camera
+                .adaptive
+                .map(|adaptive| (adaptive.threshold, adaptive.max_samples)),
+            shutter: args.shutter,
+            projection: &args.projection,
+            ortho_width: args.ortho_width,
+            tonemap: &args.tonemap,
+            exposure: args.exposure,
+            gamma: args.gamma,
+            srgb: args.srgb,
+            lut: args.lut.as_deref(),
+            lut_preserve_luminance: args.lut_preserve_luminance,
+            half_precision: args.half_precision,
+            blueprint: args.blueprint.as_deref(),
+            aov: args.aov.as_deref(),
+            sweep: &args.sweep,
+            animate: args.animate,
+            fps: args.fps,
+            balls: &args.ball,
+            ball_radius: args.ball_radius,
+            ball_restitution: args.ball_restitution,
+        }
+    }
+}
+
+// Wraps the --obj mesh with its --obj-lod versions and impostor
+fn mesh_lod(args: &Args, mesh: Group, log: &Log) -> Result<Lod, String> {
+    // Apparent sizes are angles, and a pixel covers pixel_size radians
+    let pixel_size = Camera::new(args.width, args.height, args.fov.to_radians()).pixel_size;
+    let mut lod = Lod::new(Box::new(mesh));
+    for level in &args.obj_lod {
+        let (path, pixels) = level
+            .rsplit_once(':')
+            .and_then(|(path, pixels)| Some((path, pixels.parse::<Float>().ok()?)))
+            .ok_or_else(|| format!("--obj-lod '{}' should be PATH:PIXELS", level))?;
+        log.detail(format!(
+            "Loading LOD mesh: {} (below {} pixels)",
+            path, pixels
+        ));
+        lod.add_level(pixels * pixel_size, Box::new(parse_obj_file(path)?));
+    }
+    if let Some(pixels) = args.obj_impostor_pixels {
+        lod.set_impostor_below(pixels * pixel_size);
+    }
+    Ok(lod)
+}
+
+// Centre of each --ball ball on every frame, bouncing off the world's shapes
+// under gravity
+fn simulate_balls(args: &Args, world: &World, frames: usize) -> Result<Vec<Vec<Tuple>>, String> {
+    let balls = args
+        .ball
+        .iter()
+        .map(|spec| {
+            let values = spec
+                .split(',')
+                .map(|v| v.trim().parse::<Float>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .filter(|v| v.len() == 3 || v.len() == 6)
+                .ok_or_else(|| format!("--ball '{}' should be X,Y,Z or X,Y,Z,VX,VY,VZ", spec))?;
+            let velocity = match values.len() {
+                6 => xyz(&values[3..], Tuple::vector),
+                _ => Tuple::vector(0.0, 0.0, 0.0),
+            };
+            let mut ball = Projectile::new(xyz(&values, Tuple::point), velocity);
+            ball.radius = args.ball_radius;
+            ball.restitution = args.ball_restitution;
+            Ok(ball)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if balls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Earth gravity, in scene units
+    let gravity = Tuple::vector(0.0, -9.8 / world.unit_scale, 0.0);
+    let mut simulation = Simulation::new(
+        Environment::new(gravity, Tuple::vector(0.0, 0.0, 0.0)),
+        balls,
+    );
+    let mut paths = vec![Vec::with_capacity(frames); simulation.get_projectiles().len()];
+    for _ in 0..frames {
+        for (path, ball) in paths.iter_mut().zip(simulation.get_projectiles()) {
+            path.push(ball.pos);
+        }
+        simulation.step_in(world, 1.0 / args.fps);
+    }
+    Ok(paths)
+}
+
+fn xyz(values: &[Float], make: fn(Float, Float, Float) -> Tuple) -> Tuple {
+    make(values[0], values[1], values[2])
+}
+
+fn render_to_file(
+    camera: &Camera,
+    world: &World,
+    output: &Path,
+    tone_mapping: &ToneMapping,
+    storage: CanvasStorage,
+    scene_hash: Option<&str>,
+) -> Result<(), String> {
+    // HDR formats take the linear colours before tone mapping, so the whole
+    // frame is held until it's saved
+    if is_hdr_path(output) {
+        return camera.render_with_storage(world, storage).save_hdr(output);
+    }
+
+    let (width, height) = (camera.hsize, camera.vsize);
+    let is_png = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+
+    if is_png {
+        // Encode each scanline as soon as it's rendered rather than holding the frame
+        let mut writer = png_writer(output, width, height, scene_hash)?;
+        let mut stream = writer
+            .stream_writer()
+            .map_err(|e| format!("Failed to start PNG stream: {}", e))?;
+
+        let mut bytes = Vec::with_capacity(width * 4);
+        let mut write_result = Ok(());
+        camera.render_with(world, |_, row| {
+            if write_result.is_err() {
+                return;
+            }
+            bytes.clear();
+            for colour in row {
+                bytes.extend_from_slice(&tone_mapping.to_rgba8(*colour));
+            }
+            write_result = stream.write_all(&bytes);
+        });
+        write_result.map_err(|e| format!("Failed to write image row: {}", e))?;
+        stream
+            .finish()
+            .map_err(|e| format!("Failed to finish PNG: {}", e))?;
+    } else {
+        let mut img_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
+            ImageBuffer::new(width as u32, height as u32);
+
+        camera.render_with(world, |y, row| {
+            for (x, colour) in row.iter().enumerate() {
+                img_buffer.put_pixel(x as u32, y as u32, Rgba(tone_mapping.to_rgba8(*colour)));
+            }
+        });
+
+        img_buffer
+            .save(output)
+            .map_err(|e| format!("Failed to save image: {}", e))?;
+    }
+    Ok(())
+}
+
+// Renders row by row, saving the rows done so far to path every
+// --checkpoint-interval seconds. With --resume, carries on from the rows
+// already in the checkpoint.
+fn render_with_checkpoints(
+    camera: &Camera,
+    world: &World,
+    args: &Args,
+    path: &str,
+    scene_hash: Option<&str>,
+    log: &Log,
+) -> Result<Canvas, String> {
+    let (width, height) = (camera.hsize, camera.vsize);
+    let mut checkpoint = match &args.resume {
+        Some(resume) => {
+            let checkpoint = Checkpoint::load(resume)?;
+            checkpoint.check_matches(width, height, scene_hash)?;
+            log.info(format!(
+                "Resuming from row {} of {}",
+                checkpoint.rows_done(),
+                height
+            ));
+            checkpoint
+        }
+        None => Checkpoint::new(width, height, scene_hash.map(str::to_string)),
+    };
+
+    let interval = args.checkpoint_interval.max(0.0);
+    let mut last_saved = Instant::now();
+    camera.render_rows_from(world, checkpoint.rows_done(), |y, row| {
+        checkpoint.add_row(row);
+        log.progress(y + 1, height);
+        if !checkpoint.is_complete() && last_saved.elapsed().as_secs_f64() >= interval {
+            // A failed save shouldn't throw away the render itself
+            match checkpoint.save(path) {
+                Ok(()) => log.detail(format!("Checkpoint saved at row {}", y + 1)),
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+            last_saved = Instant::now();
+        }
+    });
+    Ok(checkpoint.to_canvas())
+}
+
+fn report(
+    args: &Args,
+    width: usize,
+    height: usize,
+    start_time: Instant,
+    world: &World,
+    scene_hash: Option<String>,
+) -> RenderReport {
+    RenderReport {
+        status: "ok",
+        output: args.output.clone(),
+        scene: args.scene.clone(),
+        frames: 1,
+        width,
+        height,
+        seconds: start_time.elapsed().as_secs_f64(),
+        rays_traced: world.rays_traced(),
+        scene_hash,
+        stats: render_stats(world),
+    }
+}
+
+#[cfg(feature = "stats")]
+fn render_stats(world: &World) -> Option<RenderStats> {
+    Some(world.render_stats())
+}
+
+#[cfg(not(feature = "stats"))]
+fn render_stats(_world: &World) -> Option<RenderStats> {
+    None
+}
+
+// Starts a PNG, recording the scene hash in a SceneHash text chunk
+fn png_writer(
+    output: &Path,
+    width: usize,
+    height: usize,
+    scene_hash: Option<&str>,
+) -> Result<png::Writer<BufWriter<fs::File>>, String> {
+    let file =
+        fs::File::create(output).map_err(|e| format!("Failed to create output file: {}", e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(hash) = scene_hash {
+        encoder
+            .add_text_chunk("SceneHash".to_string(), hash.to_string())
+            .map_err(|e| format!("Failed to add PNG metadata: {}", e))?;
+    }
+    encoder
+        .write_header()
+        .map_err(|e| format!("Failed to write PNG header: {}", e))
+}
+
+fn save_canvas(
+    canvas: &Canvas,
+    path: &str,
+    tone_mapping: &ToneMapping,
+    scene_hash: Option<&str>,
+) -> Result<(), String> {
+    let path = Path::new(path);
+    if is_hdr_path(path) {
+        return canvas.save_hdr(path);
+    }
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+    {
+        let mut bytes = Vec::with_capacity(canvas.width * canvas.height * 4);
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                bytes.extend_from_slice(&tone_mapping.to_rgba8(canvas.pixel_at(x, y)));
+            }
+        }
+        return png_writer(path, canvas.width, canvas.height, scene_hash)?
+            .write_image_data(&bytes)
+            .map_err(|e| format!("Failed to save image: {}", e));
+    }
 
-    // Convert canvas to image buffer
-    println!("Converting to image format...");
     let mut img_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        ImageBuffer::new(args.width as u32, args.height as u32);
+        ImageBuffer::new(canvas.width as u32, canvas.height as u32);
+    for (x, y, pixel) in img_buffer.enumerate_pixels_mut() {
+        *pixel = Rgba(tone_mapping.to_rgba8(canvas.pixel_at(x as usize, y as usize)));
+    }
+    img_buffer
+        .save(path)
+        .map_err(|e| format!("Failed to save image: {}", e))
+}
+
+// Renders each manifest entry with the same code path as a single frame. With
+// more than one job, entries are handed to child processes of this binary so
+// that renders run side by side.
+fn run_batch(manifest_path: &str, batch_args: &Args) -> Result<Vec<BatchResult>, String> {
+    let log = Log::from_args(batch_args);
+    let jobs = batch_args.jobs;
+    let manifest = BatchManifest::load(manifest_path)?;
+    let base_dir = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    log.info(format!(
+        "Rendering {} scenes from {}",
+        manifest.renders.len(),
+        manifest_path
+    ));
 
-    for y in 0..args.height {
-        for x in 0..args.width {
-            let colour = canvas.pixel_at(x, y);
-            let r = (colour.r.clamp(0.0, 1.0) * 255.0) as u8;
-            let g = (colour.g.clamp(0.0, 1.0) * 255.0) as u8;
-            let b = (colour.b.clamp(0.0, 1.0) * 255.0) as u8;
-            let a = 255u8;
+    // Indexed by manifest entry, as jobs can finish in any order
+    let mut results = Vec::new();
+    let mut running: Vec<(usize, String, Instant, Child)> = Vec::new();
 
-            img_buffer.put_pixel(x as u32, y as u32, Rgba([r, g, b, a]));
+    for (index, entry) in manifest.renders.iter().enumerate() {
+        let entry_args = entry.to_args(base_dir);
+        let output = base_dir.join(&entry.output).display().to_string();
+        let start = Instant::now();
+
+        // Don't let a typo silently fall back to the default scene
+        if !base_dir.join(&entry.scene).is_file() && World::from_name(&entry.scene).is_none() {
+            results.push((
+                index,
+                BatchResult {
+                    output,
+                    seconds: 0.0,
+                    error: Some(format!("Unknown scene '{}'", entry.scene)),
+                    scene_hash: None,
+                },
+            ));
+            continue;
+        }
+
+        if jobs <= 1 {
+            let rendered = Args::try_parse_from(
+                std::iter::once("raytracer-cli".to_string()).chain(entry_args),
+            )
+            .map_err(|e| e.to_string())
+            .and_then(|mut args| {
+                // Entries inherit the batch's verbosity
+                args.quiet = log.verbosity == Verbosity::Quiet;
+                args.verbose = batch_args.verbose;
+                render(&args)
+            });
+            results.push((
+                index,
+                BatchResult {
+                    output,
+                    seconds: start.elapsed().as_secs_f64(),
+                    scene_hash: rendered.as_ref().ok().and_then(|r| r.scene_hash.clone()),
+                    error: rendered.err(),
+                },
+            ));
+            continue;
+        }
+
+        if running.len() >= jobs {
+            results.push(wait_for_any(&mut running));
+        }
+        let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+        // The child's JSON report carries its scene hash back
+        match Command::new(exe)
+            .arg("render")
+            .args(&entry_args)
+            .arg("--json-output")
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => running.push((index, output, start, child)),
+            Err(e) => results.push((
+                index,
+                BatchResult {
+                    output,
+                    seconds: 0.0,
+                    error: Some(format!("Failed to start renderer: {}", e)),
+                    scene_hash: None,
+                },
+            )),
         }
     }
+    while !running.is_empty() {
+        results.push(wait_for_any(&mut running));
+    }
 
-    // Create output directory if it doesn't exist
-    if let Some(parent) = Path::new(&args.output).parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent).expect("Failed to create output directory");
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+// Waits for whichever child exits first, polling as std can't wait on several
+fn wait_for_any(running: &mut Vec<(usize, String, Instant, Child)>) -> (usize, BatchResult) {
+    loop {
+        let finished = running
+            .iter_mut()
+            .position(|(_, _, _, child)| !matches!(child.try_wait(), Ok(None)));
+        if let Some(i) = finished {
+            let (index, output, start, child) = running.swap_remove(i);
+            return (index, wait_for(output, start, child));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn wait_for(output: String, start: Instant, child: Child) -> BatchResult {
+    let (error, report) = match child.wait_with_output() {
+        Ok(finished) => {
+            let report: serde_json::Value =
+                serde_json::from_slice(&finished.stdout).unwrap_or_default();
+            let error = if finished.status.success() {
+                None
+            } else {
+                Some(report["error"].as_str().map_or_else(
+                    || format!("Renderer exited with {}", finished.status),
+                    str::to_string,
+                ))
+            };
+            (error, report)
         }
+        Err(e) => (Some(e.to_string()), serde_json::Value::Null),
+    };
+    BatchResult {
+        output,
+        seconds: start.elapsed().as_secs_f64(),
+        error,
+        scene_hash: report["scene_hash"].as_str().map(str::to_string),
     }
+}
 
-    // Save the image
-    println!("Saving image to {}...", args.output);
-    img_buffer.save(&args.output).expect("Failed to save image");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let total_time = start_time.elapsed();
-    println!("Total time: {:.2}s", total_time.as_secs_f64());
-    println!("Image saved successfully!");
+    fn hash(options: &[&str]) -> String {
+        let args = Args::parse_from(["raytracer-cli"].iter().chain(options));
+        let mut camera = Camera::new(args.width, args.height, args.fov.to_radians());
+        let mut world = World::new();
+        set_sampling(&mut world, &mut camera, &args, SamplingMode::Grid);
+        let view = [
+            Tuple::point(0.0, 1.5, -5.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ];
+        world
+            .scene_hash(&HashedSettings::new(&args, &camera, view))
+            .unwrap()
+    }
+
+    #[test]
+    fn every_setting_that_changes_the_image_changes_the_hash() {
+        let base = hash(&["--lut", "grade.cube", "--samples", "4"]);
+        for options in [
+            &[
+                "--lut",
+                "grade.cube",
+                "--samples",
+                "4",
+                "--lut-preserve-luminance",
+            ][..],
+            &[
+                "--lut",
+                "grade.cube",
+                "--samples",
+                "4",
+                "--camera-pos-end",
+                "1,2,3",
+            ],
+            &[
+                "--lut",
+                "grade.cube",
+                "--samples",
+                "4",
+                "--camera-target-end",
+                "1,2,3",
+            ],
+            &["--lut", "grade.cube", "--samples", "4", "--preview"],
+            &["--lut", "grade.cube", "--samples", "4", "--adaptive", "0.1"],
+            &["--lut", "grade.cube", "--samples", "4", "--aov", "depth"],
+            &["--lut", "grade.cube", "--samples", "4", "--half-precision"],
+            &[
+                "--lut",
+                "grade.cube",
+                "--samples",
+                "4",
+                "--blueprint",
+                "top",
+            ],
+            &[
+                "--lut",
+                "grade.cube",
+                "--samples",
+                "4",
+                "--sweep",
+                "reflective=0:1:3",
+            ],
+        ] {
+            assert_ne!(hash(options), base, "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn samples_are_hashed_as_rendered() {
+        // --preview renders one sample whatever --samples says
+        assert_eq!(
+            hash(&["--preview", "--samples", "4"]),
+            hash(&["--preview", "--samples", "16"])
+        );
+        assert_ne!(
+            hash(&["--adaptive", "0.1", "--samples", "4"]),
+            hash(&["--adaptive", "0.1", "--samples", "16"])
+        );
+    }
 }
@@ -0,0 +1,305 @@
+use crate::{
+    bounds::BoundingBox,
+    camera::{Camera, Canvas},
+    colour::Colour,
+    ray::Ray,
+    scalar::Float,
+    tuple::Tuple,
+    world::World,
+};
+
+const BACKGROUND: Colour = Colour {
+    r: 0.05,
+    g: 0.15,
+    b: 0.35,
+};
+const OUTLINE: Colour = Colour {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+};
+const FRUSTUM: Colour = Colour {
+    r: 1.0,
+    g: 0.8,
+    b: 0.2,
+};
+
+// Orthographic views along the world axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlueprintView {
+    // Looking down -y, with +x to the right and +z up the image
+    Top,
+    // Looking along +z, with +x to the right and +y up
+    Front,
+    // Looking along -x, with +z to the right and +y up
+    Side,
+}
+
+impl BlueprintView {
+    pub fn from_name(name: &str) -> Option<BlueprintView> {
+        match name.to_ascii_lowercase().as_str() {
+            "top" => Some(BlueprintView::Top),
+            "front" => Some(BlueprintView::Front),
+            "side" => Some(BlueprintView::Side),
+            _ => None,
+        }
+    }
+
+    // World point -> (image right, image up, distance towards the viewer)
+    fn project(&self, p: Tuple) -> (Float, Float, Float) {
+        match self {
+            BlueprintView::Top => (p.x, p.z, p.y),
+            BlueprintView::Front => (p.x, p.y, -p.z),
+            BlueprintView::Side => (p.z, p.y, p.x),
+        }
+    }
+
+    fn unproject(&self, u: Float, v: Float, depth: Float) -> Tuple {
+        match self {
+            BlueprintView::Top => Tuple::point(u, depth, v),
+            BlueprintView::Front => Tuple::point(u, v, -depth),
+            BlueprintView::Side => Tuple::point(depth, v, u),
+        }
+    }
+
+    fn direction(&self) -> Tuple {
+        match self {
+            BlueprintView::Top => Tuple::vector(0.0, -1.0, 0.0),
+            BlueprintView::Front => Tuple::vector(0.0, 0.0, 1.0),
+            BlueprintView::Side => Tuple::vector(-1.0, 0.0, 0.0),
+        }
+    }
+}
+
+// Maps between image pixels and the (u, v) plane of a view
+struct Frame {
+    min_u: Float,
+    max_v: Float,
+    pixel_size: Float,
+}
+
+impl Frame {
+    fn to_pixel(&self, u: Float, v: Float) -> (Float, Float) {
+        (
+            (u - self.min_u) / self.pixel_size,
+            (self.max_v - v) / self.pixel_size,
+        )
+    }
+
+    fn to_plane(&self, x: Float, y: Float) -> (Float, Float) {
+        (
+            self.min_u + x * self.pixel_size,
+            self.max_v - y * self.pixel_size,
+        )
+    }
+}
+
+// Renders a flat orthographic overview of the scene: objects are filled by
+// depth and outlined, and if a camera is given its position and viewing
+// frustum are drawn on top. The view is framed to fit every bounded object and
+// the camera, so unbounded shapes like floor planes just fill the background.
+pub fn render_blueprint(
+    world: &World,
+    camera: Option<&Camera>,
+    view: BlueprintView,
+    width: usize,
+    height: usize,
+) -> Canvas {
+    let mut extent = BoundingBox::empty();
+    for shape in world.registry.iter() {
+        let b = shape.world_bounds();
+        if b.is_finite() {
+            extent.add_box(&b);
+        }
+    }
+    let camera_origin = camera.map(|c| &c.inverse_transform * Tuple::point(0.0, 0.0, 0.0));
+    if let Some(origin) = camera_origin {
+        extent.add_point(origin);
+    }
+    if extent.is_empty() {
+        extent = BoundingBox::new(Tuple::point(-5.0, -5.0, -5.0), Tuple::point(5.0, 5.0, 5.0));
+    }
+
+    let mut projected = BoundingBox::empty();
+    for x in [extent.min.x, extent.max.x] {
+        for y in [extent.min.y, extent.max.y] {
+            for z in [extent.min.z, extent.max.z] {
+                let (u, v, depth) = view.project(Tuple::point(x, y, z));
+                projected.add_point(Tuple::point(u, v, depth));
+            }
+        }
+    }
+
+    // Pad by 10% and keep pixels square
+    let centre = projected.centre();
+    let span_u = (projected.max.x - projected.min.x).max(1.0) * 1.2;
+    let span_v = (projected.max.y - projected.min.y).max(1.0) * 1.2;
+    let pixel_size = (span_u / width as Float).max(span_v / height as Float);
+    let frame = Frame {
+        min_u: centre.x - pixel_size * width as Float / 2.0,
+        max_v: centre.y + pixel_size * height as Float / 2.0,
+        pixel_size,
+    };
+    let near = projected.max.z + 1.0;
+    let depth_range = (projected.max.z - projected.min.z).max(1.0);
+
+    // Nearest object at each pixel, as (top-level id, distance from the near plane)
+    let mut hits = vec![None; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let (u, v) = frame.to_plane(x as Float + 0.5, y as Float + 0.5);
+            let ray = Ray::new(view.unproject(u, v, near), view.direction());
+            hits[y * width + x] = world
+                .first_hit(&ray, 0.0)
+                .map(|i| (world.registry.root_id(i.object_id), i.t));
+        }
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let here = hits[y * width + x];
+            let id = here.map(|(id, _)| id);
+            let edge = (x + 1 < width && hits[y * width + x + 1].map(|(id, _)| id) != id)
+                || (y + 1 < height && hits[(y + 1) * width + x].map(|(id, _)| id) != id);
+
+            let colour = if edge {
+                OUTLINE
+            } else {
+                match here {
+                    // Nearer surfaces are drawn lighter
+                    Some((_, t)) => {
+                        let closeness = 1.0 - ((t - 1.0) / depth_range).clamp(0.0, 1.0);
+                        BACKGROUND + Colour::new(0.15, 0.25, 0.35) * (0.4 + 0.6 * closeness)
+                    }
+                    None => BACKGROUND,
+                }
+            };
+            canvas.write_pixel(x, y, colour);
+        }
+    }
+
+    if let (Some(camera), Some(origin)) = (camera, camera_origin) {
+        draw_frustum(
+            &mut canvas,
+            &frame,
+            view,
+            camera,
+            origin,
+            span_u.max(span_v),
+        );
+    }
+
+    canvas
+}
+
+// Draws the camera as a small square with lines along the four corner rays
+fn draw_frustum(
+    canvas: &mut Canvas,
+    frame: &Frame,
+    view: BlueprintView,
+    camera: &Camera,
+    origin: Tuple,
+    length: Float,
+) {
+    let (u, v, _) = view.project(origin);
+    let pixel = |(x, y): (Float, Float)| (x.floor() as i64, y.floor() as i64);
+    let centre = pixel(frame.to_pixel(u, v));
+    canvas.fill_rect(centre.0 - 2, centre.1 - 2, 5, 5, FRUSTUM);
+
+    let right = camera.hsize.saturating_sub(1);
+    let bottom = camera.vsize.saturating_sub(1);
+    for (x, y, dx, dy) in [
+        (0, 0, 0.0, 0.0),
+        (right, 0, 1.0, 0.0),
+        (0, bottom, 0.0, 1.0),
+        (right, bottom, 1.0, 1.0),
+    ] {
+        let corner = camera.ray_for_pixel_offset(x, y, dx, dy);
+        let (u, v, _) = view.project(corner.position(length));
+        canvas.draw_line(centre, pixel(frame.to_pixel(u, v)), FRUSTUM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        matrix::Matrix, shape::sphere::Sphere, shape::Shape, transformations::view_transform,
+    };
+
+    fn world_with_sphere_at(x: Float) -> World {
+        let mut world = World::new();
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::translation(x, 0.0, 0.0));
+        world.add_object(s);
+        world
+    }
+
+    #[test]
+    fn top_view_outlines_a_sphere() {
+        let world = world_with_sphere_at(0.0);
+        let canvas = render_blueprint(&world, None, BlueprintView::Top, 21, 21);
+
+        // Framed to fit the sphere, so the middle is filled and the corners are empty
+        let middle = canvas.pixel_at(10, 10);
+        assert!(middle.b > BACKGROUND.b && middle != OUTLINE);
+        assert_eq!(canvas.pixel_at(0, 0), BACKGROUND);
+
+        let row: Vec<_> = (0..21).map(|x| canvas.pixel_at(x, 10)).collect();
+        assert_eq!(row.iter().filter(|c| **c == OUTLINE).count(), 2);
+    }
+
+    #[test]
+    fn views_are_oriented_along_the_world_axes() {
+        let mut world = world_with_sphere_at(3.0);
+        world.add_object(Sphere::new());
+
+        // From the side, the sphere at x = 3 sits directly in front of the one
+        // at the origin, so there's only one silhouette
+        let side = render_blueprint(&world, None, BlueprintView::Side, 40, 20);
+        let front = render_blueprint(&world, None, BlueprintView::Front, 40, 20);
+        // Number of separate objects crossed by the middle row
+        let silhouettes = |c: &Canvas| {
+            (0..40)
+                .filter(|x| {
+                    c.pixel_at(*x, 10) != BACKGROUND
+                        && (*x == 0 || c.pixel_at(*x - 1, 10) == BACKGROUND)
+                })
+                .count()
+        };
+
+        assert_eq!(silhouettes(&side), 1);
+        assert_eq!(silhouettes(&front), 2);
+    }
+
+    #[test]
+    fn camera_frustum_is_drawn() {
+        let world = world_with_sphere_at(0.0);
+        let mut camera = Camera::new(10, 10, crate::scalar::consts::FRAC_PI_2);
+        camera.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+        let without = render_blueprint(&world, None, BlueprintView::Top, 40, 40);
+        let with = render_blueprint(&world, Some(&camera), BlueprintView::Top, 40, 40);
+
+        let count = |c: &Canvas| {
+            (0..40)
+                .flat_map(|y| (0..40).map(move |x| (x, y)))
+                .filter(|(x, y)| c.pixel_at(*x, *y) == FRUSTUM)
+                .count()
+        };
+        assert_eq!(count(&without), 0);
+        assert!(count(&with) > 25);
+    }
+
+    #[test]
+    fn views_are_looked_up_by_name() {
+        assert_eq!(BlueprintView::from_name("Top"), Some(BlueprintView::Top));
+        assert_eq!(BlueprintView::from_name("side"), Some(BlueprintView::Side));
+        assert_eq!(BlueprintView::from_name("iso"), None);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{matrix::Matrix, ray::Ray, scalar::Float, tuple::Tuple};
+
+// Axis-aligned bounding box. Infinite extents are allowed for shapes like planes.
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple, max: Tuple) -> BoundingBox {
+        BoundingBox { min, max }
+    }
+
+    // Contains nothing; adding the first point or box makes it exactly that size
+    pub fn empty() -> BoundingBox {
+        BoundingBox {
+            min: Tuple::point(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            max: Tuple::point(
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+            ),
+        }
+    }
+
+    pub fn infinite() -> BoundingBox {
+        BoundingBox {
+            min: Tuple::point(
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+            ),
+            max: Tuple::point(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [
+            self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z,
+        ]
+        .iter()
+        .all(|v| v.is_finite())
+    }
+
+    pub fn add_point(&mut self, point: Tuple) {
+        self.min = Tuple::point(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Tuple::point(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn add_box(&mut self, other: &BoundingBox) {
+        if !other.is_empty() {
+            self.add_point(other.min);
+            self.add_point(other.max);
+        }
+    }
+
+    pub fn contains_point(&self, point: Tuple) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    // Straight-line distance from a point to the box, 0 for points inside it
+    pub fn distance_to(&self, point: Tuple) -> Float {
+        let gap = |v: Float, min: Float, max: Float| (min - v).max(v - max).max(0.0);
+        let x = gap(point.x, self.min.x, self.max.x);
+        let y = gap(point.y, self.min.y, self.max.y);
+        let z = gap(point.z, self.min.z, self.max.z);
+        (x * x + y * y + z * z).sqrt()
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    pub fn centre(&self) -> Tuple {
+        Tuple::point(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    // Box around all eight transformed corners. Transforming an infinite box could
+    // mix infinities into NaNs, so unbounded boxes stay unbounded.
+    pub fn transform(&self, matrix: &Matrix) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            return BoundingBox::infinite();
+        }
+
+        let mut result = BoundingBox::empty();
+        for x in [self.min.x, self.max.x] {
+            for y in [self.min.y, self.max.y] {
+                for z in [self.min.z, self.max.z] {
+                    result.add_point(matrix * Tuple::point(x, y, z));
+                }
+            }
+        }
+        result
+    }
+
+    // Slab test: returns true if the ray passes through the box at any t
+    pub fn intersects(&self, ray: &Ray) -> bool {
+        self.intersection_range(ray).is_some()
+    }
+
+    // Values of t where the ray enters and leaves the box
+    pub fn intersection_range(&self, ray: &Ray) -> Option<(Float, Float)> {
+        let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
+        let (ytmin, ytmax) = check_axis(ray.origin.y, ray.direction.y, self.min.y, self.max.y);
+        let (ztmin, ztmax) = check_axis(ray.origin.z, ray.direction.z, self.min.z, self.max.z);
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        (tmin <= tmax).then_some((tmin, tmax))
+    }
+}
+
+fn check_axis(origin: Float, direction: Float, min: Float, max: Float) -> (Float, Float) {
+    let tmin_numerator = min - origin;
+    let tmax_numerator = max - origin;
+
+    // Dividing by zero gives the right infinities, except when the numerator is
+    // also zero (ray origin on an infinite slab), so handle parallel rays directly
+    if direction == 0.0 {
+        return if (min..=max).contains(&origin) {
+            (Float::NEG_INFINITY, Float::INFINITY)
+        } else {
+            (Float::INFINITY, Float::NEG_INFINITY)
+        };
+    }
+
+    let tmin = tmin_numerator / direction;
+    let tmax = tmax_numerator / direction;
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::consts::{FRAC_1_SQRT_2, PI, SQRT_2};
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn creating_an_empty_bounding_box() {
+        let b = BoundingBox::empty();
+
+        assert!(b.is_empty());
+        assert_eq!(b.min.x, Float::INFINITY);
+        assert_eq!(b.max.x, Float::NEG_INFINITY);
+    }
+
+    #[test]
+    fn adding_points_to_empty_bounding_box() {
+        let mut b = BoundingBox::empty();
+        b.add_point(Tuple::point(-5.0, 2.0, 0.0));
+        b.add_point(Tuple::point(7.0, 0.0, -3.0));
+
+        assert_eq!(b.min, Tuple::point(-5.0, 0.0, -3.0));
+        assert_eq!(b.max, Tuple::point(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn adding_one_bounding_box_to_another() {
+        let mut b1 = BoundingBox::new(Tuple::point(-5.0, -2.0, 0.0), Tuple::point(7.0, 4.0, 4.0));
+        let b2 = BoundingBox::new(Tuple::point(8.0, -7.0, -2.0), Tuple::point(14.0, 2.0, 8.0));
+        b1.add_box(&b2);
+
+        assert_eq!(b1.min, Tuple::point(-5.0, -7.0, -2.0));
+        assert_eq!(b1.max, Tuple::point(14.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn checking_if_box_contains_point() {
+        let b = BoundingBox::new(Tuple::point(5.0, -2.0, 0.0), Tuple::point(11.0, 4.0, 7.0));
+        let cases = [
+            (Tuple::point(5.0, -2.0, 0.0), true),
+            (Tuple::point(11.0, 4.0, 7.0), true),
+            (Tuple::point(8.0, 1.0, 3.0), true),
+            (Tuple::point(3.0, 0.0, 3.0), false),
+            (Tuple::point(8.0, -4.0, 3.0), false),
+            (Tuple::point(8.0, 1.0, -1.0), false),
+            (Tuple::point(13.0, 1.0, 3.0), false),
+            (Tuple::point(8.0, 5.0, 3.0), false),
+            (Tuple::point(8.0, 1.0, 8.0), false),
+        ];
+
+        for (point, expected) in cases {
+            assert_eq!(b.contains_point(point), expected, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn checking_if_box_contains_box() {
+        let b = BoundingBox::new(Tuple::point(5.0, -2.0, 0.0), Tuple::point(11.0, 4.0, 7.0));
+        let cases = [
+            (
+                Tuple::point(5.0, -2.0, 0.0),
+                Tuple::point(11.0, 4.0, 7.0),
+                true,
+            ),
+            (
+                Tuple::point(6.0, -1.0, 1.0),
+                Tuple::point(10.0, 3.0, 6.0),
+                true,
+            ),
+            (
+                Tuple::point(4.0, -3.0, -1.0),
+                Tuple::point(10.0, 3.0, 6.0),
+                false,
+            ),
+            (
+                Tuple::point(6.0, -1.0, 1.0),
+                Tuple::point(12.0, 5.0, 8.0),
+                false,
+            ),
+        ];
+
+        for (min, max, expected) in cases {
+            assert_eq!(b.contains_box(&BoundingBox::new(min, max)), expected);
+        }
+    }
+
+    #[test]
+    fn transforming_a_bounding_box() {
+        let b = BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+        let m = Matrix::rotation_x(PI / 4.0) * Matrix::rotation_y(PI / 4.0);
+        let b2 = b.transform(&m);
+
+        assert_abs_diff_eq!(
+            b2.min,
+            Tuple::point(-SQRT_2, -1.0 - FRAC_1_SQRT_2, -1.0 - FRAC_1_SQRT_2),
+            epsilon = 0.0001
+        );
+        assert_abs_diff_eq!(
+            b2.max,
+            Tuple::point(SQRT_2, 1.0 + FRAC_1_SQRT_2, 1.0 + FRAC_1_SQRT_2),
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn transforming_an_infinite_box_stays_infinite() {
+        let plane = BoundingBox::new(
+            Tuple::point(Float::NEG_INFINITY, 0.0, Float::NEG_INFINITY),
+            Tuple::point(Float::INFINITY, 0.0, Float::INFINITY),
+        );
+
+        let b = plane.transform(&Matrix::rotation_z(1.0));
+        assert_eq!(b.min, BoundingBox::infinite().min);
+        assert_eq!(b.max, BoundingBox::infinite().max);
+    }
+
+    #[test]
+    fn intersecting_ray_with_bounding_box_at_origin() {
+        let b = BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+        let cases = [
+            (
+                Tuple::point(5.0, 0.5, 0.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(-5.0, 0.5, 0.0),
+                Tuple::vector(1.0, 0.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(0.5, 5.0, 0.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(0.5, -5.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(0.5, 0.0, 5.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+                true,
+            ),
+            (
+                Tuple::point(0.5, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                true,
+            ),
+            (
+                Tuple::point(0.0, 0.5, 0.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                true,
+            ),
+            (
+                Tuple::point(-2.0, 0.0, 0.0),
+                Tuple::vector(2.0, 4.0, 6.0),
+                false,
+            ),
+            (
+                Tuple::point(0.0, -2.0, 0.0),
+                Tuple::vector(6.0, 2.0, 4.0),
+                false,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -2.0),
+                Tuple::vector(4.0, 6.0, 2.0),
+                false,
+            ),
+            (
+                Tuple::point(2.0, 0.0, 2.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+                false,
+            ),
+            (
+                Tuple::point(0.0, 2.0, 2.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                false,
+            ),
+            (
+                Tuple::point(2.0, 2.0, 0.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                false,
+            ),
+        ];
+
+        for (origin, direction, expected) in cases {
+            let r = Ray::new(origin, direction.normalise());
+            assert_eq!(b.intersects(&r), expected, "{:?} {:?}", origin, direction);
+        }
+    }
+
+    #[test]
+    fn intersection_range_gives_entry_and_exit() {
+        let b = BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_eq!(b.intersection_range(&r), Some((4.0, 6.0)));
+        let miss = Ray::new(Tuple::point(2.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(b.intersection_range(&miss), None);
+    }
+
+    #[test]
+    fn ray_parallel_to_infinite_slab_inside_it() {
+        let plane = BoundingBox::new(
+            Tuple::point(Float::NEG_INFINITY, 0.0, Float::NEG_INFINITY),
+            Tuple::point(Float::INFINITY, 0.0, Float::INFINITY),
+        );
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(plane.intersects(&r));
+    }
+}
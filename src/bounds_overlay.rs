@@ -0,0 +1,112 @@
+use crate::{
+    bounds::BoundingBox, colour::Colour, ray::Ray, scalar::Float, shape::Shape, world::World,
+};
+
+const EDGE_COLOUR: Colour = Colour {
+    r: 0.1,
+    g: 1.0,
+    b: 0.3,
+};
+// Opacity of edges in front of and behind the visible surface
+const FRONT_ALPHA: Float = 0.7;
+const BEHIND_ALPHA: Float = 0.3;
+// Edge half-width per unit of distance from the eye, so lines keep roughly the
+// same on-screen thickness however far away a box is
+const EDGE_WIDTH: Float = 0.004;
+
+// Debug view: draws the world-space bounding box of every top-level shape and
+// every nested composite (e.g. the groups of an OBJ file) as wireframe over an
+// already shaded primary ray. Edges hidden behind surfaces are drawn fainter.
+pub fn overlay_bounds(world: &World, ray: &Ray, colour: Colour, hit_t: Option<Float>) -> Colour {
+    let mut result = colour;
+    for shape in world.registry.iter() {
+        overlay_shape(shape, ray, hit_t, &mut result);
+    }
+    result
+}
+
+fn overlay_shape(shape: &dyn Shape, ray: &Ray, hit_t: Option<Float>, colour: &mut Colour) {
+    let bounds = shape.world_bounds();
+    if bounds.is_finite() && !bounds.is_empty() {
+        if let Some((enter, exit)) = bounds.intersection_range(ray) {
+            for t in [enter, exit] {
+                if t > 0.0 && on_edge(&bounds, ray, t) {
+                    let alpha = match hit_t {
+                        Some(hit) if hit < t => BEHIND_ALPHA,
+                        _ => FRONT_ALPHA,
+                    };
+                    *colour = *colour * (1.0 - alpha) + EDGE_COLOUR * alpha;
+                }
+            }
+        }
+    }
+
+    for child in shape.children() {
+        if !child.children().is_empty() {
+            overlay_shape(child, ray, hit_t, colour);
+        }
+    }
+}
+
+// A point on the surface of a box is on an edge when it's close to the box's
+// faces along at least two axes
+fn on_edge(bounds: &BoundingBox, ray: &Ray, t: Float) -> bool {
+    let p = ray.position(t);
+    let width = (t * EDGE_WIDTH).max(1e-4);
+    let near =
+        |v: Float, min: Float, max: Float| (v - min).abs() < width || (v - max).abs() < width;
+
+    [
+        near(p.x, bounds.min.x, bounds.max.x),
+        near(p.y, bounds.min.y, bounds.max.y),
+        near(p.z, bounds.min.z, bounds.max.z),
+    ]
+    .iter()
+    .filter(|n| **n)
+    .count()
+        >= 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shape::sphere::Sphere, tuple::Tuple};
+
+    fn world_with_unit_sphere() -> World {
+        let mut world = World::new();
+        world.add_object(Sphere::new());
+        world
+    }
+
+    #[test]
+    fn rays_through_box_edges_are_tinted() {
+        let world = world_with_unit_sphere();
+        // Passes through the box's edge at x = 1, y = 1 but misses the sphere
+        let ray = Ray::new(Tuple::point(1.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let c = overlay_bounds(&world, &ray, Colour::black(), None);
+
+        assert!(c.g > 0.5);
+    }
+
+    #[test]
+    fn rays_through_box_faces_are_untouched() {
+        let world = world_with_unit_sphere();
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let c = overlay_bounds(&world, &ray, Colour::black(), Some(4.0));
+
+        assert_eq!(c, Colour::black());
+    }
+
+    #[test]
+    fn hidden_edges_are_fainter() {
+        let world = world_with_unit_sphere();
+        let ray = Ray::new(Tuple::point(1.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let visible = overlay_bounds(&world, &ray, Colour::black(), None);
+        let hidden = overlay_bounds(&world, &ray, Colour::black(), Some(1.0));
+
+        assert!(hidden.g < visible.g);
+    }
+}
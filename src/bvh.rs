@@ -0,0 +1,277 @@
+use crate::{bounds::BoundingBox, ray::Ray, scalar::Float};
+
+// Leaves stop splitting once they hold this many items
+const MAX_LEAF_SIZE: usize = 4;
+
+// Nodes waiting to be visited during traversal. Every split is at the median,
+// so a tree is at most about log2 of its item count deep and never holds more
+// pending nodes than this.
+const TRAVERSAL_STACK_SIZE: usize = 64;
+
+#[derive(Clone)]
+enum BvhNode {
+    Leaf {
+        bounds: BoundingBox,
+        items: Vec<usize>,
+    },
+    Branch {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Bounding volume hierarchy over a list of boxes. It only stores indices into
+// that list, so the owner (a world or a group) keeps its shapes where they are
+// and asks the tree which of them a ray might hit. Items with infinite bounds
+// (planes, open cylinders) can't be partitioned and are always reported.
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[BoundingBox]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            unbounded: Vec::new(),
+        };
+
+        // Empty boxes (e.g. empty groups) can never be hit, so they're left out
+        let mut items = Vec::new();
+        for (index, b) in bounds.iter().enumerate() {
+            if b.is_empty() {
+                continue;
+            }
+            if b.is_finite() {
+                items.push(index);
+            } else {
+                bvh.unbounded.push(index);
+            }
+        }
+
+        if !items.is_empty() {
+            bvh.build_node(bounds, &mut items);
+        }
+        bvh
+    }
+
+    // Splits at the median centre along the axis where the centres are most spread out
+    fn build_node(&mut self, bounds: &[BoundingBox], items: &mut [usize]) -> usize {
+        let mut node_bounds = BoundingBox::empty();
+        let mut centres = BoundingBox::empty();
+        for &item in items.iter() {
+            node_bounds.add_box(&bounds[item]);
+            centres.add_point(bounds[item].centre());
+        }
+
+        let extent = centres.max - centres.min;
+        let (axis, spread) = [extent.x, extent.y, extent.z].into_iter().enumerate().fold(
+            (0, Float::NEG_INFINITY),
+            |best, (axis, spread)| {
+                if spread > best.1 {
+                    (axis, spread)
+                } else {
+                    best
+                }
+            },
+        );
+
+        let index = self.nodes.len();
+        if items.len() <= MAX_LEAF_SIZE || spread <= 0.0 {
+            self.nodes.push(BvhNode::Leaf {
+                bounds: node_bounds,
+                items: items.to_vec(),
+            });
+            return index;
+        }
+
+        let centre_on_axis = |item: &usize| {
+            let c = bounds[*item].centre();
+            [c.x, c.y, c.z][axis]
+        };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| centre_on_axis(a).total_cmp(&centre_on_axis(b)));
+
+        // Reserve this node's slot before building its children
+        self.nodes.push(BvhNode::Leaf {
+            bounds: node_bounds,
+            items: Vec::new(),
+        });
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let left = self.build_node(bounds, left_items);
+        let right = self.build_node(bounds, right_items);
+        self.nodes[index] = BvhNode::Branch {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    // Calls visit with the index of every item whose leaf the ray passes through.
+    // Items may still be missed by the ray, so callers must intersect them properly.
+    pub fn traverse<F: FnMut(usize)>(&self, ray: &Ray, mut visit: F) {
+        self.traverse_until(ray, |item| {
+            visit(item);
+            false
+        });
+    }
+
+    // As traverse, but stops as soon as visit returns true, and returns
+    // whether it did
+    pub fn traverse_until<F: FnMut(usize) -> bool>(&self, ray: &Ray, mut visit: F) -> bool {
+        for &item in &self.unbounded {
+            if visit(item) {
+                return true;
+            }
+        }
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut stack = [0; TRAVERSAL_STACK_SIZE];
+        let mut pending = 1;
+        while pending > 0 {
+            pending -= 1;
+            let node = &self.nodes[stack[pending]];
+            if !node.bounds().intersects(ray) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    if items.iter().any(|&item| visit(item)) {
+                        return true;
+                    }
+                }
+                BvhNode::Branch { left, right, .. } => {
+                    stack[pending] = *right;
+                    stack[pending + 1] = *left;
+                    pending += 2;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn depth(&self) -> usize {
+        fn depth_of(nodes: &[BvhNode], index: usize) -> usize {
+            match &nodes[index] {
+                BvhNode::Leaf { .. } => 1,
+                BvhNode::Branch { left, right, .. } => {
+                    1 + depth_of(nodes, *left).max(depth_of(nodes, *right))
+                }
+            }
+        }
+
+        if self.nodes.is_empty() {
+            0
+        } else {
+            depth_of(&self.nodes, 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::Tuple;
+
+    fn unit_box_at(x: Float) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(x - 0.5, -0.5, -0.5),
+            Tuple::point(x + 0.5, 0.5, 0.5),
+        )
+    }
+
+    fn visited(bvh: &Bvh, ray: &Ray) -> Vec<usize> {
+        let mut items = Vec::new();
+        bvh.traverse(ray, |i| items.push(i));
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn empty_bvh_visits_nothing() {
+        let bvh = Bvh::build(&[]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(visited(&bvh, &r).is_empty());
+        assert_eq!(bvh.depth(), 0);
+    }
+
+    #[test]
+    fn ray_only_visits_boxes_along_its_path() {
+        let boxes: Vec<_> = (0..100).map(|i| unit_box_at(i as Float * 2.0)).collect();
+        let bvh = Bvh::build(&boxes);
+
+        let r = Ray::new(Tuple::point(20.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let items = visited(&bvh, &r);
+
+        assert!(items.contains(&10));
+        assert!(items.len() <= MAX_LEAF_SIZE);
+    }
+
+    #[test]
+    fn ray_missing_everything_visits_nothing() {
+        let boxes: Vec<_> = (0..20).map(|i| unit_box_at(i as Float * 2.0)).collect();
+        let bvh = Bvh::build(&boxes);
+
+        let r = Ray::new(Tuple::point(0.0, 5.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(visited(&bvh, &r).is_empty());
+    }
+
+    #[test]
+    fn tree_depth_is_logarithmic() {
+        let boxes: Vec<_> = (0..1024).map(|i| unit_box_at(i as Float * 2.0)).collect();
+        let bvh = Bvh::build(&boxes);
+
+        // 1024 items in leaves of up to 4 is 256 leaves, 9 levels of branches
+        assert!(bvh.depth() <= 10, "depth {}", bvh.depth());
+    }
+
+    #[test]
+    fn ray_along_every_box_visits_them_all() {
+        let boxes: Vec<_> = (0..1024).map(|i| unit_box_at(i as Float * 2.0)).collect();
+        let bvh = Bvh::build(&boxes);
+
+        let r = Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+
+        assert_eq!(visited(&bvh, &r), (0..1024).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unbounded_items_are_always_visited() {
+        let boxes = [
+            unit_box_at(0.0),
+            BoundingBox::infinite(),
+            BoundingBox::empty(),
+        ];
+        let bvh = Bvh::build(&boxes);
+
+        let r = Ray::new(Tuple::point(50.0, 50.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_eq!(visited(&bvh, &r), vec![1]);
+    }
+
+    #[test]
+    fn identical_boxes_end_up_in_one_leaf() {
+        let boxes = vec![unit_box_at(0.0); 10];
+        let bvh = Bvh::build(&boxes);
+
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_eq!(bvh.depth(), 1);
+        assert_eq!(visited(&bvh, &r), (0..10).collect::<Vec<_>>());
+    }
+}
@@ -1,14 +1,49 @@
-use crate::{colour::Colour, matrix::Matrix, ray::Ray, tuple::Tuple, world::World};
+use crate::{
+    camera_shake::CameraShake,
+    colour::Colour,
+    matrix::Matrix,
+    quaternion::Quaternion,
+    ray::Ray,
+    scalar::{consts::PI, to_f64, Float},
+    tuple::Tuple,
+    world::World,
+};
+use half::f16;
+use std::{collections::VecDeque, path::Path};
+
+// How a canvas stores its pixels. Half-float storage uses a quarter of the memory
+// of full f64 precision, which matters for very large renders; values are
+// widened again whenever they are read or accumulated into. Each write rounds
+// to 11 significant bits, a relative error of at most 2^-11, for magnitudes
+// from 2^-14 up to 65504; the CLI's --half-precision uses it for HDR output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CanvasStorage {
+    #[default]
+    Full,
+    Half,
+}
+
+enum Pixels {
+    Full(Vec<Colour>),
+    Half(Vec<[f16; 3]>),
+}
 
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
-    pixels: Vec<Colour>,
+    pixels: Pixels,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
-        let pixels = vec![Colour::black(); width * height];
+        Canvas::with_storage(width, height, CanvasStorage::Full)
+    }
+
+    pub fn with_storage(width: usize, height: usize, storage: CanvasStorage) -> Self {
+        let pixels = match storage {
+            CanvasStorage::Full => Pixels::Full(vec![Colour::black(); width * height]),
+            CanvasStorage::Half => Pixels::Half(vec![[f16::ZERO; 3]; width * height]),
+        };
         Canvas {
             width,
             height,
@@ -16,9 +51,35 @@ impl Canvas {
         }
     }
 
+    pub fn storage(&self) -> CanvasStorage {
+        match self.pixels {
+            Pixels::Full(_) => CanvasStorage::Full,
+            Pixels::Half(_) => CanvasStorage::Half,
+        }
+    }
+
+    // Bytes used by the pixel data alone
+    pub fn memory_size(&self) -> usize {
+        match &self.pixels {
+            Pixels::Full(p) => std::mem::size_of_val(p.as_slice()),
+            Pixels::Half(p) => std::mem::size_of_val(p.as_slice()),
+        }
+    }
+
     pub fn pixel_at(&self, x: usize, y: usize) -> Colour {
         if x < self.width && y < self.height {
-            self.pixels[y * self.width + x]
+            let index = y * self.width + x;
+            match &self.pixels {
+                Pixels::Full(p) => p[index],
+                Pixels::Half(p) => {
+                    let [r, g, b] = p[index];
+                    Colour::new(
+                        r.to_f64() as Float,
+                        g.to_f64() as Float,
+                        b.to_f64() as Float,
+                    )
+                }
+            }
         } else {
             Colour::black()
         }
@@ -26,28 +87,233 @@ impl Canvas {
 
     pub fn write_pixel(&mut self, x: usize, y: usize, colour: Colour) {
         if x < self.width && y < self.height {
-            self.pixels[y * self.width + x] = colour;
+            let index = y * self.width + x;
+            match &mut self.pixels {
+                Pixels::Full(p) => p[index] = colour,
+                Pixels::Half(p) => {
+                    p[index] = [
+                        f16::from_f64(to_f64(colour.r)),
+                        f16::from_f64(to_f64(colour.g)),
+                        f16::from_f64(to_f64(colour.b)),
+                    ]
+                }
+            }
+        }
+    }
+
+    // Adds to the existing value, for accumulating several samples into one pixel.
+    // With half-float storage the sum is rounded to 11 significant bits after
+    // every add, so the error grows with the number of samples, and small
+    // samples stop counting once the sum is over ~2000 times larger. Sum many
+    // samples in an AccumulationBuffer and write the result here instead.
+    pub fn accumulate_pixel(&mut self, x: usize, y: usize, colour: Colour) {
+        let current = self.pixel_at(x, y);
+        self.write_pixel(x, y, current + colour);
+    }
+
+    // Drawing, for overlays and the simulation demos. Coordinates are in
+    // pixels from the top-left corner and may be off the canvas; anything
+    // outside it is clipped.
+    pub fn fill_rect(&mut self, x: i64, y: i64, width: usize, height: usize, colour: Colour) {
+        let clip = |start: i64, size: usize, limit: usize| {
+            let end = start.saturating_add(size as i64).min(limit as i64);
+            (start.max(0) as usize)..(end.max(0) as usize)
+        };
+        for py in clip(y, height, self.height) {
+            for px in clip(x, width, self.width) {
+                self.write_pixel(px, py, colour);
+            }
+        }
+    }
+
+    // Filled, covering the pixels whose centres are within radius of centre
+    pub fn draw_circle(&mut self, centre: (i64, i64), radius: usize, colour: Colour) {
+        let r = radius as i64;
+        for dy in -r..=r {
+            let half_width = ((r * r - dy * dy) as Float).sqrt() as i64;
+            let width = (2 * half_width + 1) as usize;
+            self.fill_rect(centre.0 - half_width, centre.1 + dy, width, 1, colour);
+        }
+    }
+
+    // Bresenham's line, including both ends
+    pub fn draw_line(&mut self, from: (i64, i64), to: (i64, i64), colour: Colour) {
+        // Lines can run far off the canvas, so only the part on it is stepped
+        let Some(((mut x, mut y), (x1, y1))) = self.clip_line(from, to) else {
+            return;
+        };
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+        loop {
+            if x >= 0 && y >= 0 {
+                self.write_pixel(x as usize, y as usize, colour);
+            }
+            if (x, y) == (x1, y1) {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // Liang-Barsky clipping to the canvas, or None if the line misses it
+    fn clip_line(&self, from: (i64, i64), to: (i64, i64)) -> Option<((i64, i64), (i64, i64))> {
+        let (x0, y0) = (from.0 as Float, from.1 as Float);
+        let (dx, dy) = (to.0 as Float - x0, to.1 as Float - y0);
+        let (max_x, max_y) = (self.width as Float - 1.0, self.height as Float - 1.0);
+        let (mut enter, mut exit) = (0.0 as Float, 1.0 as Float);
+        for (p, q) in [(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                enter = enter.max(q / p);
+            } else {
+                exit = exit.min(q / p);
+            }
+        }
+        if enter > exit {
+            return None;
+        }
+        let at = |t: Float| ((x0 + t * dx).round() as i64, (y0 + t * dy).round() as i64);
+        Some((at(enter), at(exit)))
+    }
+
+    // Writes the colours unclamped to a Radiance (.hdr) or OpenEXR (.exr) file,
+    // for tone mapping and grading in other tools. Both store 32-bit floats
+    // per channel (.hdr with a shared exponent), so negative values are lost.
+    pub fn save_hdr<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if !is_hdr_path(path) {
+            return Err(format!("{} is not an .hdr or .exr file", path.display()));
+        }
+        let image = image::Rgb32FImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let c = self.pixel_at(x as usize, y as usize);
+            image::Rgb([c.r, c.g, c.b].map(|v| to_f64(v.max(0.0)) as f32))
+        });
+        image
+            .save(path)
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+}
+
+// Whether an output path asks for full dynamic range; see Canvas::save_hdr
+pub fn is_hdr_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("hdr") || ext.eq_ignore_ascii_case("exr"))
+}
+
+// How the sub-pixel positions of multiple samples are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplingMode {
+    // Evenly spaced n x n grid; the sample count is rounded to the nearest square
+    #[default]
+    Grid,
+    // Uniformly random positions, seeded by pixel so renders are repeatable
+    Jittered,
+}
+
+impl SamplingMode {
+    pub fn from_name(name: &str) -> Option<SamplingMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "grid" => Some(SamplingMode::Grid),
+            "jittered" => Some(SamplingMode::Jittered),
+            _ => None,
         }
     }
 }
 
+// How pixels map to ray directions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    // Rays spread from the eye through a canvas one unit in front of it
+    #[default]
+    Perspective,
+    // Parallel rays from a view plane this many units wide, for schematic
+    // renders where size doesn't change with distance
+    Orthographic {
+        view_width: Float,
+    },
+    // Equidistant fisheye: a ray's angle from the view direction grows
+    // linearly from the centre, reaching half the field of view at the middle
+    // of the shorter edge
+    Fisheye,
+    // A full 360 x 180 degree panorama; ignores the field of view. Use a 2:1
+    // image for square pixels.
+    Equirectangular,
+}
+
+impl Projection {
+    // Orthographic views are given a width of 10 units; set view_width for others
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name.to_ascii_lowercase().as_str() {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic { view_width: 10.0 }),
+            "fisheye" => Some(Projection::Fisheye),
+            "equirectangular" => Some(Projection::Equirectangular),
+            _ => None,
+        }
+    }
+}
+
+// Supersamples only where the image has detail. Each pixel is first traced
+// once through its centre; pixels whose colour differs from a neighbour's by
+// more than threshold in any channel are then traced again with max_samples
+// rays, placed as for the camera's sampling mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub threshold: Float,
+    pub max_samples: usize,
+}
+
+// A rectangle of the image, in pixels from the top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
-    pub field_of_view: f64,
+    pub field_of_view: Float,
     pub transform: Matrix,
     pub inverse_transform: Matrix,
-    pub half_width: f64,
-    pub half_height: f64,
-    pub pixel_size: f64,
+    pub half_width: Float,
+    pub half_height: Float,
+    pub pixel_size: Float,
+    // Where each of RenderSettings::samples falls inside the pixel
+    pub sampling: SamplingMode,
+    pub projection: Projection,
+    // Seconds the shutter stays open. Each sample is traced at a random
+    // moment in that time, so shapes with a velocity blur along their path.
+    pub shutter: Float,
+    // When set, replaces RenderSettings::samples
+    pub adaptive: Option<AdaptiveSampling>,
+    // Procedural jitter applied by at_time, for animated renders
+    pub shake: Option<CameraShake>,
 }
 
 impl Camera {
-    pub fn new(hsize: usize, vsize: usize, field_of_view: f64) -> Self {
+    pub fn new(hsize: usize, vsize: usize, field_of_view: Float) -> Self {
         let half_view = (field_of_view / 2.0).tan();
-        let aspect = hsize as f64 / vsize as f64;
-        let half_width: f64;
-        let half_height: f64;
+        let aspect = hsize as Float / vsize as Float;
+        let half_width: Float;
+        let half_height: Float;
         if aspect >= 1.0 {
             half_width = half_view;
             half_height = half_view / aspect;
@@ -64,7 +330,12 @@ impl Camera {
             inverse_transform: identity,
             half_width,
             half_height,
-            pixel_size: (half_width * 2.0) / hsize as f64,
+            pixel_size: (half_width * 2.0) / hsize as Float,
+            sampling: SamplingMode::Grid,
+            projection: Projection::Perspective,
+            shutter: 0.0,
+            adaptive: None,
+            shake: None,
         }
     }
 
@@ -73,55 +344,380 @@ impl Camera {
         self.transform = transform;
     }
 
+    // Places the camera `distance` from `target`, looking at it. With no yaw or
+    // pitch it sits on the +z side; yaw then swings it around the world y axis
+    // and positive pitch raises it above the target. Built from quaternions, so
+    // it stays well defined looking straight up or down.
+    pub fn orbit(&mut self, target: Tuple, yaw: Float, pitch: Float, distance: Float) {
+        let rotation = Quaternion::from_axis_angle(Tuple::vector(0.0, 1.0, 0.0), yaw)
+            * Quaternion::from_axis_angle(Tuple::vector(1.0, 0.0, 0.0), -pitch);
+        let position = target + rotation.rotate(Tuple::vector(0.0, 0.0, distance));
+        self.set_transform(
+            rotation.conjugate().to_matrix()
+                * Matrix::translation(-position.x, -position.y, -position.z),
+        );
+    }
+
+    // Zooms without touching sampling, projection or any other settings
+    pub fn set_field_of_view(&mut self, field_of_view: Float) {
+        let resized = Camera::new(self.hsize, self.vsize, field_of_view);
+        self.field_of_view = field_of_view;
+        self.half_width = resized.half_width;
+        self.half_height = resized.half_height;
+        self.pixel_size = resized.pixel_size;
+    }
+
+    // The camera as seen at `time` seconds, with any shake applied on top of its
+    // transform. Without shake it's just a copy.
+    pub fn at_time(&self, time: Float) -> Camera {
+        let mut camera = self.clone();
+        if let Some(shake) = &self.shake {
+            camera.set_transform(shake.apply(&self.transform, time));
+            camera.shake = None;
+        }
+        camera
+    }
+
+    pub fn set_adaptive(&mut self, threshold: Float, max_samples: usize) {
+        self.adaptive = Some(AdaptiveSampling {
+            threshold: threshold.max(0.0),
+            max_samples: max_samples.max(1),
+        });
+    }
+
     pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
-        let xoffset = (x as f64 + 0.5) * self.pixel_size;
-        let yoffset = (y as f64 + 0.5) * self.pixel_size;
+        self.ray_for_pixel_offset(x, y, 0.5, 0.5)
+    }
 
-        let world_x = self.half_width - xoffset as f64;
-        let world_y = self.half_height - yoffset as f64;
+    // Ray through a point inside the pixel; offsets are in [0, 1) from its top-left corner
+    pub fn ray_for_pixel_offset(&self, x: usize, y: usize, dx: Float, dy: Float) -> Ray {
+        let xoffset = (x as Float + dx) * self.pixel_size;
+        let yoffset = (y as Float + dy) * self.pixel_size;
 
-        // canvas at -1
-        let pixel = self.inverse_transform.clone() * Tuple::point(world_x, world_y, -1.0);
-        let origin = self.inverse_transform.clone() * Tuple::point(0.0, 0.0, 0.0);
-        let direction = (pixel - origin).normalise();
+        let world_x = self.half_width - xoffset;
+        let world_y = self.half_height - yoffset;
 
-        return Ray::new(origin, direction);
+        // In camera space, looking down -z with +x to the left of the image
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => (
+                Tuple::point(0.0, 0.0, 0.0),
+                // canvas at -1
+                Tuple::vector(world_x, world_y, -1.0),
+            ),
+            Projection::Orthographic { view_width } => {
+                let scale = view_width / (2.0 * self.half_width);
+                (
+                    Tuple::point(world_x * scale, world_y * scale, 0.0),
+                    Tuple::vector(0.0, 0.0, -1.0),
+                )
+            }
+            Projection::Fisheye => {
+                let half_min = self.half_width.min(self.half_height);
+                let (u, v) = (world_x / half_min, world_y / half_min);
+                let r = (u * u + v * v).sqrt();
+                let angle = (r * self.field_of_view / 2.0).min(PI);
+                let (sin, cos) = angle.sin_cos();
+                let (u, v) = if r > 0.0 { (u / r, v / r) } else { (0.0, 0.0) };
+                (
+                    Tuple::point(0.0, 0.0, 0.0),
+                    Tuple::vector(u * sin, v * sin, -cos),
+                )
+            }
+            Projection::Equirectangular => {
+                let longitude = ((x as Float + dx) / self.hsize as Float - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - (y as Float + dy) / self.vsize as Float) * PI;
+                (
+                    Tuple::point(0.0, 0.0, 0.0),
+                    Tuple::vector(
+                        -longitude.sin() * latitude.cos(),
+                        latitude.sin(),
+                        -longitude.cos() * latitude.cos(),
+                    ),
+                )
+            }
+        };
+
+        Ray::new(
+            &self.inverse_transform * origin,
+            (&self.inverse_transform * direction).normalise(),
+        )
     }
 
-    pub fn render(&self, world: &World) -> Canvas {
-        let mut image = Canvas::new(self.hsize, self.vsize);
+    // Sub-pixel offsets for each of `samples` samples taken in pixel (x, y)
+    pub fn sample_offsets(&self, x: usize, y: usize, samples: usize) -> Vec<(Float, Float)> {
+        if samples <= 1 {
+            return vec![(0.5, 0.5)];
+        }
 
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let colour = world.colour_at(&ray, crate::world::MAX_BOUNCES);
-                image.write_pixel(x, y, colour);
+        match self.sampling {
+            SamplingMode::Grid => {
+                let n = ((samples as Float).sqrt().round() as usize).max(1);
+                let step = 1.0 / n as Float;
+                (0..n * n)
+                    .map(|i| {
+                        let (col, row) = (i % n, i / n);
+                        ((col as Float + 0.5) * step, (row as Float + 0.5) * step)
+                    })
+                    .collect()
             }
+            SamplingMode::Jittered => (0..samples)
+                .map(|i| {
+                    let seed = ((y * self.hsize + x) as u64) << 16 | i as u64;
+                    (unit_random(seed, 0), unit_random(seed, 1))
+                })
+                .collect(),
+        }
+    }
+
+    // Average colour of all samples in the pixel. Ignores adaptive sampling,
+    // which needs the neighbouring pixels too.
+    pub fn colour_for_pixel(&self, world: &World, x: usize, y: usize) -> Colour {
+        let offsets = self.sample_offsets(x, y, world.settings.samples);
+        self.average_samples(world, x, y, &offsets)
+    }
+
+    fn average_samples(
+        &self,
+        world: &World,
+        x: usize,
+        y: usize,
+        offsets: &[(Float, Float)],
+    ) -> Colour {
+        let total = offsets
+            .iter()
+            .enumerate()
+            .fold(Colour::black(), |sum, (i, (dx, dy))| {
+                let ray = self
+                    .ray_for_pixel_offset(x, y, *dx, *dy)
+                    .with_time(self.sample_time(x, y, i))
+                    .with_seed(i as u64);
+                sum + world.colour_at(&ray, world.settings.max_bounces)
+            });
+        total * (1.0 / offsets.len() as Float)
+    }
+
+    // When sample i of pixel (x, y) is taken, in seconds after the shutter
+    // opens. Repeatable like jittered sample positions.
+    pub fn sample_time(&self, x: usize, y: usize, i: usize) -> Float {
+        if self.shutter <= 0.0 {
+            return 0.0;
+        }
+        let seed = ((y * self.hsize + x) as u64) << 16 | i as u64;
+        unit_random(seed, 2) * self.shutter
+    }
+
+    // The final colour of a pixel under adaptive sampling, given the centre
+    // samples of it and whichever of its four neighbours lie in the image
+    fn refine_pixel<F>(&self, world: &World, x: usize, y: usize, centre: F) -> Colour
+    where
+        F: Fn(usize, usize) -> Colour,
+    {
+        let Some(adaptive) = self.adaptive else {
+            return self.colour_for_pixel(world, x, y);
+        };
+        let colour = centre(x, y);
+        let mut neighbours = Vec::with_capacity(4);
+        if x > 0 {
+            neighbours.push((x - 1, y));
+        }
+        if x + 1 < self.hsize {
+            neighbours.push((x + 1, y));
+        }
+        if y > 0 {
+            neighbours.push((x, y - 1));
+        }
+        if y + 1 < self.vsize {
+            neighbours.push((x, y + 1));
+        }
+        let contrast = neighbours
+            .into_iter()
+            .map(|(nx, ny)| {
+                let d = centre(nx, ny) - colour;
+                d.r.abs().max(d.g.abs()).max(d.b.abs())
+            })
+            .fold(0.0, Float::max);
+
+        if contrast > adaptive.threshold && adaptive.max_samples > 1 {
+            let offsets = self.sample_offsets(x, y, adaptive.max_samples);
+            self.average_samples(world, x, y, &offsets)
+        } else {
+            colour
         }
+    }
+
+    fn centre_sample(&self, world: &World, x: usize, y: usize) -> Colour {
+        let ray = self
+            .ray_for_pixel(x, y)
+            .with_time(self.sample_time(x, y, 0));
+        world.colour_at(&ray, world.settings.max_bounces)
+    }
+
+    pub fn render(&self, world: &World) -> Canvas {
+        self.render_with_storage(world, CanvasStorage::Full)
+    }
+
+    pub fn render_with_storage(&self, world: &World, storage: CanvasStorage) -> Canvas {
+        let mut image = Canvas::with_storage(self.hsize, self.vsize, storage);
+
+        self.render_with(world, |y, row| {
+            for (x, colour) in row.iter().enumerate() {
+                image.write_pixel(x, y, *colour);
+            }
+        });
 
         image
     }
 
     pub fn render_to_buffer(&self, world: &World, buffer: &mut [Colour]) {
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let colour = world.colour_at(&ray, crate::world::MAX_BOUNCES);
-                let pixel_index = y * self.hsize + x;
-                buffer[pixel_index] = colour;
+        self.render_with(world, |y, row| {
+            let start = y * self.hsize;
+            buffer[start..start + self.hsize].copy_from_slice(row);
+        });
+    }
+
+    // Renders top to bottom, handing each scanline to on_row as soon as it is
+    // finished. The row slice is reused between calls, so copy it if you need it.
+    pub fn render_with<F>(&self, world: &World, on_row: F)
+    where
+        F: FnMut(usize, &[Colour]),
+    {
+        self.render_rows_from(world, 0, on_row);
+    }
+
+    // As render_with, starting at first_row, for finishing a render that was
+    // interrupted. The rows match those of a render from the top.
+    pub fn render_rows_from<F>(&self, world: &World, first_row: usize, mut on_row: F)
+    where
+        F: FnMut(usize, &[Colour]),
+    {
+        world.reset_render_stats();
+        let mut row = vec![Colour::black(); self.hsize];
+
+        if self.adaptive.is_none() {
+            for y in first_row..self.vsize {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    *pixel = self.colour_for_pixel(world, x, y);
+                }
+                on_row(y, &row);
             }
+            return;
+        }
+
+        // Centre samples are kept for the rows either side of the current one
+        let centre_row = |y: usize| -> Vec<Colour> {
+            if y < self.vsize {
+                (0..self.hsize)
+                    .map(|x| self.centre_sample(world, x, y))
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        };
+        let mut above = match first_row {
+            0 => Vec::new(),
+            y => centre_row(y - 1),
+        };
+        let mut current = centre_row(first_row);
+        for y in first_row..self.vsize {
+            let below = centre_row(y + 1);
+            let centre = |cx: usize, cy: usize| match cy.cmp(&y) {
+                std::cmp::Ordering::Less => above[cx],
+                std::cmp::Ordering::Equal => current[cx],
+                std::cmp::Ordering::Greater => below[cx],
+            };
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.refine_pixel(world, x, y, centre);
+            }
+            on_row(y, &row);
+            above = std::mem::replace(&mut current, below);
+        }
+    }
+
+    // Splits the image into tile_size squares in reading order. Tiles on the
+    // right and bottom edges are cut short to fit.
+    pub fn tiles(&self, tile_size: usize) -> Vec<Tile> {
+        tiles(self.hsize, self.vsize, tile_size)
+    }
+
+    // Works through a queue of tiles, handing each to on_tile as soon as it is
+    // finished along with its pixels row by row. As with render_with, the pixel
+    // slice is reused between calls.
+    pub fn render_tiled<F>(&self, world: &World, tile_size: usize, mut on_tile: F)
+    where
+        F: FnMut(&Tile, &[Colour]),
+    {
+        world.reset_render_stats();
+        let mut queue: VecDeque<Tile> = self.tiles(tile_size).into();
+        let mut pixels = Vec::new();
+
+        while let Some(tile) = queue.pop_front() {
+            pixels.clear();
+            if self.adaptive.is_some() {
+                // Centre samples for the tile and a one pixel border around it
+                let (x0, y0) = (tile.x.saturating_sub(1), tile.y.saturating_sub(1));
+                let x1 = (tile.x + tile.width + 1).min(self.hsize);
+                let y1 = (tile.y + tile.height + 1).min(self.vsize);
+                let centres: Vec<Colour> = (y0..y1)
+                    .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                    .map(|(x, y)| self.centre_sample(world, x, y))
+                    .collect();
+                let centre = |x: usize, y: usize| centres[(y - y0) * (x1 - x0) + x - x0];
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        pixels.push(self.refine_pixel(world, x, y, centre));
+                    }
+                }
+            } else {
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        pixels.push(self.colour_for_pixel(world, x, y));
+                    }
+                }
+            }
+            on_tile(&tile, &pixels);
         }
     }
 }
 
+// Splits a width x height image into tile_size squares; see Camera::tiles
+pub fn tiles(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size) {
+        for x in (0..width).step_by(tile_size) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+// Hashes a seed and stream to a value in [0, 1)
+fn unit_random(seed: u64, stream: u64) -> Float {
+    let mut h = seed
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(stream.wrapping_mul(0xd1b5_4a32_d192_ed03));
+    h ^= h >> 31;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h >> 11) as Float / (1u64 << 53) as Float
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::scalar::TEST_EPSILON;
     use approx::assert_abs_diff_eq;
 
-    use crate::tuple::Tuple;
+    use crate::{pattern::uv_pattern::UvImage, tuple::Tuple};
 
     use super::*;
-    use std::f64::consts::PI;
+    use crate::scalar::consts::PI;
 
     #[test]
     fn constructing_a_camera() {
@@ -179,8 +775,12 @@ mod tests {
         c.set_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0));
         let r = c.ray_for_pixel(100, 50);
 
-        assert_abs_diff_eq!(r.origin, Tuple::point(0.0, 2.0, -5.0));
-        let sqrt_2_div_2 = (2.0_f64).sqrt() / 2.0;
+        assert_abs_diff_eq!(
+            r.origin,
+            Tuple::point(0.0, 2.0, -5.0),
+            epsilon = TEST_EPSILON
+        );
+        let sqrt_2_div_2 = Float::sqrt(2.0) / 2.0;
         assert_abs_diff_eq!(
             r.direction,
             Tuple::vector(sqrt_2_div_2, 0.0, -sqrt_2_div_2),
@@ -188,6 +788,101 @@ mod tests {
         );
     }
 
+    #[test]
+    fn orbiting_matches_an_equivalent_view_transform() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        let target = Tuple::point(1.0, 2.0, 3.0);
+        c.orbit(target, PI / 2.0, 0.0, 5.0);
+
+        let expected = crate::transformations::view_transform(
+            Tuple::point(6.0, 2.0, 3.0),
+            target,
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        assert_abs_diff_eq!(c.transform, expected, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn orbiting_overhead_looks_straight_down() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.orbit(Tuple::point(0.0, 0.0, 0.0), 0.3, PI / 2.0, 4.0);
+        let r = c.ray_for_pixel(5, 5);
+
+        assert_abs_diff_eq!(
+            r.origin,
+            Tuple::point(0.0, 4.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            r.direction,
+            Tuple::vector(0.0, -1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut c = Camera::new(200, 100, PI / 2.0);
+        c.projection = Projection::Orthographic { view_width: 4.0 };
+        c.set_transform(Matrix::translation(0.0, 0.0, 5.0));
+
+        let centre = c.ray_for_pixel_offset(100, 50, 0.0, 0.0);
+        let corner = c.ray_for_pixel_offset(0, 0, 0.0, 0.0);
+        assert_abs_diff_eq!(centre.origin, Tuple::point(0.0, 0.0, -5.0));
+        assert_abs_diff_eq!(corner.origin, Tuple::point(2.0, 1.0, -5.0));
+        assert_abs_diff_eq!(corner.direction, Tuple::vector(0.0, 0.0, -1.0));
+        assert_abs_diff_eq!(centre.direction, corner.direction);
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly_from_the_centre() {
+        let mut c = Camera::new(100, 100, PI);
+        c.projection = Projection::Fisheye;
+
+        // Half way to the edge is a quarter of the 180 degree view
+        let r = c.ray_for_pixel_offset(25, 50, 0.0, 0.0);
+        let angle = PI / 4.0;
+        assert_abs_diff_eq!(
+            r.direction,
+            Tuple::vector(angle.sin(), 0.0, -angle.cos()),
+            epsilon = TEST_EPSILON
+        );
+        let edge = c.ray_for_pixel_offset(50, 0, 0.0, 0.0);
+        assert_abs_diff_eq!(
+            edge.direction,
+            Tuple::vector(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn equirectangular_covers_every_direction() {
+        let mut c = Camera::new(200, 100, PI / 3.0);
+        c.projection = Projection::Equirectangular;
+
+        let ray = |x, y| c.ray_for_pixel_offset(x, y, 0.0, 0.0).direction;
+        assert_abs_diff_eq!(
+            ray(100, 50),
+            Tuple::vector(0.0, 0.0, -1.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            ray(50, 50),
+            Tuple::vector(1.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            ray(0, 50),
+            Tuple::vector(0.0, 0.0, 1.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            ray(100, 0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
     #[test]
     fn rendering_world_with_camera() {
         use crate::{colour::Colour, transformations::view_transform, world::World};
@@ -207,4 +902,403 @@ mod tests {
             epsilon = 0.0001
         );
     }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn render_stats_count_the_work_done() {
+        use crate::{transformations::view_transform, world::World};
+
+        let mut w = World::default_world();
+        let outer = w.registry.get_by_index(0).unwrap().id();
+        w.registry
+            .get_mut(outer)
+            .unwrap()
+            .data_mut()
+            .material
+            .reflective = 0.5;
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+        c.render(&w);
+        let stats = w.render_stats();
+
+        // No BVH, so every camera and reflection ray is tested against both
+        // spheres. Shadow rays stop at the first opaque blocker, so they test
+        // one or both.
+        let shadow_tests = stats.intersection_tests - (stats.rays_cast - stats.shadow_rays) * 2;
+        assert!(shadow_tests >= stats.shadow_rays);
+        assert!(shadow_tests <= stats.shadow_rays * 2);
+        assert!(stats.shadow_rays > 0);
+        assert!(stats.reflection_depths[0] > 0);
+        let reflections: u64 = stats.reflection_depths.iter().sum();
+        assert_eq!(stats.rays_cast, 11 * 11 + stats.shadow_rays + reflections);
+
+        // Each render starts counting afresh
+        c.render(&w);
+        assert_eq!(w.render_stats(), stats);
+    }
+
+    #[test]
+    fn render_with_delivers_rows_in_order() {
+        use crate::world::World;
+
+        let w = World::default_world();
+        let c = Camera::new(4, 3, PI / 2.0);
+        let canvas = c.render(&w);
+
+        let mut rows = Vec::new();
+        c.render_with(&w, |y, row| {
+            assert_eq!(row.len(), 4);
+            for (x, colour) in row.iter().enumerate() {
+                assert_eq!(*colour, canvas.pixel_at(x, y));
+            }
+            rows.push(y);
+        });
+
+        assert_eq!(rows, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn rendering_from_a_row_matches_a_full_render() {
+        use crate::world::World;
+
+        let w = World::default_world();
+        let mut c = Camera::new(5, 4, PI / 2.0);
+        c.set_adaptive(0.01, 4);
+        let canvas = c.render(&w);
+
+        let mut rows = Vec::new();
+        c.render_rows_from(&w, 2, |y, row| {
+            for (x, colour) in row.iter().enumerate() {
+                assert_eq!(*colour, canvas.pixel_at(x, y));
+            }
+            rows.push(y);
+        });
+        assert_eq!(rows, vec![2, 3]);
+    }
+
+    #[test]
+    fn half_canvas_stores_colours_at_reduced_precision() {
+        let mut canvas = Canvas::with_storage(4, 2, CanvasStorage::Half);
+        canvas.write_pixel(1, 1, Colour::new(0.25, 0.5, 1.0 / 3.0));
+
+        let c = canvas.pixel_at(1, 1);
+        assert_eq!(canvas.storage(), CanvasStorage::Half);
+        assert_eq!(c.r, 0.25);
+        assert_eq!(c.g, 0.5);
+        assert_abs_diff_eq!(c.b, 1.0 / 3.0, epsilon = 0.001);
+        assert_eq!(canvas.pixel_at(0, 0), Colour::black());
+    }
+
+    #[test]
+    fn half_canvas_uses_two_bytes_per_channel() {
+        let full = Canvas::new(16, 8);
+        let half = Canvas::with_storage(16, 8, CanvasStorage::Half);
+
+        assert_eq!(
+            full.memory_size(),
+            16 * 8 * 3 * std::mem::size_of::<Float>()
+        );
+        assert_eq!(half.memory_size(), 16 * 8 * 3 * 2);
+    }
+
+    #[test]
+    fn accumulating_into_half_canvas() {
+        let mut canvas = Canvas::with_storage(1, 1, CanvasStorage::Half);
+        for _ in 0..4 {
+            canvas.accumulate_pixel(0, 0, Colour::new(0.5, 1.0, 2.0));
+        }
+
+        assert_eq!(canvas.pixel_at(0, 0), Colour::new(2.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn half_canvas_rounds_within_its_documented_bound() {
+        let mut canvas = Canvas::with_storage(1, 1, CanvasStorage::Half);
+        for value in [1e-4, 0.1, 1.0 / 3.0, 0.7, 1.9, 37.3, 1000.1, 60000.0] {
+            canvas.write_pixel(0, 0, Colour::new(value, value, value));
+            let error = (canvas.pixel_at(0, 0).r - value).abs();
+            assert!(error <= value / 2048.0, "{} off by {}", value, error);
+        }
+    }
+
+    #[test]
+    fn accumulating_many_samples_into_half_canvas_stays_within_bound() {
+        let samples = 1000;
+        let sample = Colour::new(0.1, 0.1, 0.1);
+        let mut full = Canvas::new(1, 1);
+        let mut half = Canvas::with_storage(1, 1, CanvasStorage::Half);
+        for _ in 0..samples {
+            full.accumulate_pixel(0, 0, sample);
+            half.accumulate_pixel(0, 0, sample);
+        }
+
+        assert_abs_diff_eq!(full.pixel_at(0, 0).r, 100.0, epsilon = 1e-3);
+        // Each add is off by at most 2^-11 of the running sum, which never
+        // exceeds the final 100
+        let error = (half.pixel_at(0, 0).r - 100.0).abs();
+        assert!(error <= samples as Float * 100.0 / 2048.0, "{}", error);
+    }
+
+    // The lit pixels, as rows of '#' and '.'
+    fn drawn(canvas: &Canvas) -> Vec<String> {
+        (0..canvas.height)
+            .map(|y| {
+                (0..canvas.width)
+                    .map(|x| {
+                        if canvas.pixel_at(x, y) == Colour::WHITE {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drawing_lines() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.draw_line((0, 0), (4, 2), Colour::WHITE);
+        assert_eq!(drawn(&canvas), ["#....", ".##..", "...##"]);
+
+        // Only the part on the canvas is drawn, however far off the ends are
+        let mut canvas = Canvas::new(5, 3);
+        canvas.draw_line((-1_000_000, 1), (1_000_000, 1), Colour::WHITE);
+        canvas.draw_line((2, -50), (2, -1), Colour::WHITE);
+        assert_eq!(drawn(&canvas), [".....", "#####", "....."]);
+    }
+
+    #[test]
+    fn drawing_rectangles_and_circles() {
+        let mut canvas = Canvas::new(5, 5);
+        canvas.fill_rect(-2, 3, 4, 10, Colour::WHITE);
+        assert_eq!(
+            drawn(&canvas),
+            [".....", ".....", ".....", "##...", "##..."]
+        );
+
+        let mut canvas = Canvas::new(5, 5);
+        canvas.draw_circle((2, 2), 2, Colour::WHITE);
+        assert_eq!(
+            drawn(&canvas),
+            ["..#..", ".###.", "#####", ".###.", "..#.."]
+        );
+    }
+
+    #[test]
+    fn hdr_files_keep_values_above_one() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Colour::new(3.5, 0.25, 12.0));
+
+        for name in ["raytracer_hdr_test.exr", "raytracer_hdr_test.hdr"] {
+            let path = std::env::temp_dir().join(name);
+            canvas.save_hdr(&path).unwrap();
+            let saved = UvImage::load(&path).unwrap();
+            std::fs::remove_file(&path).ok();
+
+            // .hdr shares one exponent between channels, losing precision in
+            // the dimmer ones
+            let c = saved.colour_at(0.0, 1.0);
+            assert_abs_diff_eq!(c.r, 3.5, epsilon = 0.05);
+            assert_abs_diff_eq!(c.g, 0.25, epsilon = 0.05);
+            assert_abs_diff_eq!(c.b, 12.0, epsilon = 0.05);
+        }
+        assert!(canvas.save_hdr("image.png").is_err());
+    }
+
+    #[test]
+    fn single_sample_goes_through_pixel_centre() {
+        let c = Camera::new(201, 101, PI / 2.0);
+
+        assert_eq!(c.sample_offsets(3, 4, 1), vec![(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn grid_sampling_covers_pixel_evenly() {
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        c.sampling = SamplingMode::Grid;
+
+        assert_eq!(
+            c.sample_offsets(0, 0, 4),
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+    }
+
+    #[test]
+    fn jittered_samples_stay_inside_pixel_and_are_repeatable() {
+        let mut c = Camera::new(10, 10, PI / 2.0);
+        c.sampling = SamplingMode::Jittered;
+
+        let offsets = c.sample_offsets(3, 7, 8);
+        assert_eq!(offsets.len(), 8);
+        assert!(offsets
+            .iter()
+            .all(|(dx, dy)| (0.0..1.0).contains(dx) && (0.0..1.0).contains(dy)));
+        assert_eq!(offsets, c.sample_offsets(3, 7, 8));
+        assert_ne!(offsets, c.sample_offsets(4, 7, 8));
+    }
+
+    #[test]
+    fn supersampling_blends_colours_across_an_edge() {
+        use crate::{transformations::view_transform, world::World};
+
+        let mut w = World::default_world();
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+        // The centre pixel is entirely inside the sphere, so samples barely differ
+        let single_row: Vec<Colour> = (0..11).map(|x| c.colour_for_pixel(&w, x, 5)).collect();
+        w.settings.samples = 16;
+        let sampled_row: Vec<Colour> = (0..11).map(|x| c.colour_for_pixel(&w, x, 5)).collect();
+        assert_abs_diff_eq!(sampled_row[5], single_row[5], epsilon = 0.02);
+
+        // A pixel on the silhouette mixes in the black background
+        let blended = single_row
+            .iter()
+            .zip(&sampled_row)
+            .any(|(single, sampled)| sampled.g > 0.0 && sampled.g < single.g * 0.75);
+        assert!(blended);
+    }
+
+    #[test]
+    fn tiles_cover_the_image_once_and_are_clipped_at_the_edges() {
+        let c = Camera::new(10, 7, PI / 2.0);
+        let tiles = c.tiles(4);
+
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[2],
+            Tile {
+                x: 8,
+                y: 0,
+                width: 2,
+                height: 4
+            }
+        );
+        let area: usize = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 10 * 7);
+    }
+
+    #[test]
+    fn tiled_render_matches_scanline_render() {
+        use crate::{transformations::view_transform, world::World};
+
+        let w = World::default_world();
+        let mut c = Camera::new(13, 9, PI / 2.0);
+        c.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+        let expected = c.render(&w);
+
+        let mut tiled = Canvas::new(13, 9);
+        let mut tiles_seen = 0;
+        c.render_tiled(&w, 5, |tile, pixels| {
+            assert_eq!(pixels.len(), tile.width * tile.height);
+            for (i, colour) in pixels.iter().enumerate() {
+                tiled.write_pixel(tile.x + i % tile.width, tile.y + i / tile.width, *colour);
+            }
+            tiles_seen += 1;
+        });
+
+        assert_eq!(tiles_seen, 6);
+        for y in 0..9 {
+            for x in 0..13 {
+                assert_eq!(tiled.pixel_at(x, y), expected.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling_only_supersamples_high_contrast_pixels() {
+        use crate::{transformations::view_transform, world::World};
+
+        let mut w = World::default_world();
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+        let single = c.render(&w);
+        w.settings.samples = 16;
+        let full = c.render(&w);
+        w.settings.samples = 1;
+        c.set_adaptive(0.1, 16);
+        let adaptive = c.render(&w);
+
+        // Flat areas such as the background keep one sample; edges get all
+        // sixteen
+        let (mut kept, mut refined) = (0, 0);
+        for y in 0..11 {
+            for x in 0..11 {
+                let pixel = adaptive.pixel_at(x, y);
+                if pixel == single.pixel_at(x, y) {
+                    kept += 1;
+                } else {
+                    assert_eq!(pixel, full.pixel_at(x, y));
+                    refined += 1;
+                }
+            }
+        }
+        assert_eq!(adaptive.pixel_at(0, 0), single.pixel_at(0, 0));
+        assert!(kept > 0 && refined > 0);
+
+        let mut tiled = Canvas::new(11, 11);
+        c.render_tiled(&w, 4, |tile, pixels| {
+            for (i, colour) in pixels.iter().enumerate() {
+                tiled.write_pixel(tile.x + i % tile.width, tile.y + i / tile.width, *colour);
+            }
+        });
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_eq!(tiled.pixel_at(x, y), adaptive.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn open_shutter_blurs_moving_shapes() {
+        use crate::{light::Light, shape::sphere::Sphere, shape::Shape, world::World};
+
+        // One bright sphere sweeping two units to the right during the exposure
+        let mut w = World::new();
+        w.lights = vec![Light::point_light(
+            Tuple::point(0.0, 0.0, 0.0),
+            Colour::white(),
+        )];
+        let mut s = Sphere::new();
+        s.data.material.ambient = 1.0;
+        s.set_transform(Matrix::translation(0.0, 0.0, -5.0));
+        s.set_velocity(Some(Tuple::vector(-4.0, 0.0, 0.0)));
+        w.add_object(s);
+        w.settings.samples = 64;
+        let mut c = Camera::new(21, 11, PI / 2.0);
+
+        let still = c.render(&w);
+        c.shutter = 0.5;
+        let blurred = c.render(&w);
+
+        let times: Vec<Float> = (0..64).map(|i| c.sample_time(3, 4, i)).collect();
+        assert!(times.iter().all(|t| (0.0..0.5).contains(t)));
+        assert_eq!(times[5], c.sample_time(3, 4, 5));
+
+        // The centre is covered for part of the exposure, and pixels the sphere
+        // only passes over pick up some of its colour
+        let centre = blurred.pixel_at(10, 5);
+        assert!(centre.r > 0.2 && centre.r < still.pixel_at(10, 5).r);
+        assert_eq!(still.pixel_at(14, 5), Colour::black());
+        assert!(blurred.pixel_at(14, 5).r > 0.0);
+    }
 }
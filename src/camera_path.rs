@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use crate::{matrix::Matrix, scalar::Float, tuple::Tuple};
+
+// Where the camera sits and what it looks at over the course of an animation
+#[derive(Debug, Clone, Copy)]
+pub enum CameraPath {
+    // Circles the look-at point about the vertical axis once. The last frame
+    // stops one step short of the first so the animation loops cleanly.
+    Orbit {
+        from: Tuple,
+        to: Tuple,
+    },
+    // Moves in a straight line from one pose to another, ending on the second
+    Between {
+        from: Tuple,
+        to: Tuple,
+        end_from: Tuple,
+        end_to: Tuple,
+    },
+}
+
+impl CameraPath {
+    // Camera position and look-at point for a frame of an animation with
+    // `frames` frames in total
+    pub fn pose(&self, frame: usize, frames: usize) -> (Tuple, Tuple) {
+        match *self {
+            CameraPath::Orbit { from, to } => {
+                let angle =
+                    2.0 * crate::scalar::consts::PI * frame as Float / frames.max(1) as Float;
+                let orbit = &(&Matrix::translation(to.x, to.y, to.z) * &Matrix::rotation_y(angle))
+                    * &Matrix::translation(-to.x, -to.y, -to.z);
+                (&orbit * from, to)
+            }
+            CameraPath::Between {
+                from,
+                to,
+                end_from,
+                end_to,
+            } => {
+                let t = if frames > 1 {
+                    frame as Float / (frames - 1) as Float
+                } else {
+                    0.0
+                };
+                (from + (end_from - from) * t, to + (end_to - to) * t)
+            }
+        }
+    }
+}
+
+// Numbers a frame's file after the output path: out/spin.png gives
+// out/spin_0001.png, out/spin_0002.png and so on, counting from 1
+pub fn frame_path(output: &str, frame: usize) -> PathBuf {
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{:04}.{}", stem, frame + 1, ext.to_string_lossy()),
+        None => format!("{}_{:04}", stem, frame + 1),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn orbit_circles_the_target_at_a_constant_distance() {
+        let path = CameraPath::Orbit {
+            from: Tuple::point(0.0, 1.0, -5.0),
+            to: Tuple::point(1.0, 0.0, 0.0),
+        };
+
+        let (start, target) = path.pose(0, 4);
+        assert_abs_diff_eq!(start, Tuple::point(0.0, 1.0, -5.0), epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(target, Tuple::point(1.0, 0.0, 0.0), epsilon = TEST_EPSILON);
+
+        // A quarter turn about y through the target
+        let (quarter, _) = path.pose(1, 4);
+        assert_abs_diff_eq!(
+            quarter,
+            Tuple::point(-4.0, 1.0, 1.0),
+            epsilon = TEST_EPSILON
+        );
+        let (half, _) = path.pose(2, 4);
+        assert_abs_diff_eq!(half, Tuple::point(2.0, 1.0, 5.0), epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn between_ends_on_the_second_pose() {
+        let path = CameraPath::Between {
+            from: Tuple::point(0.0, 0.0, -5.0),
+            to: Tuple::point(0.0, 0.0, 0.0),
+            end_from: Tuple::point(4.0, 2.0, -5.0),
+            end_to: Tuple::point(0.0, 2.0, 0.0),
+        };
+
+        let (middle, middle_target) = path.pose(1, 3);
+        assert_abs_diff_eq!(middle, Tuple::point(2.0, 1.0, -5.0), epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(
+            middle_target,
+            Tuple::point(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        let (end, _) = path.pose(2, 3);
+        assert_abs_diff_eq!(end, Tuple::point(4.0, 2.0, -5.0), epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn frames_are_numbered_after_the_output() {
+        assert_eq!(
+            frame_path("renders/spin.png", 0),
+            PathBuf::from("renders/spin_0001.png")
+        );
+        assert_eq!(frame_path("frame", 41), PathBuf::from("frame_0042"));
+    }
+}
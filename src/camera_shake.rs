@@ -0,0 +1,89 @@
+use crate::{matrix::Matrix, noise::Noise, scalar::Float};
+
+// Handheld-style jitter for animated cameras. Each of the six degrees of
+// freedom follows its own smooth 1D Perlin noise curve, so the camera drifts
+// rather than jumping between frames. Offsets are in camera space: position in
+// scene units and rotation in radians, both at most the given amplitude.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraShake {
+    pub position_amplitude: Float,
+    pub rotation_amplitude: Float,
+    // Roughly how many direction changes per second
+    pub frequency: Float,
+    pub seed: u32,
+}
+
+impl CameraShake {
+    pub fn new(
+        position_amplitude: Float,
+        rotation_amplitude: Float,
+        frequency: Float,
+        seed: u32,
+    ) -> CameraShake {
+        CameraShake {
+            position_amplitude,
+            rotation_amplitude,
+            frequency,
+            seed,
+        }
+    }
+
+    // Camera-space transform to apply on top of the view transform at `time`
+    pub fn offset_at(&self, time: Float) -> Matrix {
+        let x = time * self.frequency;
+        let channel =
+            |index: u32| Noise::new(self.seed.wrapping_mul(6).wrapping_add(index)).perlin_1d(x);
+
+        let translation = Matrix::translation(
+            channel(0) * self.position_amplitude,
+            channel(1) * self.position_amplitude,
+            channel(2) * self.position_amplitude,
+        );
+        let rotation = Matrix::rotation_z(channel(5) * self.rotation_amplitude)
+            * Matrix::rotation_y(channel(4) * self.rotation_amplitude)
+            * Matrix::rotation_x(channel(3) * self.rotation_amplitude);
+        translation * rotation
+    }
+
+    pub fn apply(&self, view_transform: &Matrix, time: Float) -> Matrix {
+        &self.offset_at(time) * view_transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::Tuple;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn shake_stays_within_its_amplitude() {
+        let shake = CameraShake::new(0.1, 0.0, 2.0, 7);
+
+        for i in 0..200 {
+            let moved = shake.offset_at(i as Float * 0.031) * Tuple::point(0.0, 0.0, 0.0);
+            assert!(moved.x.abs() <= 0.1 && moved.y.abs() <= 0.1 && moved.z.abs() <= 0.1);
+        }
+    }
+
+    #[test]
+    fn shake_is_smooth_and_repeatable() {
+        let shake = CameraShake::new(0.5, 0.05, 1.0, 1);
+        let at = |t: Float| shake.offset_at(t) * Tuple::point(0.0, 0.0, 1.0);
+
+        // Same time, same offset
+        assert_abs_diff_eq!(at(0.3), at(0.3));
+        // A millisecond later the camera has barely moved
+        assert_abs_diff_eq!(at(0.3), at(0.301), epsilon = 0.01);
+        // ...but over a longer span it has
+        assert!((at(0.3) - at(1.7)).magnitude() > 1e-3);
+    }
+
+    #[test]
+    fn different_seeds_shake_differently() {
+        let a = CameraShake::new(1.0, 0.0, 1.0, 1).offset_at(0.5) * Tuple::point(0.0, 0.0, 0.0);
+        let b = CameraShake::new(1.0, 0.0, 1.0, 2).offset_at(0.5) * Tuple::point(0.0, 0.0, 0.0);
+
+        assert!((a - b).magnitude() > 1e-6);
+    }
+}
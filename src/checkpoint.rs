@@ -0,0 +1,231 @@
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::{
+    camera::Canvas,
+    colour::Colour,
+    scalar::{to_f64, Float},
+};
+
+const MAGIC: &[u8; 8] = b"RTCHECK1";
+
+// The rows of an image rendered so far, saved now and then during long renders
+// so they can be finished after an interruption. Rows are rendered top to
+// bottom, so the finished part is always the first rows_done() rows.
+//
+// Colours are stored at full precision, so a resumed render gives the same
+// image as one that was never interrupted. The scene hash, when there is one,
+// makes sure a render is only resumed with the scene and settings it started
+// with.
+pub struct Checkpoint {
+    pub width: usize,
+    pub height: usize,
+    pub scene_hash: Option<String>,
+    // The finished rows, in reading order
+    pub pixels: Vec<Colour>,
+}
+
+impl Checkpoint {
+    pub fn new(width: usize, height: usize, scene_hash: Option<String>) -> Checkpoint {
+        Checkpoint {
+            width,
+            height,
+            scene_hash,
+            pixels: Vec::new(),
+        }
+    }
+
+    pub fn rows_done(&self) -> usize {
+        self.pixels.len() / self.width.max(1)
+    }
+
+    pub fn add_row(&mut self, row: &[Colour]) {
+        self.pixels.extend_from_slice(row);
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.rows_done() >= self.height
+    }
+
+    // Fails unless the checkpoint was taken from a render of this size and
+    // scene hash
+    pub fn check_matches(
+        &self,
+        width: usize,
+        height: usize,
+        scene_hash: Option<&str>,
+    ) -> Result<(), String> {
+        if (self.width, self.height) != (width, height) {
+            return Err(format!(
+                "Checkpoint is for a {}x{} image, not {}x{}",
+                self.width, self.height, width, height
+            ));
+        }
+        if self.scene_hash.as_deref() != scene_hash {
+            return Err("Checkpoint is from a different scene or render settings".to_string());
+        }
+        Ok(())
+    }
+
+    // The finished rows, with the rest of the image black
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (i, colour) in self.pixels.iter().enumerate() {
+            canvas.write_pixel(i % self.width, i / self.width, *colour);
+        }
+        canvas
+    }
+
+    // Written to a temporary file first, so being interrupted part way through
+    // saving leaves the previous checkpoint intact
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        let error = |e: std::io::Error| format!("Failed to save checkpoint: {}", e);
+
+        let file = fs::File::create(&temporary).map_err(error)?;
+        let mut writer = BufWriter::new(file);
+        let hash = self.scene_hash.as_deref().unwrap_or("").as_bytes();
+        writer.write_all(MAGIC).map_err(error)?;
+        for value in [self.width, self.height, hash.len(), self.pixels.len()] {
+            writer
+                .write_all(&(value as u64).to_le_bytes())
+                .map_err(error)?;
+        }
+        writer.write_all(hash).map_err(error)?;
+        for colour in &self.pixels {
+            for channel in [colour.r, colour.g, colour.b] {
+                writer
+                    .write_all(&to_f64(channel).to_le_bytes())
+                    .map_err(error)?;
+            }
+        }
+        writer.flush().map_err(error)?;
+        drop(writer);
+        fs::rename(&temporary, path).map_err(error)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, String> {
+        let path = path.as_ref();
+        let file = fs::File::open(path)
+            .map_err(|e| format!("Failed to open checkpoint {}: {}", path.display(), e))?;
+        let mut reader = BufReader::new(file);
+        let invalid = |_| format!("{} is not a complete checkpoint", path.display());
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(invalid)?;
+        if &magic != MAGIC {
+            return Err(format!("{} is not a checkpoint", path.display()));
+        }
+        let mut read_u64 = || -> Result<u64, String> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes).map_err(invalid)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let [width, height, hash_len, pixel_count] =
+            [read_u64()?, read_u64()?, read_u64()?, read_u64()?].map(|v| v as usize);
+        let fits = width
+            .checked_mul(height)
+            .is_some_and(|size| pixel_count <= size);
+        // Sizes are checked against the file before anything is allocated for
+        // them: the magic number, four sizes, the hash, then 24 bytes a pixel
+        let file_len = reader
+            .get_ref()
+            .metadata()
+            .map_err(|e| format!("Failed to read checkpoint {}: {}", path.display(), e))?
+            .len();
+        let expected_len = (pixel_count as u64)
+            .checked_mul(24)
+            .and_then(|len| len.checked_add(hash_len as u64))
+            .and_then(|len| len.checked_add(8 + 4 * 8));
+        if width == 0 || !fits || pixel_count % width != 0 || expected_len != Some(file_len) {
+            return Err(format!("{} is not a valid checkpoint", path.display()));
+        }
+
+        let mut hash = vec![0; hash_len];
+        reader.read_exact(&mut hash).map_err(invalid)?;
+        let hash = String::from_utf8(hash)
+            .map_err(|_| format!("{} is not a valid checkpoint", path.display()))?;
+
+        let mut pixels = Vec::with_capacity(pixel_count);
+        let mut bytes = [0; 24];
+        for _ in 0..pixel_count {
+            reader.read_exact(&mut bytes).map_err(invalid)?;
+            let channel =
+                |i: usize| f64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap()) as Float;
+            pixels.push(Colour::new(channel(0), channel(1), channel(2)));
+        }
+
+        Ok(Checkpoint {
+            width,
+            height,
+            scene_hash: Some(hash).filter(|hash| !hash.is_empty()),
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("raytracer_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn checkpoints_round_trip_through_a_file() {
+        let mut checkpoint = Checkpoint::new(2, 3, Some("abc123".to_string()));
+        checkpoint.add_row(&[Colour::new(0.1, 0.2, 0.3), Colour::new(1.0 / 3.0, 4.0, 0.0)]);
+        let path = temp_path("round_trip.ckpt");
+        checkpoint.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((loaded.width, loaded.height), (2, 3));
+        assert_eq!(loaded.rows_done(), 1);
+        assert!(!loaded.is_complete());
+        assert_eq!(loaded.pixels, checkpoint.pixels);
+        assert!(loaded.check_matches(2, 3, Some("abc123")).is_ok());
+        assert!(loaded.check_matches(2, 3, Some("other")).is_err());
+        assert!(loaded.check_matches(3, 2, Some("abc123")).is_err());
+
+        let canvas = loaded.to_canvas();
+        assert_eq!(canvas.pixel_at(1, 0), Colour::new(1.0 / 3.0, 4.0, 0.0));
+        assert_eq!(canvas.pixel_at(1, 2), Colour::black());
+    }
+
+    #[test]
+    fn checkpoints_with_impossible_sizes_are_rejected() {
+        let path = temp_path("impossible.ckpt");
+        let header = |sizes: [u64; 4]| {
+            let mut bytes = MAGIC.to_vec();
+            for size in sizes {
+                bytes.extend_from_slice(&size.to_le_bytes());
+            }
+            bytes
+        };
+
+        // A huge hash, and a huge image claiming every pixel is there
+        for sizes in [[1, 1, 1 << 62, 0], [1 << 31, 1 << 31, 0, 1 << 62]] {
+            fs::write(&path, header(sizes)).unwrap();
+            assert!(Checkpoint::load(&path).is_err());
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_checkpoints_are_rejected() {
+        let mut checkpoint = Checkpoint::new(1, 1, None);
+        checkpoint.add_row(&[Colour::white()]);
+        let path = temp_path("truncated.ckpt");
+        checkpoint.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+
+        let result = Checkpoint::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,27 +1,99 @@
+use crate::scalar::Float;
 use std::ops::{Add, Mul, Sub};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 #[derive(Debug, Copy, Clone)]
 pub struct Colour {
-    pub r: f64,
-    pub g: f64,
-    pub b: f64,
+    pub r: Float,
+    pub g: Float,
+    pub b: Float,
 }
 
 #[wasm_bindgen]
 impl Colour {
     #[wasm_bindgen(constructor)]
-    pub fn new(r: f64, g: f64, b: f64) -> Colour {
+    pub fn new(r: Float, g: Float, b: Float) -> Colour {
         Colour { r, g, b }
     }
 
     pub fn black() -> Colour {
-        Colour::new(0.0, 0.0, 0.0)
+        Colour::BLACK
     }
 
     pub fn white() -> Colour {
-        Colour::new(1.0, 1.0, 1.0)
+        Colour::WHITE
+    }
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Colour = Colour::rgb(1.0, 1.0, 1.0);
+    pub const GREY: Colour = Colour::rgb(0.5, 0.5, 0.5);
+    pub const RED: Colour = Colour::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Colour = Colour::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Colour = Colour::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Colour = Colour::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Colour = Colour::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Colour = Colour::rgb(1.0, 0.0, 1.0);
+
+    // new, for constants; wasm_bindgen methods can't be const
+    const fn rgb(r: Float, g: Float, b: Float) -> Colour {
+        Colour { r, g, b }
+    }
+
+    // "#ff8800", "ff8800" or "#f80". The channels are used as they are, with
+    // no sRGB decoding, so a colour comes back out of to_rgba8 unchanged.
+    pub fn from_hex(hex: &str) -> Result<Colour, String> {
+        let invalid = || format!("Invalid hex colour '{}'", hex);
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        // Each digit of the short form is doubled, so f80 is ff8800
+        let digits: String = match digits.len() {
+            3 => digits.chars().flat_map(|c| [c, c]).collect(),
+            _ => digits.to_string(),
+        };
+        if digits.len() != 6 || !digits.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid());
+        let [r, g, b] = [channel(0)?, channel(2)?, channel(4)?];
+        Ok(Colour::new(
+            r as Float / 255.0,
+            g as Float / 255.0,
+            b as Float / 255.0,
+        ))
+    }
+
+    // Hue in degrees, saturation and lightness from 0 to 1
+    pub fn from_hsl(hue: Float, saturation: Float, lightness: Float) -> Colour {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = lightness - chroma / 2.0;
+        Colour::new(r + m, g + m, b + m)
+    }
+
+    // Opaque 8-bit RGBA, clamping each channel to [0, 1]. Use
+    // ToneMapping::to_rgba8 for rendered colours, which may be far brighter.
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let byte = |v: Float| (v.clamp(0.0, 1.0) * 255.0) as u8;
+        [byte(self.r), byte(self.g), byte(self.b), 255]
+    }
+
+    // Rec. 709 relative luminance
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
     }
 }
 
@@ -48,9 +120,9 @@ impl Sub for Colour {
     }
 }
 
-impl Mul<f64> for Colour {
+impl Mul<Float> for Colour {
     type Output = Colour;
-    fn mul(self, scalar: f64) -> Colour {
+    fn mul(self, scalar: Float) -> Colour {
         Colour {
             r: self.r * scalar,
             g: self.g * scalar,
@@ -82,16 +154,16 @@ mod tests {
     }
 
     impl AbsDiffEq for Colour {
-        type Epsilon = f64;
+        type Epsilon = Float;
 
         fn default_epsilon() -> Self::Epsilon {
-            f64::EPSILON
+            Float::EPSILON
         }
 
         fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
-            f64::abs_diff_eq(&self.r, &other.r, epsilon)
-                && f64::abs_diff_eq(&self.g, &other.g, epsilon)
-                && f64::abs_diff_eq(&self.b, &other.b, epsilon)
+            Float::abs_diff_eq(&self.r, &other.r, epsilon)
+                && Float::abs_diff_eq(&self.g, &other.g, epsilon)
+                && Float::abs_diff_eq(&self.b, &other.b, epsilon)
         }
     }
 
@@ -125,4 +197,32 @@ mod tests {
         let result = c1 * c2;
         assert_abs_diff_eq!(result, Colour::new(0.9, 0.2, 0.04));
     }
+
+    #[test]
+    fn colours_from_hex() {
+        let orange = Colour::from_hex("#ff8800").unwrap();
+        assert_eq!(orange, Colour::new(1.0, 136.0 / 255.0, 0.0));
+        assert_eq!(Colour::from_hex("FF8800").unwrap(), orange);
+        assert_eq!(Colour::from_hex("#f80").unwrap(), orange);
+        assert_eq!(orange.to_rgba8(), [255, 136, 0, 255]);
+
+        for bad in ["", "#ff88", "#ff880g", "#ff8800ff", "#€€"] {
+            assert!(Colour::from_hex(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn colours_from_hsl() {
+        assert_eq!(Colour::from_hsl(0.0, 1.0, 0.5), Colour::RED);
+        assert_eq!(Colour::from_hsl(120.0, 1.0, 0.5), Colour::GREEN);
+        assert_eq!(Colour::from_hsl(-120.0, 1.0, 0.5), Colour::BLUE);
+        assert_eq!(Colour::from_hsl(300.0, 1.0, 0.5), Colour::MAGENTA);
+        assert_eq!(Colour::from_hsl(45.0, 0.0, 0.5), Colour::GREY);
+        assert_abs_diff_eq!(Colour::from_hsl(30.0, 1.0, 0.5), Colour::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn colours_are_clamped_to_bytes() {
+        assert_eq!(Colour::new(1.5, 0.5, -0.2).to_rgba8(), [255, 127, 0, 255]);
+    }
 }
@@ -0,0 +1,27 @@
+use crate::scalar::Float;
+
+// Tolerances for ray/surface maths. Single precision rounds far more coarsely
+// than double, so the f32 feature widens every margin.
+
+// For values that should match but were computed differently, such as a hit
+// point's height and the height of the cap it lies on
+#[cfg(not(feature = "f32"))]
+pub const EPSILON: Float = f64::EPSILON * 50000.0;
+#[cfg(feature = "f32")]
+pub const EPSILON: Float = 1e-4;
+
+// How far hit points are lifted along the normal before shadow and reflection
+// rays leave them, so those rays can't hit the surface they start on. Worlds
+// take it as metres and scale it to their units, unless renders set
+// RenderSettings::shadow_bias; see World::shadow_bias.
+#[cfg(not(feature = "f32"))]
+pub const SHADOW_BIAS: Float = f64::EPSILON * 50000.0;
+#[cfg(feature = "f32")]
+pub const SHADOW_BIAS: Float = 1e-4;
+
+// Rays moving less than this towards a flat surface, or across the axis of a
+// cylinder or cone, count as parallel to it
+#[cfg(not(feature = "f32"))]
+pub const PARALLEL_THRESHOLD: Float = f64::EPSILON * 50000.0;
+#[cfg(feature = "f32")]
+pub const PARALLEL_THRESHOLD: Float = 1e-4;
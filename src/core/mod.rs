@@ -0,0 +1,2 @@
+// Numeric foundations shared by the rest of the crate
+pub mod constants;
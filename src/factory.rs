@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{pattern::PatternType, scene::PatternDescription, shape::Shape};
+
+// Constructors for shape and pattern types defined outside this crate, so
+// scene files can use them by name. Register them at startup, before loading
+// any scenes. The built-in type names always take precedence.
+//
+// Shape factories get the object's fields other than "type", "pivot",
+// "transform", "material" and the visibility flags, which the loader applies
+// afterwards as for any other shape, replacing any transform the factory set.
+// Pattern factories get the whole pattern description, with fields the loader
+// doesn't know about in its params; the loader applies the pattern's
+// transform afterwards.
+pub type ShapeFactory = dyn Fn(&Map<String, Value>) -> Result<Box<dyn Shape>, String> + Send + Sync;
+pub type PatternFactory = dyn Fn(&PatternDescription) -> Result<PatternType, String> + Send + Sync;
+
+#[derive(Default)]
+struct Factories {
+    shapes: HashMap<String, Arc<ShapeFactory>>,
+    patterns: HashMap<String, Arc<PatternFactory>>,
+}
+
+fn factories() -> &'static RwLock<Factories> {
+    static FACTORIES: OnceLock<RwLock<Factories>> = OnceLock::new();
+    FACTORIES.get_or_init(Default::default)
+}
+
+// Registering a name again replaces the earlier factory
+pub fn register_shape<F>(name: &str, factory: F)
+where
+    F: Fn(&Map<String, Value>) -> Result<Box<dyn Shape>, String> + Send + Sync + 'static,
+{
+    factories()
+        .write()
+        .unwrap()
+        .shapes
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+pub fn register_pattern<F>(name: &str, factory: F)
+where
+    F: Fn(&PatternDescription) -> Result<PatternType, String> + Send + Sync + 'static,
+{
+    factories()
+        .write()
+        .unwrap()
+        .patterns
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+// The lock is released before the factory runs, so factories can build nested
+// custom shapes and patterns of their own
+pub fn build_shape(name: &str, params: &Map<String, Value>) -> Result<Box<dyn Shape>, String> {
+    let factory = factories().read().unwrap().shapes.get(name).cloned();
+    match factory {
+        Some(factory) => factory(params),
+        None => Err(format!("Unknown shape type '{}'", name)),
+    }
+}
+
+pub fn build_pattern(name: &str, description: &PatternDescription) -> Result<PatternType, String> {
+    let factory = factories().read().unwrap().patterns.get(name).cloned();
+    match factory {
+        Some(factory) => factory(description),
+        None => Err(format!("Unknown pattern type '{}'", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Float;
+    use crate::{
+        colour::Colour,
+        matrix::Matrix,
+        pattern::{Pattern, PatternData},
+        scene::load_world,
+        shape::cylinder::Cylinder,
+        tuple::Tuple,
+    };
+
+    // Colours each unit cell along x from a fixed list
+    #[derive(Clone)]
+    struct Palette {
+        data: PatternData,
+        colours: Vec<Colour>,
+    }
+
+    impl Pattern for Palette {
+        fn data(&self) -> &PatternData {
+            &self.data
+        }
+
+        fn data_mut(&mut self) -> &mut PatternData {
+            &mut self.data
+        }
+
+        fn pattern_at(&self, point: Tuple) -> Colour {
+            let cell = point.x.floor().rem_euclid(self.colours.len() as Float);
+            self.colours[cell as usize]
+        }
+    }
+
+    #[test]
+    fn scenes_can_use_registered_shapes() {
+        register_shape("test_post", |params| {
+            let height = params
+                .get("height")
+                .and_then(Value::as_f64)
+                .ok_or("test_post needs a height")?;
+            Ok(Box::new(Cylinder::truncated(0.0, height as Float, true)))
+        });
+
+        let json = r#"{
+            "objects": [{
+                "type": "test_post", "height": 2,
+                "transform": [{ "translate": [0, 1, 0] }],
+                "material": { "ambient": 0.5 }
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let post = world.registry.get_by_index(0).unwrap();
+        assert_eq!(post.bounds().max.y, 2.0);
+        assert_eq!(post.world_bounds().max.y, 3.0);
+        assert_eq!(post.material().ambient, 0.5);
+
+        let err = load_world(r#"{ "objects": [{ "type": "test_post" }] }"#)
+            .err()
+            .unwrap();
+        assert!(err.contains("needs a height"), "{}", err);
+    }
+
+    #[test]
+    fn scenes_can_use_registered_patterns() {
+        register_pattern("test_palette", |description| {
+            let colours = description
+                .params
+                .get("colours")
+                .and_then(|c| serde_json::from_value::<Vec<[Float; 3]>>(c.clone()).ok())
+                .ok_or("test_palette needs colours")?;
+            let identity = Matrix::identity();
+            Ok(PatternType::Custom(Box::new(Palette {
+                data: PatternData {
+                    a: Colour::black().into(),
+                    b: Colour::black().into(),
+                    transform: identity.clone(),
+                    inverse_transform: identity,
+                },
+                colours: colours
+                    .iter()
+                    .map(|c| Colour::new(c[0], c[1], c[2]))
+                    .collect(),
+            })))
+        });
+
+        let json = r#"{
+            "objects": [{
+                "type": "plane",
+                "material": { "pattern": {
+                    "type": "test_palette",
+                    "colours": [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+                    "transform": [{ "scale": [2, 1, 1] }]
+                } }
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let plane = world.registry.get_by_index(0).unwrap();
+        let pattern = plane.material().pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(2.5, 0.0, 0.0)),
+            Colour::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn unregistered_types_are_errors() {
+        let err = load_world(r#"{ "objects": [{ "type": "teapot" }] }"#)
+            .err()
+            .unwrap();
+        assert!(err.contains("Unknown shape type 'teapot'"), "{}", err);
+
+        let json = r#"{ "objects": [{ "type": "plane", "material": { "pattern": { "type": "paisley" } } }] }"#;
+        let err = load_world(json).err().unwrap();
+        assert!(err.contains("Unknown pattern type 'paisley'"), "{}", err);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{projectile::Projectile, scalar::Float, tuple::Tuple};
+
+// Something pushing on projectiles, added to a Simulation with with_force.
+// Forces are divided by each projectile's mass, unlike the environment's
+// gravity and wind which accelerate everything alike.
+pub trait Force {
+    // The force on projectiles[index], given where all of them are at the
+    // start of the step
+    fn force(&self, index: usize, projectiles: &[Projectile]) -> Tuple;
+}
+
+// Air resistance, growing with the square of the speed and pushing against
+// the direction of travel
+pub struct Drag {
+    pub coefficient: Float,
+}
+
+impl Drag {
+    pub fn new(coefficient: Float) -> Drag {
+        Drag { coefficient }
+    }
+}
+
+impl Force for Drag {
+    fn force(&self, index: usize, projectiles: &[Projectile]) -> Tuple {
+        let velocity = projectiles[index].vel;
+        velocity * (-self.coefficient * velocity.magnitude())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpringEnd {
+    Projectile(usize),
+    Point(Tuple),
+}
+
+// A spring from one projectile to another, or to a fixed point, pulling when
+// stretched past its rest length and pushing when squashed. Damping adds a
+// force against the ends' speed towards or away from each other, so the
+// spring settles instead of bouncing forever.
+pub struct Spring {
+    pub from: usize,
+    pub to: SpringEnd,
+    pub stiffness: Float,
+    pub rest_length: Float,
+    pub damping: Float,
+}
+
+impl Spring {
+    pub fn new(from: usize, to: SpringEnd, stiffness: Float, rest_length: Float) -> Spring {
+        Spring {
+            from,
+            to,
+            stiffness,
+            rest_length,
+            damping: 0.0,
+        }
+    }
+
+    pub fn with_damping(mut self, damping: Float) -> Spring {
+        self.damping = damping;
+        self
+    }
+}
+
+impl Force for Spring {
+    fn force(&self, index: usize, projectiles: &[Projectile]) -> Tuple {
+        let other = match self.to {
+            _ if index == self.from => self.to,
+            SpringEnd::Projectile(to) if index == to => SpringEnd::Projectile(self.from),
+            _ => return Tuple::vector(0.0, 0.0, 0.0),
+        };
+        let this = &projectiles[index];
+        let (position, velocity) = match other {
+            SpringEnd::Projectile(other) => (projectiles[other].pos, projectiles[other].vel),
+            SpringEnd::Point(point) => (point, Tuple::vector(0.0, 0.0, 0.0)),
+        };
+
+        let offset = position - this.pos;
+        let length = offset.magnitude();
+        if length == 0.0 {
+            return Tuple::vector(0.0, 0.0, 0.0);
+        }
+        let direction = offset / length;
+        // Positive while the ends are moving apart
+        let separating = (velocity - this.vel).dot(&direction);
+        direction * (self.stiffness * (length - self.rest_length) + self.damping * separating)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn drag_opposes_motion_with_the_square_of_speed() {
+        let projectiles = [Projectile::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 0.0, 2.0),
+        )];
+
+        let force = Drag::new(0.5).force(0, &projectiles);
+
+        assert_abs_diff_eq!(force, Tuple::vector(0.0, 0.0, -2.0), epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn springs_pull_both_ends_together_when_stretched() {
+        let still = Tuple::vector(0.0, 0.0, 0.0);
+        let projectiles = [
+            Projectile::new(Tuple::point(0.0, 0.0, 0.0), still),
+            Projectile::new(Tuple::point(3.0, 0.0, 0.0), still),
+            Projectile::new(Tuple::point(9.0, 0.0, 0.0), still),
+        ];
+        let spring = Spring::new(0, SpringEnd::Projectile(1), 2.0, 1.0);
+
+        assert_abs_diff_eq!(
+            spring.force(0, &projectiles),
+            Tuple::vector(4.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            spring.force(1, &projectiles),
+            Tuple::vector(-4.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_eq!(spring.force(2, &projectiles), still);
+
+        // Squashed against a fixed point, it pushes away
+        let anchored = Spring::new(2, SpringEnd::Point(Tuple::point(10.0, 0.0, 0.0)), 2.0, 3.0);
+        assert_abs_diff_eq!(
+            anchored.force(2, &projectiles),
+            Tuple::vector(-4.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+}
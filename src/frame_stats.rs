@@ -0,0 +1,52 @@
+use serde::Serialize;
+
+// Timing breakdown for a single rendered frame, in milliseconds. Trace time covers
+// finding the nearest hit for camera rays; shading includes any shadow and
+// reflection rays spawned from those hits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FrameStats {
+    pub trace_ms: f64,
+    pub shade_ms: f64,
+    pub buffer_ms: f64,
+    pub rays_traced: u64,
+}
+
+impl FrameStats {
+    pub fn total_ms(&self) -> f64 {
+        self.trace_ms + self.shade_ms + self.buffer_ms
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "trace_ms": self.trace_ms,
+            "shade_ms": self.shade_ms,
+            "buffer_ms": self.buffer_ms,
+            "total_ms": self.total_ms(),
+            "rays_traced": self.rays_traced,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_serialise_to_json() {
+        let stats = FrameStats {
+            trace_ms: 1.5,
+            shade_ms: 2.0,
+            buffer_ms: 0.5,
+            rays_traced: 42,
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+
+        assert_eq!(json["trace_ms"], 1.5);
+        assert_eq!(json["shade_ms"], 2.0);
+        assert_eq!(json["buffer_ms"], 0.5);
+        assert_eq!(json["total_ms"], 4.0);
+        assert_eq!(json["rays_traced"], 42);
+    }
+}
@@ -1,41 +1,91 @@
 use crate::{
+    core::constants::SHADOW_BIAS,
     ray::Ray,
+    scalar::Float,
     shape::Shape,
     tuple::{reflect, Tuple},
 };
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Intersection {
-    pub t: f64,
+    pub t: Float,
     pub object_id: u32,
+    // Barycentric coordinates of a triangle hit. Other shapes' uv is only
+    // worked out for the hit, by prepare_computations.
+    pub uv: Option<(Float, Float)>,
+    // For hits on an Instance, the shape hit inside its prototype
+    pub part_id: Option<u32>,
 }
 
 impl Intersection {
-    pub fn new(t: f64, object: &dyn Shape) -> Self {
+    pub fn new(t: Float, object: &dyn Shape) -> Self {
         Intersection {
             t,
             object_id: object.data().id,
+            uv: None,
+            part_id: None,
+        }
+    }
+
+    pub fn with_uv(t: Float, object: &dyn Shape, u: Float, v: Float) -> Self {
+        Intersection {
+            t,
+            object_id: object.data().id,
+            uv: Some((u, v)),
+            part_id: None,
         }
     }
 }
 
 pub fn hit(xs: &[Intersection]) -> Option<&Intersection> {
+    hit_after(xs, 0.0)
+}
+
+// Nearest intersection at or beyond t_min. Secondary rays use a small positive
+// t_min so they can't hit the surface they were spawned from.
+pub fn hit_after(xs: &[Intersection], t_min: Float) -> Option<&Intersection> {
     xs.iter()
-        .filter(|intersection| intersection.t >= 0.0)
+        .filter(|intersection| intersection.t >= t_min)
         .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal))
 }
 
 pub struct PreComputedData<'a> {
-    pub t: f64,
+    pub t: Float,
     pub object: &'a dyn Shape,
     pub point: Tuple,
     pub over_point: Tuple,
+    // Just below the surface, where rays passing through it start
+    pub under_point: Tuple,
     pub eyev: Tuple,
     pub normalv: Tuple,
     pub reflectv: Tuple,
     pub inside: bool,
-    pub n1: f64,
-    pub n2: f64,
+    pub n1: Float,
+    pub n2: Float,
+    // Material::dispersion on either side of the surface, as for n1 and n2
+    pub dispersion: (Float, Float),
+    // The hit's surface coordinates, if its shape gives them
+    pub uv: Option<(Float, Float)>,
+    // The ray's time, eye and channel, for rays spawned from the hit
+    pub time: Float,
+    pub eye: Tuple,
+    pub channel: Option<usize>,
+}
+
+// Fraction of the light reflected rather than refracted where the ray meets
+// the surface, by Schlick's approximation to the Fresnel equations
+pub fn schlick(comps: &PreComputedData) -> Float {
+    let mut cos = comps.eyev.dot(&comps.normalv);
+    if comps.n1 > comps.n2 {
+        let n = comps.n1 / comps.n2;
+        let sin2_t = n * n * (1.0 - cos * cos);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        cos = (1.0 - sin2_t).sqrt();
+    }
+    let r0 = ((comps.n1 - comps.n2) / (comps.n1 + comps.n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
 
 fn intersection_eq(a: &Intersection, b: &Intersection) -> bool {
@@ -46,15 +96,53 @@ pub fn prepare_computations<'a>(
     hit: &Intersection,
     ray: &Ray,
     registry: &'a crate::shape_registry::ShapeRegistry,
-    all_intersections: Option<&Vec<Intersection>>,
+    all_intersections: Option<&[Intersection]>,
+) -> Option<PreComputedData<'a>> {
+    prepare_computations_with_bias(hit, ray, registry, all_intersections, SHADOW_BIAS)
+}
+
+// As prepare_computations, offsetting over_point and under_point from the
+// surface by `bias`
+pub fn prepare_computations_with_bias<'a>(
+    hit: &Intersection,
+    ray: &Ray,
+    registry: &'a crate::shape_registry::ShapeRegistry,
+    all_intersections: Option<&[Intersection]>,
+    bias: Float,
 ) -> Option<PreComputedData<'a>> {
     let sphere = registry.get(hit.object_id)?;
     let point = ray.position(hit.t);
     let eyev = -(ray.direction);
-    let mut normalv = sphere.normal_at(&point);
+    // A moving shape's normal is found where the shape was at time 0
+    let rest_point = sphere.data().at_rest(point, ray.time);
+    let mut normalv = match hit.part_id {
+        // The part's normal in the instance's object space, carried out to
+        // world space by the instance's transform
+        Some(part_id) => {
+            let data = sphere.data();
+            let part = sphere.part(part_id)?;
+            let n = &data.inverse_transpose
+                * part.normal_at_hit(&(&data.inverse_transform * rest_point), hit);
+            Tuple::vector(n.x, n.y, n.z).normalise()
+        }
+        None => sphere.normal_at_hit(&rest_point, hit),
+    };
+    // Triangles give uv with their hits; other shapes work it out for the
+    // hit alone here
+    let uv = hit.uv.or_else(|| match hit.part_id {
+        Some(part_id) => {
+            let part = sphere.part(part_id)?;
+            let local = &sphere.data().inverse_transform * rest_point;
+            part.local_uv_at(&(&part.data().inverse_transform * local))
+        }
+        None => sphere.local_uv_at(&(&sphere.data().inverse_transform * rest_point)),
+    });
+    if let Some(normal_map) = &sphere.material().normal_map {
+        normalv = normal_map.perturb(sphere, rest_point, normalv);
+    }
 
     let inside: bool;
-    if normalv.clone().dot(&eyev) < 0.0 {
+    if normalv.dot(&eyev) < 0.0 {
         inside = true;
         normalv = -normalv;
     } else {
@@ -63,25 +151,21 @@ pub fn prepare_computations<'a>(
 
     let reflectv = reflect(&ray.direction, &normalv);
 
-    let mut n1 = 1.0;
-    let mut n2 = 1.0;
+    // Refractive index and dispersion of whatever the ray is travelling through
+    let medium = |containers: &[&dyn Shape]| {
+        containers.last().map_or((1.0, 0.0), |obj| {
+            let material = &obj.data().material;
+            (material.refractive_index, material.dispersion)
+        })
+    };
+    let (mut n1, mut d1) = (1.0, 0.0);
+    let (mut n2, mut d2) = (1.0, 0.0);
     let mut containers: Vec<&dyn Shape> = Vec::new();
     if let Some(all_intersections) = all_intersections {
         for i in all_intersections {
-            println!(
-                "t: {}, object_id: {}, containers: {:?}",
-                i.t,
-                i.object_id,
-                containers
-                    .iter()
-                    .map(|o| (o.data().id, o.data().material.refractive_index))
-                    .collect::<Vec<_>>()
-            );
             // Set n1 before updating containers
             if intersection_eq(i, hit) {
-                n1 = containers
-                    .last()
-                    .map_or(1.0, |obj| obj.data().material.refractive_index);
+                (n1, d1) = medium(&containers);
             }
 
             // Update containers
@@ -97,9 +181,7 @@ pub fn prepare_computations<'a>(
 
             // Set n2 after updating containers, then break
             if intersection_eq(i, hit) {
-                n2 = containers
-                    .last()
-                    .map_or(1.0, |obj| obj.data().material.refractive_index);
+                (n2, d2) = medium(&containers);
                 break;
             }
         }
@@ -108,15 +190,20 @@ pub fn prepare_computations<'a>(
     Some(PreComputedData {
         t: hit.t,
         object: sphere,
-        point: point.clone(),
-        // Epsilon is too small, resulted in artifacts. Making it 50000 times larger works.
-        over_point: point + normalv * 50000.0 * f64::EPSILON,
+        point,
+        over_point: point + normalv * bias,
+        under_point: point - normalv * bias,
         eyev,
         normalv,
         reflectv,
         inside,
         n1,
         n2,
+        dispersion: (d1, d2),
+        uv,
+        time: ray.time,
+        eye: ray.eye,
+        channel: ray.channel,
     })
 }
 
@@ -143,7 +230,7 @@ mod tests {
         let s = Sphere::new();
         let i1 = Intersection::new(1.0, &s);
         let i2 = Intersection::new(2.0, &s);
-        let xs = vec![i1, i2];
+        let xs = [i1, i2];
 
         assert_eq!(xs.len(), 2);
         assert_eq!(xs[0].t, 1.0);
@@ -247,7 +334,7 @@ mod tests {
 
         let comps = prepare_computations(&i, &r, &registry, None).unwrap();
 
-        assert_eq!(comps.inside, false);
+        assert!(!comps.inside);
     }
 
     #[test]
@@ -267,7 +354,7 @@ mod tests {
 
         assert_eq!(comps.point, crate::tuple::Tuple::point(0.0, 0.0, 1.0));
         assert_eq!(comps.eyev, crate::tuple::Tuple::vector(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, true);
+        assert!(comps.inside);
         // normal would have been (0, 0, 1), but is inverted!
         assert_eq!(comps.normalv, crate::tuple::Tuple::vector(0.0, 0.0, -1.0));
     }
@@ -287,18 +374,104 @@ mod tests {
 
         let comps = prepare_computations(&i, &r, &registry, None).unwrap();
 
-        assert!(comps.over_point.z < -f64::EPSILON / 2.0);
+        assert!(comps.over_point.z < -SHADOW_BIAS / 2.0);
         assert!(comps.point.z > comps.over_point.z);
     }
 
+    #[test]
+    fn under_point_is_offset_below_the_surface() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut shape = Sphere::glass();
+        shape.set_transform(Matrix::translation(0.0, 0.0, 1.0));
+        let i = Intersection::new(5.0, &shape);
+        let xs = vec![i.clone()];
+        let mut registry = crate::shape_registry::ShapeRegistry::new();
+        registry.register(shape);
+
+        let comps = prepare_computations(&i, &r, &registry, Some(&xs)).unwrap();
+
+        assert!(comps.under_point.z > SHADOW_BIAS / 2.0);
+        assert!(comps.point.z < comps.under_point.z);
+    }
+
+    #[test]
+    fn precomputing_carries_the_hit_uv() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = Sphere::new();
+        let i = Intersection::with_uv(4.0, &shape, 0.25, 0.75);
+        let mut registry = crate::shape_registry::ShapeRegistry::new();
+        registry.register(shape);
+
+        let comps = prepare_computations(&i, &r, &registry, None).unwrap();
+
+        assert_eq!(comps.uv, Some((0.25, 0.75)));
+    }
+
+    #[test]
+    fn schlick_under_total_internal_reflection() {
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let r = Ray::new(Tuple::point(0.0, 0.0, half), Tuple::vector(0.0, 1.0, 0.0));
+        let shape = Sphere::glass();
+        let xs = vec![
+            Intersection::new(-half, &shape),
+            Intersection::new(half, &shape),
+        ];
+        let mut registry = crate::shape_registry::ShapeRegistry::new();
+        registry.register(shape);
+
+        let comps = prepare_computations(&xs[1], &r, &registry, Some(&xs)).unwrap();
+
+        assert_eq!(schlick(&comps), 1.0);
+    }
+
+    #[test]
+    fn schlick_grows_towards_grazing_angles() {
+        let shape = Sphere::glass();
+        let perpendicular = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let perpendicular_xs = vec![
+            Intersection::new(-1.0, &shape),
+            Intersection::new(1.0, &shape),
+        ];
+        let grazing = Ray::new(Tuple::point(0.0, 0.99, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let grazing_xs = vec![Intersection::new(1.8589, &shape)];
+        let mut registry = crate::shape_registry::ShapeRegistry::new();
+        registry.register(shape);
+
+        let comps = prepare_computations(
+            &perpendicular_xs[1],
+            &perpendicular,
+            &registry,
+            Some(&perpendicular_xs),
+        )
+        .unwrap();
+        assert!((schlick(&comps) - 0.04).abs() < 1e-5);
+        let comps =
+            prepare_computations(&grazing_xs[0], &grazing, &registry, Some(&grazing_xs)).unwrap();
+        assert!((schlick(&comps) - 0.48873).abs() < 1e-4);
+    }
+
+    #[test]
+    fn over_point_is_lifted_by_the_given_bias() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = Sphere::new();
+        let i = Intersection::new(4.0, &shape);
+        let mut registry = crate::shape_registry::ShapeRegistry::new();
+        registry.register(shape);
+
+        let comps = prepare_computations_with_bias(&i, &r, &registry, None, 0.01).unwrap();
+
+        assert!((comps.over_point.z + 1.01).abs() < 1e-6);
+        assert!((comps.under_point.z + 0.99).abs() < 1e-6);
+    }
+
     #[test]
     fn precomputing_reflection_vector() {
         let plane = Plane::new();
         let r = Ray::new(
             Tuple::point(0.0, 1.0, -1.0),
-            Tuple::vector(0.0, -(2.0 as f64).sqrt() / 2.0, (2.0 as f64).sqrt() / 2.0),
+            Tuple::vector(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
-        let i = Intersection::new((2.0 as f64).sqrt(), &plane);
+        let i = Intersection::new(Float::sqrt(2.0), &plane);
 
         let mut registry = crate::shape_registry::ShapeRegistry::new();
         registry.register(plane);
@@ -306,7 +479,7 @@ mod tests {
         let comps = prepare_computations(&i, &r, &registry, None).unwrap();
         assert_eq!(
             comps.reflectv,
-            Tuple::vector(0.0, (2.0 as f64).sqrt() / 2.0, (2.0 as f64).sqrt() / 2.0)
+            Tuple::vector(0.0, Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0)
         )
     }
 
@@ -368,4 +541,16 @@ mod tests {
             assert_eq!(comps.n2, expected_n2, "Failed at index {}: n2", index);
         }
     }
+
+    #[test]
+    fn hit_after_skips_intersections_below_t_min() {
+        let s = Sphere::new();
+        let i1 = Intersection::new(0.00001, &s);
+        let i2 = Intersection::new(2.0, &s);
+        let xs = vec![i1, i2];
+
+        assert_eq!(hit(&xs), Some(&xs[0]));
+        assert_eq!(hit_after(&xs, 0.001), Some(&xs[1]));
+        assert_eq!(hit_after(&xs, 3.0), None);
+    }
 }
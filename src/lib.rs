@@ -1,17 +1,49 @@
+pub mod accumulation_buffer;
+pub mod animation;
+pub mod aov;
+pub mod background;
+pub mod bake;
+pub mod batch;
+pub mod blueprint;
+pub mod bounds;
+pub mod bounds_overlay;
+pub mod bvh;
 pub mod camera;
+pub mod camera_path;
+pub mod camera_shake;
+pub mod checkpoint;
 pub mod colour;
+pub mod core;
 pub mod environment;
+pub mod factory;
+pub mod force;
+pub mod frame_stats;
 pub mod intersection;
 pub mod light;
+pub mod light_sampler;
+pub mod lut;
 pub mod materials;
 pub mod matrix;
+pub mod noise;
+pub mod normal_map;
+pub mod obj_parser;
 pub mod pattern;
 pub mod projectile;
+pub mod quaternion;
 pub mod ray;
 pub mod render_context;
+pub mod render_stats;
+pub mod scalar;
+pub mod scene;
+pub mod scene_graph;
+pub mod scene_hash;
+pub mod shadow_map;
 pub mod shape;
 pub mod shape_registry;
 pub mod simulation;
+pub mod sweep;
+pub mod tile_scheduler;
+pub mod tonemap;
 pub mod transformations;
 pub mod tuple;
 pub mod world;
@@ -1,9 +1,58 @@
-use crate::{colour::Colour, tuple::Tuple};
+use serde::{Deserialize, Serialize};
+
+use crate::{colour::Colour, noise::Noise, scalar::Float, tuple::Tuple};
 
 #[derive(Clone)]
 pub struct Light {
     pub position: Tuple,
     pub intensity: Colour,
+    // Varies the intensity over time, e.g. for candles and fires
+    pub flicker: Option<Flicker>,
+    pub falloff: Falloff,
+    // Shapes can choose which groups of lights reach them; see
+    // shape::LightLinks. Lights with no group reach every shape that doesn't
+    // ask for particular groups.
+    pub group: Option<String>,
+}
+
+// Bulb radius in metres for falloff given without one in scene files
+pub const DEFAULT_BULB_RADIUS: Float = 0.05;
+
+// How a light dims with distance. radius is the size of the bulb: intensity
+// is the brightness at its surface, and points inside it get no brighter.
+// Lights with falloff need a larger intensity than constant ones to light a
+// scene the same, e.g. intensity * distance^2 / radius^2 for inverse square.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Falloff {
+    // The same brightness at any distance
+    #[default]
+    None,
+    Linear {
+        radius: Float,
+    },
+    // Physically based
+    InverseSquare {
+        radius: Float,
+    },
+}
+
+impl Falloff {
+    // Scale applied to the intensity at a distance from the light
+    pub fn factor(&self, distance: Float) -> Float {
+        match *self {
+            Falloff::None => 1.0,
+            Falloff::Linear { radius } => radius / distance.max(radius),
+            Falloff::InverseSquare { radius } => (radius / distance.max(radius)).powi(2),
+        }
+    }
+
+    pub fn radius(&self) -> Option<Float> {
+        match *self {
+            Falloff::None => None,
+            Falloff::Linear { radius } | Falloff::InverseSquare { radius } => Some(radius),
+        }
+    }
 }
 
 impl Light {
@@ -11,8 +60,76 @@ impl Light {
         Light {
             position,
             intensity,
+            flicker: None,
+            falloff: Falloff::None,
+            group: None,
+        }
+    }
+
+    pub fn with_group(mut self, group: &str) -> Light {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    // A bulb with no size would light points at it with infinite brightness
+    pub fn with_falloff(mut self, falloff: Falloff) -> Light {
+        if let Some(radius) = falloff.radius() {
+            assert!(radius > 0.0, "falloff radius must be positive");
+        }
+        self.falloff = falloff;
+        self
+    }
+
+    // The light's brightness where it reaches a point
+    pub fn intensity_at(&self, point: Tuple) -> Colour {
+        match self.falloff {
+            Falloff::None => self.intensity,
+            falloff => self.intensity * falloff.factor((self.position - point).magnitude()),
+        }
+    }
+
+    pub fn with_flicker(mut self, flicker: Flicker) -> Light {
+        self.flicker = Some(flicker);
+        self
+    }
+
+    // The light as it is at the given time in seconds
+    pub fn at_time(&self, time: Float) -> Light {
+        match &self.flicker {
+            Some(flicker) => Light {
+                position: self.position,
+                intensity: self.intensity * flicker.factor_at(time),
+                flicker: None,
+                falloff: self.falloff,
+                group: self.group.clone(),
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+// Scales a light's intensity by 1 + amplitude * noise(time * frequency), where the
+// noise is smooth and lies in [-1, 1]. The seed lets several lights flicker
+// independently; the same seed and time always give the same value.
+#[derive(Debug, Clone, Copy)]
+pub struct Flicker {
+    pub amplitude: Float,
+    pub frequency: Float,
+    pub seed: u32,
+}
+
+impl Flicker {
+    pub fn new(amplitude: Float, frequency: Float, seed: u32) -> Flicker {
+        Flicker {
+            amplitude,
+            frequency,
+            seed,
         }
     }
+
+    pub fn factor_at(&self, time: Float) -> Float {
+        (1.0 + self.amplitude * Noise::new(self.seed).value_1d(time * self.frequency)).max(0.0)
+    }
 }
 
 #[cfg(test)]
@@ -28,4 +145,77 @@ mod tests {
         assert_eq!(light.position, position);
         assert_eq!(light.intensity, intensity);
     }
+
+    #[test]
+    fn light_without_flicker_is_constant() {
+        let light = Light::point_light(Tuple::point(0.0, 0.0, 0.0), Colour::white());
+
+        assert_eq!(light.at_time(12.5).intensity, Colour::white());
+    }
+
+    #[test]
+    fn flicker_stays_within_amplitude() {
+        let flicker = Flicker::new(0.3, 8.0, 7);
+
+        for i in 0..1000 {
+            let factor = flicker.factor_at(i as Float * 0.013);
+            assert!((0.7..=1.3).contains(&factor), "{}", factor);
+        }
+    }
+
+    #[test]
+    fn flicker_varies_smoothly_over_time() {
+        let flicker = Flicker::new(0.5, 4.0, 1);
+        let samples: Vec<Float> = (0..200)
+            .map(|i| flicker.factor_at(i as Float * 0.001))
+            .collect();
+
+        assert!(samples.windows(2).all(|w| (w[0] - w[1]).abs() < 0.05));
+        assert!(samples.iter().any(|f| (f - samples[0]).abs() > 1e-6));
+    }
+
+    #[test]
+    fn flicker_is_deterministic_per_seed() {
+        let a = Flicker::new(0.5, 3.0, 1);
+        let b = Flicker::new(0.5, 3.0, 2);
+
+        assert_eq!(a.factor_at(1.37), a.factor_at(1.37));
+        assert_ne!(a.factor_at(1.37), b.factor_at(1.37));
+    }
+
+    #[test]
+    fn flickering_light_scales_its_intensity() {
+        let flicker = Flicker::new(0.4, 2.0, 3);
+        let light = Light::point_light(Tuple::point(0.0, 0.0, 0.0), Colour::new(1.0, 0.5, 0.25))
+            .with_flicker(flicker);
+
+        let factor = flicker.factor_at(0.8);
+        let lit = light.at_time(0.8);
+        assert_eq!(
+            lit.intensity,
+            Colour::new(factor, 0.5 * factor, 0.25 * factor)
+        );
+        assert!(lit.flicker.is_none());
+    }
+
+    #[test]
+    fn falloff_dims_light_with_distance_outside_the_bulb() {
+        let light = Light::point_light(Tuple::point(0.0, 0.0, 0.0), Colour::white())
+            .with_falloff(Falloff::InverseSquare { radius: 0.5 });
+
+        let at = |d| light.intensity_at(Tuple::point(d, 0.0, 0.0)).r;
+        assert_eq!(at(0.1), 1.0);
+        assert_eq!(at(0.5), 1.0);
+        assert_eq!(at(1.0), 0.25);
+        assert_eq!(at(2.0), 0.0625);
+        assert_eq!(Falloff::Linear { radius: 0.5 }.factor(2.0), 0.25);
+        assert_eq!(Falloff::None.factor(100.0), 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "falloff radius must be positive")]
+    fn falloff_needs_a_bulb_with_a_size() {
+        Light::point_light(Tuple::point(0.0, 0.0, 0.0), Colour::white())
+            .with_falloff(Falloff::Linear { radius: 0.0 });
+    }
 }
@@ -0,0 +1,172 @@
+use crate::{light::Light, scalar::Float, tuple::Tuple};
+
+pub struct LightSample {
+    pub index: usize,
+    pub pdf: Float,
+}
+
+// Picks one light per shading point with probability proportional to its
+// estimated contribution there, so scenes with many lights spend samples on
+// the ones that matter.
+pub struct LightSampler<'a> {
+    lights: &'a [Light],
+}
+
+impl<'a> LightSampler<'a> {
+    pub fn new(lights: &'a [Light]) -> Self {
+        LightSampler { lights }
+    }
+
+    // Approximate contribution of a light: its brightness where it reaches the
+    // point, after any falloff, ignoring shadows and the surface's angle
+    pub fn estimated_contribution(light: &Light, point: Tuple) -> Float {
+        light.intensity_at(point).luminance()
+    }
+
+    pub fn weights_at(&self, point: Tuple) -> Vec<Float> {
+        self.lights
+            .iter()
+            .map(|light| LightSampler::estimated_contribution(light, point))
+            .collect()
+    }
+
+    // Selects a light using u in [0, 1). Returns None if there are no lights or
+    // none of them can contribute at this point.
+    pub fn sample(&self, point: Tuple, u: Float) -> Option<LightSample> {
+        let weights = self.weights_at(point);
+        let total: Float = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let target = u.clamp(0.0, 1.0) * total;
+        let mut cumulative = 0.0;
+        let mut last_nonzero = None;
+        for (index, weight) in weights.iter().enumerate() {
+            if *weight <= 0.0 {
+                continue;
+            }
+            cumulative += weight;
+            last_nonzero = Some(index);
+            if target < cumulative {
+                return Some(LightSample {
+                    index,
+                    pdf: weight / total,
+                });
+            }
+        }
+
+        // u == 1.0 or rounding pushed the target past the final bucket
+        last_nonzero.map(|index| LightSample {
+            index,
+            pdf: weights[index] / total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{colour::Colour, light::Falloff};
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn sampling_without_lights_returns_none() {
+        let sampler = LightSampler::new(&[]);
+
+        assert!(sampler.sample(Tuple::point(0.0, 0.0, 0.0), 0.5).is_none());
+    }
+
+    #[test]
+    fn single_light_is_always_chosen() {
+        let lights = [Light::point_light(
+            Tuple::point(0.0, 10.0, 0.0),
+            Colour::white(),
+        )];
+        let sampler = LightSampler::new(&lights);
+
+        for u in [0.0, 0.5, 0.999, 1.0] {
+            let sample = sampler.sample(Tuple::point(0.0, 0.0, 0.0), u).unwrap();
+            assert_eq!(sample.index, 0);
+            assert_eq!(sample.pdf, 1.0);
+        }
+    }
+
+    fn fading(position: Tuple, falloff: Falloff) -> Light {
+        Light::point_light(position, Colour::white()).with_falloff(falloff)
+    }
+
+    #[test]
+    fn closer_light_gets_more_weight_as_it_falls_off() {
+        let square = Falloff::InverseSquare { radius: 0.5 };
+        let linear = Falloff::Linear { radius: 0.5 };
+        let lights = [
+            fading(Tuple::point(0.0, 1.0, 0.0), square),
+            fading(Tuple::point(0.0, 2.0, 0.0), square),
+            fading(Tuple::point(0.0, -1.0, 0.0), linear),
+            fading(Tuple::point(0.0, -2.0, 0.0), linear),
+        ];
+        let sampler = LightSampler::new(&lights);
+        let weights = sampler.weights_at(Tuple::point(0.0, 0.0, 0.0));
+
+        assert_abs_diff_eq!(weights[0] / weights[1], 4.0, epsilon = 0.0001);
+        assert_abs_diff_eq!(weights[2] / weights[3], 2.0, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn lights_without_falloff_weigh_the_same_at_any_distance() {
+        let lights = [
+            Light::point_light(Tuple::point(0.0, 1.0, 0.0), Colour::white()),
+            Light::point_light(Tuple::point(0.0, 100.0, 0.0), Colour::white()),
+        ];
+        let sampler = LightSampler::new(&lights);
+        let weights = sampler.weights_at(Tuple::point(0.0, 0.0, 0.0));
+
+        assert_eq!(weights[0], weights[1]);
+    }
+
+    #[test]
+    fn brighter_light_gets_more_weight() {
+        let lights = [
+            Light::point_light(Tuple::point(0.0, 1.0, 0.0), Colour::new(0.5, 0.5, 0.5)),
+            Light::point_light(Tuple::point(0.0, -1.0, 0.0), Colour::new(2.0, 2.0, 2.0)),
+        ];
+        let sampler = LightSampler::new(&lights);
+        let weights = sampler.weights_at(Tuple::point(0.0, 0.0, 0.0));
+
+        assert_abs_diff_eq!(weights[1] / weights[0], 4.0, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn sample_pdf_matches_relative_weight() {
+        let falloff = Falloff::InverseSquare { radius: 0.5 };
+        let lights = [
+            fading(Tuple::point(0.0, 1.0, 0.0), falloff),
+            fading(Tuple::point(0.0, 0.0, 3.0), falloff),
+        ];
+        let sampler = LightSampler::new(&lights);
+        let point = Tuple::point(0.0, 0.0, 0.0);
+
+        let near = sampler.sample(point, 0.0).unwrap();
+        let far = sampler.sample(point, 0.99).unwrap();
+
+        assert_eq!(near.index, 0);
+        assert_eq!(far.index, 1);
+        assert_abs_diff_eq!(near.pdf, 0.9, epsilon = 0.0001);
+        assert_abs_diff_eq!(far.pdf, 0.1, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn black_lights_are_never_chosen() {
+        let lights = [
+            Light::point_light(Tuple::point(0.0, 1.0, 0.0), Colour::white()),
+            Light::point_light(Tuple::point(0.0, 1.0, 0.0), Colour::black()),
+        ];
+        let sampler = LightSampler::new(&lights);
+
+        let sample = sampler.sample(Tuple::point(0.0, 0.0, 0.0), 1.0).unwrap();
+
+        assert_eq!(sample.index, 0);
+        assert_eq!(sample.pdf, 1.0);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{colour::Colour, scalar::Float};
+use std::path::Path;
+
+// Largest LUT_3D_SIZE the .cube format allows
+const MAX_LUT_SIZE: usize = 256;
+
+// 3D colour lookup table in the Adobe/Resolve .cube format, applied to display
+// colours after tone mapping. With preserve_luminance set, only the LUT's hue
+// and saturation shift is kept: each result is rescaled to the brightness of
+// the colour that went in, so a grade can't crush or blow out the render.
+#[derive(Debug, Clone)]
+pub struct ColourLut {
+    size: usize,
+    domain_min: Colour,
+    domain_max: Colour,
+    // size^3 entries, red changing fastest, then green, then blue
+    table: Vec<Colour>,
+    pub preserve_luminance: bool,
+}
+
+impl ColourLut {
+    pub fn parse_cube(contents: &str) -> Result<ColourLut, String> {
+        let mut size = None;
+        let mut domain_min = Colour::new(0.0, 0.0, 0.0);
+        let mut domain_max = Colour::new(1.0, 1.0, 1.0);
+        let mut table = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("Line {}: {}", number + 1, message);
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap_or_default();
+            let values: Vec<&str> = parts.collect();
+
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err(error("1D LUTs are not supported")),
+                "LUT_3D_SIZE" => {
+                    let n = values
+                        .first()
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .filter(|n| (2..=MAX_LUT_SIZE).contains(n))
+                        .ok_or_else(|| {
+                            error(&format!("LUT_3D_SIZE must be from 2 to {}", MAX_LUT_SIZE))
+                        })?;
+                    size = Some(n);
+                }
+                "DOMAIN_MIN" => {
+                    domain_min = parse_rgb(&values).ok_or_else(|| error("bad DOMAIN_MIN"))?
+                }
+                "DOMAIN_MAX" => {
+                    domain_max = parse_rgb(&values).ok_or_else(|| error("bad DOMAIN_MAX"))?
+                }
+                _ => {
+                    let mut rgb = vec![keyword];
+                    rgb.extend(values);
+                    table.push(parse_rgb(&rgb).ok_or_else(|| error("expected three numbers"))?);
+                }
+            }
+        }
+
+        let size = size.ok_or("Missing LUT_3D_SIZE")?;
+        // Can't overflow, as the size is at most MAX_LUT_SIZE
+        let entries = size * size * size;
+        if table.len() != entries {
+            return Err(format!(
+                "Expected {} table entries for a size {} LUT, found {}",
+                entries,
+                size,
+                table.len()
+            ));
+        }
+
+        Ok(ColourLut {
+            size,
+            domain_min,
+            domain_max,
+            table,
+            preserve_luminance: false,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ColourLut, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        ColourLut::parse_cube(&contents)
+    }
+
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let graded = self.lookup(colour);
+        if !self.preserve_luminance {
+            return graded;
+        }
+
+        let target = colour.luminance();
+        let actual = graded.luminance();
+        if actual <= 0.0 {
+            return Colour::new(target, target, target);
+        }
+        graded * (target / actual)
+    }
+
+    // Trilinear interpolation between the eight surrounding table entries
+    fn lookup(&self, colour: Colour) -> Colour {
+        let max_index = (self.size - 1) as Float;
+        let scaled = |v: Float, min: Float, max: Float| {
+            let t = if max > min {
+                (v - min) / (max - min)
+            } else {
+                0.0
+            };
+            t.clamp(0.0, 1.0) * max_index
+        };
+        let r = scaled(colour.r, self.domain_min.r, self.domain_max.r);
+        let g = scaled(colour.g, self.domain_min.g, self.domain_max.g);
+        let b = scaled(colour.b, self.domain_min.b, self.domain_max.b);
+
+        let (r0, g0, b0) = (r.floor() as usize, g.floor() as usize, b.floor() as usize);
+        let (r1, g1, b1) = (
+            (r0 + 1).min(self.size - 1),
+            (g0 + 1).min(self.size - 1),
+            (b0 + 1).min(self.size - 1),
+        );
+        let (fr, fg, fb) = (r - r0 as Float, g - g0 as Float, b - b0 as Float);
+
+        let at = |r: usize, g: usize, b: usize| self.table[r + self.size * (g + self.size * b)];
+        let lerp = |a: Colour, b: Colour, t: Float| a + (b - a) * t;
+
+        let c00 = lerp(at(r0, g0, b0), at(r1, g0, b0), fr);
+        let c10 = lerp(at(r0, g1, b0), at(r1, g1, b0), fr);
+        let c01 = lerp(at(r0, g0, b1), at(r1, g0, b1), fr);
+        let c11 = lerp(at(r0, g1, b1), at(r1, g1, b1), fr);
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+}
+
+fn parse_rgb(values: &[&str]) -> Option<Colour> {
+    if values.len() != 3 {
+        return None;
+    }
+    let mut rgb = [0.0; 3];
+    for (v, text) in rgb.iter_mut().zip(values) {
+        *v = text.parse().ok()?;
+    }
+    Some(Colour::new(rgb[0], rgb[1], rgb[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    // Size 2 LUT that swaps the red and blue channels
+    const SWAP_RED_BLUE: &str = "
+        TITLE \"swap\"
+        # comment
+        LUT_3D_SIZE 2
+        0 0 0
+        0 0 1
+        0 1 0
+        0 1 1
+        1 0 0
+        1 0 1
+        1 1 0
+        1 1 1
+    ";
+
+    fn identity_cube(size: usize) -> String {
+        let mut cube = format!("LUT_3D_SIZE {}\n", size);
+        let step = 1.0 / (size - 1) as Float;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    cube.push_str(&format!(
+                        "{} {} {}\n",
+                        r as Float * step,
+                        g as Float * step,
+                        b as Float * step
+                    ));
+                }
+            }
+        }
+        cube
+    }
+
+    #[test]
+    fn identity_lut_leaves_colours_alone() {
+        let lut = ColourLut::parse_cube(&identity_cube(5)).unwrap();
+        let c = Colour::new(0.1, 0.55, 0.9);
+
+        assert_abs_diff_eq!(lut.apply(c), c, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn lut_values_are_interpolated() {
+        let lut = ColourLut::parse_cube(SWAP_RED_BLUE).unwrap();
+
+        assert_abs_diff_eq!(
+            lut.apply(Colour::new(0.25, 0.5, 0.75)),
+            Colour::new(0.75, 0.5, 0.25),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn preserving_luminance_keeps_brightness() {
+        let mut lut = ColourLut::parse_cube(SWAP_RED_BLUE).unwrap();
+        lut.preserve_luminance = true;
+        let c = Colour::new(0.2, 0.4, 0.9);
+
+        let graded = lut.apply(c);
+
+        assert_abs_diff_eq!(graded.luminance(), c.luminance(), epsilon = TEST_EPSILON);
+        assert!(graded.r > graded.b);
+    }
+
+    #[test]
+    fn inputs_outside_the_domain_are_clamped() {
+        let lut = ColourLut::parse_cube(&identity_cube(3)).unwrap();
+
+        assert_abs_diff_eq!(
+            lut.apply(Colour::new(-1.0, 2.0, 0.5)),
+            Colour::new(0.0, 1.0, 0.5),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn malformed_cubes_are_rejected() {
+        assert!(ColourLut::parse_cube("0 0 0").is_err());
+        assert!(ColourLut::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(ColourLut::parse_cube("LUT_1D_SIZE 16").is_err());
+        assert!(ColourLut::parse_cube("LUT_3D_SIZE 2\n0 0 x\n").is_err());
+        let huge = format!("LUT_3D_SIZE {}\n0 0 0\n", usize::MAX);
+        assert!(ColourLut::parse_cube(&huge).is_err());
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{
     colour::Colour,
     light::Light,
+    normal_map::NormalMap,
     pattern::PatternType,
+    scalar::Float,
     shape::Shape,
     tuple::{reflect, Tuple},
 };
@@ -9,14 +11,30 @@ use crate::{
 #[derive(Clone)]
 pub struct Material {
     pub colour: Colour,
-    pub ambient: f64,
-    pub diffuse: f64,
-    pub specular: f64,
-    pub shininess: f64,
-    pub reflective: f64,
-    pub transparency: f64,
-    pub refractive_index: f64,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    pub reflective: Float,
+    pub transparency: Float,
+    pub refractive_index: Float,
+    // Spread of the refractive index across colour channels: red light sees
+    // refractive_index - dispersion and blue light refractive_index +
+    // dispersion, so transparent shapes split white light into fringes. At 0
+    // one refracted ray is traced rather than one per channel.
+    pub dispersion: Float,
     pub pattern: Option<PatternType>,
+    // Perturbs shading normals for surface detail
+    pub normal_map: Option<NormalMap>,
+    // Light given off by the surface itself. It lights other surfaces only
+    // under the path integrator.
+    pub emissive: Colour,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Material {
@@ -30,7 +48,88 @@ impl Material {
             reflective: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: 0.0,
             pattern: None,
+            normal_map: None,
+            emissive: Colour::black(),
+        }
+    }
+
+    // Presets for common surfaces. Scene files can use them by name as
+    // materials to extend; see from_name.
+
+    // Clear, mostly reflective at grazing angles thanks to the Fresnel term
+    pub fn glass() -> Material {
+        Material {
+            colour: Colour::black(),
+            ambient: 0.0,
+            diffuse: 0.1,
+            specular: 1.0,
+            shininess: 300.0,
+            reflective: 0.9,
+            transparency: 0.9,
+            refractive_index: 1.5,
+            ..Material::new()
+        }
+    }
+
+    pub fn mirror() -> Material {
+        Material {
+            colour: Colour::black(),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 1.0,
+            shininess: 1000.0,
+            reflective: 1.0,
+            ..Material::new()
+        }
+    }
+
+    // No highlights at all
+    pub fn matte(colour: Colour) -> Material {
+        Material {
+            colour,
+            diffuse: 0.9,
+            specular: 0.0,
+            ..Material::new()
+        }
+    }
+
+    // roughness from 0 for polished to 1 for brushed, which widens the
+    // highlight and blurs it into the diffuse colour as reflections fade
+    pub fn metal(colour: Colour, roughness: Float) -> Material {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Material {
+            colour,
+            ambient: 0.05,
+            diffuse: 0.2 + 0.4 * roughness,
+            specular: 1.0 - 0.5 * roughness,
+            // Roughly the Phong exponent matching a Beckmann roughness
+            shininess: (2.0 / (roughness * roughness).max(1e-3) - 2.0).clamp(10.0, 1000.0),
+            reflective: 0.8 * (1.0 - roughness),
+            ..Material::new()
+        }
+    }
+
+    // The presets with default arguments: glass, mirror, matte (white),
+    // metal (grey, roughness 0.2), chrome (metal with roughness 0) and gold
+    pub fn from_name(name: &str) -> Option<Material> {
+        match name.to_ascii_lowercase().as_str() {
+            "glass" => Some(Material::glass()),
+            "mirror" => Some(Material::mirror()),
+            "matte" => Some(Material::matte(Colour::white())),
+            "metal" => Some(Material::metal(Colour::new(0.6, 0.6, 0.6), 0.2)),
+            "chrome" => Some(Material::metal(Colour::new(0.8, 0.8, 0.8), 0.0)),
+            "gold" => Some(Material::metal(Colour::new(1.0, 0.77, 0.34), 0.25)),
+            _ => None,
+        }
+    }
+
+    // The surface colour at a point on object, from the pattern if there is one
+    pub fn colour_at(&self, object: &dyn Shape, point: Tuple) -> Colour {
+        match &self.pattern {
+            Some(pattern) => pattern.pattern_at_shape(object, point),
+            None => self.colour,
         }
     }
 
@@ -38,19 +137,19 @@ impl Material {
         &self.colour
     }
 
-    pub fn ambient(&self) -> f64 {
+    pub fn ambient(&self) -> Float {
         self.ambient
     }
 
-    pub fn diffuse(&self) -> f64 {
+    pub fn diffuse(&self) -> Float {
         self.diffuse
     }
 
-    pub fn specular(&self) -> f64 {
+    pub fn specular(&self) -> Float {
         self.specular
     }
 
-    pub fn shininess(&self) -> f64 {
+    pub fn shininess(&self) -> Float {
         self.shininess
     }
 
@@ -63,19 +162,19 @@ impl Material {
         self.colour = colour;
     }
 
-    pub fn set_ambient(&mut self, ambient: f64) {
+    pub fn set_ambient(&mut self, ambient: Float) {
         self.ambient = ambient;
     }
 
-    pub fn set_diffuse(&mut self, diffuse: f64) {
+    pub fn set_diffuse(&mut self, diffuse: Float) {
         self.diffuse = diffuse;
     }
 
-    pub fn set_specular(&mut self, specular: f64) {
+    pub fn set_specular(&mut self, specular: Float) {
         self.specular = specular;
     }
 
-    pub fn set_shininess(&mut self, shininess: f64) {
+    pub fn set_shininess(&mut self, shininess: Float) {
         self.shininess = shininess;
     }
 
@@ -84,28 +183,59 @@ impl Material {
     }
 }
 
+// object is the shape that was hit, whose transform places material's pattern.
+// transmission is how much of the light gets past anything in the way, per
+// channel (see World::light_transmission); black for a point in full shadow.
 pub fn lighting(
-    material: Material,
+    material: &Material,
+    object: &dyn Shape,
+    light: &Light,
+    point: Tuple,
+    eyev: Tuple,
+    normalv: Tuple,
+    transmission: Colour,
+) -> Colour {
+    let [ambient, diffuse, specular] =
+        lighting_terms(material, object, light, point, eyev, normalv);
+    // Only the diffuse and specular terms depend on the light getting through
+    ambient + diffuse * transmission + specular * transmission
+}
+
+// As lighting, without the ambient term, for integrators that trace the
+// light bouncing between surfaces instead
+pub fn direct_lighting(
+    material: &Material,
     object: &dyn Shape,
-    light: Light,
+    light: &Light,
     point: Tuple,
     eyev: Tuple,
     normalv: Tuple,
-    in_shadow: bool,
+    transmission: Colour,
 ) -> Colour {
-    let colour = match material.pattern() {
-        Some(pattern) => pattern.pattern_at_shape(object, point),
-        None => material.colour,
-    };
+    let [_, diffuse, specular] = lighting_terms(material, object, light, point, eyev, normalv);
+    diffuse * transmission + specular * transmission
+}
+
+// Ambient, diffuse and specular light, before any shadowing
+fn lighting_terms(
+    material: &Material,
+    object: &dyn Shape,
+    light: &Light,
+    point: Tuple,
+    eyev: Tuple,
+    normalv: Tuple,
+) -> [Colour; 3] {
+    let colour = material.colour_at(object, point);
 
-    let effective_colour = colour * light.intensity;
+    let intensity = light.intensity_at(point);
+    let effective_colour = colour * intensity;
     let lightv = (light.position - point).normalise();
     let ambient = effective_colour * material.ambient;
     let light_dot_normal = lightv.dot(&normalv);
 
     let specular: Colour;
     let diffuse: Colour;
-    if light_dot_normal < 0.0 || in_shadow {
+    if light_dot_normal < 0.0 {
         diffuse = Colour::black();
         specular = Colour::black();
     } else {
@@ -117,15 +247,16 @@ pub fn lighting(
             specular = Colour::black();
         } else {
             let factor = reflect_dot_eye.powf(material.shininess);
-            specular = light.intensity * material.specular * factor;
+            specular = intensity * material.specular * factor;
         }
     }
 
-    return ambient + diffuse + specular;
+    [ambient, diffuse, specular]
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::scalar::TEST_EPSILON;
     use crate::{pattern::striped::Striped, shape::sphere::Sphere};
     use approx::assert_abs_diff_eq;
 
@@ -142,6 +273,26 @@ mod tests {
         assert_eq!(m.shininess, 200.0);
     }
 
+    #[test]
+    fn metals_get_duller_as_they_get_rougher() {
+        let polished = Material::metal(Colour::new(1.0, 0.77, 0.34), 0.0);
+        let brushed = Material::metal(Colour::new(1.0, 0.77, 0.34), 1.0);
+
+        assert!(polished.reflective > brushed.reflective);
+        assert!(polished.shininess > brushed.shininess);
+        assert_eq!(brushed.reflective, 0.0);
+        assert_eq!(polished.colour, Colour::new(1.0, 0.77, 0.34));
+    }
+
+    #[test]
+    fn presets_can_be_found_by_name() {
+        let glass = Material::from_name("Glass").unwrap();
+        assert_eq!(glass.refractive_index, 1.5);
+        assert_eq!(Material::from_name("mirror").unwrap().reflective, 1.0);
+        assert_eq!(Material::from_name("matte").unwrap().specular, 0.0);
+        assert!(Material::from_name("velvet").is_none());
+    }
+
     #[test]
     fn lighting_with_eye_between_light_and_surface() {
         let m = Material::new();
@@ -149,9 +300,17 @@ mod tests {
         let eyev = Tuple::vector(0.0, 0.0, -1.0);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = false;
+        let transmission = Colour::white();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_eq!(result, Colour::new(1.9, 1.9, 1.9));
     }
@@ -160,13 +319,21 @@ mod tests {
     fn lighting_with_eye_between_light_and_surface_eye_offset_45() {
         let m = Material::new();
         let position = Tuple::point(0.0, 0.0, 0.0);
-        let sqrt_2_div_2 = (2.0_f64).sqrt() / 2.0;
+        let sqrt_2_div_2 = Float::sqrt(2.0) / 2.0;
         let eyev = Tuple::vector(0.0, sqrt_2_div_2, -sqrt_2_div_2);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = false;
+        let transmission = Colour::white();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_eq!(result, Colour::new(1.0, 1.0, 1.0));
     }
@@ -178,9 +345,17 @@ mod tests {
         let eyev = Tuple::vector(0.0, 0.0, -1.0);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = false;
+        let transmission = Colour::white();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_abs_diff_eq!(
             result,
@@ -193,13 +368,21 @@ mod tests {
     fn lighting_with_eye_in_path_of_reflection_vector() {
         let m = Material::new();
         let position = Tuple::point(0.0, 0.0, 0.0);
-        let sqrt_2_div_2 = (2.0_f64).sqrt() / 2.0;
+        let sqrt_2_div_2 = Float::sqrt(2.0) / 2.0;
         let eyev = Tuple::vector(0.0, -sqrt_2_div_2, -sqrt_2_div_2);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 10.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = false;
+        let transmission = Colour::white();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_abs_diff_eq!(
             result,
@@ -215,9 +398,17 @@ mod tests {
         let eyev = Tuple::vector(0.0, 0.0, -1.0);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 0.0, 10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = false;
+        let transmission = Colour::white();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_eq!(result, Colour::new(0.1, 0.1, 0.1));
     }
@@ -229,13 +420,63 @@ mod tests {
         let eyev = Tuple::vector(0.0, 0.0, -1.0);
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
-        let in_shadow = true;
+        let transmission = Colour::black();
 
-        let result = lighting(m, &Sphere::new(), light, position, eyev, normalv, in_shadow);
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
 
         assert_eq!(result, Colour::new(0.1, 0.1, 0.1));
     }
 
+    #[test]
+    fn lighting_through_tinted_glass() {
+        let m = Material::new();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let transmission = Colour::new(1.0, 0.5, 0.0);
+
+        let result = lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            position,
+            eyev,
+            normalv,
+            transmission,
+        );
+
+        // Ambient light is untouched; diffuse and specular are filtered
+        assert_abs_diff_eq!(result, Colour::new(1.9, 1.0, 0.1), epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn direct_lighting_leaves_out_ambient() {
+        let m = Material::new();
+        let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+
+        let result = direct_lighting(
+            &m,
+            &Sphere::new(),
+            &light,
+            Tuple::point(0.0, 0.0, 0.0),
+            normalv,
+            normalv,
+            Colour::new(1.0, 0.5, 0.0),
+        );
+
+        assert_abs_diff_eq!(result, Colour::new(1.8, 0.9, 0.0), epsilon = TEST_EPSILON);
+    }
+
     #[test]
     fn lighting_with_a_pattern_applied() {
         let mut m = Material::new();
@@ -252,22 +493,22 @@ mod tests {
         let light = Light::point_light(Tuple::point(0.0, 0.0, -10.0), Colour::new(1.0, 1.0, 1.0));
 
         let c1 = lighting(
-            m.clone(),
+            &m,
             &Sphere::new(),
-            light.clone(),
+            &light,
             Tuple::point(0.9, 0.0, 0.0),
             eyev,
             normalv,
-            false,
+            Colour::white(),
         );
         let c2 = lighting(
-            m,
+            &m,
             &Sphere::new(),
-            light,
+            &light,
             Tuple::point(1.1, 0.0, 0.0),
             eyev,
             normalv,
-            false,
+            Colour::white(),
         );
 
         assert_eq!(c1, Colour::new(1.0, 1.0, 1.0));
@@ -1,27 +1,34 @@
 use std::ops::{Index, IndexMut, Mul};
 
-use crate::tuple::Tuple;
+use crate::{scalar::Float, tuple::Tuple};
 
+// Stored inline as a 4x4 array so matrices never touch the heap. Smaller
+// matrices, as produced by submatrix, use the top-left corner.
 #[derive(Debug, Clone)]
 pub struct Matrix {
-    data: Vec<Vec<f64>>,
+    data: [[Float; 4]; 4],
     rows: usize,
     cols: usize,
 }
 
 impl Matrix {
     pub fn new(rows: usize, cols: usize) -> Self {
+        assert!(rows <= 4 && cols <= 4, "matrices are at most 4x4");
         Matrix {
-            data: vec![vec![0.0; cols]; rows],
+            data: [[0.0; 4]; 4],
             rows,
             cols,
         }
     }
 
-    pub fn from_vec(data: Vec<Vec<f64>>) -> Self {
+    pub fn from_vec(data: Vec<Vec<Float>>) -> Self {
         let rows = data.len();
         let cols = if rows > 0 { data[0].len() } else { 0 };
-        Matrix { data, rows, cols }
+        let mut matrix = Matrix::new(rows, cols);
+        for (row, values) in data.iter().enumerate() {
+            matrix.data[row][..cols].copy_from_slice(&values[..cols]);
+        }
+        matrix
     }
 
     pub fn identity() -> Self {
@@ -33,7 +40,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn translation(x: f64, y: f64, z: f64) -> Matrix {
+    pub fn translation(x: Float, y: Float, z: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         matrix.data[0][3] = x;
         matrix.data[1][3] = y;
@@ -41,7 +48,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn scaling(x: f64, y: f64, z: f64) -> Matrix {
+    pub fn scaling(x: Float, y: Float, z: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         matrix.data[0][0] = x;
         matrix.data[1][1] = y;
@@ -49,7 +56,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn rotation_x(radians: f64) -> Matrix {
+    pub fn rotation_x(radians: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         let cos_r = radians.cos();
         let sin_r = radians.sin();
@@ -62,7 +69,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn rotation_y(radians: f64) -> Matrix {
+    pub fn rotation_y(radians: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         let cos_r = radians.cos();
         let sin_r = radians.sin();
@@ -75,7 +82,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn rotation_z(radians: f64) -> Matrix {
+    pub fn rotation_z(radians: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         let cos_r = radians.cos();
         let sin_r = radians.sin();
@@ -88,7 +95,7 @@ impl Matrix {
         matrix
     }
 
-    pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix {
+    pub fn shearing(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Matrix {
         let mut matrix = Matrix::identity();
         matrix.data[0][1] = xy;
         matrix.data[0][2] = xz;
@@ -99,6 +106,42 @@ impl Matrix {
         matrix
     }
 
+    // Chainable versions of the constructors above, applying each operation
+    // after the ones before it, so
+    //
+    //   Matrix::identity().rotate_x(r).scale(5.0, 5.0, 5.0).translate(x, y, z)
+    //
+    // rotates first and translates last, the same as
+    // translation * scaling * rotation_x.
+    pub fn translate(self, x: Float, y: Float, z: Float) -> Matrix {
+        &Matrix::translation(x, y, z) * &self
+    }
+
+    pub fn scale(self, x: Float, y: Float, z: Float) -> Matrix {
+        &Matrix::scaling(x, y, z) * &self
+    }
+
+    pub fn rotate_x(self, radians: Float) -> Matrix {
+        &Matrix::rotation_x(radians) * &self
+    }
+
+    pub fn rotate_y(self, radians: Float) -> Matrix {
+        &Matrix::rotation_y(radians) * &self
+    }
+
+    pub fn rotate_z(self, radians: Float) -> Matrix {
+        &Matrix::rotation_z(radians) * &self
+    }
+
+    pub fn shear(self, xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Matrix {
+        &Matrix::shearing(xy, xz, yx, yz, zx, zy) * &self
+    }
+
+    // Any other transform, applied after this one
+    pub fn then(self, transform: &Matrix) -> Matrix {
+        transform * &self
+    }
+
     pub fn transpose(&self) -> Self {
         let mut result = Matrix::new(self.cols, self.rows);
 
@@ -111,7 +154,7 @@ impl Matrix {
         result
     }
 
-    pub fn determinant(&self) -> f64 {
+    pub fn determinant(&self) -> Float {
         if self.rows == 2 && self.cols == 2 {
             self.data[0][0] * self.data[1][1] - self.data[0][1] * self.data[1][0]
         } else {
@@ -149,14 +192,14 @@ impl Matrix {
         result
     }
 
-    pub fn minor(&self, row: usize, col: usize) -> f64 {
+    pub fn minor(&self, row: usize, col: usize) -> Float {
         let sub = self.submatrix(row, col);
-        return sub.determinant();
+        sub.determinant()
     }
 
-    pub fn cofactor(&self, row: usize, col: usize) -> f64 {
+    pub fn cofactor(&self, row: usize, col: usize) -> Float {
         let minor = self.minor(row, col);
-        if (row + col) % 2 == 0 {
+        if (row + col).is_multiple_of(2) {
             minor
         } else {
             -minor
@@ -164,24 +207,25 @@ impl Matrix {
     }
 
     pub fn inverse(&self) -> Matrix {
-        let det = self.determinant();
-        if det == 0.0 {
-            panic!("Matrix is not invertible");
-        }
-
-        let mut cofactor_matrix = Matrix::new(self.rows, self.cols);
+        let mut cofactors = Matrix::new(self.rows, self.cols);
         for row in 0..self.rows {
             for col in 0..self.cols {
-                cofactor_matrix[(row, col)] = self.cofactor(row, col);
+                cofactors.data[row][col] = self.cofactor(row, col);
             }
         }
 
-        let transposed_cofactors = cofactor_matrix.transpose();
+        // Expanding along the first row reuses the cofactors just computed
+        let det: Float = (0..self.cols)
+            .map(|col| self.data[0][col] * cofactors.data[0][col])
+            .sum();
+        if det == 0.0 {
+            panic!("Matrix is not invertible");
+        }
 
         let mut result = Matrix::new(self.rows, self.cols);
         for row in 0..self.rows {
             for col in 0..self.cols {
-                result[(row, col)] = transposed_cofactors[(row, col)] / det;
+                result.data[col][row] = cofactors.data[row][col] / det;
             }
         }
 
@@ -190,7 +234,7 @@ impl Matrix {
 }
 
 impl Index<(usize, usize)> for Matrix {
-    type Output = f64;
+    type Output = Float;
 
     fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
         &self.data[row][col]
@@ -212,7 +256,7 @@ impl PartialEq for Matrix {
         for row in 0..self.rows {
             for col in 0..self.cols {
                 let diff = (self.data[row][col] - other.data[row][col]).abs();
-                if diff > f64::EPSILON {
+                if diff > Float::EPSILON {
                     return false;
                 }
             }
@@ -222,10 +266,19 @@ impl PartialEq for Matrix {
     }
 }
 
-impl Mul<Matrix> for Matrix {
-    type Output = Self;
+impl Mul<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        #[cfg(feature = "simd")]
+        if (self.rows, self.cols, rhs.rows, rhs.cols) == (4, 4, 4, 4) {
+            return Matrix {
+                data: simd::mul_matrix(&self.data, &rhs.data),
+                rows: 4,
+                cols: 4,
+            };
+        }
 
-    fn mul(self, rhs: Matrix) -> Matrix {
         let mut result = Matrix::new(self.rows, rhs.cols);
 
         for row in 0..self.rows {
@@ -242,22 +295,73 @@ impl Mul<Matrix> for Matrix {
     }
 }
 
-impl Mul<Tuple> for Matrix {
+impl Mul<Matrix> for Matrix {
+    type Output = Self;
+
+    fn mul(self, rhs: Matrix) -> Matrix {
+        &self * &rhs
+    }
+}
+
+impl Mul<Tuple> for &Matrix {
     type Output = Tuple;
 
     fn mul(self, rhs: Tuple) -> Self::Output {
-        let tuple_vec = vec![rhs.x, rhs.y, rhs.z, rhs.w];
-        let mut result = vec![0.0; self.rows];
+        #[cfg(feature = "simd")]
+        return simd::mul_tuple(&self.data, rhs);
 
-        for row in 0..self.rows {
-            let mut sum = 0.0;
-            for col in 0..self.cols {
-                sum += self.data[row][col] * tuple_vec[col];
-            }
-            result[row] = sum;
-        }
+        #[cfg(not(feature = "simd"))]
+        let row = |r: usize| {
+            let m = &self.data[r];
+            m[0] * rhs.x + m[1] * rhs.y + m[2] * rhs.z + m[3] * rhs.w
+        };
+
+        #[cfg(not(feature = "simd"))]
+        Tuple::new(row(0), row(1), row(2), row(3))
+    }
+}
+
+impl Mul<Tuple> for Matrix {
+    type Output = Tuple;
+
+    fn mul(self, rhs: Tuple) -> Self::Output {
+        &self * rhs
+    }
+}
 
-        Tuple::new(result[0], result[1], result[2], result[3])
+// Products computed four lanes at a time
+#[cfg(feature = "simd")]
+mod simd {
+    #[cfg(feature = "f32")]
+    use wide::f32x4 as Lanes;
+    #[cfg(not(feature = "f32"))]
+    use wide::f64x4 as Lanes;
+
+    use crate::{scalar::Float, tuple::Tuple};
+
+    type Data = [[Float; 4]; 4];
+
+    // Each component is a row of m times the tuple, summed across lanes.
+    // Summing pairwise can round the last bit differently from the scalar
+    // code. Gathering columns instead keeps the order but is slower than
+    // plain scalar code.
+    pub(super) fn mul_tuple(m: &Data, t: Tuple) -> Tuple {
+        let t = Lanes::from(t.to_array());
+        let row = |r: usize| (Lanes::from(m[r]) * t).reduce_add();
+        Tuple::new(row(0), row(1), row(2), row(3))
+    }
+
+    // Each row of the product is the rows of b weighted by a row of a. Each
+    // lane adds its terms in the same order as the scalar code.
+    pub(super) fn mul_matrix(a: &Data, b: &Data) -> Data {
+        let rows = b.map(Lanes::from);
+        a.map(|r| {
+            (rows[0] * Lanes::splat(r[0])
+                + rows[1] * Lanes::splat(r[1])
+                + rows[2] * Lanes::splat(r[2])
+                + rows[3] * Lanes::splat(r[3]))
+            .to_array()
+        })
     }
 }
 
@@ -267,10 +371,10 @@ mod tests {
     use approx::{assert_abs_diff_eq, AbsDiffEq};
 
     impl AbsDiffEq for Matrix {
-        type Epsilon = f64;
+        type Epsilon = Float;
 
         fn default_epsilon() -> Self::Epsilon {
-            f64::EPSILON
+            Float::EPSILON
         }
 
         fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
@@ -280,7 +384,7 @@ mod tests {
 
             for row in 0..self.rows {
                 for col in 0..self.cols {
-                    if !f64::abs_diff_eq(&self.data[row][col], &other.data[row][col], epsilon) {
+                    if !Float::abs_diff_eq(&self.data[row][col], &other.data[row][col], epsilon) {
                         return false;
                     }
                 }
@@ -591,9 +695,9 @@ mod tests {
 
         assert_eq!(matrix_a.determinant(), 532.0);
         assert_eq!(matrix_a.cofactor(2, 3), -160.0);
-        assert!((b[(3, 2)] - (-160.0 / 532.0)).abs() < f64::EPSILON);
+        assert!((b[(3, 2)] - (-160.0 / 532.0)).abs() < Float::EPSILON);
         assert_eq!(matrix_a.cofactor(3, 2), 105.0);
-        assert!((b[(2, 3)] - (105.0 / 532.0)).abs() < f64::EPSILON);
+        assert!((b[(2, 3)] - (105.0 / 532.0)).abs() < Float::EPSILON);
 
         let expected = Matrix::from_vec(vec![
             vec![0.21805, 0.45113, 0.24060, -0.04511],
@@ -743,14 +847,14 @@ mod tests {
     #[test]
     fn rotating_point_around_x_axis() {
         let p = Tuple::point(0.0, 1.0, 0.0);
-        let half_quarter = Matrix::rotation_x(std::f64::consts::PI / 4.0);
-        let full_quarter = Matrix::rotation_x(std::f64::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_x(crate::scalar::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_x(crate::scalar::consts::PI / 2.0);
 
         let half_result = half_quarter * p;
         let expected_half = Tuple::point(
             0.0,
-            std::f64::consts::SQRT_2 / 2.0,
-            std::f64::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
         );
         assert_abs_diff_eq!(half_result, expected_half);
 
@@ -762,14 +866,14 @@ mod tests {
     #[test]
     fn inverse_of_x_rotation_rotates_in_opposite_direction() {
         let p = Tuple::point(0.0, 1.0, 0.0);
-        let half_quarter = Matrix::rotation_x(std::f64::consts::PI / 4.0);
+        let half_quarter = Matrix::rotation_x(crate::scalar::consts::PI / 4.0);
         let inv = half_quarter.inverse();
 
         let result = inv * p;
         let expected = Tuple::point(
             0.0,
-            std::f64::consts::SQRT_2 / 2.0,
-            -std::f64::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
+            -crate::scalar::consts::SQRT_2 / 2.0,
         );
 
         assert_abs_diff_eq!(result, expected);
@@ -778,14 +882,14 @@ mod tests {
     #[test]
     fn rotating_point_around_y_axis() {
         let p = Tuple::point(0.0, 0.0, 1.0);
-        let half_quarter = Matrix::rotation_y(std::f64::consts::PI / 4.0);
-        let full_quarter = Matrix::rotation_y(std::f64::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_y(crate::scalar::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_y(crate::scalar::consts::PI / 2.0);
 
         let half_result = half_quarter * p;
         let expected_half = Tuple::point(
-            std::f64::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
             0.0,
-            std::f64::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
         );
         assert_abs_diff_eq!(half_result, expected_half);
 
@@ -797,13 +901,13 @@ mod tests {
     #[test]
     fn rotating_point_around_z_axis() {
         let p = Tuple::point(0.0, 1.0, 0.0);
-        let half_quarter = Matrix::rotation_z(std::f64::consts::PI / 4.0);
-        let full_quarter = Matrix::rotation_z(std::f64::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_z(crate::scalar::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_z(crate::scalar::consts::PI / 2.0);
 
         let half_result = half_quarter * p;
         let expected_half = Tuple::point(
-            -std::f64::consts::SQRT_2 / 2.0,
-            std::f64::consts::SQRT_2 / 2.0,
+            -crate::scalar::consts::SQRT_2 / 2.0,
+            crate::scalar::consts::SQRT_2 / 2.0,
             0.0,
         );
         assert_abs_diff_eq!(half_result, expected_half);
@@ -870,7 +974,7 @@ mod tests {
     #[test]
     fn individual_transformations_are_applied_in_sequence() {
         let p = Tuple::point(1.0, 0.0, 1.0);
-        let a = Matrix::rotation_x(std::f64::consts::PI / 2.0);
+        let a = Matrix::rotation_x(crate::scalar::consts::PI / 2.0);
         let b = Matrix::scaling(5.0, 5.0, 5.0);
         let c = Matrix::translation(10.0, 5.0, 7.0);
 
@@ -893,7 +997,7 @@ mod tests {
     #[test]
     fn chained_transformations_must_be_applied_in_reverse_order() {
         let p = Tuple::point(1.0, 0.0, 1.0);
-        let a = Matrix::rotation_x(std::f64::consts::PI / 2.0);
+        let a = Matrix::rotation_x(crate::scalar::consts::PI / 2.0);
         let b = Matrix::scaling(5.0, 5.0, 5.0);
         let c = Matrix::translation(10.0, 5.0, 7.0);
 
@@ -902,4 +1006,42 @@ mod tests {
         let expected = Tuple::point(15.0, 0.0, 7.0);
         assert_abs_diff_eq!(result, expected, epsilon = 0.0001);
     }
+
+    #[test]
+    fn fluent_transformations_are_applied_in_the_order_written() {
+        let p = Tuple::point(1.0, 0.0, 1.0);
+        let t = Matrix::identity()
+            .rotate_x(crate::scalar::consts::PI / 2.0)
+            .scale(5.0, 5.0, 5.0)
+            .translate(10.0, 5.0, 7.0);
+
+        assert_abs_diff_eq!(
+            t.clone() * p,
+            Tuple::point(15.0, 0.0, 7.0),
+            epsilon = 0.0001
+        );
+        let by_hand = Matrix::translation(10.0, 5.0, 7.0)
+            * Matrix::scaling(5.0, 5.0, 5.0)
+            * Matrix::rotation_x(crate::scalar::consts::PI / 2.0);
+        assert_eq!(t, by_hand);
+    }
+
+    #[test]
+    fn fluent_transformations_can_continue_from_any_matrix() {
+        let view = Matrix::translation(0.0, 0.0, -8.0);
+        let t = Matrix::scaling(2.0, 2.0, 2.0).then(&view).rotate_y(0.5);
+
+        assert_eq!(
+            t,
+            Matrix::rotation_y(0.5)
+                * Matrix::translation(0.0, 0.0, -8.0)
+                * Matrix::scaling(2.0, 2.0, 2.0)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 4x4")]
+    fn matrices_larger_than_4x4_are_rejected() {
+        Matrix::from_vec(vec![vec![0.0; 5]; 5]);
+    }
 }
@@ -0,0 +1,203 @@
+use crate::{scalar::Float, tuple::Tuple};
+
+// Seeded procedural noise shared by patterns, lights and cameras. Values only
+// depend on the seed and the input, so renders and animations are repeatable
+// and two features given different seeds vary independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Noise {
+    pub seed: u32,
+}
+
+// Mixed into the lattice hash so each kind of noise has its own random values
+const VALUE_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+const GRADIENT_KEY: u64 = 0x2545_f491_4f6c_dd1d;
+
+// Edge midpoints of a cube, the gradients used by improved Perlin noise
+const GRADIENTS_3D: [[Float; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+impl Noise {
+    pub fn new(seed: u32) -> Noise {
+        Noise { seed }
+    }
+
+    // Random values in [-1, 1] at integer positions, smoothstepped in between
+    pub fn value_1d(&self, x: Float) -> Float {
+        let cell = x.floor();
+        let frac = x - cell;
+        let a = self.lattice(cell as i64 as u64, VALUE_KEY);
+        let b = self.lattice((cell as i64 + 1) as u64, VALUE_KEY);
+        let t = frac * frac * (3.0 - 2.0 * frac);
+        a + (b - a) * t
+    }
+
+    // Gradient noise in [-1, 1] that is zero at every integer
+    pub fn perlin_1d(&self, x: Float) -> Float {
+        let cell = x.floor();
+        let frac = x - cell;
+        let g0 = self.lattice(cell as i64 as u64, GRADIENT_KEY);
+        let g1 = self.lattice((cell as i64 + 1) as u64, GRADIENT_KEY);
+        let t = fade(frac);
+        // A single gradient can reach at most 0.5 between lattice points
+        (g0 * frac + (g1 * (frac - 1.0) - g0 * frac) * t) * 2.0
+    }
+
+    // Improved Perlin noise, roughly in [-1, 1] and zero at every lattice point
+    pub fn perlin_3d(&self, point: Tuple) -> Float {
+        let cell = [point.x.floor(), point.y.floor(), point.z.floor()];
+        let frac = [point.x - cell[0], point.y - cell[1], point.z - cell[2]];
+
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let key = (cell[0] as i64 + dx as i64) as u64
+                ^ ((cell[1] as i64 + dy as i64) as u64).wrapping_mul(0x8cb9_2ba7_2f3d_8dd7)
+                ^ ((cell[2] as i64 + dz as i64) as u64).wrapping_mul(0xd6e8_feb8_6659_fd93);
+            let g = GRADIENTS_3D[(self.hash(key, GRADIENT_KEY) % 12) as usize];
+            g[0] * (frac[0] - dx as Float)
+                + g[1] * (frac[1] - dy as Float)
+                + g[2] * (frac[2] - dz as Float)
+        };
+
+        let lerp = |a: Float, b: Float, t: Float| a + (b - a) * t;
+        let (u, v, w) = (fade(frac[0]), fade(frac[1]), fade(frac[2]));
+        lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+                v,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+                v,
+            ),
+            w,
+        )
+    }
+
+    // Fractal Brownian motion: octaves of perlin_3d, each at twice the
+    // frequency and half the weight of the last, scaled back to about [-1, 1]
+    pub fn fbm(&self, point: Tuple, octaves: u32) -> Float {
+        self.octaves(point, octaves, |n| n)
+    }
+
+    // Like fbm but summing the absolute value of each octave, giving the
+    // creased look of marble veins and flames, in [0, 1]
+    pub fn turbulence(&self, point: Tuple, octaves: u32) -> Float {
+        self.octaves(point, octaves, Float::abs)
+    }
+
+    fn octaves(&self, point: Tuple, octaves: u32, shape: impl Fn(Float) -> Float) -> Float {
+        let (mut sum, mut weight, mut total, mut frequency) = (0.0, 1.0, 0.0, 1.0);
+        for octave in 0..octaves.max(1) {
+            // Each octave gets its own seed so they don't line up at the origin
+            let noise = Noise::new(self.seed.wrapping_add(octave));
+            sum += shape(noise.perlin_3d(point * frequency)) * weight;
+            total += weight;
+            weight *= 0.5;
+            frequency *= 2.0;
+        }
+        (sum / total).clamp(-1.0, 1.0)
+    }
+
+    // Hashes a lattice position to a value in [-1, 1]
+    fn lattice(&self, i: u64, key: u64) -> Float {
+        (self.hash(i, key) >> 11) as Float / (1u64 << 53) as Float * 2.0 - 1.0
+    }
+
+    fn hash(&self, i: u64, key: u64) -> u64 {
+        let mut h = i ^ ((self.seed as u64) << 32) ^ key;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^= h >> 33;
+        h
+    }
+}
+
+// Perlin's quintic smoothstep, flat in value and slope at 0 and 1
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Tuple> {
+        (0..2000).map(|i| {
+            let i = i as Float;
+            Tuple::point(i * 0.137 - 50.0, i * 0.071 - 20.0, i * 0.293 - 90.0)
+        })
+    }
+
+    #[test]
+    fn noise_is_repeatable_and_depends_on_the_seed() {
+        let point = Tuple::point(1.3, -2.7, 0.4);
+
+        assert_eq!(
+            Noise::new(3).perlin_3d(point),
+            Noise::new(3).perlin_3d(point)
+        );
+        assert_ne!(
+            Noise::new(3).perlin_3d(point),
+            Noise::new(4).perlin_3d(point)
+        );
+        assert_ne!(Noise::new(3).value_1d(0.5), Noise::new(4).value_1d(0.5));
+    }
+
+    #[test]
+    fn gradient_noise_is_zero_on_the_lattice() {
+        let noise = Noise::new(11);
+
+        for i in -5..5 {
+            assert_eq!(noise.perlin_1d(i as Float), 0.0);
+            let point = Tuple::point(i as Float, (i * 2) as Float, -i as Float);
+            assert_eq!(noise.perlin_3d(point), 0.0);
+        }
+    }
+
+    #[test]
+    fn value_noise_hits_its_lattice_values() {
+        let noise = Noise::new(2);
+
+        assert_eq!(noise.value_1d(4.0), noise.lattice(4, VALUE_KEY));
+        assert_ne!(noise.value_1d(4.0), 0.0);
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let noise = Noise::new(5);
+
+        for point in sample_points() {
+            assert!(noise.perlin_1d(point.x).abs() <= 1.0);
+            assert!(noise.value_1d(point.x).abs() <= 1.0);
+            assert!(noise.perlin_3d(point).abs() <= 1.1);
+            assert!(noise.fbm(point, 4).abs() <= 1.0);
+            assert!((0.0..=1.0).contains(&noise.turbulence(point, 4)));
+        }
+    }
+
+    #[test]
+    fn perlin_3d_varies_smoothly() {
+        let noise = Noise::new(9);
+        let step = Tuple::vector(0.001, 0.001, 0.001);
+
+        for point in sample_points() {
+            let change = noise.perlin_3d(point + step) - noise.perlin_3d(point);
+            assert!(change.abs() < 0.01);
+        }
+    }
+}
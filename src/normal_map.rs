@@ -0,0 +1,99 @@
+use crate::{
+    pattern::uv_pattern::{UvImage, UvMapping},
+    scalar::Float,
+    shape::Shape,
+    tuple::Tuple,
+};
+
+// An image of surface normals in tangent space, for bumpy detail without extra
+// geometry. Each pixel's red, green and blue give a normal's components along
+// the surface tangent (the direction of increasing u), the bitangent
+// (increasing v) and the unperturbed normal, scaled from [-1, 1] to [0, 1] as
+// normal map images usually are. A flat pixel is (0.5, 0.5, 1).
+//
+// The image is placed with a uv mapping of the object-space point, as for
+// image textures. Tangents come from Shape::local_tangent_at.
+#[derive(Clone)]
+pub struct NormalMap {
+    pub image: UvImage,
+    pub mapping: UvMapping,
+    // Scales the tilt; 0 leaves normals untouched
+    pub strength: Float,
+}
+
+impl NormalMap {
+    pub fn new(image: UvImage, mapping: UvMapping) -> NormalMap {
+        NormalMap {
+            image,
+            mapping,
+            strength: 1.0,
+        }
+    }
+
+    // Tilts a world-space normal at a point on the shape
+    pub fn perturb(&self, shape: &dyn Shape, world_point: Tuple, normal: Tuple) -> Tuple {
+        let object_point = shape.inverse_transform() * world_point;
+        let (u, v) = self.mapping.map_on(shape, object_point);
+        let c = self.image.colour_at(u, v);
+
+        let tangent = shape.transform() * shape.local_tangent_at(&object_point);
+        let tangent = tangent - normal * tangent.dot(&normal);
+        if tangent.magnitude() < 1e-9 {
+            return normal;
+        }
+        let tangent = tangent.normalise();
+        let bitangent = tangent.cross(&normal);
+
+        let tilt = self.strength;
+        (tangent * ((2.0 * c.r - 1.0) * tilt)
+            + bitangent * ((2.0 * c.g - 1.0) * tilt)
+            + normal * (2.0 * c.b - 1.0))
+            .normalise()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::{camera::Canvas, colour::Colour, matrix::Matrix, shape::plane::Plane};
+
+    fn uniform(colour: Colour) -> UvImage {
+        let mut canvas = Canvas::new(2, 2);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            canvas.write_pixel(x, y, colour);
+        }
+        UvImage::from_canvas(&canvas)
+    }
+
+    #[test]
+    fn flat_pixels_leave_the_normal_alone() {
+        let map = NormalMap::new(uniform(Colour::new(0.5, 0.5, 1.0)), UvMapping::Planar);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+
+        let n = map.perturb(&Plane::new(), Tuple::point(0.3, 0.0, 0.6), up);
+        assert_abs_diff_eq!(n, up);
+    }
+
+    #[test]
+    fn normals_tilt_towards_the_tangent_in_world_space() {
+        // Halfway between the tangent and the normal
+        let map = NormalMap::new(uniform(Colour::new(1.0, 0.5, 1.0)), UvMapping::Planar);
+        let mut plane = Plane::new();
+        plane.set_transform(Matrix::rotation_y(crate::scalar::consts::PI / 2.0));
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+
+        // The plane's x axis, and so its tangent, now points along -z
+        let n = map.perturb(&plane, Tuple::point(0.0, 0.0, 0.0), up);
+        let h = Float::sqrt(2.0) / 2.0;
+        assert_abs_diff_eq!(n, Tuple::vector(0.0, h, -h), epsilon = TEST_EPSILON);
+
+        let gentle = NormalMap {
+            strength: 0.0,
+            ..map
+        };
+        assert_abs_diff_eq!(gentle.perturb(&plane, Tuple::point(0.0, 0.0, 0.0), up), up);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{
+    scalar::Float,
+    shape::{group::Group, smooth_triangle::SmoothTriangle, triangle::Triangle, Shape},
+    tuple::Tuple,
+};
+use std::path::Path;
+
+// Minimal Wavefront OBJ reader. Understands vertices (v), texture coordinates
+// (vt), vertex normals (vn), faces (f) and named groups (g); anything else is
+// counted and skipped. Polygons are fan-triangulated from their first vertex,
+// and faces whose vertices all carry normals become smooth triangles. Texture
+// coordinates are kept on triangles whose corners all have them, for
+// UvMapping::Surface.
+pub struct ObjParser {
+    pub vertices: Vec<Tuple>,
+    pub texture_coords: Vec<(Float, Float)>,
+    pub normals: Vec<Tuple>,
+    pub ignored: usize,
+    pub default_group: Group,
+    pub groups: Vec<(String, Group)>,
+}
+
+// A face's vertex, with its texture coordinates and normal if it has them
+type Corner = (Tuple, Option<(Float, Float)>, Option<Tuple>);
+
+impl ObjParser {
+    pub fn parse(contents: &str) -> ObjParser {
+        let mut parser = ObjParser {
+            vertices: Vec::new(),
+            texture_coords: Vec::new(),
+            normals: Vec::new(),
+            ignored: 0,
+            default_group: Group::new(),
+            groups: Vec::new(),
+        };
+        let mut current_group: Option<usize> = None;
+
+        for line in contents.lines() {
+            let mut parts = line.split_whitespace();
+            let parsed = match parts.next() {
+                Some("v") => parse_xyz(parts).map(|[x, y, z]| {
+                    parser.vertices.push(Tuple::point(x, y, z));
+                }),
+                // A third, w, coordinate is allowed but not used
+                Some("vt") => parse_uv(parts).map(|uv| {
+                    parser.texture_coords.push(uv);
+                }),
+                Some("vn") => parse_xyz(parts).map(|[x, y, z]| {
+                    parser.normals.push(Tuple::vector(x, y, z));
+                }),
+                Some("f") => parser.parse_face(parts).map(|triangles| {
+                    let group = match current_group {
+                        Some(index) => &mut parser.groups[index].1,
+                        None => &mut parser.default_group,
+                    };
+                    for triangle in triangles {
+                        group.add_child(triangle);
+                    }
+                }),
+                Some("g") => parts.next().map(|name| {
+                    current_group = Some(parser.group_index(name));
+                }),
+                _ => None,
+            };
+
+            if parsed.is_none() && !line.trim().is_empty() {
+                parser.ignored += 1;
+            }
+        }
+
+        parser
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .find(|(group_name, _)| group_name == name)
+            .map(|(_, group)| group)
+    }
+
+    // Everything in the file as a single group, with each named group nested inside
+    // it. Every group gets a BVH, since meshes are usually large.
+    pub fn into_group(self) -> Group {
+        let mut group = self.default_group;
+        for (_, mut named) in self.groups {
+            named.build_bvh();
+            group.add_child(Box::new(named));
+        }
+        group.build_bvh();
+        group
+    }
+
+    fn group_index(&mut self, name: &str) -> usize {
+        match self.groups.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.groups.push((name.to_string(), Group::new()));
+                self.groups.len() - 1
+            }
+        }
+    }
+
+    fn parse_face<'a>(&self, parts: impl Iterator<Item = &'a str>) -> Option<Vec<Box<dyn Shape>>> {
+        let corners = parts
+            .map(|part| self.parse_face_vertex(part))
+            .collect::<Option<Vec<_>>>()?;
+        if corners.len() < 3 {
+            return None;
+        }
+
+        let (p1, t1, n1) = corners[0];
+        let triangles = corners[1..]
+            .windows(2)
+            .map(|pair| {
+                let ((p2, t2, n2), (p3, t3, n3)) = (pair[0], pair[1]);
+                let texture = t1.zip(t2).zip(t3).map(|((t1, t2), t3)| [t1, t2, t3]);
+                match (n1, n2, n3) {
+                    (Some(n1), Some(n2), Some(n3)) => {
+                        let triangle = SmoothTriangle::new(p1, p2, p3, n1, n2, n3);
+                        match texture {
+                            Some(texture) => Box::new(triangle.with_texture(texture)),
+                            None => Box::new(triangle) as Box<dyn Shape>,
+                        }
+                    }
+                    _ => {
+                        let triangle = Triangle::new(p1, p2, p3);
+                        match texture {
+                            Some(texture) => Box::new(triangle.with_texture(texture)),
+                            None => Box::new(triangle) as Box<dyn Shape>,
+                        }
+                    }
+                }
+            })
+            .collect();
+
+        Some(triangles)
+    }
+
+    // Accepts "v", "v/vt", "v//vn" and "v/vt/vn". A texture index that's out
+    // of range only loses the face its texture, as they often are in files
+    // that don't use them.
+    fn parse_face_vertex(&self, part: &str) -> Option<Corner> {
+        let mut indices = part.split('/');
+        let vertex = *self
+            .vertices
+            .get(resolve_index(indices.next()?, self.vertices.len())?)?;
+
+        let texture = indices
+            .next()
+            .and_then(|index| resolve_index(index, self.texture_coords.len()))
+            .and_then(|index| self.texture_coords.get(index).copied());
+
+        let normal = match indices.next() {
+            Some(index) if !index.is_empty() => Some(
+                *self
+                    .normals
+                    .get(resolve_index(index, self.normals.len())?)?,
+            ),
+            _ => None,
+        };
+
+        Some((vertex, texture, normal))
+    }
+}
+
+pub fn parse_obj_file<P: AsRef<Path>>(path: P) -> Result<Group, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(ObjParser::parse(&contents).into_group())
+}
+
+// OBJ indices start at 1, and negative indices count back from the most recent element
+fn resolve_index(index: &str, len: usize) -> Option<usize> {
+    let index: i64 = index.parse().ok()?;
+    if index > 0 {
+        Some(index as usize - 1)
+    } else if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        None
+    }
+}
+
+fn parse_uv<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<(Float, Float)> {
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+fn parse_xyz<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<[Float; 3]> {
+    let mut xyz = [0.0; 3];
+    for value in xyz.iter_mut() {
+        *value = parts.next()?.parse().ok()?;
+    }
+    Some(xyz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{intersection::Intersection, ray::Ray, scalar::TEST_EPSILON};
+    use approx::assert_abs_diff_eq;
+
+    // The parser hands back trait objects, so check each triangle by firing a ray
+    // through the centroid of the expected face (all test faces lie in z = 0)
+    fn covers(shape: &dyn Shape, a: Tuple, b: Tuple, c: Tuple) -> bool {
+        let centroid = (a + b + c) * (1.0 / 3.0);
+        let r = Ray::new(
+            Tuple::point(centroid.x, centroid.y, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        let xs = shape.intersect(&r);
+        xs.len() == 1 && (xs[0].t - 5.0).abs() < 0.0001
+    }
+
+    #[test]
+    fn ignoring_unrecognized_lines() {
+        let gibberish = "There was a young lady named Bright\n\
+                         who traveled much faster than light.\n\
+                         She set out one day\n\
+                         in a relative way,\n\
+                         and came back the previous night.";
+        let parser = ObjParser::parse(gibberish);
+
+        assert_eq!(parser.ignored, 5);
+    }
+
+    #[test]
+    fn vertex_records() {
+        let file = "v -1 1 0\nv -1.0000 0.5000 0.0000\nv 1 0 0\nv 1 1 0";
+        let parser = ObjParser::parse(file);
+
+        assert_eq!(parser.vertices[0], Tuple::point(-1.0, 1.0, 0.0));
+        assert_eq!(parser.vertices[1], Tuple::point(-1.0, 0.5, 0.0));
+        assert_eq!(parser.vertices[2], Tuple::point(1.0, 0.0, 0.0));
+        assert_eq!(parser.vertices[3], Tuple::point(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn parsing_triangle_faces() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 3\nf 1 3 4";
+        let parser = ObjParser::parse(file);
+
+        assert_eq!(parser.ignored, 0);
+        let g = &parser.default_group;
+        let v = &parser.vertices;
+        assert_eq!(g.len(), 2);
+        assert!(covers(g.children[0].as_ref(), v[0], v[1], v[2]));
+        assert!(covers(g.children[1].as_ref(), v[0], v[2], v[3]));
+    }
+
+    #[test]
+    fn triangulating_polygons() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nv 0 2 0\n\nf 1 2 3 4 5";
+        let parser = ObjParser::parse(file);
+
+        let g = &parser.default_group;
+        let v = &parser.vertices;
+        assert_eq!(g.len(), 3);
+        assert!(covers(g.children[0].as_ref(), v[0], v[1], v[2]));
+        assert!(covers(g.children[1].as_ref(), v[0], v[2], v[3]));
+        assert!(covers(g.children[2].as_ref(), v[0], v[3], v[4]));
+    }
+
+    #[test]
+    fn triangles_in_groups() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\
+                    g FirstGroup\nf 1 2 3\ng SecondGroup\nf 1 3 4";
+        let parser = ObjParser::parse(file);
+
+        let v = &parser.vertices;
+        let g1 = parser.group("FirstGroup").unwrap();
+        let g2 = parser.group("SecondGroup").unwrap();
+        assert!(covers(g1.children[0].as_ref(), v[0], v[1], v[2]));
+        assert!(covers(g2.children[0].as_ref(), v[0], v[2], v[3]));
+        assert!(parser.default_group.is_empty());
+    }
+
+    #[test]
+    fn converting_obj_file_to_group() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\
+                    g FirstGroup\nf 1 2 3\ng SecondGroup\nf 1 3 4";
+        let g = ObjParser::parse(file).into_group();
+
+        assert_eq!(g.len(), 2);
+        assert_eq!(g.children()[0].children().len(), 1);
+        assert_eq!(g.children()[1].children().len(), 1);
+    }
+
+    #[test]
+    fn vertex_normal_records() {
+        let file = "vn 0 0 1\nvn 0.707 0 -0.707\nvn 1 2 3";
+        let parser = ObjParser::parse(file);
+
+        assert_eq!(parser.normals[0], Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(parser.normals[1], Tuple::vector(0.707, 0.0, -0.707));
+        assert_eq!(parser.normals[2], Tuple::vector(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn faces_with_normals() {
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\n\
+                    vn -1 0 0\nvn 1 0 0\nvn 0 1 0\n\
+                    f 1//3 2//1 3//2\nf 1/0/3 2/102/1 3/14/2";
+        let parser = ObjParser::parse(file);
+
+        let g = &parser.default_group;
+        assert_eq!(g.len(), 2);
+        for child in &g.children {
+            // At u = v = 0 the interpolated normal is the first vertex's normal
+            let hit = Intersection::with_uv(1.0, child.as_ref(), 0.0, 0.0);
+            let n = child.normal_at_hit(&Tuple::point(0.0, 1.0, 0.0), &hit);
+            assert_eq!(n, parser.normals[2]);
+        }
+    }
+
+    #[test]
+    fn faces_with_texture_coordinates() {
+        let file = "v 0 1 0\nv -1 0 0\nv 1 0 0\n\
+                    vt 0.5 1\nvt 0 0 0\nvt 1 0\n\
+                    f 1/1 2/2 3/3\nf 1 2 3";
+        let parser = ObjParser::parse(file);
+
+        assert_eq!(parser.texture_coords[1], (0.0, 0.0));
+        let g = &parser.default_group;
+        assert_eq!(g.len(), 2);
+        let textured = g.children[0].as_ref();
+        assert_eq!(
+            textured.local_uv_at(&Tuple::point(1.0, 0.0, 0.0)),
+            Some((1.0, 0.0))
+        );
+        let (u, v) = textured.local_uv_at(&Tuple::point(0.0, 0.5, 0.0)).unwrap();
+        assert_abs_diff_eq!(u, 0.5, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(v, 0.5, epsilon = TEST_EPSILON);
+        assert_eq!(
+            g.children[1].local_uv_at(&Tuple::point(1.0, 0.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let file = "v -1 1 0\nv -1 0 0\nv 1 0 0\nf -3 -2 -1";
+        let parser = ObjParser::parse(file);
+
+        let v = &parser.vertices;
+        assert_eq!(parser.default_group.len(), 1);
+        assert!(covers(
+            parser.default_group.children[0].as_ref(),
+            v[0],
+            v[1],
+            v[2]
+        ));
+    }
+
+    #[test]
+    fn faces_with_missing_vertices_are_ignored() {
+        let file = "v -1 1 0\nv -1 0 0\nf 1 2 3";
+        let parser = ObjParser::parse(file);
+
+        assert_eq!(parser.ignored, 1);
+        assert!(parser.default_group.is_empty());
+    }
+}
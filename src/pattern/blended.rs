@@ -0,0 +1,74 @@
+use crate::{
+    colour::Colour,
+    matrix::Matrix,
+    pattern::{Pattern, PatternData, PatternSlot},
+    tuple::Tuple,
+};
+
+// Averages its two slots everywhere, which is mostly useful with nested
+// patterns, e.g. stripes along x blended with stripes along z for a plaid
+#[derive(Clone)]
+pub struct Blended {
+    data: PatternData,
+}
+
+impl Pattern for Blended {
+    fn data(&self) -> &PatternData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut PatternData {
+        &mut self.data
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Colour {
+        (self.a_at(point) + self.b_at(point)) * 0.5
+    }
+}
+
+impl Blended {
+    pub fn new(a: impl Into<PatternSlot>, b: impl Into<PatternSlot>) -> Self {
+        let identity: Matrix = Matrix::identity();
+        Self {
+            data: PatternData {
+                a: a.into(),
+                b: b.into(),
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{striped::Striped, PatternType};
+
+    #[test]
+    fn blending_two_colours_averages_them() {
+        let pattern = Blended::new(Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(3.0, 2.0, 1.0)),
+            Colour::new(0.5, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn blending_crossed_stripes() {
+        let along_x = Striped::new(Colour::white(), Colour::black());
+        let mut along_z = Striped::new(Colour::white(), Colour::black());
+        along_z.set_transform(Matrix::rotation_y(crate::scalar::consts::FRAC_PI_2));
+        let pattern = Blended::new(PatternType::Striped(along_x), PatternType::Striped(along_z));
+
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.5, 0.0, -0.5)),
+            Colour::white()
+        );
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(1.5, 0.0, -0.5)),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
+}
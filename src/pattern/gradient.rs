@@ -1,7 +1,7 @@
 use crate::{
     colour::Colour,
     matrix::Matrix,
-    pattern::{Pattern, PatternData},
+    pattern::{Pattern, PatternData, PatternSlot},
     tuple::Tuple,
 };
 
@@ -20,8 +20,8 @@ impl Pattern for Gradient {
     }
 
     fn pattern_at(&self, point: Tuple) -> Colour {
-        let a = self.data().a;
-        let b = self.data().b;
+        let a = self.a_at(point);
+        let b = self.b_at(point);
 
         let dist = b - a;
         let frac = point.x - point.x.floor();
@@ -31,12 +31,12 @@ impl Pattern for Gradient {
 }
 
 impl Gradient {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternSlot>, b: impl Into<PatternSlot>) -> Self {
         let identity: Matrix = Matrix::identity();
         Self {
             data: PatternData {
-                a,
-                b,
+                a: a.into(),
+                b: b.into(),
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
             },
@@ -1,17 +1,27 @@
+pub mod blended;
 pub mod checkered;
 pub mod gradient;
+pub mod noise_pattern;
+#[allow(clippy::module_inception)]
 pub mod pattern;
+pub mod preview;
+pub mod radial_gradient;
 pub mod ring;
 pub mod striped;
+pub mod uv_pattern;
 
 use crate::{
     colour::Colour,
-    pattern::{checkered::Checkered, gradient::Gradient, ring::Ring, striped::Striped},
+    matrix::Matrix,
+    pattern::{
+        blended::Blended, checkered::Checkered, gradient::Gradient, noise_pattern::NoisePattern,
+        radial_gradient::RadialGradient, ring::Ring, striped::Striped, uv_pattern::TextureMap,
+    },
     shape::Shape,
     tuple::Tuple,
 };
 
-pub use pattern::{Pattern, PatternData};
+pub use pattern::{Pattern, PatternClone, PatternData, PatternSlot};
 
 #[derive(Clone)]
 pub enum PatternType {
@@ -19,6 +29,12 @@ pub enum PatternType {
     Gradient(Gradient),
     Ring(Ring),
     Checkered(Checkered),
+    RadialGradient(RadialGradient),
+    Blended(Blended),
+    TextureMap(TextureMap),
+    Noise(NoisePattern),
+    // Pattern types from outside the crate; see factory::register_pattern
+    Custom(Box<dyn Pattern>),
 }
 
 impl PatternType {
@@ -28,6 +44,99 @@ impl PatternType {
             PatternType::Gradient(pattern) => pattern.pattern_at_shape(shape, world_point),
             PatternType::Ring(pattern) => pattern.pattern_at_shape(shape, world_point),
             PatternType::Checkered(pattern) => pattern.pattern_at_shape(shape, world_point),
+            PatternType::RadialGradient(pattern) => pattern.pattern_at_shape(shape, world_point),
+            PatternType::Blended(pattern) => pattern.pattern_at_shape(shape, world_point),
+            PatternType::TextureMap(pattern) => pattern.pattern_at_shape(shape, world_point),
+            PatternType::Noise(pattern) => pattern.pattern_at_shape(shape, world_point),
+            PatternType::Custom(pattern) => pattern.pattern_at_shape(shape, world_point),
         }
     }
+
+    pub fn pattern_at_object(&self, object_point: Tuple) -> Colour {
+        match self {
+            PatternType::Striped(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Gradient(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Ring(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Checkered(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::RadialGradient(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Blended(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::TextureMap(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Noise(pattern) => pattern.pattern_at_object(object_point),
+            PatternType::Custom(pattern) => pattern.pattern_at_object(object_point),
+        }
+    }
+
+    pub fn data(&self) -> &PatternData {
+        match self {
+            PatternType::Striped(pattern) => pattern.data(),
+            PatternType::Gradient(pattern) => pattern.data(),
+            PatternType::Ring(pattern) => pattern.data(),
+            PatternType::Checkered(pattern) => pattern.data(),
+            PatternType::RadialGradient(pattern) => pattern.data(),
+            PatternType::Blended(pattern) => pattern.data(),
+            PatternType::TextureMap(pattern) => pattern.data(),
+            PatternType::Noise(pattern) => pattern.data(),
+            PatternType::Custom(pattern) => pattern.data(),
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) {
+        match self {
+            PatternType::Striped(pattern) => pattern.set_transform(transform),
+            PatternType::Gradient(pattern) => pattern.set_transform(transform),
+            PatternType::Ring(pattern) => pattern.set_transform(transform),
+            PatternType::Checkered(pattern) => pattern.set_transform(transform),
+            PatternType::RadialGradient(pattern) => pattern.set_transform(transform),
+            PatternType::Blended(pattern) => pattern.set_transform(transform),
+            PatternType::TextureMap(pattern) => pattern.set_transform(transform),
+            PatternType::Noise(pattern) => pattern.set_transform(transform),
+            PatternType::Custom(pattern) => pattern.set_transform(transform),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+
+    #[test]
+    fn checkers_of_stripes() {
+        let red_white = Striped::new(Colour::new(1.0, 0.0, 0.0), Colour::white());
+        let mut green_black = Striped::new(Colour::new(0.0, 1.0, 0.0), Colour::black());
+        green_black.set_transform(Matrix::scaling(0.5, 1.0, 1.0));
+        let pattern = PatternType::Checkered(Checkered::new(
+            PatternType::Striped(red_white),
+            PatternType::Striped(green_black),
+        ));
+
+        // Even cells use the unit stripes, odd cells the half-width ones
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(0.5, 0.0, 0.0)),
+            Colour::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(1.2, 0.0, 0.0)),
+            Colour::new(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(1.7, 0.0, 0.0)),
+            Colour::black()
+        );
+    }
+
+    #[test]
+    fn nested_patterns_inherit_the_outer_transform() {
+        let inner = Striped::new(Colour::white(), Colour::black());
+        let mut outer = Checkered::new(PatternType::Striped(inner), Colour::black());
+        outer.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+        let pattern = PatternType::Checkered(outer);
+
+        // Object x 1.5 is x 0.75 in the checkers' space, which is inside the
+        // first (white) stripe; in object space it would have been black
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(1.5, 0.0, 0.0)),
+            Colour::white()
+        );
+    }
 }
@@ -0,0 +1,90 @@
+use crate::{
+    colour::Colour,
+    matrix::Matrix,
+    noise::Noise,
+    pattern::{Pattern, PatternData, PatternSlot},
+    tuple::Tuple,
+};
+
+// Blends from a to b by fractal noise, for clouds, stone and other organic
+// surfaces. Scale the pattern transform to change the size of the blotches.
+#[derive(Clone)]
+pub struct NoisePattern {
+    data: PatternData,
+    noise: Noise,
+    octaves: u32,
+}
+
+impl Pattern for NoisePattern {
+    fn data(&self) -> &PatternData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut PatternData {
+        &mut self.data
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Colour {
+        let a = self.a_at(point);
+        let b = self.b_at(point);
+        let frac = (self.noise.fbm(point, self.octaves) + 1.0) / 2.0;
+
+        a + (b - a) * frac
+    }
+}
+
+impl NoisePattern {
+    pub fn new(
+        a: impl Into<PatternSlot>,
+        b: impl Into<PatternSlot>,
+        octaves: u32,
+        seed: u32,
+    ) -> Self {
+        let identity: Matrix = Matrix::identity();
+        Self {
+            data: PatternData {
+                a: a.into(),
+                b: b.into(),
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+            },
+            noise: Noise::new(seed),
+            octaves,
+        }
+    }
+
+    pub fn octaves(&self) -> u32 {
+        self.octaves
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.noise.seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Float;
+
+    #[test]
+    fn noise_pattern_is_halfway_on_the_lattice() {
+        let pattern = NoisePattern::new(Colour::black(), Colour::white(), 1, 0);
+
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(2.0, -1.0, 3.0)),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn noise_pattern_stays_between_its_colours() {
+        let pattern = NoisePattern::new(Colour::black(), Colour::white(), 4, 3);
+
+        for i in 0..500 {
+            let i = i as Float;
+            let c = pattern.pattern_at(Tuple::point(i * 0.31, i * 0.17, -i * 0.23));
+            assert!((0.0..=1.0).contains(&c.r), "{}", c.r);
+        }
+    }
+}
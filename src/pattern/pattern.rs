@@ -1,25 +1,93 @@
-use crate::{colour::Colour, matrix::Matrix, shape::Shape, tuple::Tuple};
+use crate::{colour::Colour, matrix::Matrix, pattern::PatternType, shape::Shape, tuple::Tuple};
+
+// What fills one of a pattern's two slots: a plain colour, or another pattern
+// evaluated in the outer pattern's space (e.g. checkers of stripes)
+#[derive(Clone)]
+pub enum PatternSlot {
+    Solid(Colour),
+    Pattern(Box<PatternType>),
+}
+
+impl PatternSlot {
+    pub fn colour_at(&self, pattern_point: Tuple) -> Colour {
+        match self {
+            PatternSlot::Solid(colour) => *colour,
+            PatternSlot::Pattern(pattern) => pattern.pattern_at_object(pattern_point),
+        }
+    }
+
+    pub fn as_colour(&self) -> Option<Colour> {
+        match self {
+            PatternSlot::Solid(colour) => Some(*colour),
+            PatternSlot::Pattern(_) => None,
+        }
+    }
+}
+
+impl From<Colour> for PatternSlot {
+    fn from(colour: Colour) -> Self {
+        PatternSlot::Solid(colour)
+    }
+}
+
+impl From<PatternType> for PatternSlot {
+    fn from(pattern: PatternType) -> Self {
+        PatternSlot::Pattern(Box::new(pattern))
+    }
+}
 
 #[derive(Clone)]
 pub struct PatternData {
-    pub a: Colour,
-    pub b: Colour,
+    pub a: PatternSlot,
+    pub b: PatternSlot,
     pub transform: Matrix,
     pub inverse_transform: Matrix,
 }
 
-pub trait Pattern {
+// Lets boxed patterns be cloned, as ShapeClone does for shapes. Implemented
+// automatically for every Pattern that is Clone.
+pub trait PatternClone {
+    fn clone_box(&self) -> Box<dyn Pattern>;
+}
+
+impl<T: Pattern + Clone + 'static> PatternClone for T {
+    fn clone_box(&self) -> Box<dyn Pattern> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Pattern> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+pub trait Pattern: PatternClone {
     fn set_transform(&mut self, transform: Matrix) {
         self.data_mut().inverse_transform = transform.inverse();
         self.data_mut().transform = transform;
     }
 
     fn pattern_at_shape(&self, shape: &dyn Shape, world_point: Tuple) -> Colour {
-        let object_point = shape.data().inverse_transform.clone() * world_point;
-        let pattern_point = self.data().inverse_transform.clone() * object_point;
+        let object_point = &shape.data().inverse_transform * world_point;
+        self.pattern_at_object(object_point)
+    }
+
+    // Applies the pattern's own transform but no shape's
+    fn pattern_at_object(&self, object_point: Tuple) -> Colour {
+        let pattern_point = &self.data().inverse_transform * object_point;
         self.pattern_at(pattern_point)
     }
 
+    // Colours of the two slots at a point in this pattern's space
+    fn a_at(&self, point: Tuple) -> Colour {
+        self.data().a.colour_at(point)
+    }
+
+    fn b_at(&self, point: Tuple) -> Colour {
+        self.data().b.colour_at(point)
+    }
+
     // Abstract methods
     fn data(&self) -> &PatternData;
     fn data_mut(&mut self) -> &mut PatternData;
@@ -32,6 +100,7 @@ mod tests {
 
     use super::*;
 
+    #[derive(Clone)]
     struct TestPattern {
         data: PatternData,
     }
@@ -41,8 +110,8 @@ mod tests {
             let identity: Matrix = Matrix::identity();
             Self {
                 data: PatternData {
-                    a: Colour::black(),
-                    b: Colour::white(),
+                    a: Colour::black().into(),
+                    b: Colour::white().into(),
                     transform: identity.clone(),
                     inverse_transform: identity.inverse(),
                 },
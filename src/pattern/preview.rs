@@ -0,0 +1,92 @@
+use crate::{camera::Canvas, pattern::PatternType, scalar::Float, tuple::Tuple};
+
+// Axis-aligned plane through the origin that a preview samples the pattern on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplePlane {
+    Xy,
+    Xz,
+    Yz,
+}
+
+impl SamplePlane {
+    // Image right and image up map to the first and second axis respectively
+    fn point(&self, u: Float, v: Float) -> Tuple {
+        match self {
+            SamplePlane::Xy => Tuple::point(u, v, 0.0),
+            SamplePlane::Xz => Tuple::point(u, 0.0, v),
+            SamplePlane::Yz => Tuple::point(0.0, u, v),
+        }
+    }
+}
+
+// Renders a pattern straight to a canvas, without a scene, camera or lighting.
+// The image is centred on the origin and `scale` is how many units of pattern
+// space its width covers; pixels are square, so the height follows the aspect.
+pub fn render_pattern(
+    pattern: &PatternType,
+    width: usize,
+    height: usize,
+    plane: SamplePlane,
+    scale: Float,
+) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    let pixel_size = scale / width as Float;
+
+    for y in 0..height {
+        // Flip so that up in the image is the positive axis
+        let v = (height as Float / 2.0 - (y as Float + 0.5)) * pixel_size;
+        for x in 0..width {
+            let u = (x as Float + 0.5 - width as Float / 2.0) * pixel_size;
+            canvas.write_pixel(x, y, pattern.pattern_at_object(plane.point(u, v)));
+        }
+    }
+
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        colour::Colour,
+        matrix::Matrix,
+        pattern::{checkered::Checkered, striped::Striped, Pattern},
+    };
+
+    #[test]
+    fn preview_of_stripes_alternates_along_x() {
+        let pattern = PatternType::Striped(Striped::new(Colour::white(), Colour::black()));
+        let canvas = render_pattern(&pattern, 4, 2, SamplePlane::Xy, 4.0);
+
+        // Pixel centres sit at x = -1.5, -0.5, 0.5, 1.5
+        assert_eq!(canvas.pixel_at(0, 0), Colour::white());
+        assert_eq!(canvas.pixel_at(1, 0), Colour::black());
+        assert_eq!(canvas.pixel_at(2, 0), Colour::white());
+        assert_eq!(canvas.pixel_at(3, 1), Colour::black());
+    }
+
+    #[test]
+    fn preview_uses_the_pattern_transform() {
+        let mut stripes = Striped::new(Colour::white(), Colour::black());
+        stripes.set_transform(Matrix::scaling(2.0, 1.0, 1.0));
+        let pattern = PatternType::Striped(stripes);
+        let canvas = render_pattern(&pattern, 4, 1, SamplePlane::Xy, 4.0);
+
+        assert_eq!(canvas.pixel_at(0, 0), Colour::black());
+        assert_eq!(canvas.pixel_at(1, 0), Colour::black());
+        assert_eq!(canvas.pixel_at(2, 0), Colour::white());
+        assert_eq!(canvas.pixel_at(3, 0), Colour::white());
+    }
+
+    #[test]
+    fn preview_samples_the_chosen_plane() {
+        let pattern = PatternType::Checkered(Checkered::new(Colour::white(), Colour::black()));
+        let canvas = render_pattern(&pattern, 2, 2, SamplePlane::Xz, 2.0);
+
+        // Top-left samples (-0.5, 0, 0.5), top-right (0.5, 0, 0.5)
+        assert_eq!(canvas.pixel_at(0, 0), Colour::black());
+        assert_eq!(canvas.pixel_at(1, 0), Colour::white());
+        assert_eq!(canvas.pixel_at(0, 1), Colour::white());
+        assert_eq!(canvas.pixel_at(1, 1), Colour::black());
+    }
+}
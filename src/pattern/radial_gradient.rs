@@ -0,0 +1,69 @@
+use crate::{
+    colour::Colour,
+    matrix::Matrix,
+    pattern::{Pattern, PatternData, PatternSlot},
+    tuple::Tuple,
+};
+
+// Like Gradient, but blends outwards from the y axis in repeating unit-wide rings
+#[derive(Clone)]
+pub struct RadialGradient {
+    data: PatternData,
+}
+
+impl Pattern for RadialGradient {
+    fn data(&self) -> &PatternData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut PatternData {
+        &mut self.data
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Colour {
+        let a = self.a_at(point);
+        let b = self.b_at(point);
+
+        let distance = (point.x.powi(2) + point.z.powi(2)).sqrt();
+        let frac = distance - distance.floor();
+
+        a + (b - a) * frac
+    }
+}
+
+impl RadialGradient {
+    pub fn new(a: impl Into<PatternSlot>, b: impl Into<PatternSlot>) -> Self {
+        let identity: Matrix = Matrix::identity();
+        Self {
+            data: PatternData {
+                a: a.into(),
+                b: b.into(),
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radial_gradient_blends_with_distance_from_y_axis() {
+        let pattern = RadialGradient::new(Colour::white(), Colour::black());
+
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)),
+            Colour::white()
+        );
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.0, 5.0, 0.5)),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.6, 0.0, 0.8)),
+            Colour::white()
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     colour::Colour,
     matrix::Matrix,
-    pattern::{Pattern, PatternData},
+    pattern::{Pattern, PatternData, PatternSlot},
     tuple::Tuple,
 };
 
@@ -21,20 +21,20 @@ impl Pattern for Ring {
 
     fn pattern_at(&self, point: Tuple) -> Colour {
         if (point.x.powi(2) + point.z.powi(2)).sqrt().floor() % 2.0 == 0.0 {
-            self.data().a
+            self.a_at(point)
         } else {
-            self.data().b
+            self.b_at(point)
         }
     }
 }
 
 impl Ring {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternSlot>, b: impl Into<PatternSlot>) -> Self {
         let identity: Matrix = Matrix::identity();
         Self {
             data: PatternData {
-                a,
-                b,
+                a: a.into(),
+                b: b.into(),
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
             },
@@ -1,7 +1,7 @@
 use crate::{
     colour::Colour,
     matrix::Matrix,
-    pattern::{Pattern, PatternData},
+    pattern::{Pattern, PatternData, PatternSlot},
     tuple::Tuple,
 };
 
@@ -21,20 +21,20 @@ impl Pattern for Striped {
 
     fn pattern_at(&self, point: Tuple) -> Colour {
         if point.x.floor() as i32 % 2 == 0 {
-            self.data().a
+            self.a_at(point)
         } else {
-            self.data().b
+            self.b_at(point)
         }
     }
 }
 
 impl Striped {
-    pub fn new(a: Colour, b: Colour) -> Self {
+    pub fn new(a: impl Into<PatternSlot>, b: impl Into<PatternSlot>) -> Self {
         let identity: Matrix = Matrix::identity();
         Self {
             data: PatternData {
-                a,
-                b,
+                a: a.into(),
+                b: b.into(),
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
             },
@@ -54,8 +54,8 @@ mod tests {
         let black = Colour::new(0.0, 0.0, 0.0);
         let pattern = Striped::new(white, black);
 
-        assert_eq!(pattern.data.a, white);
-        assert_eq!(pattern.data.b, black);
+        assert_eq!(pattern.data.a.as_colour(), Some(white));
+        assert_eq!(pattern.data.b.as_colour(), Some(black));
     }
 
     #[test]
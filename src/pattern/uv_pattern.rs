@@ -0,0 +1,541 @@
+use crate::scalar::consts::PI;
+use crate::{
+    camera::Canvas,
+    colour::Colour,
+    matrix::Matrix,
+    pattern::{Pattern, PatternData, PatternSlot},
+    scalar::Float,
+    shape::Shape,
+    tuple::Tuple,
+};
+use std::path::Path;
+
+// How a point on (or near) a shape is flattened to texture coordinates, with
+// u and v in [0, 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UvMapping {
+    // Longitude and latitude around the origin; suits spheres
+    Spherical,
+    // Tiles the xz plane every unit; suits planes
+    Planar,
+    // Angle around the y axis and height, tiling every unit of y
+    Cylindrical,
+    // Projects onto whichever face of the unit cube the point is nearest
+    Cubic,
+    // The shape's own coordinates, such as a mesh's texture coordinates from
+    // an OBJ file; see Shape::local_uv_at. Shapes without any, and points off
+    // any shape, are mapped as planar.
+    Surface,
+}
+
+impl UvMapping {
+    pub fn from_name(name: &str) -> Option<UvMapping> {
+        match name.to_ascii_lowercase().as_str() {
+            "spherical" => Some(UvMapping::Spherical),
+            "planar" => Some(UvMapping::Planar),
+            "cylindrical" => Some(UvMapping::Cylindrical),
+            "cubic" => Some(UvMapping::Cubic),
+            "surface" => Some(UvMapping::Surface),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UvMapping::Spherical => "spherical",
+            UvMapping::Planar => "planar",
+            UvMapping::Cylindrical => "cylindrical",
+            UvMapping::Cubic => "cubic",
+            UvMapping::Surface => "surface",
+        }
+    }
+
+    pub fn map(&self, point: Tuple) -> (Float, Float) {
+        match self {
+            UvMapping::Spherical => spherical_map(point),
+            UvMapping::Planar | UvMapping::Surface => planar_map(point),
+            UvMapping::Cylindrical => cylindrical_map(point),
+            UvMapping::Cubic => {
+                let (_, u, v) = cubic_map(point);
+                (u, v)
+            }
+        }
+    }
+
+    // As map, for a point in shape's object space
+    pub fn map_on(&self, shape: &dyn Shape, object_point: Tuple) -> (Float, Float) {
+        match self {
+            UvMapping::Surface => shape
+                .local_uv_at(&object_point)
+                .unwrap_or_else(|| planar_map(object_point)),
+            _ => self.map(object_point),
+        }
+    }
+}
+
+pub fn spherical_map(point: Tuple) -> (Float, Float) {
+    let theta = point.x.atan2(point.z);
+    let radius = Tuple::vector(point.x, point.y, point.z).magnitude();
+    let phi = (point.y / radius).acos();
+
+    // theta runs from -pi to pi; flip so u increases anticlockwise seen from above
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    let v = 1.0 - phi / PI;
+    (u, v)
+}
+
+pub fn planar_map(point: Tuple) -> (Float, Float) {
+    (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0))
+}
+
+pub fn cylindrical_map(point: Tuple) -> (Float, Float) {
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    (u, point.y.rem_euclid(1.0))
+}
+
+// Declared in the order skybox faces are given: +x, -x, +y, -y, +z, -z
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    Right,
+    Left,
+    Up,
+    Down,
+    Front,
+    Back,
+}
+
+impl CubeFace {
+    pub fn of(point: Tuple) -> CubeFace {
+        let coord = point.x.abs().max(point.y.abs()).max(point.z.abs());
+        if coord == point.x {
+            CubeFace::Right
+        } else if coord == -point.x {
+            CubeFace::Left
+        } else if coord == point.y {
+            CubeFace::Up
+        } else if coord == -point.y {
+            CubeFace::Down
+        } else if coord == point.z {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+}
+
+// Face of the cube around the origin and the uv on that face. Each face is
+// unwrapped as seen from the centre of the cube, with +y up on the side faces,
+// -z up on the top face and +z up on the bottom face.
+pub fn cubic_map(point: Tuple) -> (CubeFace, Float, Float) {
+    let wrap = |v: Float| v.rem_euclid(2.0) / 2.0;
+    let face = CubeFace::of(point);
+    let (u, v) = match face {
+        CubeFace::Front => (wrap(point.x + 1.0), wrap(point.y + 1.0)),
+        CubeFace::Back => (wrap(1.0 - point.x), wrap(point.y + 1.0)),
+        CubeFace::Left => (wrap(point.z + 1.0), wrap(point.y + 1.0)),
+        CubeFace::Right => (wrap(1.0 - point.z), wrap(point.y + 1.0)),
+        CubeFace::Up => (wrap(point.x + 1.0), wrap(1.0 - point.z)),
+        CubeFace::Down => (wrap(point.x + 1.0), wrap(point.z + 1.0)),
+    };
+    (face, u, v)
+}
+
+// A picture to wrap around shapes. v = 0 is the bottom row of the image.
+#[derive(Clone)]
+pub struct UvImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    // File the image was loaded from, so scenes can be saved with a reference
+    // to it
+    path: Option<String>,
+}
+
+impl UvImage {
+    pub fn from_canvas(canvas: &Canvas) -> UvImage {
+        let mut pixels = Vec::with_capacity(canvas.width * canvas.height);
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                pixels.push(canvas.pixel_at(x, y));
+            }
+        }
+        UvImage {
+            width: canvas.width,
+            height: canvas.height,
+            pixels,
+            path: None,
+        }
+    }
+
+    // Loads any format the image crate understands (PNG, JPEG, ...). Pixel
+    // values are used as they are stored, without undoing sRGB encoding.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<UvImage, String> {
+        let path = path.as_ref();
+        let decoded = image::open(path)
+            .map_err(|e| format!("Failed to load texture {}: {}", path.display(), e))?
+            .to_rgb32f();
+        let pixels = decoded
+            .pixels()
+            .map(|p| Colour::new(p[0] as Float, p[1] as Float, p[2] as Float))
+            .collect();
+        Ok(UvImage {
+            width: decoded.width() as usize,
+            height: decoded.height() as usize,
+            pixels,
+            path: Some(path.to_string_lossy().into_owned()),
+        })
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    // Bilinear blend of the four pixels around (u, v)
+    pub fn colour_at(&self, u: Float, v: Float) -> Colour {
+        if self.pixels.is_empty() {
+            return Colour::black();
+        }
+        let x = (u * (self.width - 1) as Float).clamp(0.0, (self.width - 1) as Float);
+        let y = ((1.0 - v) * (self.height - 1) as Float).clamp(0.0, (self.height - 1) as Float);
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as Float, y - y0 as Float);
+
+        let at = |x: usize, y: usize| self.pixels[y * self.width + x];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+// What to draw in uv space
+#[derive(Clone)]
+pub enum UvPattern {
+    // width x height squares alternating between the pattern's a and b slots
+    Checkers { width: Float, height: Float },
+    Image(UvImage),
+}
+
+// Wraps a 2D uv pattern around a shape using one of the mappings. Transforms
+// work as for any other pattern, applied before the mapping.
+#[derive(Clone)]
+pub struct TextureMap {
+    data: PatternData,
+    uv_pattern: UvPattern,
+    mapping: UvMapping,
+}
+
+impl Pattern for TextureMap {
+    fn data(&self) -> &PatternData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut PatternData {
+        &mut self.data
+    }
+
+    // Surface mapping takes its coordinates from the shape, at the object
+    // space point, so the pattern's transform doesn't move them
+    fn pattern_at_shape(&self, shape: &dyn Shape, world_point: Tuple) -> Colour {
+        let object_point = &shape.data().inverse_transform * world_point;
+        let pattern_point = &self.data.inverse_transform * object_point;
+        let uv = match self.mapping {
+            UvMapping::Surface => self.mapping.map_on(shape, object_point),
+            _ => self.mapping.map(pattern_point),
+        };
+        self.colour_at_uv(uv, pattern_point)
+    }
+
+    fn pattern_at(&self, point: Tuple) -> Colour {
+        self.colour_at_uv(self.mapping.map(point), point)
+    }
+}
+
+impl TextureMap {
+    // point is in pattern space, for the checkers' slots
+    fn colour_at_uv(&self, (u, v): (Float, Float), point: Tuple) -> Colour {
+        match &self.uv_pattern {
+            UvPattern::Checkers { width, height } => {
+                if ((u * width).floor() + (v * height).floor()) as i64 % 2 == 0 {
+                    self.a_at(point)
+                } else {
+                    self.b_at(point)
+                }
+            }
+            UvPattern::Image(image) => image.colour_at(u, v),
+        }
+    }
+
+    pub fn checkers(
+        width: Float,
+        height: Float,
+        a: impl Into<PatternSlot>,
+        b: impl Into<PatternSlot>,
+        mapping: UvMapping,
+    ) -> Self {
+        Self::new(
+            UvPattern::Checkers { width, height },
+            a.into(),
+            b.into(),
+            mapping,
+        )
+    }
+
+    pub fn image(image: UvImage, mapping: UvMapping) -> Self {
+        Self::new(
+            UvPattern::Image(image),
+            Colour::black().into(),
+            Colour::white().into(),
+            mapping,
+        )
+    }
+
+    pub fn uv_pattern(&self) -> &UvPattern {
+        &self.uv_pattern
+    }
+
+    pub fn mapping(&self) -> UvMapping {
+        self.mapping
+    }
+
+    fn new(uv_pattern: UvPattern, a: PatternSlot, b: PatternSlot, mapping: UvMapping) -> Self {
+        let identity: Matrix = Matrix::identity();
+        Self {
+            data: PatternData {
+                a,
+                b,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+            },
+            uv_pattern,
+            mapping,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::consts::FRAC_1_SQRT_2;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn checker_pattern_in_2d() {
+        let pattern = TextureMap::checkers(
+            2.0,
+            2.0,
+            Colour::black(),
+            Colour::white(),
+            UvMapping::Planar,
+        );
+        let cases = [
+            (0.0, 0.0, Colour::black()),
+            (0.5, 0.0, Colour::white()),
+            (0.0, 0.5, Colour::white()),
+            (0.5, 0.5, Colour::black()),
+            (0.99, 0.99, Colour::black()),
+        ];
+
+        for (u, v, expected) in cases {
+            // Planar mapping passes x and z straight through as u and v
+            assert_eq!(pattern.pattern_at(Tuple::point(u, 0.0, v)), expected);
+        }
+    }
+
+    #[test]
+    fn using_a_spherical_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0.0, 0.0, -1.0), 0.0, 0.5),
+            (Tuple::point(1.0, 0.0, 0.0), 0.25, 0.5),
+            (Tuple::point(0.0, 0.0, 1.0), 0.5, 0.5),
+            (Tuple::point(-1.0, 0.0, 0.0), 0.75, 0.5),
+            (Tuple::point(0.0, 1.0, 0.0), 0.5, 1.0),
+            (Tuple::point(0.0, -1.0, 0.0), 0.5, 0.0),
+            (Tuple::point(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0), 0.25, 0.75),
+        ];
+
+        for (point, u, v) in cases {
+            let (mu, mv) = spherical_map(point);
+            assert_abs_diff_eq!(mu, u, epsilon = TEST_EPSILON);
+            assert_abs_diff_eq!(mv, v, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn using_a_planar_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0.25, 0.0, 0.5), 0.25, 0.5),
+            (Tuple::point(0.25, 0.0, -0.25), 0.25, 0.75),
+            (Tuple::point(0.25, 0.5, -0.25), 0.25, 0.75),
+            (Tuple::point(1.25, 0.0, 0.5), 0.25, 0.5),
+            (Tuple::point(0.25, 0.0, -1.75), 0.25, 0.25),
+            (Tuple::point(1.0, 0.0, -1.0), 0.0, 0.0),
+            (Tuple::point(0.0, 0.0, 0.0), 0.0, 0.0),
+        ];
+
+        for (point, u, v) in cases {
+            let (mu, mv) = planar_map(point);
+            assert_abs_diff_eq!(mu, u, epsilon = TEST_EPSILON);
+            assert_abs_diff_eq!(mv, v, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn using_a_cylindrical_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0.0, 0.0, -1.0), 0.0, 0.0),
+            (Tuple::point(0.0, 0.5, -1.0), 0.0, 0.5),
+            (Tuple::point(0.0, 1.0, -1.0), 0.0, 0.0),
+            (Tuple::point(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), 0.125, 0.5),
+            (Tuple::point(1.0, 0.5, 0.0), 0.25, 0.5),
+            (Tuple::point(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.375, 0.5),
+            (Tuple::point(0.0, -0.25, 1.0), 0.5, 0.75),
+            (Tuple::point(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.625, 0.5),
+            (Tuple::point(-1.0, 1.25, 0.0), 0.75, 0.25),
+            (
+                Tuple::point(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2),
+                0.875,
+                0.5,
+            ),
+        ];
+
+        for (point, u, v) in cases {
+            let (mu, mv) = cylindrical_map(point);
+            assert_abs_diff_eq!(mu, u, epsilon = 1e-4);
+            assert_abs_diff_eq!(mv, v, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn identifying_the_face_of_a_cube_from_a_point() {
+        let cases = [
+            (Tuple::point(-1.0, 0.5, -0.25), CubeFace::Left),
+            (Tuple::point(1.1, -0.75, 0.8), CubeFace::Right),
+            (Tuple::point(0.1, 0.6, 0.9), CubeFace::Front),
+            (Tuple::point(-0.7, 0.0, -2.0), CubeFace::Back),
+            (Tuple::point(0.5, 1.0, 0.9), CubeFace::Up),
+            (Tuple::point(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+
+        for (point, face) in cases {
+            assert_eq!(CubeFace::of(point), face, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn uv_mapping_the_faces_of_a_cube() {
+        let cases = [
+            (Tuple::point(-0.5, 0.5, 1.0), CubeFace::Front, 0.25, 0.75),
+            (Tuple::point(0.5, -0.5, 1.0), CubeFace::Front, 0.75, 0.25),
+            (Tuple::point(0.5, 0.5, -1.0), CubeFace::Back, 0.25, 0.75),
+            (Tuple::point(-1.0, 0.5, -0.5), CubeFace::Left, 0.25, 0.75),
+            (Tuple::point(1.0, 0.5, 0.5), CubeFace::Right, 0.25, 0.75),
+            (Tuple::point(-0.5, 1.0, -0.5), CubeFace::Up, 0.25, 0.75),
+            (Tuple::point(-0.5, -1.0, 0.5), CubeFace::Down, 0.25, 0.75),
+        ];
+
+        for (point, face, u, v) in cases {
+            let (f, mu, mv) = cubic_map(point);
+            assert_eq!(f, face);
+            assert_abs_diff_eq!(mu, u, epsilon = TEST_EPSILON);
+            assert_abs_diff_eq!(mv, v, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn image_textures_are_sampled_with_v_up() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.write_pixel(0, 0, Colour::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(1, 1, Colour::new(0.0, 0.0, 1.0));
+        let image = UvImage::from_canvas(&canvas);
+
+        assert_eq!(image.colour_at(0.0, 1.0), Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(image.colour_at(1.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+        assert_eq!(image.colour_at(1.0, 1.0), Colour::black());
+    }
+
+    #[test]
+    fn surface_mapping_uses_the_shapes_texture_coordinates() {
+        use crate::shape::{sphere::Sphere, triangle::Triangle};
+
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(1, 0, Colour::white());
+        let pattern = TextureMap::image(UvImage::from_canvas(&canvas), UvMapping::Surface);
+        let mut triangle = Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        )
+        .with_texture([(0.0, 0.0), (0.0, 0.0), (1.0, 0.0)]);
+        triangle.set_transform(Matrix::translation(0.0, 0.0, 5.0));
+
+        assert_eq!(
+            pattern.pattern_at_shape(&triangle, Tuple::point(1.0, 0.0, 5.0)),
+            Colour::white()
+        );
+        assert_eq!(
+            pattern.pattern_at_shape(&triangle, Tuple::point(-1.0, 0.0, 5.0)),
+            Colour::black()
+        );
+        // Spheres give their spherical coordinates
+        assert_eq!(
+            pattern.pattern_at_shape(&Sphere::new(), Tuple::point(0.0, 0.0, 1.0)),
+            Colour::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn image_textures_are_interpolated_between_pixels() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.write_pixel(0, 0, Colour::black());
+        canvas.write_pixel(1, 0, Colour::white());
+        let image = UvImage::from_canvas(&canvas);
+
+        assert_abs_diff_eq!(
+            image.colour_at(0.25, 0.5),
+            Colour::new(0.25, 0.25, 0.25),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn loading_a_png_texture() {
+        let path = std::env::temp_dir().join("raytracer_uv_texture_test.png");
+        let mut img = image::RgbImage::new(2, 2);
+        img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        img.put_pixel(1, 1, image::Rgb([0, 0, 255]));
+        img.save(&path).unwrap();
+
+        let image = UvImage::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_abs_diff_eq!(image.colour_at(0.0, 1.0), Colour::new(1.0, 0.0, 0.0));
+        assert_abs_diff_eq!(image.colour_at(1.0, 0.0), Colour::new(0.0, 0.0, 1.0));
+        assert!(UvImage::load("no/such/texture.png").is_err());
+    }
+
+    #[test]
+    fn spherical_checkers_on_a_sphere() {
+        let pattern = TextureMap::checkers(
+            16.0,
+            8.0,
+            Colour::black(),
+            Colour::white(),
+            UvMapping::Spherical,
+        );
+
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(0.4315, 0.4670, 0.7719)),
+            Colour::white()
+        );
+        assert_eq!(
+            pattern.pattern_at(Tuple::point(-0.9654, 0.2552, -0.0534)),
+            Colour::black()
+        );
+    }
+}
@@ -1,12 +1,24 @@
-use crate::tuple::Tuple;
+use crate::{scalar::Float, tuple::Tuple};
 
 pub struct Projectile {
     pub pos: Tuple,
     pub vel: Tuple,
+    // Only used for collisions; see Simulation::step_in and with_ground
+    pub radius: Float,
+    // Fraction of the speed kept after each bounce
+    pub restitution: Float,
+    // Scales how far forces move it; see force::Force
+    pub mass: Float,
 }
 
 impl Projectile {
     pub fn new(p: Tuple, v: Tuple) -> Projectile {
-        Projectile { pos: p, vel: v }
+        Projectile {
+            pos: p,
+            vel: v,
+            radius: 0.0,
+            restitution: 1.0,
+            mass: 1.0,
+        }
     }
 }
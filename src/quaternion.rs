@@ -0,0 +1,199 @@
+use std::ops::Mul;
+
+use crate::{matrix::Matrix, scalar::Float, tuple::Tuple};
+
+// A rotation, stored as a unit quaternion. Unlike a chain of rotation_x/y/z
+// matrices there's no gimbal lock, and two rotations can be smoothly
+// interpolated with slerp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Turns by `radians` about `axis`, in the same direction as the matching
+    // Matrix::rotation_* for the x, y and z axes. The axis needn't be unit length.
+    pub fn from_axis_angle(axis: Tuple, radians: Float) -> Quaternion {
+        let axis = Tuple::vector(axis.x, axis.y, axis.z).normalise();
+        let (sin, cos) = (radians / 2.0).sin_cos();
+        Quaternion::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalise(&self) -> Quaternion {
+        let mag = self.magnitude();
+        Quaternion::new(self.w / mag, self.x / mag, self.y / mag, self.z / mag)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    // The opposite rotation
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(&self, v: Tuple) -> Tuple {
+        let p = Quaternion::new(0.0, v.x, v.y, v.z);
+        let r = *self * p * self.conjugate();
+        Tuple::new(r.x, r.y, r.z, v.w)
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let Quaternion { w, x, y, z } = self.normalise();
+        Matrix::from_vec(vec![
+            vec![
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            vec![
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            vec![
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Spherical interpolation, turning at a constant rate from self at t = 0
+    // to other at t = 1 the short way round
+    pub fn slerp(&self, other: &Quaternion, t: Float) -> Quaternion {
+        let mut other = *other;
+        let mut cos = self.dot(&other);
+        // q and -q are the same rotation; pick the one nearer to self
+        if cos < 0.0 {
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+            cos = -cos;
+        }
+
+        // Nearly parallel, where sin(theta) is too small to divide by
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalise()
+    }
+}
+
+// Composes like matrices: (a * b) rotates by b first, then a
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::consts::PI;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn axis_angle_matches_rotation_matrices() {
+        let p = Tuple::point(1.0, 2.0, 3.0);
+        let cases = [
+            (Tuple::vector(1.0, 0.0, 0.0), Matrix::rotation_x(0.7)),
+            (Tuple::vector(0.0, 2.0, 0.0), Matrix::rotation_y(0.7)),
+            (Tuple::vector(0.0, 0.0, 1.0), Matrix::rotation_z(0.7)),
+        ];
+
+        for (axis, matrix) in cases {
+            let q = Quaternion::from_axis_angle(axis, 0.7);
+            assert_abs_diff_eq!(
+                q.to_matrix() * p,
+                matrix.clone() * p,
+                epsilon = TEST_EPSILON
+            );
+            assert_abs_diff_eq!(q.rotate(p), matrix * p, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn multiplying_composes_rotations_like_matrices() {
+        let a = Quaternion::from_axis_angle(Tuple::vector(0.0, 1.0, 0.0), PI / 3.0);
+        let b = Quaternion::from_axis_angle(Tuple::vector(1.0, 0.0, 0.0), PI / 5.0);
+        let p = Tuple::point(0.5, -1.0, 2.0);
+
+        let by_matrix = Matrix::rotation_y(PI / 3.0) * Matrix::rotation_x(PI / 5.0) * p;
+        assert_abs_diff_eq!((a * b).rotate(p), by_matrix, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!((a * a.conjugate()).rotate(p), p, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn slerp_turns_at_a_constant_rate() {
+        let axis = Tuple::vector(0.0, 0.0, 1.0);
+        let from = Quaternion::identity();
+        let to = Quaternion::from_axis_angle(axis, PI / 2.0);
+        let v = Tuple::vector(1.0, 0.0, 0.0);
+
+        assert_abs_diff_eq!(from.slerp(&to, 0.0).rotate(v), v, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(
+            from.slerp(&to, 1.0 / 3.0).rotate(v),
+            Quaternion::from_axis_angle(axis, PI / 6.0).rotate(v),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            from.slerp(&to, 1.0).rotate(v),
+            Tuple::vector(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way_round() {
+        let axis = Tuple::vector(0.0, 1.0, 0.0);
+        let from = Quaternion::from_axis_angle(axis, 0.1);
+        // The same rotation as 0.2 radians, written the long way
+        let to = Quaternion::from_axis_angle(axis, 0.2 - 2.0 * PI);
+
+        let halfway = from.slerp(&to, 0.5);
+        let expected = Quaternion::from_axis_angle(axis, 0.15);
+        assert_abs_diff_eq!(
+            halfway.rotate(Tuple::vector(1.0, 0.0, 0.0)),
+            expected.rotate(Tuple::vector(1.0, 0.0, 0.0)),
+            epsilon = TEST_EPSILON
+        );
+    }
+}
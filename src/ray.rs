@@ -1,24 +1,65 @@
-use crate::{matrix::Matrix, tuple::Tuple};
+use crate::{matrix::Matrix, scalar::Float, tuple::Tuple};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Tuple,
     pub direction: Tuple,
+    // Seconds since the camera's shutter opened, for motion blur. Secondary
+    // rays inherit it, so a sample sees every shape at the same moment.
+    pub time: Float,
+    // Where the camera ray this one descends from started. Level-of-detail
+    // shapes pick their version by distance from it, so shadow and reflection
+    // rays leaving a surface see the version the camera sees.
+    pub eye: Tuple,
+    // The colour channel (0 for red to 2 for blue) this ray carries alone,
+    // once dispersion has split it off. Later refractions bend it by that
+    // channel's index instead of splitting it again.
+    pub channel: Option<usize>,
+    // Varies the random choices path tracing makes along the ray, so samples
+    // that happen to take the same ray still differ. Secondary rays inherit it.
+    pub seed: u64,
 }
 
 impl Ray {
     pub fn new(origin: Tuple, direction: Tuple) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+            eye: origin,
+            channel: None,
+            seed: 0,
+        }
+    }
+
+    pub fn with_time(self, time: Float) -> Ray {
+        Ray { time, ..self }
+    }
+
+    pub fn with_eye(self, eye: Tuple) -> Ray {
+        Ray { eye, ..self }
+    }
+
+    pub fn with_channel(self, channel: Option<usize>) -> Ray {
+        Ray { channel, ..self }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Ray {
+        Ray { seed, ..self }
     }
 
-    pub fn position(&self, t: f64) -> Tuple {
+    pub fn position(&self, t: Float) -> Tuple {
         self.origin + self.direction * t
     }
 
-    pub fn transform(&self, translation: &Matrix) -> Ray {
+    pub fn transform(&self, matrix: &Matrix) -> Ray {
         Ray {
-            origin: translation.clone() * self.origin,
-            direction: translation.clone() * self.direction,
+            origin: matrix * self.origin,
+            direction: matrix * self.direction,
+            time: self.time,
+            eye: matrix * self.eye,
+            channel: self.channel,
+            seed: self.seed,
         }
     }
 }
@@ -73,6 +114,17 @@ mod tests {
         assert_eq!(r2.direction, Tuple::vector(0.0, 3.0, 0.0));
     }
 
+    #[test]
+    fn transforming_a_ray_leaves_the_original_unchanged() {
+        use crate::matrix::Matrix;
+
+        let r = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        let r2 = r.transform(&Matrix::translation(3.0, 4.0, 5.0));
+
+        assert_eq!(r.origin, Tuple::point(1.0, 2.0, 3.0));
+        assert_eq!(r2.origin, Tuple::point(4.0, 6.0, 8.0));
+    }
+
     #[test]
     fn sphere_default_transformation() {
         use crate::matrix::Matrix;
@@ -1,5 +1,32 @@
-use crate::{camera::Camera, colour::Colour, tuple::Tuple, world::World};
+use crate::{
+    accumulation_buffer::AccumulationBuffer,
+    aov::Aov,
+    camera::{Camera, Projection, SamplingMode},
+    camera_shake::CameraShake,
+    colour::Colour,
+    frame_stats::FrameStats,
+    intersection::{hit, Intersection},
+    light::Light,
+    lut::ColourLut,
+    matrix::Matrix,
+    scalar::Float,
+    shape::{plane::Plane, sphere::Sphere, Shape},
+    shape_registry::{RegistryEvent, RegistrySnapshot},
+    tonemap::{ToneMapOperator, ToneMapping},
+    transformations::view_transform,
+    tuple::Tuple,
+    world::{Integrator, World},
+};
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
+use web_time::Instant;
+
+// Progressive mode first fills the image in blocks of these sizes, one traced
+// pixel per block, before refining at full resolution
+const PREVIEW_BLOCK_SIZES: [usize; 3] = [8, 4, 2];
+
+// Texels along each edge of a cube face of the draft shadow map
+const DRAFT_SHADOW_MAP_SIZE: usize = 256;
 
 #[wasm_bindgen]
 pub struct RenderContext {
@@ -10,6 +37,68 @@ pub struct RenderContext {
     world: World,
     camera: Camera,
     tile_buffer: Vec<u8>,
+    // The last debug view from render_aov, as RGBA bytes
+    aov_buffer: Vec<u8>,
+    last_frame_stats: FrameStats,
+    tone_mapping: ToneMapping,
+    undo_stack: Vec<RegistrySnapshot>,
+    redo_stack: Vec<RegistrySnapshot>,
+    progressive: bool,
+    // Passes completed since progressive rendering last restarted
+    pass: usize,
+    // The full-resolution samples taken for each pixel so far
+    samples: AccumulationBuffer,
+    // Reused by every camera ray to save allocating per sample
+    intersections: Vec<Intersection>,
+    // Shadows come from a depth map around the light instead of shadow rays
+    draft_shadows: bool,
+    // Where pan, zoom and orbit move the camera from, and where reset_view
+    // returns it to
+    view: OrbitView,
+    home_view: OrbitView,
+    // Frames rendered while the camera is moving trace one pixel in every
+    // preview_scale x preview_scale block; 1 disables this
+    preview_scale: usize,
+    camera_moved: bool,
+}
+
+// The camera as a point it orbits and its angles and distance from it, as
+// taken by Camera::orbit
+#[derive(Debug, Clone, Copy)]
+struct OrbitView {
+    target: Tuple,
+    yaw: Float,
+    pitch: Float,
+    distance: Float,
+}
+
+impl OrbitView {
+    fn looking(from: Tuple, to: Tuple) -> OrbitView {
+        let offset = from - to;
+        let distance = offset.magnitude();
+        OrbitView {
+            target: to,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).clamp(-1.0, 1.0).asin(),
+            distance,
+        }
+    }
+}
+
+// What a pixel shows, from RenderContext::pick
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct PickResult {
+    // The shape hit, which may be nested in a group or mesh
+    pub object_id: u32,
+    // The top-level shape it belongs to, as taken by the edit methods
+    pub root_id: u32,
+    // Distance along the ray, in scene units when the camera is perspective
+    pub t: Float,
+    // Where the ray hit, in world space
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 #[wasm_bindgen]
@@ -21,32 +110,578 @@ impl RenderContext {
         let buffer_size = (width * height * 4) as usize;
         let buffer = vec![0; buffer_size];
 
-        let mut camera = Camera::new(width as usize, height as usize, std::f64::consts::PI / 3.0);
+        let mut camera = Camera::new(
+            width as usize,
+            height as usize,
+            crate::scalar::consts::PI / 3.0,
+        );
         let from = Tuple::point(0.0, 1.5, -5.0);
         let to = Tuple::point(0.0, 1.0, 0.0);
         let up = Tuple::vector(0.0, 1.0, 0.0);
-        camera.set_transform(crate::transformations::view_transform(from, to, up));
+        camera.set_transform(view_transform(from, to, up));
+        let view = OrbitView::looking(from, to);
+
+        let mut world = World::third_world();
+        world.build_bvh();
 
-        let context = RenderContext {
+        RenderContext {
             width,
             height,
             colours,
             buffer,
-            world: World::third_world(),
+            world,
             camera,
             tile_buffer: Vec::new(),
-        };
+            aov_buffer: Vec::new(),
+            last_frame_stats: FrameStats::default(),
+            tone_mapping: ToneMapping::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            progressive: false,
+            pass: 0,
+            samples: AccumulationBuffer::new(width as usize, height as usize),
+            intersections: Vec::new(),
+            draft_shadows: false,
+            view,
+            home_view: view,
+            preview_scale: 1,
+            camera_moved: false,
+        }
+    }
+
+    // dt is the time in seconds since the previous frame, which advances
+    // time-varying parts of the scene such as flickering lights. In progressive
+    // mode each call performs one refinement pass instead of a whole frame.
+    pub fn render(&mut self, dt: f32) {
+        self.world.time += dt as Float;
+        let mut stats = FrameStats::default();
+        self.world.reset_rays_traced();
+
+        let camera = self.camera.at_time(self.world.time);
+        let interacting = std::mem::take(&mut self.camera_moved);
+        if interacting && self.preview_scale > 1 {
+            self.render_blocks(&camera, self.preview_scale, &mut stats);
+        } else if self.progressive {
+            self.render_pass(&camera, &mut stats);
+        } else {
+            for y in 0..self.height as usize {
+                for x in 0..self.width as usize {
+                    let offsets = self.sample_offsets(x, y);
+                    let mut colour = Colour::black();
+                    for (sample, offset) in offsets.iter().enumerate() {
+                        colour =
+                            colour + self.trace_sample(&camera, x, y, sample, *offset, &mut stats);
+                    }
+                    self.colours[y * self.width as usize + x] =
+                        colour * (1.0 / offsets.len() as Float);
+                }
+            }
+        }
 
-        context
+        let start = Instant::now();
+        self.update_buffer_from_colours();
+        stats.buffer_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        stats.rays_traced = self.world.rays_traced();
+        self.last_frame_stats = stats;
+    }
+
+    // When enabled, render() starts with a blocky preview and sharpens it over
+    // successive calls, ending with every pixel at the full sample count
+    pub fn set_progressive(&mut self, enabled: bool) {
+        self.progressive = enabled;
+        self.restart_progressive();
+    }
+
+    // Starts refinement again from the coarsest preview, e.g. after a scene edit
+    pub fn restart_progressive(&mut self) {
+        self.pass = 0;
     }
 
-    pub fn render(&mut self, _dt: f32) {
-        for color in &mut self.colours {
-            *color = Colour::new(0.0, 0.0, 0.0);
+    // Fraction of the progressive passes done, from 0 to 1. Always 1 when not
+    // in progressive mode, since every render() produces a finished frame.
+    pub fn get_progress(&self) -> f32 {
+        if !self.progressive {
+            return 1.0;
         }
+        self.pass.min(self.total_passes()) as f32 / self.total_passes() as f32
+    }
+
+    // JSON breakdown of the most recent render() call, for the demo's perf HUD
+    pub fn last_frame_stats(&self) -> String {
+        self.last_frame_stats.to_json()
+    }
+
+    // Mode is "grid" or "jittered"; takes effect from the next render
+    pub fn set_samples(&mut self, samples_per_pixel: u32, mode: &str) -> Result<(), String> {
+        let mode = SamplingMode::from_name(mode)
+            .ok_or_else(|| format!("Unknown sampling mode '{}'", mode))?;
+        self.world.settings.samples = (samples_per_pixel as usize).max(1);
+        self.camera.sampling = mode;
+        self.restart_progressive();
+        Ok(())
+    }
+
+    // Name is "perspective", "orthographic", "fisheye" or "equirectangular";
+    // view_width is the width in scene units of an orthographic view and is
+    // ignored by the others
+    pub fn set_projection(&mut self, name: &str, view_width: Float) -> Result<(), String> {
+        let projection = match Projection::from_name(name) {
+            Some(Projection::Orthographic { .. }) => Projection::Orthographic { view_width },
+            Some(projection) => projection,
+            None => return Err(format!("Unknown projection '{}'", name)),
+        };
+        self.camera.projection = projection;
+        self.restart_progressive();
+        Ok(())
+    }
+
+    // Adds handheld-style jitter that evolves with scene time. Amplitudes are in
+    // scene units and degrees; frequency is roughly wobbles per second.
+    pub fn set_camera_shake(
+        &mut self,
+        position_amplitude: Float,
+        rotation_degrees: Float,
+        frequency: Float,
+        seed: u32,
+    ) {
+        self.camera.shake = Some(CameraShake::new(
+            position_amplitude,
+            rotation_degrees.to_radians(),
+            frequency,
+            seed,
+        ));
+    }
+
+    pub fn clear_camera_shake(&mut self) {
+        self.camera.shake = None;
+    }
 
-        self.camera.render_to_buffer(&self.world, &mut self.colours);
+    // Debug overlay of every shape's bounding box, from the next render
+    pub fn set_show_bounds(&mut self, show: bool) {
+        self.world.settings.show_bounds = show;
+        self.restart_progressive();
+    }
+
+    // While the camera is being moved, render at 1/scale resolution, scaled
+    // back up to fill the image. The first render() after it stops moving is
+    // at full resolution again.
+    pub fn set_preview_scale(&mut self, scale: u32) {
+        self.preview_scale = scale.max(1) as usize;
+    }
+
+    // Direct lighting and hard shadows only, one ray per pixel, for fast
+    // interactive frames. Turn it off again for final output.
+    pub fn set_preview(&mut self, enabled: bool) {
+        self.world.settings.integrator = if enabled {
+            Integrator::Preview
+        } else {
+            Integrator::Full
+        };
+        self.restart_progressive();
+    }
+
+    // Name is "full", "preview" or "path". Path tracing is noisy, so pair it
+    // with progressive mode and plenty of samples.
+    pub fn set_integrator(&mut self, name: &str) -> Result<(), String> {
+        self.world.settings.integrator =
+            Integrator::from_name(name).ok_or_else(|| format!("Unknown integrator '{}'", name))?;
+        self.restart_progressive();
+        Ok(())
+    }
+
+    // Lets light through transparent materials, so glass casts tinted shadows
+    // instead of black ones
+    pub fn set_transparent_shadows(&mut self, enabled: bool) {
+        self.world.settings.transparent_shadows = enabled;
+        self.restart_progressive();
+    }
+
+    // Reflections followed from each camera ray
+    pub fn set_max_bounces(&mut self, max_bounces: u32) {
+        self.world.settings.max_bounces = max_bounces;
+        self.restart_progressive();
+    }
+
+    // Reflections and refractions this deep or deeper are ended at random by
+    // reflectivity or transparency; None turns it off
+    pub fn set_roulette_depth(&mut self, depth: Option<u32>) {
+        self.world.settings.roulette_depth = depth;
+        self.restart_progressive();
+    }
+
+    // When off, surfaces are lit as if nothing blocked the light
+    pub fn set_shadows(&mut self, enabled: bool) {
+        self.world.settings.shadows = enabled;
+        self.restart_progressive();
+    }
+
+    // Hits closer than this along shadow and reflection rays are ignored. Raise
+    // it if large or heavily scaled scenes show speckled shadow acne.
+    pub fn set_epsilon(&mut self, epsilon: Float) {
+        self.world.settings.secondary_t_min = Some(epsilon);
+        self.restart_progressive();
+    }
+
+    // How far hit points are lifted off surfaces before secondary rays leave
+    // them, for trying out precision settings
+    pub fn set_shadow_bias(&mut self, bias: Float) {
+        self.world.settings.shadow_bias = Some(bias);
+        self.restart_progressive();
+    }
+
+    // Approximate shadows from a precomputed depth map for interactive draft
+    // renders. Edges are blockier and small shadows can go missing; turn it
+    // off again for final output.
+    pub fn set_draft_shadows(&mut self, enabled: bool) {
+        self.draft_shadows = enabled;
+        self.update_shadow_map();
+        self.restart_progressive();
+    }
+
+    // Operator is "clamp", "reinhard" or "aces". The displayed buffer is rebuilt
+    // from the last rendered colours, so changes show without re-rendering.
+    pub fn set_tone_mapping(
+        &mut self,
+        operator: &str,
+        exposure: Float,
+        gamma: Float,
+    ) -> Result<(), String> {
+        let operator = ToneMapOperator::from_name(operator)
+            .ok_or_else(|| format!("Unknown tone mapping operator '{}'", operator))?;
+        self.tone_mapping = ToneMapping {
+            lut: self.tone_mapping.lut.take(),
+            srgb: self.tone_mapping.srgb,
+            ..ToneMapping::new(operator, exposure, gamma)
+        };
+        self.update_buffer_from_colours();
+        Ok(())
+    }
+
+    // Encodes the display with the sRGB curve in place of the gamma setting
+    pub fn set_srgb_output(&mut self, enabled: bool) {
+        self.tone_mapping.srgb = enabled;
+        self.update_buffer_from_colours();
+    }
+
+    // Grades the display with the contents of a .cube 3D LUT. Like tone mapping,
+    // it's applied to the last rendered colours straight away.
+    pub fn set_lut(&mut self, cube: &str, preserve_luminance: bool) -> Result<(), String> {
+        let mut lut = ColourLut::parse_cube(cube)?;
+        lut.preserve_luminance = preserve_luminance;
+        self.tone_mapping.lut = Some(Rc::new(lut));
+        self.update_buffer_from_colours();
+        Ok(())
+    }
+
+    pub fn clear_lut(&mut self) {
+        self.tone_mapping.lut = None;
+        self.update_buffer_from_colours();
+    }
+
+    // Swaps in a built-in scene by name, or a JSON scene description. The camera,
+    // buffers and render settings are kept, but previously rendered pixels are
+    // cleared. Distance settings left at their defaults follow the new scene's
+    // unit scale.
+    pub fn reload_scene(&mut self, name_or_json: &str) -> Result<(), String> {
+        let settings = self.world.settings;
+        self.world = crate::scene::load_world(name_or_json)?;
+        self.world.settings = settings;
+        self.update_shadow_map();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.restart_progressive();
+
+        for colour in &mut self.colours {
+            *colour = Colour::new(0.0, 0.0, 0.0);
+        }
         self.update_buffer_from_colours();
+        self.tile_buffer.clear();
+
+        Ok(())
+    }
+
+    // Adds a sphere and returns its id for later edits. The radius has to be
+    // positive.
+    pub fn add_sphere(
+        &mut self,
+        x: Float,
+        y: Float,
+        z: Float,
+        radius: Float,
+    ) -> Result<u32, String> {
+        if radius <= 0.0 || !radius.is_finite() {
+            return Err(format!("Sphere radius must be positive, got {}", radius));
+        }
+        let mut sphere = Sphere::new();
+        sphere.set_transform(
+            &Matrix::translation(x, y, z) * &Matrix::scaling(radius, radius, radius),
+        );
+        let id = self.world.add_object(sphere);
+        self.scene_edited();
+        Ok(id)
+    }
+
+    // Adds an endless horizontal floor at height y and returns its id
+    pub fn add_plane(&mut self, y: Float) -> u32 {
+        let mut plane = Plane::new();
+        plane.set_transform(Matrix::translation(0.0, y, 0.0));
+        let id = self.world.add_object(plane);
+        self.scene_edited();
+        id
+    }
+
+    // Ids of the top-level shapes, in the order they were added
+    pub fn object_ids(&self) -> Vec<u32> {
+        self.world.registry.iter().map(|shape| shape.id()).collect()
+    }
+
+    // Id of the shape with this name, which stays the same when other shapes
+    // are added or removed; see ShapeRegistry::find_by_name
+    pub fn object_id(&self, name: &str) -> Option<u32> {
+        self.world.get_by_name(name).map(|shape| shape.id())
+    }
+
+    // The edit methods below take top-level ids and return false for anything
+    // else
+    pub fn remove_object(&mut self, id: u32) -> bool {
+        let removed = self.world.registry.remove(id);
+        self.scene_edited();
+        removed
+    }
+
+    // Moves a shape so its origin is at (x, y, z), keeping its size and rotation
+    pub fn move_object(&mut self, id: u32, x: Float, y: Float, z: Float) -> bool {
+        let Some(shape) = self.world.registry.get(id) else {
+            return false;
+        };
+        let origin = shape.transform() * Tuple::point(0.0, 0.0, 0.0);
+        let delta = Matrix::translation(x - origin.x, y - origin.y, z - origin.z);
+        let transform = &delta * shape.transform();
+        let moved = self.world.registry.set_transform(id, transform);
+        self.scene_edited();
+        moved
+    }
+
+    pub fn set_object_colour(&mut self, id: u32, r: Float, g: Float, b: Float) -> bool {
+        let Some(shape) = self.world.registry.get(id) else {
+            return false;
+        };
+        let mut material = shape.material().clone();
+        material.colour = Colour::new(r, g, b);
+        let recoloured = self.world.registry.set_material(id, material);
+        self.scene_edited();
+        recoloured
+    }
+
+    // Adds a point light and returns its index for later edits
+    pub fn add_light(
+        &mut self,
+        x: Float,
+        y: Float,
+        z: Float,
+        r: Float,
+        g: Float,
+        b: Float,
+    ) -> usize {
+        let light = Light::point_light(Tuple::point(x, y, z), Colour::new(r, g, b));
+        self.world.lights.push(light);
+        self.update_shadow_map();
+        self.restart_progressive();
+        self.world.lights.len() - 1
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.world.lights.len()
+    }
+
+    // The light edits take an index into the scene's lights, in the order
+    // they were added, and return false for one it doesn't have
+    pub fn move_light(&mut self, index: usize, x: Float, y: Float, z: Float) -> bool {
+        let Some(light) = self.world.lights.get_mut(index) else {
+            return false;
+        };
+        light.position = Tuple::point(x, y, z);
+        self.update_shadow_map();
+        self.restart_progressive();
+        true
+    }
+
+    pub fn set_light_intensity(&mut self, index: usize, r: Float, g: Float, b: Float) -> bool {
+        let Some(light) = self.world.lights.get_mut(index) else {
+            return false;
+        };
+        light.intensity = Colour::new(r, g, b);
+        self.restart_progressive();
+        true
+    }
+
+    // from and to are points and up a direction, each as [x, y, z]. The field
+    // of view is in degrees; other camera settings are kept.
+    pub fn set_camera(
+        &mut self,
+        from: &[Float],
+        to: &[Float],
+        up: &[Float],
+        fov_degrees: Float,
+    ) -> Result<(), String> {
+        let xyz = |v: &[Float], name: &str| match v {
+            [x, y, z] => Ok((*x, *y, *z)),
+            _ => Err(format!("Camera {} needs 3 numbers, got {}", name, v.len())),
+        };
+        let (from, to, up) = (xyz(from, "from")?, xyz(to, "to")?, xyz(up, "up")?);
+        let (from, to) = (
+            Tuple::point(from.0, from.1, from.2),
+            Tuple::point(to.0, to.1, to.2),
+        );
+        self.camera
+            .set_transform(view_transform(from, to, Tuple::vector(up.0, up.1, up.2)));
+        self.camera.set_field_of_view(fov_degrees.to_radians());
+        self.view = OrbitView::looking(from, to);
+        self.home_view = self.view;
+        self.camera_moved = true;
+        self.restart_progressive();
+        Ok(())
+    }
+
+    // Mouse navigation. Each call moves the camera from where the last one
+    // left it, keeping `to` from set_camera (or the initial view) at the centre
+    // of the image unless panned. Any roll from set_camera's up vector is
+    // dropped on the first move.
+
+    // Slides the camera across the view by a drag of dx, dy pixels, so the
+    // orbit target follows the cursor
+    pub fn pan(&mut self, dx: Float, dy: Float) {
+        let scale = self.camera.pixel_size * self.view.distance;
+        let right = &self.camera.inverse_transform * Tuple::vector(-1.0, 0.0, 0.0);
+        let up = &self.camera.inverse_transform * Tuple::vector(0.0, 1.0, 0.0);
+        self.view.target = self.view.target - right * (dx * scale) + up * (dy * scale);
+        self.update_view();
+    }
+
+    // Moves towards the target for factors above 1 and away below it
+    pub fn zoom(&mut self, factor: Float) {
+        if factor > 0.0 {
+            self.view.distance /= factor;
+            self.update_view();
+        }
+    }
+
+    // Swings the camera around the target, in radians. Positive dpitch moves
+    // it up; there's no limit, so it can carry on over the top.
+    pub fn orbit(&mut self, dyaw: Float, dpitch: Float) {
+        self.view.yaw += dyaw;
+        self.view.pitch += dpitch;
+        self.update_view();
+    }
+
+    // Back to the view from the last set_camera, or the initial one
+    pub fn reset_view(&mut self) {
+        self.view = self.home_view;
+        self.update_view();
+    }
+
+    // The shape seen through the centre of a pixel, for click-to-select.
+    // Shapes hidden from the camera are passed through, as in renders.
+    pub fn pick(&self, x: u32, y: u32) -> Option<PickResult> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let camera = self.camera.at_time(self.world.time);
+        let ray = camera.ray_for_pixel_offset(x as usize, y as usize, 0.5, 0.5);
+        let xs: Vec<_> = self
+            .world
+            .intersect_world(&ray)
+            .into_iter()
+            .filter(|x| {
+                self.world
+                    .registry
+                    .get(x.object_id)
+                    .is_some_and(|shape| shape.visibility().camera_visible)
+            })
+            .collect();
+        let hit = hit(&xs)?;
+        let point = ray.position(hit.t);
+        Some(PickResult {
+            object_id: hit.object_id,
+            root_id: self.world.registry.root_id(hit.object_id),
+            t: hit.t,
+            x: point.x,
+            y: point.y,
+            z: point.z,
+        })
+    }
+
+    // Call before each scene edit so it can be undone. Snapshots share shapes
+    // with the live scene, so this is cheap even for large meshes.
+    pub fn checkpoint(&mut self) {
+        self.undo_stack.push(self.world.registry.snapshot());
+        self.redo_stack.clear();
+    }
+
+    // Returns false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(snapshot) => {
+                self.redo_stack.push(self.world.registry.snapshot());
+                self.restore_registry(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns false when there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(snapshot) => {
+                self.undo_stack.push(self.world.registry.snapshot());
+                self.restore_registry(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_view(&mut self) {
+        let OrbitView {
+            target,
+            yaw,
+            pitch,
+            distance,
+        } = self.view;
+        self.camera.orbit(target, yaw, pitch, distance);
+        self.camera_moved = true;
+        self.restart_progressive();
+    }
+
+    // Brings the BVH and draft shadow map up to date with registry edits
+    fn scene_edited(&mut self) {
+        let events = self.world.registry.take_events();
+        if events.iter().any(RegistryEvent::changes_bounds) {
+            self.world.build_bvh();
+        }
+        if !events.is_empty() {
+            self.update_shadow_map();
+            self.restart_progressive();
+        }
+    }
+
+    fn restore_registry(&mut self, snapshot: RegistrySnapshot) {
+        self.world.registry.restore(snapshot);
+        self.world.build_bvh();
+        self.update_shadow_map();
+        self.restart_progressive();
+    }
+
+    // The map is traced against the scene as it stands, so it's rebuilt
+    // whenever shapes change
+    fn update_shadow_map(&mut self) {
+        if self.draft_shadows {
+            self.world.build_shadow_map(DRAFT_SHADOW_MAP_SIZE);
+        } else {
+            self.world.clear_shadow_map();
+        }
     }
 
     pub fn get_image_buffer_pointer(&self) -> *const u8 {
@@ -66,10 +701,8 @@ impl RenderContext {
         for (i, &colour) in self.colours.iter().enumerate() {
             let buffer_index = i * 4;
 
-            self.buffer[buffer_index] = (colour.r.clamp(0.0, 1.0) * 255.0) as u8; // R
-            self.buffer[buffer_index + 1] = (colour.g.clamp(0.0, 1.0) * 255.0) as u8; // G
-            self.buffer[buffer_index + 2] = (colour.b.clamp(0.0, 1.0) * 255.0) as u8; // B
-            self.buffer[buffer_index + 3] = 255; // Alpha
+            self.buffer[buffer_index..buffer_index + 4]
+                .copy_from_slice(&self.tone_mapping.to_rgba8(colour));
         }
     }
 
@@ -97,19 +730,13 @@ impl RenderContext {
                 let ray = self
                     .camera
                     .ray_for_pixel(global_x as usize, global_y as usize);
-                let colour = self.world.colour_at(&ray, crate::world::MAX_BOUNCES);
+                let colour = self.world.colour_at(&ray, self.world.settings.max_bounces);
 
                 let tile_pixel_index = (local_y * tile_width + local_x) as usize;
                 let buffer_index = tile_pixel_index * 4;
 
-                let r = (colour.r.clamp(0.0, 1.0) * 255.0) as u8;
-                let g = (colour.g.clamp(0.0, 1.0) * 255.0) as u8;
-                let b = (colour.b.clamp(0.0, 1.0) * 255.0) as u8;
-
-                tile_buffer[buffer_index] = r;
-                tile_buffer[buffer_index + 1] = g;
-                tile_buffer[buffer_index + 2] = b;
-                tile_buffer[buffer_index + 3] = 255;
+                tile_buffer[buffer_index..buffer_index + 4]
+                    .copy_from_slice(&self.tone_mapping.to_rgba8(colour));
             }
         }
 
@@ -143,23 +770,130 @@ impl RenderContext {
     pub fn get_tile_buffer_size(&self) -> usize {
         self.tile_buffer.len()
     }
+
+    // Renders a debug view of the scene (depth, normal, object_id, shadow_mask
+    // or bounces; see aov::Aov) into a buffer of its own, leaving the image
+    // alone. Its colours are written as they are, without tone mapping.
+    pub fn render_aov(&mut self, name: &str) -> Result<(), String> {
+        let aov = Aov::from_name(name).ok_or_else(|| format!("Unknown AOV '{}'", name))?;
+        let camera = self.camera.at_time(self.world.time);
+        let canvas = aov.render(&self.world, &camera);
+        self.aov_buffer.clear();
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                self.aov_buffer
+                    .extend_from_slice(&canvas.pixel_at(x, y).to_rgba8());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_aov_buffer_pointer(&self) -> *const u8 {
+        self.aov_buffer.as_ptr()
+    }
+
+    pub fn get_aov_buffer_size(&self) -> usize {
+        self.aov_buffer.len()
+    }
 }
 
 impl RenderContext {
+    fn total_passes(&self) -> usize {
+        PREVIEW_BLOCK_SIZES.len() + self.sample_offsets(0, 0).len()
+    }
+
+    // The preview integrator traces just the pixel centre
+    fn sample_offsets(&self, x: usize, y: usize) -> Vec<(Float, Float)> {
+        match self.world.settings.integrator {
+            Integrator::Preview => vec![(0.5, 0.5)],
+            Integrator::Full | Integrator::Path => {
+                self.camera
+                    .sample_offsets(x, y, self.world.settings.samples)
+            }
+        }
+    }
+
+    fn trace_sample(
+        &mut self,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+        sample: usize,
+        (dx, dy): (Float, Float),
+        stats: &mut FrameStats,
+    ) -> Colour {
+        let ray = camera
+            .ray_for_pixel_offset(x, y, dx, dy)
+            .with_seed(sample as u64);
+
+        let start = Instant::now();
+        self.world
+            .intersect_world_into(&ray, &mut self.intersections);
+        let traced = Instant::now();
+        let colour = self.world.colour_from_intersections(
+            &ray,
+            &self.intersections,
+            self.world.settings.max_bounces,
+        );
+        let shaded = Instant::now();
+
+        stats.trace_ms += (traced - start).as_secs_f64() * 1000.0;
+        stats.shade_ms += (shaded - traced).as_secs_f64() * 1000.0;
+        colour
+    }
+
+    // Traces the top-left pixel of each block and fills the block with it
+    fn render_blocks(&mut self, camera: &Camera, block: usize, stats: &mut FrameStats) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        for by in (0..height).step_by(block) {
+            for bx in (0..width).step_by(block) {
+                let colour = self.trace_sample(camera, bx, by, 0, (0.5, 0.5), stats);
+                for y in by..(by + block).min(height) {
+                    for x in bx..(bx + block).min(width) {
+                        self.colours[y * width + x] = colour;
+                    }
+                }
+            }
+        }
+    }
+
+    // Preview passes render in blocks, coarsest first. Later passes each add
+    // one more sample to every pixel, so the image converges on what a
+    // non-progressive render would produce.
+    fn render_pass(&mut self, camera: &Camera, stats: &mut FrameStats) {
+        if self.pass >= self.total_passes() {
+            return;
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        if let Some(&block) = PREVIEW_BLOCK_SIZES.get(self.pass) {
+            self.render_blocks(camera, block, stats);
+        } else {
+            let sample = self.pass - PREVIEW_BLOCK_SIZES.len();
+            if sample == 0 {
+                self.samples.clear();
+            }
+            for y in 0..height {
+                for x in 0..width {
+                    let offset = self.sample_offsets(x, y)[sample];
+                    let colour = self.trace_sample(camera, x, y, sample, offset, stats);
+                    self.samples.add_sample(x, y, colour);
+                    self.colours[y * width + x] = self.samples.colour_at(x, y);
+                }
+            }
+        }
+
+        self.pass += 1;
+    }
+
     pub fn write_pixel(&mut self, x: u32, y: u32, colour: Colour) {
         if x < self.width && y < self.height {
             let pixel_index = (y * self.width + x) as usize;
             self.colours[pixel_index] = colour;
 
             let buffer_index = pixel_index * 4;
-            let r = (colour.r.clamp(0.0, 1.0) * 255.0) as u8;
-            let g = (colour.g.clamp(0.0, 1.0) * 255.0) as u8;
-            let b = (colour.b.clamp(0.0, 1.0) * 255.0) as u8;
-
-            self.buffer[buffer_index] = r;
-            self.buffer[buffer_index + 1] = g;
-            self.buffer[buffer_index + 2] = b;
-            self.buffer[buffer_index + 3] = 255;
+            self.buffer[buffer_index..buffer_index + 4]
+                .copy_from_slice(&self.tone_mapping.to_rgba8(colour));
         }
     }
 
@@ -176,6 +910,8 @@ impl RenderContext {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
 
     #[test]
     fn test_scene_new() {
@@ -212,4 +948,421 @@ mod tests {
 
         assert_eq!(scene.get_pixel_colour(2, 3), red);
     }
+
+    #[test]
+    fn reload_scene_swaps_world_and_clears_pixels() {
+        let mut scene = RenderContext::new(4, 4);
+        scene.write_pixel(1, 1, Colour::new(1.0, 0.0, 0.0));
+
+        scene.reload_scene("default").unwrap();
+
+        assert_eq!(scene.world.registry.len(), 2);
+        assert_eq!(scene.get_pixel_colour(1, 1), Colour::new(0.0, 0.0, 0.0));
+        assert_eq!(scene.buffer.len(), 4 * 4 * 4);
+        assert_eq!(scene.buffer[(4 + 1) * 4], 0);
+    }
+
+    #[test]
+    fn reload_scene_keeps_render_settings() {
+        let mut scene = RenderContext::new(4, 4);
+        scene.set_epsilon(0.01);
+        scene.set_roulette_depth(Some(3));
+        scene.set_max_bounces(2);
+        let settings = scene.world.settings;
+
+        scene.reload_scene("default").unwrap();
+
+        assert_eq!(scene.world.settings, settings);
+    }
+
+    #[test]
+    fn reload_scene_follows_the_new_unit_scale() {
+        let mut scene = RenderContext::new(4, 4);
+
+        scene.reload_scene(r#"{ "unit_scale": 0.001 }"#).unwrap();
+
+        assert_abs_diff_eq!(
+            scene.world.secondary_t_min(),
+            crate::world::DEFAULT_SECONDARY_T_MIN * 1000.0
+        );
+    }
+
+    #[test]
+    fn reload_scene_accepts_json() {
+        let mut scene = RenderContext::new(4, 4);
+
+        scene
+            .reload_scene(r#"{ "objects": [{ "type": "plane" }] }"#)
+            .unwrap();
+
+        assert_eq!(scene.world.registry.len(), 1);
+    }
+
+    #[test]
+    fn reload_scene_keeps_world_on_error() {
+        let mut scene = RenderContext::new(4, 4);
+        let objects = scene.world.registry.len();
+
+        assert!(scene.reload_scene("nonexistent").is_err());
+        assert_eq!(scene.world.registry.len(), objects);
+    }
+
+    #[test]
+    fn draft_shadows_survive_scene_reloads() {
+        let mut scene = RenderContext::new(4, 4);
+        scene.set_draft_shadows(true);
+        assert!(scene.world.has_shadow_map());
+
+        scene.reload_scene("default").unwrap();
+        assert!(scene.world.has_shadow_map());
+
+        scene.set_draft_shadows(false);
+        assert!(!scene.world.has_shadow_map());
+    }
+
+    #[test]
+    fn preview_traces_at_most_two_rays_per_pixel() {
+        let mut scene = RenderContext::new(16, 12);
+        scene.set_samples(4, "grid").unwrap();
+        scene.set_preview(true);
+        scene.reload_scene("third").unwrap();
+
+        // One camera ray, plus a shadow ray if it hits something
+        scene.render(0.0);
+        assert!(scene.last_frame_stats.rays_traced <= 2 * 16 * 12);
+
+        scene.set_preview(false);
+        scene.render(0.0);
+        assert!(scene.last_frame_stats.rays_traced > 4 * 16 * 12);
+    }
+
+    #[test]
+    fn render_records_frame_stats() {
+        let mut scene = RenderContext::new(4, 3);
+        scene.reload_scene("default").unwrap();
+
+        scene.render(0.0);
+
+        let stats = scene.last_frame_stats;
+        // One camera ray per pixel, plus shadow rays for those that hit
+        assert!(stats.rays_traced >= 12);
+        assert!(stats.trace_ms >= 0.0 && stats.shade_ms >= 0.0 && stats.buffer_ms >= 0.0);
+
+        let json: serde_json::Value = serde_json::from_str(&scene.last_frame_stats()).unwrap();
+        assert_eq!(json["rays_traced"], stats.rays_traced);
+    }
+
+    #[test]
+    fn changing_tone_mapping_updates_buffer() {
+        let mut scene = RenderContext::new(2, 2);
+        scene.write_pixel(0, 0, Colour::new(3.0, 1.0, 0.0));
+        assert_eq!(&scene.buffer[0..4], &[255, 255, 0, 255]);
+
+        scene.set_tone_mapping("reinhard", 1.0, 1.0).unwrap();
+
+        assert_eq!(&scene.buffer[0..4], &[191, 127, 0, 255]);
+        assert!(scene.set_tone_mapping("sepia", 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn srgb_output_survives_tone_mapping_changes() {
+        let mut scene = RenderContext::new(1, 1);
+        scene.write_pixel(0, 0, Colour::new(0.18, 1.0, 0.0));
+
+        scene.set_srgb_output(true);
+        assert_eq!(&scene.buffer[0..4], &[117, 255, 0, 255]);
+
+        scene.set_tone_mapping("clamp", 1.0, 2.0).unwrap();
+        assert_eq!(&scene.buffer[0..4], &[117, 255, 0, 255]);
+    }
+
+    #[test]
+    fn lut_survives_tone_mapping_changes() {
+        let mut scene = RenderContext::new(1, 1);
+        scene.write_pixel(0, 0, Colour::new(1.0, 0.0, 0.0));
+        let swap_red_blue =
+            "LUT_3D_SIZE 2\n0 0 0\n0 0 1\n0 1 0\n0 1 1\n1 0 0\n1 0 1\n1 1 0\n1 1 1\n";
+
+        scene.set_lut(swap_red_blue, false).unwrap();
+        assert_eq!(&scene.buffer[0..4], &[0, 0, 255, 255]);
+
+        scene.set_tone_mapping("reinhard", 1.0, 1.0).unwrap();
+        assert_eq!(&scene.buffer[0..4], &[0, 0, 127, 255]);
+
+        scene.clear_lut();
+        assert_eq!(&scene.buffer[0..4], &[127, 0, 0, 255]);
+        assert!(scene.set_lut("LUT_3D_SIZE 2", false).is_err());
+    }
+
+    #[test]
+    fn camera_shake_moves_the_view_over_time() {
+        let mut still = RenderContext::new(6, 6);
+        still.reload_scene("default").unwrap();
+        let mut shaken = RenderContext::new(6, 6);
+        shaken.reload_scene("default").unwrap();
+        shaken.set_camera_shake(0.3, 2.0, 3.0, 1);
+
+        still.render(0.37);
+        shaken.render(0.37);
+
+        assert!(still
+            .colours
+            .iter()
+            .zip(&shaken.colours)
+            .any(|(a, b)| (a.r - b.r).abs() > 1e-6));
+        // The stored camera itself is left alone
+        assert!(shaken.camera.transform == still.camera.transform);
+    }
+
+    #[test]
+    fn render_advances_world_time() {
+        let mut scene = RenderContext::new(2, 2);
+
+        scene.render(0.5);
+        scene.render(0.25);
+
+        assert_eq!(scene.world.time, 0.75);
+    }
+
+    #[test]
+    fn scenes_can_be_built_up_and_edited() {
+        let mut scene = RenderContext::new(2, 2);
+        scene.reload_scene("{}").unwrap();
+
+        let floor = scene.add_plane(-1.0);
+        let ball = scene.add_sphere(0.0, 1.0, 0.0, 0.5).unwrap();
+        assert_eq!(scene.object_ids(), vec![floor, ball]);
+        assert!(scene.world.has_bvh());
+
+        assert!(scene.move_object(ball, 2.0, 1.0, 0.0));
+        assert!(scene.world.has_bvh());
+        let moved = scene.world.registry.get(ball).unwrap();
+        let centre = moved.transform() * Tuple::point(0.0, 0.0, 0.0);
+        assert_abs_diff_eq!(centre, Tuple::point(2.0, 1.0, 0.0));
+        assert_abs_diff_eq!(moved.world_bounds().max.y, 1.5);
+
+        for radius in [0.0, -1.0, Float::NAN, Float::INFINITY] {
+            assert!(scene.add_sphere(0.0, 0.0, 0.0, radius).is_err());
+        }
+        assert_eq!(scene.object_ids(), vec![floor, ball]);
+
+        assert!(scene.set_object_colour(ball, 1.0, 0.0, 0.0));
+        let colour = scene.world.registry.get(ball).unwrap().material().colour;
+        assert_eq!(colour, Colour::new(1.0, 0.0, 0.0));
+
+        assert!(scene.remove_object(floor));
+        assert!(!scene.remove_object(floor));
+        assert!(!scene.set_object_colour(floor, 1.0, 1.0, 1.0));
+        assert_eq!(scene.object_ids(), vec![ball]);
+    }
+
+    #[test]
+    fn light_and_camera_can_be_moved() {
+        let mut scene = RenderContext::new(4, 2);
+        scene.set_samples(4, "jittered").unwrap();
+        scene.reload_scene("{}").unwrap();
+
+        assert!(!scene.move_light(0, 1.0, 2.0, 3.0));
+        assert_eq!(scene.add_light(0.0, 1.0, 0.0, 1.0, 1.0, 1.0), 0);
+        assert_eq!(scene.add_light(0.0, 5.0, 0.0, 1.0, 1.0, 1.0), 1);
+        assert!(scene.move_light(1, 1.0, 2.0, 3.0));
+        assert!(scene.set_light_intensity(1, 0.5, 0.5, 0.5));
+        assert!(!scene.set_light_intensity(2, 0.5, 0.5, 0.5));
+        assert_eq!(scene.light_count(), 2);
+        let light = &scene.world.lights[1];
+        assert_abs_diff_eq!(light.position, Tuple::point(1.0, 2.0, 3.0));
+        assert_eq!(light.intensity, Colour::new(0.5, 0.5, 0.5));
+        assert_eq!(scene.world.lights[0].position, Tuple::point(0.0, 1.0, 0.0));
+
+        let (from, to, up) = ([0.0, 0.0, -5.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        scene.set_camera(&from, &to, &up, 90.0).unwrap();
+        assert_abs_diff_eq!(scene.camera.half_width, 1.0, epsilon = 1e-12);
+        assert_eq!(scene.world.settings.samples, 4);
+        let ray = scene.camera.ray_for_pixel_offset(2, 1, 0.0, 0.0);
+        assert_abs_diff_eq!(ray.origin, Tuple::point(0.0, 0.0, -5.0));
+
+        assert!(scene.set_camera(&from[..2], &to, &up, 90.0).is_err());
+    }
+
+    #[test]
+    fn orbit_and_zoom_keep_the_target_centred() {
+        let mut scene = RenderContext::new(11, 11);
+        let (from, to, up) = ([0.0, 0.0, -5.0], [0.0, 1.0, 0.0], [0.0, 1.0, 0.0]);
+        scene.set_camera(&from, &to, &up, 60.0).unwrap();
+        let centre_ray = |scene: &RenderContext| scene.camera.ray_for_pixel(5, 5);
+
+        // Nothing moved yet, so the orbit view matches set_camera
+        scene.orbit(0.0, 0.0);
+        let ray = centre_ray(&scene);
+        assert_abs_diff_eq!(
+            ray.origin,
+            Tuple::point(0.0, 0.0, -5.0),
+            epsilon = TEST_EPSILON
+        );
+
+        scene.orbit(crate::scalar::consts::PI / 2.0, 0.3);
+        scene.zoom(2.0);
+        let ray = centre_ray(&scene);
+        let distance = (Float::powi(5.0, 2) + 1.0).sqrt() / 2.0;
+        assert_abs_diff_eq!(
+            (ray.origin - Tuple::point(0.0, 1.0, 0.0)).magnitude(),
+            distance,
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            ray.position(distance),
+            Tuple::point(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+
+        scene.reset_view();
+        assert_abs_diff_eq!(
+            centre_ray(&scene).origin,
+            Tuple::point(0.0, 0.0, -5.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn moving_the_camera_renders_at_preview_scale_until_it_stops() {
+        let mut scene = RenderContext::new(16, 16);
+        scene.set_preview_scale(4);
+        let blocky = |scene: &RenderContext| {
+            (0..16).all(|y| {
+                (0..16).all(|x| {
+                    scene.get_pixel_colour(x, y) == scene.get_pixel_colour(x / 4 * 4, y / 4 * 4)
+                })
+            })
+        };
+
+        scene.render(0.0);
+        assert!(!blocky(&scene));
+
+        scene.orbit(0.2, 0.0);
+        scene.render(0.0);
+        assert!(blocky(&scene));
+
+        scene.render(0.0);
+        assert!(!blocky(&scene));
+    }
+
+    #[test]
+    fn panning_moves_the_target_with_the_cursor() {
+        let mut scene = RenderContext::new(11, 11);
+        let (from, to, up) = ([0.0, 0.0, -5.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        scene.set_camera(&from, &to, &up, 60.0).unwrap();
+
+        // Dragging right by two pixels, and up by one, brings the point that
+        // was at the centre under the cursor
+        scene.pan(2.0, -1.0);
+        let ray = scene.camera.ray_for_pixel(7, 4);
+        assert_abs_diff_eq!(
+            ray.position(ray.origin.z.abs() / ray.direction.z),
+            Tuple::point(0.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(ray.origin.z, -5.0, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn picking_finds_the_shape_under_a_pixel() {
+        let mut scene = RenderContext::new(11, 11);
+        scene.reload_scene("{}").unwrap();
+        let (from, to, up) = ([0.0, 0.0, -5.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        scene.set_camera(&from, &to, &up, 60.0).unwrap();
+        let ball = scene.add_sphere(0.0, 0.0, 0.0, 1.0).unwrap();
+
+        let picked = scene.pick(5, 5).unwrap();
+        assert_eq!(picked.object_id, ball);
+        assert_eq!(picked.root_id, ball);
+        assert_abs_diff_eq!(picked.t, 4.0, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(picked.z, -1.0, epsilon = TEST_EPSILON);
+
+        assert!(scene.pick(0, 0).is_none());
+        assert!(scene.pick(11, 5).is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_scene_edits() {
+        let mut scene = RenderContext::new(2, 2);
+        scene.reload_scene("default").unwrap();
+        assert!(!scene.undo());
+
+        scene.checkpoint();
+        scene.world.add_object(crate::shape::plane::Plane::new());
+        assert_eq!(scene.world.registry.len(), 3);
+
+        assert!(scene.undo());
+        assert_eq!(scene.world.registry.len(), 2);
+        assert!(scene.world.has_bvh());
+
+        assert!(scene.redo());
+        assert_eq!(scene.world.registry.len(), 3);
+        assert!(!scene.redo());
+    }
+
+    #[test]
+    fn progressive_render_refines_to_the_full_frame() {
+        let mut full = RenderContext::new(10, 10);
+        full.set_samples(4, "grid").unwrap();
+        full.render(0.0);
+
+        let mut scene = RenderContext::new(10, 10);
+        scene.set_samples(4, "grid").unwrap();
+        scene.set_progressive(true);
+        assert_eq!(scene.get_progress(), 0.0);
+
+        // The first pass traces one pixel per 8x8 block
+        scene.render(0.0);
+        assert!(scene.last_frame_stats.rays_traced > 0);
+        assert_eq!(scene.get_pixel_colour(7, 7), scene.get_pixel_colour(0, 0));
+
+        let mut passes = 1;
+        while scene.get_progress() < 1.0 {
+            scene.render(0.0);
+            passes += 1;
+        }
+
+        assert_eq!(passes, 3 + 4);
+        for (a, b) in scene.colours.iter().zip(&full.colours) {
+            assert_abs_diff_eq!(*a, *b, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn changing_samples_restarts_progressive_render() {
+        let mut scene = RenderContext::new(4, 4);
+        scene.set_progressive(true);
+        scene.render(0.0);
+        assert!(scene.get_progress() > 0.0);
+
+        scene.set_samples(2, "jittered").unwrap();
+
+        assert_eq!(scene.get_progress(), 0.0);
+    }
+
+    #[test]
+    fn checkpoint_discards_redo_history() {
+        let mut scene = RenderContext::new(2, 2);
+        scene.checkpoint();
+        scene.undo();
+
+        scene.checkpoint();
+
+        assert!(!scene.redo());
+    }
+
+    #[test]
+    fn aovs_render_into_their_own_buffer() {
+        let mut scene = RenderContext::new(8, 6);
+        scene.render(0.0);
+        let image = scene.buffer.clone();
+
+        scene.render_aov("normal").unwrap();
+
+        assert_eq!(scene.get_aov_buffer_size(), 8 * 6 * 4);
+        assert_ne!(scene.aov_buffer, image);
+        assert_eq!(scene.buffer, image);
+        assert!(scene.render_aov("beauty").is_err());
+    }
 }
@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+// Counts of the work done during a render, for judging optimisations such as
+// the BVH. Only collected when built with the "stats" feature; without it the
+// counters compile away to nothing. See World::render_stats.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenderStats {
+    // Every ray intersected with the world: camera, shadow and reflection rays
+    pub rays_cast: u64,
+    // Ray tests against top-level shapes. With a BVH only shapes whose bounds
+    // the ray reaches are tested.
+    pub intersection_tests: u64,
+    pub shadow_rays: u64,
+    // reflection_depths[i] counts reflection rays cast at bounce i + 1, with
+    // any beyond the default bounce limit counted in the last entry
+    pub reflection_depths: Vec<u64>,
+}
+
+// The live counters behind RenderStats. Atomic so that recording only needs
+// &World.
+#[derive(Default)]
+pub(crate) struct StatsCounters {
+    #[cfg(feature = "stats")]
+    counters: counting::Counters,
+}
+
+#[cfg(feature = "stats")]
+mod counting {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::world::DEFAULT_MAX_BOUNCES;
+
+    #[derive(Default)]
+    pub(super) struct Counters {
+        rays_cast: AtomicU64,
+        intersection_tests: AtomicU64,
+        shadow_rays: AtomicU64,
+        reflection_depths: [AtomicU64; DEFAULT_MAX_BOUNCES as usize],
+    }
+
+    impl StatsCounters {
+        pub(crate) fn ray_cast(&self) {
+            self.counters.rays_cast.fetch_add(1, Ordering::Relaxed);
+        }
+
+        pub(crate) fn intersection_tests(&self, count: usize) {
+            self.counters
+                .intersection_tests
+                .fetch_add(count as u64, Ordering::Relaxed);
+        }
+
+        pub(crate) fn shadow_ray(&self) {
+            self.counters.shadow_rays.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // depth is 0 for a reflection of a camera ray's hit
+        pub(crate) fn reflection_ray(&self, depth: u32) {
+            let depth = depth.min(DEFAULT_MAX_BOUNCES - 1);
+            self.counters.reflection_depths[depth as usize].fetch_add(1, Ordering::Relaxed);
+        }
+
+        pub(crate) fn reset(&self) {
+            let c = &self.counters;
+            for counter in [&c.rays_cast, &c.intersection_tests, &c.shadow_rays]
+                .into_iter()
+                .chain(&c.reflection_depths)
+            {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+
+        pub(crate) fn snapshot(&self) -> RenderStats {
+            let c = &self.counters;
+            RenderStats {
+                rays_cast: c.rays_cast.load(Ordering::Relaxed),
+                intersection_tests: c.intersection_tests.load(Ordering::Relaxed),
+                shadow_rays: c.shadow_rays.load(Ordering::Relaxed),
+                reflection_depths: c
+                    .reflection_depths
+                    .iter()
+                    .map(|count| count.load(Ordering::Relaxed))
+                    .collect(),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl StatsCounters {
+    pub(crate) fn ray_cast(&self) {}
+
+    pub(crate) fn intersection_tests(&self, _count: usize) {}
+
+    pub(crate) fn shadow_ray(&self) {}
+
+    pub(crate) fn reflection_ray(&self, _depth: u32) {}
+
+    pub(crate) fn reset(&self) {}
+}
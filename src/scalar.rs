@@ -0,0 +1,27 @@
+// The floating point type used for geometry and colour. Building with the f32
+// feature halves the size of every tuple, colour and matrix, which mostly
+// pays off in wasm where memory bandwidth is the bottleneck. Timings and
+// file formats stay in f64 either way.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+// Widens to f64 for file formats and external APIs, whichever precision is in
+// use
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f64(x: Float) -> f64 {
+    x as f64
+}
+
+// Tolerance for tests comparing results reached by different routes, such as a
+// quaternion and a matrix rotating the same point
+#[cfg(all(test, not(feature = "f32")))]
+pub const TEST_EPSILON: Float = 1e-10;
+#[cfg(all(test, feature = "f32"))]
+pub const TEST_EPSILON: Float = 1e-5;
@@ -0,0 +1,2481 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::{
+    animation::{Animatable, Animation, CameraAnimation, Interpolation, ObjectAnimation, Track},
+    background::{Background, Skybox},
+    colour::Colour,
+    factory,
+    light::{Falloff, Flicker, Light, DEFAULT_BULB_RADIUS},
+    materials::Material,
+    matrix::Matrix,
+    normal_map::NormalMap,
+    pattern::{
+        blended::Blended,
+        checkered::Checkered,
+        gradient::Gradient,
+        noise_pattern::NoisePattern,
+        radial_gradient::RadialGradient,
+        ring::Ring,
+        striped::Striped,
+        uv_pattern::{TextureMap, UvImage, UvMapping, UvPattern},
+        PatternSlot, PatternType,
+    },
+    scalar::Float,
+    shape::{
+        cone::Cone,
+        csg::{Csg, CsgOperation},
+        cylinder::Cylinder,
+        disc::Disc,
+        group::Group,
+        instance::Instance,
+        lod::Lod,
+        plane::Plane,
+        quad::Quad,
+        smooth_triangle::SmoothTriangle,
+        sphere::Sphere,
+        triangle::Triangle,
+        LightLinks, Shape, Visibility,
+    },
+    sweep::SweepParameter,
+    tuple::Tuple,
+    world::World,
+};
+
+// JSON scene format. Every section is optional, so `{}` is an empty world.
+//
+// {
+//   "unit_scale": 0.01,
+//   "lights": [
+//     { "position": [-10, 10, -10], "intensity": [1, 1, 1],
+//       "flicker": { "amplitude": 0.2, "frequency": 6, "seed": 1 },
+//       "falloff": { "type": "inverse_square", "radius": 0.5 } },
+//     { "position": [10, 10, -10], "intensity": [0.2, 0.2, 0.3] }
+//   ],
+//   "background": { "type": "gradient", "horizon": [1, 1, 1], "zenith": [0.3, 0.5, 0.9] },
+//   "objects": [
+//     {
+//       "type": "sphere",
+//       "transform": [{ "scale": [0.5, 0.5, 0.5] }, { "translate": [0, 1, 0] }],
+//       "material": { "colour": [1, 0.2, 0.2], "reflective": 0.3 }
+//     }
+//   ]
+// }
+//
+// A scene with one light can give it as "light" instead of a list.
+//
+// Transforms are applied in the order they are listed, about the object's
+// "pivot" point if it has one. Besides translate, scale, rotate_x/y/z and
+// shear, a transform can be a whole "matrix" given as four rows; saved worlds
+// use that form. unit_scale is the length of one scene unit in metres and
+// defaults to 1; distances left out, like a falloff's radius, default to
+// sizes in metres converted to scene units.
+//
+// Objects can be given a "name" to find them by, which unlike their ids
+// doesn't depend on the order they're listed in; see World::get_by_name.
+// A top-level object can name another as its "parent", making its transform
+// relative to the parent's so that it follows the parent when that's
+// animated; see World::scene_graph.
+//
+// Objects can set "cast_shadows", "receive_shadows" and "camera_visible" to
+// false, e.g. to keep a backdrop out of shadow tests or hide a light blocker
+// from the camera; see shape::Visibility. A light can be put in a "group",
+// and objects can then pick the groups that light them with
+// "light_links": { "only": ["key"] } or { "exclude": ["key"] }. A "velocity"
+// in units per second blurs an object along its path when the camera has a
+// shutter time.
+//
+// Materials used by several objects can be named in "materials" and then
+// given by name, as in "material": "glass". The presets in
+// Material::from_name can be given by name too. A material can start from a
+// named one with "extends" and change some of its fields:
+//
+//   "materials": {
+//     "glass": { "transparency": 0.9, "refractive_index": 1.5, "reflective": 0.9 },
+//     "green_glass": { "extends": "glass", "colour": [0.1, 0.4, 0.1] }
+//   },
+//   "objects": [{ "type": "sphere", "material": { "extends": "green_glass", "shininess": 300 } }]
+//
+// Groups list their members under "children". A group's transform, material
+// and visibility flags apply to everything inside it, and likewise for a "lod" and the
+// "detail" object and simpler "levels" it switches between.
+//
+// Named top-level objects and the camera can be keyed over time, in seconds,
+// under "animation". Each key sets any of translate, rotate (radians about x,
+// y, z) and scale, which together replace the object's transform, the colour,
+// and numeric material fields by name; values are blended between the keys
+// that give them, in straight lines or along a "cubic" curve:
+//
+//   "animation": {
+//     "objects": {
+//       "ball": { "interpolation": "cubic", "keys": [
+//         { "time": 0, "translate": [0, 1, 0], "reflective": 0 },
+//         { "time": 2, "translate": [0, 3, 0], "reflective": 0.8 }
+//       ] }
+//     },
+//     "camera": { "keys": [{ "time": 0, "from": [0, 1.5, -5], "to": [0, 1, 0], "field_of_view": 1.0 }] }
+//   }
+//
+// The world is loaded as it is at time 0; see World::at_time for the rest.
+//
+// Shape and pattern types from other crates can be used by name once they are
+// registered; see the factory module.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit_scale: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    // Shorthand for a scene with one light, loaded as the first of "lights".
+    // Saved scenes list every light under "lights".
+    pub light: Option<LightDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundDescription>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightDescription {
+    pub position: [Float; 3],
+    pub intensity: [Float; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flicker: Option<FlickerDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub falloff: Option<FalloffDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+// As light::Falloff, with the radius in scene units. Without one it's
+// DEFAULT_BULB_RADIUS metres, in the scene's units.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FalloffDescription {
+    None,
+    Linear {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        radius: Option<Float>,
+    },
+    InverseSquare {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        radius: Option<Float>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlickerDescription {
+    pub amplitude: Float,
+    pub frequency: Float,
+    #[serde(default)]
+    pub seed: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundDescription {
+    Solid {
+        colour: [Float; 3],
+    },
+    Gradient {
+        horizon: [Float; 3],
+        zenith: [Float; 3],
+    },
+    // Image file paths for each face of the cube map
+    Skybox {
+        right: String,
+        left: String,
+        up: String,
+        down: String,
+        front: String,
+        back: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnimationDescription {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub objects: BTreeMap<String, KeysDescription<ObjectKeyDescription>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<KeysDescription<CameraKeyDescription>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysDescription<K> {
+    // "linear" or "cubic", defaulting to linear
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpolation: Option<String>,
+    pub keys: Vec<K>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObjectKeyDescription {
+    pub time: Float,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<[Float; 3]>,
+    // Radians about x, then y, then z
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour: Option<[Float; 3]>,
+    // Numeric material fields by their sweep names, e.g. "reflective"
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, Float>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraKeyDescription {
+    pub time: Float,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field_of_view: Option<Float>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Name of the top-level object this one's transform is relative to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(flatten, with = "shape_or_custom")]
+    pub shape: ShapeDescription,
+    // Point the transform rotates and scales about, instead of the origin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pivot: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transform: Vec<TransformDescription>,
+    // Either a material or the name of one in the scene's materials
+    #[serde(
+        default,
+        deserialize_with = "material_or_name",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub material: Option<MaterialDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cast_shadows: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_shadows: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_visible: Option<bool>,
+    #[serde(default, skip_serializing_if = "LightLinks::is_default")]
+    pub light_links: LightLinks,
+    // Units per second, for motion blur
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<[Float; 3]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeDescription {
+    Sphere,
+    Plane,
+    // Unit square and circle on the xz plane; see shape::quad and shape::disc
+    Quad,
+    Disc,
+    // Missing extents mean the shape is infinite in that direction
+    Cylinder {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        minimum: Option<Float>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maximum: Option<Float>,
+        #[serde(default)]
+        closed: bool,
+    },
+    Cone {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        minimum: Option<Float>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maximum: Option<Float>,
+        #[serde(default)]
+        closed: bool,
+    },
+    // uv gives texture coordinates at each corner; see UvMapping::Surface
+    Triangle {
+        p1: [Float; 3],
+        p2: [Float; 3],
+        p3: [Float; 3],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uv: Option<[[Float; 2]; 3]>,
+    },
+    SmoothTriangle {
+        p1: [Float; 3],
+        p2: [Float; 3],
+        p3: [Float; 3],
+        n1: [Float; 3],
+        n2: [Float; 3],
+        n3: [Float; 3],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uv: Option<[[Float; 2]; 3]>,
+    },
+    Csg {
+        operation: CsgOperationDescription,
+        left: Box<ObjectDescription>,
+        right: Box<ObjectDescription>,
+    },
+    Group {
+        children: Vec<ObjectDescription>,
+    },
+    // A detailed shape with simpler versions for when it looks small; see Lod
+    Lod {
+        detail: Box<ObjectDescription>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        levels: Vec<LodLevelDescription>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        impostor_below: Option<Float>,
+    },
+    // Another copy of an earlier top-level object, given by its index in
+    // objects; see Instance. Only allowed as a top-level object itself.
+    Instance {
+        of: usize,
+    },
+    // Any other type name, built from the remaining fields by a factory
+    // registered with factory::register_shape; see shape_or_custom
+    #[serde(skip)]
+    Custom {
+        type_name: String,
+        params: Map<String, Value>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LodLevelDescription {
+    pub max_size: Float,
+    pub object: ObjectDescription,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsgOperationDescription {
+    Union,
+    Intersection,
+    Difference,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformDescription {
+    Translate([Float; 3]),
+    Scale([Float; 3]),
+    RotateX(Float),
+    RotateY(Float),
+    RotateZ(Float),
+    Shear([Float; 6]),
+    // Rows from top to bottom
+    Matrix([[Float; 4]; 4]),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaterialDescription {
+    // Name of a material in the scene's materials to start from. The fields
+    // given here replace its.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour: Option<[Float; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ambient: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diffuse: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specular: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shininess: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reflective: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transparency: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refractive_index: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispersion: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<PatternDescription>,
+    // Shadow casting belongs to objects, see ObjectDescription. Only read so
+    // that a material giving it is an error rather than quietly ignored.
+    #[serde(default, skip_serializing)]
+    pub cast_shadows: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal_map: Option<NormalMapDescription>,
+    // Glow colour, lighting other surfaces under the path integrator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emissive: Option<[Float; 3]>,
+}
+
+// A tangent-space normal image, e.g. { "path": "bumps.png", "mapping": "planar" }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalMapDescription {
+    pub path: String,
+    // Spherical if not given, as for image patterns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strength: Option<Float>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternDescription {
+    #[serde(rename = "type")]
+    pub kind: PatternKind,
+    // Required by every kind except image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub a: Option<PatternSlotDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub b: Option<PatternSlotDescription>,
+    // Image file for the image kind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // uv_checkers squares across u and v, and how uv_checkers and image map uv
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<Float>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<String>,
+    // Detail layers and random seed for the noise kind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub octaves: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transform: Vec<TransformDescription>,
+    // Any other fields, for custom pattern types
+    #[serde(flatten)]
+    pub params: Map<String, Value>,
+}
+
+// Either a colour or a nested pattern, e.g. "a": { "type": "striped", ... }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PatternSlotDescription {
+    Colour([Float; 3]),
+    Pattern(Box<PatternDescription>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    Striped,
+    Gradient,
+    Ring,
+    Checkered,
+    RadialGradient,
+    Blended,
+    UvCheckers,
+    Image,
+    Noise,
+    // Any other name, built by a factory registered with
+    // factory::register_pattern
+    #[serde(untagged)]
+    Custom(String),
+}
+
+impl<'de> Deserialize<'de> for PatternKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(match name.as_str() {
+            "striped" => PatternKind::Striped,
+            "gradient" => PatternKind::Gradient,
+            "ring" => PatternKind::Ring,
+            "checkered" => PatternKind::Checkered,
+            "radial_gradient" => PatternKind::RadialGradient,
+            "blended" => PatternKind::Blended,
+            "uv_checkers" => PatternKind::UvCheckers,
+            "image" => PatternKind::Image,
+            "noise" => PatternKind::Noise,
+            _ => PatternKind::Custom(name),
+        })
+    }
+}
+
+impl SceneDescription {
+    pub fn from_json(json: &str) -> Result<SceneDescription, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid scene: {}", e))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Scene descriptions always serialise")
+    }
+
+    pub fn build(&self) -> Result<World, String> {
+        let mut world = World::new();
+
+        if let Some(scale) = self.unit_scale {
+            if !(scale > 0.0 && scale.is_finite()) {
+                return Err(format!("unit_scale must be positive, got {}", scale));
+            }
+            world.set_unit_scale(scale);
+        }
+
+        world.lights = self
+            .light
+            .iter()
+            .chain(&self.lights)
+            .map(|light| light.build(&world))
+            .collect::<Result<_, String>>()?;
+
+        if let Some(background) = &self.background {
+            world.background = match background {
+                BackgroundDescription::Solid { colour: c } => Background::solid(colour(*c)),
+                BackgroundDescription::Gradient { horizon, zenith } => {
+                    Background::gradient(colour(*horizon), colour(*zenith))
+                }
+                BackgroundDescription::Skybox {
+                    right,
+                    left,
+                    up,
+                    down,
+                    front,
+                    back,
+                } => Background::skybox(Skybox::load([right, left, up, down, front, back])?),
+            };
+        }
+
+        // Checked up front so mistakes in unused materials are reported too
+        for (name, material) in &self.materials {
+            material
+                .resolve(&self.materials)
+                .and_then(|material| material.build())
+                .map_err(|e| format!("Material '{}': {}", name, e))?;
+        }
+
+        let mut ids = Vec::with_capacity(self.objects.len());
+        for (index, object) in self.objects.iter().enumerate() {
+            let shape = match &object.shape {
+                ShapeDescription::Instance { of } => ids
+                    .get(*of)
+                    .and_then(|&id| world.registry.get_shared(id))
+                    .ok_or_else(|| format!("no earlier object {} to instance", of))
+                    .and_then(|prototype| match prototype.is_instance() {
+                        true => Err(format!("object {} is an instance itself", of)),
+                        false => Ok(prototype),
+                    })
+                    .and_then(|prototype| {
+                        object.finish(Box::new(Instance::new(prototype)), &self.materials)
+                    }),
+                _ => object.build(&self.materials),
+            }
+            .map_err(|e| format!("Object {}: {}", index, e))?;
+            ids.push(world.add_boxed_object(shape));
+        }
+
+        // Parents and their children get a scene graph node each, with the
+        // shape's own transform as the node's. That's taken without the
+        // pivot, which setting the shape's transform puts back.
+        let mut nodes = BTreeMap::new();
+        let mut node_for = |world: &mut World, index: usize| {
+            *nodes.entry(index).or_insert_with(|| {
+                let id = ids[index];
+                let transform = world
+                    .registry
+                    .get(id)
+                    .map(|shape| shape.data().without_pivot(shape.transform()));
+                let node = world
+                    .scene_graph
+                    .add_node(None, transform.unwrap_or_else(Matrix::identity));
+                world.scene_graph.attach(node, id, Matrix::identity());
+                node
+            })
+        };
+        for (index, object) in self.objects.iter().enumerate() {
+            let Some(parent) = &object.parent else {
+                continue;
+            };
+            let parent_index = self
+                .objects
+                .iter()
+                .position(|other| other.name.as_ref() == Some(parent))
+                .ok_or_else(|| {
+                    format!("Object {}: no object named '{}' to follow", index, parent)
+                })?;
+            let child = node_for(&mut world, index);
+            let parent = node_for(&mut world, parent_index);
+            world
+                .scene_graph
+                .set_parent(child, Some(parent))
+                .map_err(|e| format!("Object {}: {}", index, e))?;
+        }
+        world.update_scene_graph();
+
+        if let Some(animation) = &self.animation {
+            let animation = animation.build()?;
+            for name in animation.objects.keys() {
+                let Some(id) = world.get_by_name(name).map(|shape| shape.id()) else {
+                    return Err(format!("Animation: no object named '{}'", name));
+                };
+                // Animation::apply only moves top-level shapes
+                if world.registry.root_id(id) != id {
+                    return Err(format!(
+                        "Animation: '{}' is inside a group; animate the group instead",
+                        name
+                    ));
+                }
+            }
+            // Loaded as at the start, so single frames look like the first one
+            animation.apply(&mut world, 0.0);
+            world.animation = Some(animation);
+        }
+
+        world.build_bvh();
+        Ok(world)
+    }
+
+    // The reverse of build, for saving worlds put together in code. Fails if
+    // the world uses an image that wasn't loaded from a file.
+    pub fn from_world(world: &World) -> Result<SceneDescription, String> {
+        let lights = world
+            .lights
+            .iter()
+            .map(LightDescription::from_light)
+            .collect();
+
+        let background = match &world.background {
+            Background::Solid(c) => BackgroundDescription::Solid { colour: rgb(*c) },
+            Background::Gradient { horizon, zenith } => BackgroundDescription::Gradient {
+                horizon: rgb(*horizon),
+                zenith: rgb(*zenith),
+            },
+            Background::Skybox(skybox) => {
+                let face = |index: usize| image_path(&skybox.faces[index]);
+                BackgroundDescription::Skybox {
+                    right: face(0)?,
+                    left: face(1)?,
+                    up: face(2)?,
+                    down: face(3)?,
+                    front: face(4)?,
+                    back: face(5)?,
+                }
+            }
+        };
+
+        let ids: Vec<u32> = world.registry.iter().map(|shape| shape.id()).collect();
+        let mut objects: Vec<ObjectDescription> = world
+            .registry
+            .iter()
+            .enumerate()
+            .map(|(index, shape)| {
+                // Saved relative to a scene graph parent if that has a name to
+                // give it by, and where it stands otherwise. The shape's pivot
+                // wraps its node's transform along with the parent's.
+                let graph = &world.scene_graph;
+                let parent = graph
+                    .node_of(shape.id())
+                    .and_then(|node| graph.parent(node))
+                    .and_then(|node| {
+                        let id = graph.shapes(node).next()?;
+                        Some((node, world.registry.get(id)?))
+                    })
+                    .filter(|(_, parent)| parent.name().is_some());
+                let parent_transform = parent.map_or_else(Matrix::identity, |(node, _)| {
+                    shape.data().about_pivot(graph.world_transform(node))
+                });
+                let mut object = ObjectDescription::from_shape(shape, &parent_transform)
+                    .map_err(|e| format!("Object {}: {}", index, e))?;
+                object.parent = parent.and_then(|(_, p)| p.name()).map(str::to_string);
+                Ok(object)
+            })
+            .collect::<Result<_, String>>()?;
+        // Instances describe their prototype by id
+        for (index, object) in objects.iter_mut().enumerate() {
+            if let ShapeDescription::Instance { of } = &mut object.shape {
+                *of = ids
+                    .iter()
+                    .position(|&id| id as usize == *of)
+                    .ok_or_else(|| {
+                        format!("Object {}: its prototype is no longer in the world", index)
+                    })?;
+            }
+        }
+
+        Ok(SceneDescription {
+            unit_scale: Some(world.unit_scale).filter(|&scale| scale != 1.0),
+            light: None,
+            lights,
+            background: Some(background),
+            materials: BTreeMap::new(),
+            objects,
+            animation: world
+                .animation
+                .as_ref()
+                .map(AnimationDescription::from_animation),
+        })
+    }
+}
+
+impl LightDescription {
+    // Distances default to sizes in metres converted to world's units
+    pub fn build(&self, world: &World) -> Result<Light, String> {
+        let mut light = Light::point_light(point(self.position), colour(self.intensity));
+        if let Some(f) = &self.flicker {
+            light = light.with_flicker(Flicker::new(f.amplitude, f.frequency, f.seed));
+        }
+        if let Some(falloff) = self.falloff {
+            let radius = |radius: Option<Float>| {
+                radius.unwrap_or(world.metres_to_units(DEFAULT_BULB_RADIUS))
+            };
+            let falloff = match falloff {
+                FalloffDescription::None => Falloff::None,
+                FalloffDescription::Linear { radius: r } => Falloff::Linear { radius: radius(r) },
+                FalloffDescription::InverseSquare { radius: r } => {
+                    Falloff::InverseSquare { radius: radius(r) }
+                }
+            };
+            if let Some(radius) = falloff.radius().filter(|&r| r <= 0.0 || r.is_nan()) {
+                return Err(format!(
+                    "Light: falloff radius must be positive, got {}",
+                    radius
+                ));
+            }
+            light = light.with_falloff(falloff);
+        }
+        if let Some(group) = &self.group {
+            light = light.with_group(group);
+        }
+        Ok(light)
+    }
+
+    pub fn from_light(light: &Light) -> LightDescription {
+        LightDescription {
+            position: triple(light.position),
+            intensity: rgb(light.intensity),
+            flicker: light.flicker.as_ref().map(|f| FlickerDescription {
+                amplitude: f.amplitude,
+                frequency: f.frequency,
+                seed: f.seed,
+            }),
+            falloff: match light.falloff {
+                Falloff::None => None,
+                Falloff::Linear { radius } => Some(FalloffDescription::Linear {
+                    radius: Some(radius),
+                }),
+                Falloff::InverseSquare { radius } => Some(FalloffDescription::InverseSquare {
+                    radius: Some(radius),
+                }),
+            },
+            group: light.group.clone(),
+        }
+    }
+}
+
+impl AnimationDescription {
+    pub fn build(&self) -> Result<Animation, String> {
+        let mut animation = Animation::default();
+        for (name, keys) in &self.objects {
+            let interpolation = keys.interpolation()?;
+            let singular = keys.keys.iter().find_map(|key| {
+                key.scale
+                    .filter(|s| s.iter().any(|&c| c == 0.0 || !c.is_finite()))
+                    .map(|s| (key.time, s))
+            });
+            if let Some((time, scale)) = singular {
+                return Err(format!(
+                    "Animation of '{}': scale {:?} at time {} is not invertible",
+                    name, scale, time
+                ));
+            }
+            let triple = |value: fn(&ObjectKeyDescription) -> Option<[Float; 3]>| {
+                keys.track(interpolation, |key| Some((key.time, vector(value(key)?))))
+            };
+            let mut fields = Vec::new();
+            let names: BTreeSet<&String> =
+                keys.keys.iter().flat_map(|key| key.fields.keys()).collect();
+            for field in names {
+                let parameter = SweepParameter::from_name(field)
+                    .ok_or_else(|| format!("Animation of '{}': unknown field '{}'", name, field))?;
+                let track = keys.track(interpolation, |key| {
+                    Some((key.time, *key.fields.get(field)?))
+                });
+                fields.extend(track.map(|track| (parameter, track)));
+            }
+            let object = ObjectAnimation {
+                translation: triple(|key| key.translate),
+                rotation: triple(|key| key.rotate),
+                scale: triple(|key| key.scale),
+                colour: keys.track(interpolation, |key| Some((key.time, colour(key.colour?)))),
+                fields,
+            };
+            animation.objects.insert(name.clone(), object);
+        }
+
+        if let Some(keys) = &self.camera {
+            let interpolation = keys.interpolation()?;
+            let empty = || Track::new(interpolation);
+            animation.camera = Some(CameraAnimation {
+                from: keys
+                    .track(interpolation, |key| Some((key.time, point(key.from?))))
+                    .unwrap_or_else(empty),
+                to: keys
+                    .track(interpolation, |key| Some((key.time, point(key.to?))))
+                    .unwrap_or_else(empty),
+                field_of_view: keys
+                    .track(interpolation, |key| Some((key.time, key.field_of_view?))),
+            });
+        }
+        Ok(animation)
+    }
+
+    // Keys of different tracks at the same time are written as one key
+    pub fn from_animation(animation: &Animation) -> AnimationDescription {
+        let objects = animation
+            .objects
+            .iter()
+            .map(|(name, object)| {
+                let mut keys: Vec<ObjectKeyDescription> = Vec::new();
+                let mut interpolation = Interpolation::Linear;
+                let mut each =
+                    |track: &Option<Track<Tuple>>,
+                     set: fn(&mut ObjectKeyDescription, [Float; 3])| {
+                        if let Some(track) = track {
+                            interpolation = track.interpolation;
+                            for (time, value) in track.keys() {
+                                set(
+                                    key_at(&mut keys, *time, |key| &mut key.time),
+                                    triple(*value),
+                                );
+                            }
+                        }
+                    };
+                each(&object.translation, |key, v| key.translate = Some(v));
+                each(&object.rotation, |key, v| key.rotate = Some(v));
+                each(&object.scale, |key, v| key.scale = Some(v));
+                if let Some(track) = &object.colour {
+                    interpolation = track.interpolation;
+                    for (time, value) in track.keys() {
+                        key_at(&mut keys, *time, |key| &mut key.time).colour = Some(rgb(*value));
+                    }
+                }
+                for (parameter, track) in &object.fields {
+                    interpolation = track.interpolation;
+                    for (time, value) in track.keys() {
+                        key_at(&mut keys, *time, |key| &mut key.time)
+                            .fields
+                            .insert(parameter.name().to_string(), *value);
+                    }
+                }
+                keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+                (name.clone(), KeysDescription::new(interpolation, keys))
+            })
+            .collect();
+
+        let camera = animation.camera.as_ref().map(|camera| {
+            let mut keys: Vec<CameraKeyDescription> = Vec::new();
+            for (time, value) in camera.from.keys() {
+                key_at(&mut keys, *time, |key| &mut key.time).from = Some(triple(*value));
+            }
+            for (time, value) in camera.to.keys() {
+                key_at(&mut keys, *time, |key| &mut key.time).to = Some(triple(*value));
+            }
+            for (time, value) in camera.field_of_view.iter().flat_map(Track::keys) {
+                key_at(&mut keys, *time, |key| &mut key.time).field_of_view = Some(*value);
+            }
+            keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+            KeysDescription::new(camera.from.interpolation, keys)
+        });
+
+        AnimationDescription { objects, camera }
+    }
+}
+
+impl<K> KeysDescription<K> {
+    fn new(interpolation: Interpolation, keys: Vec<K>) -> KeysDescription<K> {
+        KeysDescription {
+            interpolation: (interpolation != Interpolation::Linear)
+                .then(|| interpolation.name().to_string()),
+            keys,
+        }
+    }
+
+    fn interpolation(&self) -> Result<Interpolation, String> {
+        self.interpolation
+            .as_deref()
+            .map_or(Ok(Interpolation::Linear), |name| {
+                Interpolation::from_name(name)
+                    .ok_or_else(|| format!("Unknown interpolation '{}'", name))
+            })
+    }
+
+    // A track of the keys that give a value, or None if none do
+    fn track<T: Animatable>(
+        &self,
+        interpolation: Interpolation,
+        value: impl Fn(&K) -> Option<(Float, T)>,
+    ) -> Option<Track<T>> {
+        let mut keys = self.keys.iter().filter_map(value).peekable();
+        keys.peek()?;
+        Some(
+            keys.fold(Track::new(interpolation), |track, (time, value)| {
+                track.key(time, value)
+            }),
+        )
+    }
+}
+
+// The key at `time`, added if there isn't one yet
+fn key_at<K: Default>(keys: &mut Vec<K>, time: Float, time_of: fn(&mut K) -> &mut Float) -> &mut K {
+    let index = match keys.iter_mut().position(|key| *time_of(key) == time) {
+        Some(index) => index,
+        None => {
+            let mut key = K::default();
+            *time_of(&mut key) = time;
+            keys.push(key);
+            keys.len() - 1
+        }
+    };
+    &mut keys[index]
+}
+
+impl ObjectDescription {
+    // Named materials are looked up in materials
+    pub fn build(
+        &self,
+        materials: &BTreeMap<String, MaterialDescription>,
+    ) -> Result<Box<dyn Shape>, String> {
+        let shape: Box<dyn Shape> = match &self.shape {
+            ShapeDescription::Sphere => Box::new(Sphere::new()),
+            ShapeDescription::Plane => Box::new(Plane::new()),
+            ShapeDescription::Quad => Box::new(Quad::new()),
+            ShapeDescription::Disc => Box::new(Disc::new()),
+            ShapeDescription::Cylinder {
+                minimum,
+                maximum,
+                closed,
+            } => Box::new(Cylinder::truncated(
+                minimum.unwrap_or(Float::NEG_INFINITY),
+                maximum.unwrap_or(Float::INFINITY),
+                *closed,
+            )),
+            ShapeDescription::Cone {
+                minimum,
+                maximum,
+                closed,
+            } => Box::new(Cone::truncated(
+                minimum.unwrap_or(Float::NEG_INFINITY),
+                maximum.unwrap_or(Float::INFINITY),
+                *closed,
+            )),
+            ShapeDescription::Triangle { p1, p2, p3, uv } => {
+                let triangle = Triangle::new(point(*p1), point(*p2), point(*p3));
+                match uv {
+                    Some(uv) => Box::new(triangle.with_texture(uv.map(|[u, v]| (u, v)))),
+                    None => Box::new(triangle),
+                }
+            }
+            ShapeDescription::SmoothTriangle {
+                p1,
+                p2,
+                p3,
+                n1,
+                n2,
+                n3,
+                uv,
+            } => {
+                let triangle = SmoothTriangle::new(
+                    point(*p1),
+                    point(*p2),
+                    point(*p3),
+                    vector(*n1),
+                    vector(*n2),
+                    vector(*n3),
+                );
+                match uv {
+                    Some(uv) => Box::new(triangle.with_texture(uv.map(|[u, v]| (u, v)))),
+                    None => Box::new(triangle),
+                }
+            }
+            ShapeDescription::Csg {
+                operation,
+                left,
+                right,
+            } => {
+                let operation = match operation {
+                    CsgOperationDescription::Union => CsgOperation::Union,
+                    CsgOperationDescription::Intersection => CsgOperation::Intersection,
+                    CsgOperationDescription::Difference => CsgOperation::Difference,
+                };
+                Box::new(Csg::new(
+                    operation,
+                    left.build(materials)?,
+                    right.build(materials)?,
+                ))
+            }
+            ShapeDescription::Group { children } => {
+                let mut group = Group::new();
+                for (index, child) in children.iter().enumerate() {
+                    group.add_child(
+                        child
+                            .build(materials)
+                            .map_err(|e| format!("Child {}: {}", index, e))?,
+                    );
+                }
+                group.build_bvh();
+                Box::new(group)
+            }
+            ShapeDescription::Lod {
+                detail,
+                levels,
+                impostor_below,
+            } => {
+                let mut lod = Lod::new(detail.build(materials)?);
+                for (index, level) in levels.iter().enumerate() {
+                    let shape = level
+                        .object
+                        .build(materials)
+                        .map_err(|e| format!("Level {}: {}", index, e))?;
+                    lod.add_level(level.max_size, shape);
+                }
+                if let Some(max_size) = impostor_below {
+                    lod.set_impostor_below(*max_size);
+                }
+                Box::new(lod)
+            }
+            ShapeDescription::Instance { .. } => {
+                return Err("instances must be top-level objects".to_string())
+            }
+            ShapeDescription::Custom { type_name, params } => {
+                factory::build_shape(type_name, params)?
+            }
+        };
+        self.finish(shape, materials)
+    }
+
+    // Applies the settings every kind of object has to a newly made shape
+    fn finish(
+        &self,
+        mut shape: Box<dyn Shape>,
+        materials: &BTreeMap<String, MaterialDescription>,
+    ) -> Result<Box<dyn Shape>, String> {
+        if self.name.is_some() {
+            shape.set_name(self.name.clone());
+        }
+        if let Some(material) = &self.material {
+            shape.set_material(material.resolve(materials)?.build()?);
+        }
+        if let Some([x, y, z]) = self.pivot {
+            shape.set_pivot(Some(Tuple::point(x, y, z)));
+        }
+        if !self.transform.is_empty() {
+            shape.set_transform(build_transform(&self.transform)?);
+        }
+        let visibility = shape.visibility();
+        let flagged = Visibility {
+            cast_shadows: self.cast_shadows.unwrap_or(visibility.cast_shadows),
+            receive_shadows: self.receive_shadows.unwrap_or(visibility.receive_shadows),
+            camera_visible: self.camera_visible.unwrap_or(visibility.camera_visible),
+        };
+        if flagged != visibility {
+            shape.set_visibility(flagged);
+        }
+        if !self.light_links.is_default() {
+            shape.set_light_links(self.light_links.clone());
+        }
+        if let Some([x, y, z]) = self.velocity {
+            shape.set_velocity(Some(Tuple::vector(x, y, z)));
+        }
+
+        Ok(shape)
+    }
+
+    // Describes a shape as it stands. Shapes inside groups and CSGs store
+    // world-space transforms, so the enclosing shape's transform, parent, is
+    // taken back out of them.
+    pub fn from_shape(shape: &dyn Shape, parent: &Matrix) -> Result<ObjectDescription, String> {
+        let transform = shape
+            .data()
+            .without_pivot(&(&parent.inverse() * shape.transform()));
+
+        // A material on a group would replace its children's own, and CSGs
+        // don't use theirs, so only leaf shapes record one
+        let material = if shape.children().is_empty() {
+            Some(MaterialDescription::from_material(shape.material())?)
+        } else {
+            None
+        };
+
+        // Flags are only written when turned off
+        let visibility = shape.visibility();
+        let off = |on: bool| (!on).then_some(false);
+        Ok(ObjectDescription {
+            name: shape.name().map(str::to_string),
+            parent: None,
+            shape: shape.describe()?,
+            pivot: shape.data().pivot.map(triple),
+            transform: describe_transform(&transform),
+            material,
+            cast_shadows: off(visibility.cast_shadows),
+            receive_shadows: off(visibility.receive_shadows),
+            camera_visible: off(visibility.camera_visible),
+            light_links: shape.light_links().clone(),
+            velocity: shape.data().velocity.map(triple),
+        })
+    }
+}
+
+impl MaterialDescription {
+    // Follows extends through materials, returning the combined material with
+    // no extends left
+    pub fn resolve(
+        &self,
+        materials: &BTreeMap<String, MaterialDescription>,
+    ) -> Result<MaterialDescription, String> {
+        let mut resolved = self.clone();
+        let mut seen = Vec::new();
+        while let Some(name) = resolved.extends.take() {
+            if seen.contains(&name) {
+                return Err(format!("Material '{}' extends itself", name));
+            }
+            // The scene's own materials come before the presets
+            let base = match materials.get(&name) {
+                Some(base) => base.clone(),
+                None => Material::from_name(&name)
+                    .map(|preset| MaterialDescription::from_material(&preset))
+                    .transpose()?
+                    .ok_or_else(|| format!("Unknown material '{}'", name))?,
+            };
+            resolved = resolved.over(base);
+            seen.push(name);
+        }
+        Ok(resolved)
+    }
+
+    // These fields, with any missing taken from base
+    fn over(self, base: MaterialDescription) -> MaterialDescription {
+        MaterialDescription {
+            extends: base.extends,
+            colour: self.colour.or(base.colour),
+            ambient: self.ambient.or(base.ambient),
+            diffuse: self.diffuse.or(base.diffuse),
+            specular: self.specular.or(base.specular),
+            shininess: self.shininess.or(base.shininess),
+            reflective: self.reflective.or(base.reflective),
+            transparency: self.transparency.or(base.transparency),
+            refractive_index: self.refractive_index.or(base.refractive_index),
+            dispersion: self.dispersion.or(base.dispersion),
+            pattern: self.pattern.or(base.pattern),
+            cast_shadows: self.cast_shadows.or(base.cast_shadows),
+            normal_map: self.normal_map.or(base.normal_map),
+            emissive: self.emissive.or(base.emissive),
+        }
+    }
+
+    // Names must have been resolved first
+    pub fn build(&self) -> Result<Material, String> {
+        if let Some(name) = &self.extends {
+            return Err(format!("Unknown material '{}'", name));
+        }
+        if self.cast_shadows.is_some() {
+            return Err("\"cast_shadows\" is set on objects, not materials".to_string());
+        }
+        let mut material = Material::new();
+        if let Some(c) = self.colour {
+            material.colour = colour(c);
+        }
+        if let Some(ambient) = self.ambient {
+            material.ambient = ambient;
+        }
+        if let Some(diffuse) = self.diffuse {
+            material.diffuse = diffuse;
+        }
+        if let Some(specular) = self.specular {
+            material.specular = specular;
+        }
+        if let Some(shininess) = self.shininess {
+            material.shininess = shininess;
+        }
+        if let Some(reflective) = self.reflective {
+            material.reflective = reflective;
+        }
+        if let Some(transparency) = self.transparency {
+            material.transparency = transparency;
+        }
+        if let Some(refractive_index) = self.refractive_index {
+            material.refractive_index = refractive_index;
+        }
+        if let Some(dispersion) = self.dispersion {
+            material.dispersion = dispersion;
+        }
+        if let Some(pattern) = &self.pattern {
+            material.pattern = Some(pattern.build()?);
+        }
+        if let Some(normal_map) = &self.normal_map {
+            material.normal_map = Some(normal_map.build()?);
+        }
+        if let Some(c) = self.emissive {
+            material.emissive = colour(c);
+        }
+        Ok(material)
+    }
+
+    pub fn from_material(material: &Material) -> Result<MaterialDescription, String> {
+        Ok(MaterialDescription {
+            extends: None,
+            colour: Some(rgb(material.colour)),
+            ambient: Some(material.ambient),
+            diffuse: Some(material.diffuse),
+            specular: Some(material.specular),
+            shininess: Some(material.shininess),
+            reflective: Some(material.reflective),
+            transparency: Some(material.transparency),
+            refractive_index: Some(material.refractive_index),
+            dispersion: (material.dispersion != 0.0).then_some(material.dispersion),
+            pattern: material
+                .pattern
+                .as_ref()
+                .map(PatternDescription::from_pattern)
+                .transpose()?,
+            cast_shadows: None,
+            normal_map: material
+                .normal_map
+                .as_ref()
+                .map(NormalMapDescription::from_normal_map)
+                .transpose()?,
+            emissive: Some(rgb(material.emissive)).filter(|&c| c != [0.0; 3]),
+        })
+    }
+}
+
+impl NormalMapDescription {
+    pub fn build(&self) -> Result<NormalMap, String> {
+        let name = self.mapping.as_deref().unwrap_or("spherical");
+        let mapping =
+            UvMapping::from_name(name).ok_or_else(|| format!("Unknown uv mapping '{}'", name))?;
+        let mut normal_map = NormalMap::new(UvImage::load(&self.path)?, mapping);
+        if let Some(strength) = self.strength {
+            normal_map.strength = strength;
+        }
+        Ok(normal_map)
+    }
+
+    pub fn from_normal_map(normal_map: &NormalMap) -> Result<NormalMapDescription, String> {
+        Ok(NormalMapDescription {
+            path: image_path(&normal_map.image)?,
+            mapping: Some(normal_map.mapping.name().to_string()),
+            strength: (normal_map.strength != 1.0).then_some(normal_map.strength),
+        })
+    }
+}
+
+impl PatternDescription {
+    pub fn build(&self) -> Result<PatternType, String> {
+        let mut pattern = match &self.kind {
+            PatternKind::Image => {
+                let path = self.path.as_ref().ok_or("Image pattern needs a path")?;
+                let image = UvImage::load(path)?;
+                PatternType::TextureMap(TextureMap::image(image, self.uv_mapping()?))
+            }
+            PatternKind::Custom(name) => factory::build_pattern(name, self)?,
+            kind => {
+                let slot = |slot: &Option<PatternSlotDescription>, name: &str| match slot {
+                    Some(slot) => slot.build(),
+                    None => Err(format!("Pattern needs '{}'", name)),
+                };
+                let (a, b) = (slot(&self.a, "a")?, slot(&self.b, "b")?);
+                match kind {
+                    PatternKind::Striped => PatternType::Striped(Striped::new(a, b)),
+                    PatternKind::Gradient => PatternType::Gradient(Gradient::new(a, b)),
+                    PatternKind::Ring => PatternType::Ring(Ring::new(a, b)),
+                    PatternKind::Checkered => PatternType::Checkered(Checkered::new(a, b)),
+                    PatternKind::RadialGradient => {
+                        PatternType::RadialGradient(RadialGradient::new(a, b))
+                    }
+                    PatternKind::Blended => PatternType::Blended(Blended::new(a, b)),
+                    PatternKind::UvCheckers => PatternType::TextureMap(TextureMap::checkers(
+                        self.width.unwrap_or(2.0),
+                        self.height.unwrap_or(2.0),
+                        a,
+                        b,
+                        self.uv_mapping()?,
+                    )),
+                    PatternKind::Noise => PatternType::Noise(NoisePattern::new(
+                        a,
+                        b,
+                        self.octaves.unwrap_or(4),
+                        self.seed.unwrap_or(0),
+                    )),
+                    PatternKind::Image | PatternKind::Custom(_) => unreachable!("handled above"),
+                }
+            }
+        };
+
+        if !self.transform.is_empty() {
+            pattern.set_transform(build_transform(&self.transform)?);
+        }
+
+        Ok(pattern)
+    }
+}
+
+impl PatternDescription {
+    fn uv_mapping(&self) -> Result<UvMapping, String> {
+        let name = self.mapping.as_deref().unwrap_or("spherical");
+        UvMapping::from_name(name).ok_or_else(|| format!("Unknown uv mapping '{}'", name))
+    }
+
+    pub fn from_pattern(pattern: &PatternType) -> Result<PatternDescription, String> {
+        let data = pattern.data();
+        let a = PatternSlotDescription::from_slot(&data.a)?;
+        let b = PatternSlotDescription::from_slot(&data.b)?;
+        let described = |kind| PatternDescription {
+            kind,
+            a: Some(a.clone()),
+            b: Some(b.clone()),
+            path: None,
+            width: None,
+            height: None,
+            mapping: None,
+            octaves: None,
+            seed: None,
+            transform: describe_transform(&data.transform),
+            params: Map::new(),
+        };
+
+        Ok(match pattern {
+            PatternType::Striped(_) => described(PatternKind::Striped),
+            PatternType::Gradient(_) => described(PatternKind::Gradient),
+            PatternType::Ring(_) => described(PatternKind::Ring),
+            PatternType::Checkered(_) => described(PatternKind::Checkered),
+            PatternType::RadialGradient(_) => described(PatternKind::RadialGradient),
+            PatternType::Blended(_) => described(PatternKind::Blended),
+            PatternType::TextureMap(map) => {
+                let mapping = Some(map.mapping().name().to_string());
+                match map.uv_pattern() {
+                    UvPattern::Checkers { width, height } => PatternDescription {
+                        width: Some(*width),
+                        height: Some(*height),
+                        mapping,
+                        ..described(PatternKind::UvCheckers)
+                    },
+                    UvPattern::Image(image) => PatternDescription {
+                        a: None,
+                        b: None,
+                        path: Some(image_path(image)?),
+                        mapping,
+                        ..described(PatternKind::Image)
+                    },
+                }
+            }
+            PatternType::Noise(noise) => PatternDescription {
+                octaves: Some(noise.octaves()),
+                seed: Some(noise.seed()),
+                ..described(PatternKind::Noise)
+            },
+            PatternType::Custom(_) => return Err("custom patterns can't be saved".to_string()),
+        })
+    }
+}
+
+impl PatternSlotDescription {
+    pub fn build(&self) -> Result<PatternSlot, String> {
+        match self {
+            PatternSlotDescription::Colour(c) => Ok(colour(*c).into()),
+            PatternSlotDescription::Pattern(pattern) => Ok(pattern.build()?.into()),
+        }
+    }
+
+    pub fn from_slot(slot: &PatternSlot) -> Result<PatternSlotDescription, String> {
+        match slot {
+            PatternSlot::Solid(c) => Ok(PatternSlotDescription::Colour(rgb(*c))),
+            PatternSlot::Pattern(pattern) => Ok(PatternSlotDescription::Pattern(Box::new(
+                PatternDescription::from_pattern(pattern)?,
+            ))),
+        }
+    }
+}
+
+impl TransformDescription {
+    pub fn to_matrix(&self) -> Matrix {
+        match *self {
+            TransformDescription::Translate([x, y, z]) => Matrix::translation(x, y, z),
+            TransformDescription::Scale([x, y, z]) => Matrix::scaling(x, y, z),
+            TransformDescription::RotateX(r) => Matrix::rotation_x(r),
+            TransformDescription::RotateY(r) => Matrix::rotation_y(r),
+            TransformDescription::RotateZ(r) => Matrix::rotation_z(r),
+            TransformDescription::Shear([xy, xz, yx, yz, zx, zy]) => {
+                Matrix::shearing(xy, xz, yx, yz, zx, zy)
+            }
+            TransformDescription::Matrix(rows) => {
+                Matrix::from_vec(rows.iter().map(|row| row.to_vec()).collect())
+            }
+        }
+    }
+}
+
+// Combines a list of transforms so the first one listed is applied first
+pub fn build_transform(transforms: &[TransformDescription]) -> Result<Matrix, String> {
+    let matrix = transforms
+        .iter()
+        .fold(Matrix::identity(), |acc, t| t.to_matrix() * acc);
+
+    if matrix.determinant() == 0.0 {
+        return Err("transform is not invertible".to_string());
+    }
+    Ok(matrix)
+}
+
+// A single matrix transform, or none at all for the identity
+fn describe_transform(matrix: &Matrix) -> Vec<TransformDescription> {
+    if *matrix == Matrix::identity() {
+        return Vec::new();
+    }
+    let mut rows = [[0.0; 4]; 4];
+    for (r, row) in rows.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = matrix[(r, c)];
+        }
+    }
+    vec![TransformDescription::Matrix(rows)]
+}
+
+// "material": "glass" is short for "material": { "extends": "glass" }
+fn material_or_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<MaterialDescription>, D::Error> {
+    // Not an untagged enum, so mistakes in a material still get a useful error
+    match Value::deserialize(deserializer)? {
+        Value::String(name) => Ok(Some(MaterialDescription {
+            extends: Some(name),
+            ..MaterialDescription::default()
+        })),
+        value => MaterialDescription::deserialize(value)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+// The type names ShapeDescription's own variants are read from
+const BUILT_IN_SHAPES: [&str; 12] = [
+    "sphere",
+    "plane",
+    "quad",
+    "disc",
+    "cylinder",
+    "cone",
+    "triangle",
+    "smooth_triangle",
+    "csg",
+    "group",
+    "lod",
+    "instance",
+];
+
+// Built-in shape types are read as such, so mistakes in them get a useful
+// error; only other type names are left to the factory
+mod shape_or_custom {
+    use super::*;
+    use serde::{de::Error, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        shape: &ShapeDescription,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match shape {
+            ShapeDescription::Custom { type_name, params } => {
+                let mut fields = params.clone();
+                fields.insert("type".to_string(), Value::String(type_name.clone()));
+                fields.serialize(serializer)
+            }
+            shape => shape.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ShapeDescription, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        let type_name = match fields.get("type") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => return Err(D::Error::custom("shape type must be a string")),
+            None => return Err(D::Error::missing_field("type")),
+        };
+        if BUILT_IN_SHAPES.contains(&type_name.as_str()) {
+            return ShapeDescription::deserialize(Value::Object(fields))
+                .map_err(|e| D::Error::custom(format!("{}: {}", type_name, e)));
+        }
+        fields.remove("type");
+        Ok(ShapeDescription::Custom {
+            type_name,
+            params: fields,
+        })
+    }
+}
+
+fn image_path(image: &UvImage) -> Result<String, String> {
+    image
+        .path()
+        .map(str::to_string)
+        .ok_or_else(|| "images that weren't loaded from a file can't be saved".to_string())
+}
+
+// Accepts either the name of a built-in scene or a JSON scene description
+pub fn load_world(name_or_json: &str) -> Result<World, String> {
+    let source = name_or_json.trim();
+    if source.starts_with('{') {
+        SceneDescription::from_json(source)?.build()
+    } else {
+        World::from_name(source).ok_or_else(|| format!("Unknown scene '{}'", source))
+    }
+}
+
+pub fn load_scene_file<P: AsRef<Path>>(path: P) -> Result<World, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    SceneDescription::from_json(&contents)?.build()
+}
+
+fn point(p: [Float; 3]) -> Tuple {
+    Tuple::point(p[0], p[1], p[2])
+}
+
+fn vector(v: [Float; 3]) -> Tuple {
+    Tuple::vector(v[0], v[1], v[2])
+}
+
+fn colour(c: [Float; 3]) -> Colour {
+    Colour::new(c[0], c[1], c[2])
+}
+
+pub(crate) fn triple(t: Tuple) -> [Float; 3] {
+    [t.x, t.y, t.z]
+}
+
+fn rgb(c: Colour) -> [Float; 3] {
+    [c.r, c.g, c.b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn empty_scene_builds_empty_world() {
+        let world = SceneDescription::from_json("{}").unwrap().build().unwrap();
+
+        assert_eq!(world.registry.len(), 0);
+        assert!(world.lights.is_empty());
+    }
+
+    #[test]
+    fn scene_with_light_and_objects() {
+        let json = r#"{
+            "light": { "position": [-10, 10, -10], "intensity": [1, 1, 1] },
+            "objects": [
+                {
+                    "type": "sphere",
+                    "material": { "colour": [0.8, 1.0, 0.6], "diffuse": 0.7, "specular": 0.2 }
+                },
+                { "type": "sphere", "transform": [{ "scale": [0.5, 0.5, 0.5] }] }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        // Same layout as the default world, so it should shade the same
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let c = world.colour_at(&r, crate::world::DEFAULT_MAX_BOUNCES);
+        assert_abs_diff_eq!(c, Colour::new(0.38066, 0.47583, 0.2855), epsilon = 0.0001);
+    }
+
+    #[test]
+    fn transforms_are_applied_in_listed_order() {
+        let transforms = vec![
+            TransformDescription::RotateX(crate::scalar::consts::PI / 2.0),
+            TransformDescription::Scale([5.0, 5.0, 5.0]),
+            TransformDescription::Translate([10.0, 5.0, 7.0]),
+        ];
+        let m = build_transform(&transforms).unwrap();
+
+        assert_abs_diff_eq!(
+            m * Tuple::point(1.0, 0.0, 1.0),
+            Tuple::point(15.0, 0.0, 7.0),
+            epsilon = 0.0001
+        );
+    }
+
+    #[test]
+    fn non_invertible_transform_is_an_error() {
+        let json = r#"{ "objects": [{ "type": "plane", "transform": [{ "scale": [1, 0, 1] }] }] }"#;
+
+        let err = load_world(json).err().unwrap();
+        assert!(err.contains("Object 0"));
+    }
+
+    #[test]
+    fn unknown_shape_type_is_an_error() {
+        let json = r#"{ "objects": [{ "type": "teapot" }] }"#;
+
+        assert!(load_world(json).is_err());
+    }
+
+    #[test]
+    fn mistakes_in_built_in_shapes_are_reported_as_such() {
+        for (json, expected) in [
+            (
+                r#"{ "type": "csg", "operation": "union", "left": { "type": "sphere" } }"#,
+                "right",
+            ),
+            (r#"{ "type": "cylinder", "minimum": "zero" }"#, "zero"),
+        ] {
+            let scene = format!(r#"{{ "objects": [{}] }}"#, json);
+
+            let err = load_world(&scene).err().unwrap();
+
+            assert!(err.contains(expected), "{}", err);
+            assert!(!err.contains("Unknown shape type"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn built_in_scenes_are_loaded_by_name() {
+        assert_eq!(load_world("default").unwrap().registry.len(), 2);
+        assert!(load_world("nonexistent").is_err());
+    }
+
+    #[test]
+    fn csg_and_patterns_are_parsed() {
+        let json = r#"{
+            "objects": [{
+                "type": "csg",
+                "operation": "difference",
+                "left": {
+                    "type": "cylinder", "minimum": 0, "maximum": 1, "closed": true,
+                    "material": { "pattern": { "type": "checkered", "a": [1, 1, 1], "b": [0, 0, 0] } }
+                },
+                "right": { "type": "sphere", "transform": [{ "translate": [0, 1, 0] }] }
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        assert_eq!(world.registry.len(), 1);
+        let csg = world.registry.get_by_index(0).unwrap();
+        assert_eq!(csg.children().len(), 2);
+        assert!(csg.children()[0].material().pattern.is_some());
+    }
+
+    #[test]
+    fn scene_description_round_trips_through_json() {
+        let json = r#"{
+            "background": { "type": "solid", "colour": [0.1, 0.2, 0.3] },
+            "objects": [{ "type": "cone", "maximum": 1, "transform": [{ "rotate_y": 0.5 }] }]
+        }"#;
+        let scene = SceneDescription::from_json(json).unwrap();
+        let reparsed = SceneDescription::from_json(&scene.to_json()).unwrap();
+
+        assert_eq!(reparsed.objects.len(), 1);
+        assert!(matches!(
+            reparsed.objects[0].shape,
+            ShapeDescription::Cone {
+                minimum: None,
+                maximum: Some(_),
+                closed: false
+            }
+        ));
+    }
+
+    #[test]
+    fn light_flicker_is_parsed() {
+        let json = r#"{
+            "light": {
+                "position": [0, 10, 0], "intensity": [1, 1, 1],
+                "flicker": { "amplitude": 0.25, "frequency": 5 }
+            }
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let flicker = world.lights[0].flicker.unwrap();
+        assert_eq!(flicker.amplitude, 0.25);
+        assert_eq!(flicker.frequency, 5.0);
+        assert_eq!(flicker.seed, 0);
+    }
+
+    #[test]
+    fn every_light_is_parsed_and_saved() {
+        let json = r#"{
+            "light": { "position": [0, 10, 0], "intensity": [1, 1, 1] },
+            "lights": [
+                { "position": [5, 5, 0], "intensity": [0.5, 0.5, 0.5] },
+                { "position": [-5, 5, 0], "intensity": [0.2, 0.2, 0.2], "group": "fill" }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+        let description = world.to_scene_description().unwrap();
+        assert!(description.light.is_none());
+        let saved = load_world(&description.to_json()).unwrap();
+
+        // "light" comes first, ahead of the list
+        for world in [world, saved] {
+            assert_eq!(world.lights.len(), 3);
+            assert_eq!(world.lights[0].position, Tuple::point(0.0, 10.0, 0.0));
+            assert_eq!(world.lights[1].position, Tuple::point(5.0, 5.0, 0.0));
+            assert_eq!(world.lights[2].group.as_deref(), Some("fill"));
+        }
+    }
+
+    #[test]
+    fn light_falloff_is_parsed_and_saved() {
+        let json = r#"{
+            "light": {
+                "position": [0, 10, 0], "intensity": [40, 40, 40],
+                "falloff": { "type": "inverse_square", "radius": 0.5 }
+            }
+        }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        for world in [world, saved] {
+            assert_eq!(
+                world.lights[0].falloff,
+                Falloff::InverseSquare { radius: 0.5 }
+            );
+        }
+        let constant =
+            load_world(r#"{ "light": { "position": [0, 1, 0], "intensity": [1, 1, 1] } }"#)
+                .unwrap();
+        let saved = constant.to_scene_description().unwrap().to_json();
+        assert!(!saved.contains("falloff"), "{}", saved);
+
+        // A bulb of the default size, in centimetres
+        let sized = load_world(
+            r#"{
+                "unit_scale": 0.01,
+                "light": { "position": [0, 1, 0], "intensity": [1, 1, 1], "falloff": { "type": "linear" } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sized.lights[0].falloff,
+            Falloff::Linear {
+                radius: DEFAULT_BULB_RADIUS * 100.0
+            }
+        );
+
+        let err = load_world(
+            r#"{ "lights": [{ "position": [0, 1, 0], "intensity": [1, 1, 1], "falloff": { "type": "linear", "radius": 0 } }] }"#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.contains("falloff radius must be positive"), "{}", err);
+    }
+
+    #[test]
+    fn objects_rotate_about_their_pivot() {
+        let json = r#"{
+            "objects": [{
+                "type": "sphere",
+                "pivot": [0, -1, 0],
+                "transform": [{ "rotate_z": 3.141592653589793 }]
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let sphere = world.registry.get_by_index(0).unwrap();
+        assert_abs_diff_eq!(
+            sphere.transform() * Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, -2.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn unit_scale_is_applied_to_the_world() {
+        let world = load_world(r#"{ "unit_scale": 0.01 }"#).unwrap();
+
+        assert_eq!(world.unit_scale, 0.01);
+        assert_abs_diff_eq!(
+            world.secondary_t_min(),
+            crate::world::DEFAULT_SECONDARY_T_MIN * 100.0
+        );
+        assert!(load_world(r#"{ "unit_scale": 0 }"#).is_err());
+    }
+
+    #[test]
+    fn pattern_slots_can_hold_nested_patterns() {
+        let json = r#"{
+            "objects": [{
+                "type": "plane",
+                "material": { "pattern": {
+                    "type": "checkered",
+                    "a": { "type": "striped", "a": [1, 0, 0], "b": [1, 1, 1] },
+                    "b": [0, 0, 0]
+                } }
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let plane = world.registry.get_by_index(0).unwrap();
+        let pattern = plane.material().pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(0.5, 0.0, 0.0)),
+            Colour::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(1.5, 0.0, 0.0)),
+            Colour::black()
+        );
+    }
+
+    #[test]
+    fn uv_checkers_are_parsed() {
+        let json = r#"{
+            "objects": [{
+                "type": "sphere",
+                "material": { "pattern": {
+                    "type": "uv_checkers", "width": 16, "height": 8, "mapping": "spherical",
+                    "a": [0, 0, 0], "b": [1, 1, 1]
+                } }
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let sphere = world.registry.get_by_index(0).unwrap();
+        let pattern = sphere.material().pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.pattern_at_object(Tuple::point(0.4315, 0.4670, 0.7719)),
+            Colour::white()
+        );
+
+        let bad = json.replace("spherical", "toroidal");
+        assert!(load_world(&bad).is_err());
+    }
+
+    #[test]
+    fn noise_patterns_use_their_seed() {
+        let json = r#"{
+            "objects": [{
+                "type": "plane",
+                "material": { "pattern": {
+                    "type": "noise", "octaves": 3, "seed": 1,
+                    "a": [0, 0, 0], "b": [1, 1, 1]
+                } }
+            }]
+        }"#;
+        let colour_at = |json: &str| {
+            let world = load_world(json).unwrap();
+            let plane = world.registry.get_by_index(0).unwrap();
+            let pattern = plane.material().pattern.as_ref().unwrap();
+            pattern.pattern_at_object(Tuple::point(0.3, 0.0, 0.6))
+        };
+
+        assert_eq!(colour_at(json), colour_at(json));
+        assert_ne!(
+            colour_at(json),
+            colour_at(&json.replace("\"seed\": 1", "\"seed\": 2"))
+        );
+    }
+
+    #[test]
+    fn image_patterns_load_their_texture() {
+        let path = std::env::temp_dir().join("raytracer_scene_texture_test.png");
+        image::RgbImage::from_pixel(2, 2, image::Rgb([0, 255, 0]))
+            .save(&path)
+            .unwrap();
+        let json = format!(
+            r#"{{ "objects": [{{ "type": "sphere", "material": {{ "pattern": {{
+                "type": "image", "path": {:?}, "mapping": "spherical"
+            }} }} }}] }}"#,
+            path.to_string_lossy()
+        );
+
+        let world = load_world(&json).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let sphere = world.registry.get_by_index(0).unwrap();
+        let pattern = sphere.material().pattern.as_ref().unwrap();
+        assert_abs_diff_eq!(
+            pattern.pattern_at_object(Tuple::point(0.0, 0.0, -1.0)),
+            Colour::new(0.0, 1.0, 0.0)
+        );
+        assert!(load_world(r#"{ "objects": [{ "type": "plane", "material": { "pattern": { "type": "image" } } }] }"#).is_err());
+        assert!(load_world(r#"{ "objects": [{ "type": "plane", "material": { "pattern": { "type": "striped", "a": [1, 1, 1] } } }] }"#).is_err());
+    }
+
+    #[test]
+    fn normal_maps_are_loaded_and_saved() {
+        let path = std::env::temp_dir().join("raytracer_scene_normal_map_test.png");
+        image::RgbImage::from_pixel(2, 2, image::Rgb([128, 128, 255]))
+            .save(&path)
+            .unwrap();
+        let json = format!(
+            r#"{{ "objects": [{{ "type": "plane", "material": {{ "normal_map": {{
+                "path": {:?}, "mapping": "planar", "strength": 0.5
+            }} }} }}] }}"#,
+            path.to_string_lossy()
+        );
+
+        let world = load_world(&json).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let plane = world.registry.get_by_index(0).unwrap();
+        let normal_map = plane.material().normal_map.as_ref().unwrap();
+        assert_eq!(normal_map.mapping, UvMapping::Planar);
+        assert_eq!(normal_map.strength, 0.5);
+
+        let saved = SceneDescription::from_world(&world).unwrap();
+        let description = saved.objects[0].material.as_ref().unwrap();
+        let saved_map = description.normal_map.as_ref().unwrap();
+        assert_eq!(saved_map.path, path.to_string_lossy());
+        assert_eq!(saved_map.strength, Some(0.5));
+    }
+
+    #[test]
+    fn skybox_background_loads_its_faces() {
+        let dir = std::env::temp_dir();
+        let faces = ["right", "left", "up", "down", "front", "back"];
+        for (i, face) in faces.iter().enumerate() {
+            let shade = (i * 40) as u8;
+            image::RgbImage::from_pixel(2, 2, image::Rgb([shade, shade, shade]))
+                .save(dir.join(format!("raytracer_sky_{}.png", face)))
+                .unwrap();
+        }
+        let paths: Vec<String> = faces
+            .iter()
+            .map(|face| {
+                format!(
+                    "{:?}: {:?}",
+                    face,
+                    dir.join(format!("raytracer_sky_{}.png", face))
+                        .to_string_lossy()
+                )
+            })
+            .collect();
+        let json = format!(
+            r#"{{ "background": {{ "type": "skybox", {} }} }}"#,
+            paths.join(", ")
+        );
+
+        let world = load_world(&json).unwrap();
+        for face in faces {
+            std::fs::remove_file(dir.join(format!("raytracer_sky_{}.png", face))).ok();
+        }
+
+        let up = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_abs_diff_eq!(
+            world.background.colour_at(&up),
+            Colour::new(80.0 / 255.0, 80.0 / 255.0, 80.0 / 255.0),
+            epsilon = 1e-6
+        );
+    }
+
+    // Fires a fan of rays through the middle of the scene
+    fn sample_colours(world: &World) -> Vec<Colour> {
+        (0..25)
+            .map(|i| {
+                let (x, y) = ((i % 5) as Float - 2.0, (i / 5) as Float - 1.0);
+                let direction = Tuple::vector(x * 0.15, y * 0.15, 1.0).normalise();
+                let ray = Ray::new(Tuple::point(0.0, 1.0, -6.0), direction);
+                world.colour_at(&ray, world.settings.max_bounces)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saved_worlds_render_the_same_when_reloaded() {
+        let json = r#"{
+            "light": {
+                "position": [-10, 10, -10], "intensity": [1, 1, 1],
+                "flicker": { "amplitude": 0.2, "frequency": 3, "seed": 4 }
+            },
+            "background": { "type": "gradient", "horizon": [1, 1, 1], "zenith": [0.2, 0.4, 0.8] },
+            "objects": [
+                {
+                    "type": "plane",
+                    "material": { "reflective": 0.3, "pattern": {
+                        "type": "checkered",
+                        "a": { "type": "noise", "octaves": 2, "seed": 7, "a": [1, 0, 0], "b": [0, 0, 1] },
+                        "b": [0, 0, 0],
+                        "transform": [{ "scale": [0.5, 0.5, 0.5] }]
+                    } }
+                },
+                {
+                    "type": "csg",
+                    "operation": "difference",
+                    "pivot": [0, 1, 0],
+                    "transform": [{ "rotate_y": 0.4 }, { "translate": [0.5, 0.5, 0] }],
+                    "left": {
+                        "type": "sphere", "transform": [{ "translate": [0, 1, 0] }],
+                        "material": { "colour": [0.9, 0.6, 0.1] }
+                    },
+                    "right": { "type": "sphere", "transform": [{ "translate": [0, 1.5, -0.5] }] }
+                },
+                {
+                    "type": "group",
+                    "transform": [{ "translate": [-1.5, 0, 1] }],
+                    "children": [
+                        { "type": "cylinder", "minimum": 0, "maximum": 1, "closed": true },
+                        {
+                            "type": "sphere", "pivot": [0, -1, 0],
+                            "transform": [{ "scale": [0.5, 0.5, 0.5] }, { "translate": [0, 2, 0] }],
+                            "material": { "pattern": {
+                                "type": "uv_checkers", "width": 8, "height": 4, "mapping": "spherical",
+                                "a": [1, 1, 1], "b": [0.2, 0.2, 0.2]
+                            } }
+                        }
+                    ]
+                }
+            ]
+        }"#;
+        let mut world = load_world(json).unwrap();
+        world.time = 0.7;
+
+        let saved = world.to_scene_description().unwrap().to_json();
+        let mut reloaded = load_world(&saved).unwrap();
+        reloaded.time = 0.7;
+
+        assert_eq!(reloaded.registry.len(), 3);
+        for (a, b) in sample_colours(&world)
+            .into_iter()
+            .zip(sample_colours(&reloaded))
+        {
+            assert_abs_diff_eq!(a, b, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn lods_are_parsed_and_saved() {
+        let json = r#"{
+            "objects": [{
+                "type": "lod",
+                "transform": [{ "translate": [0, 0, 10] }],
+                "detail": { "type": "sphere" },
+                "levels": [{ "max_size": 0.5, "object": { "type": "sphere", "transform": [{ "scale": [0.5, 0.5, 0.5] }] } }],
+                "impostor_below": 0.01
+            }]
+        }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        // Far enough away for the half-size level
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -40.0), Tuple::vector(0.0, 0.0, 1.0));
+        for world in [world, saved] {
+            let xs = world.intersect_world(&ray);
+            assert_abs_diff_eq!(xs[0].t, 49.5, epsilon = TEST_EPSILON);
+            // Hits on the level resolve to a registered shape
+            assert!(world.registry.get(xs[0].object_id).is_some());
+        }
+    }
+
+    #[test]
+    fn visibility_flags_are_parsed_and_saved() {
+        let json = r#"{
+            "objects": [
+                { "type": "plane", "receive_shadows": false },
+                { "type": "group", "cast_shadows": false, "camera_visible": false,
+                  "children": [{ "type": "sphere" }] }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        for world in [world, saved] {
+            let plane = world.registry.get_by_index(0).unwrap().visibility();
+            assert!(!plane.receive_shadows && plane.cast_shadows && plane.camera_visible);
+            let group = world.registry.get_by_index(1).unwrap();
+            let hidden = Visibility {
+                cast_shadows: false,
+                receive_shadows: true,
+                camera_visible: false,
+            };
+            assert_eq!(group.visibility(), hidden);
+            assert_eq!(group.children()[0].visibility(), hidden);
+        }
+    }
+
+    #[test]
+    fn shadow_casting_is_set_on_objects_not_materials() {
+        let json = r#"{
+            "objects": [{ "type": "sphere", "material": { "cast_shadows": false } }]
+        }"#;
+        let err = load_world(json).err().unwrap();
+        assert!(err.contains("cast_shadows"), "{}", err);
+    }
+
+    #[test]
+    fn object_names_are_parsed_and_saved() {
+        let json = r#"{ "objects": [
+            { "type": "sphere" },
+            { "type": "plane", "name": "floor" },
+            { "type": "group", "name": "props", "children": [{ "type": "sphere", "name": "ball" }] }
+        ] }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        for world in [world, saved] {
+            let floor = world.get_by_name("floor").unwrap();
+            assert_eq!(floor.id(), world.registry.get_by_index(1).unwrap().id());
+            assert!(world.get_by_name("ball").is_some());
+            assert_eq!(world.registry.get_by_index(0).unwrap().name(), None);
+        }
+    }
+
+    #[test]
+    fn light_groups_and_links_are_parsed_and_saved() {
+        let json = r#"{
+            "light": { "position": [0, 10, 0], "intensity": [1, 1, 1], "group": "key" },
+            "objects": [
+                { "type": "plane", "light_links": { "exclude": ["key"] } },
+                { "type": "group", "light_links": { "only": ["key", "rim"] },
+                  "children": [{ "type": "sphere" }] }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        for world in [world, saved] {
+            let light = &world.lights[0];
+            assert_eq!(light.group.as_deref(), Some("key"));
+            let plane = world.registry.get_by_index(0).unwrap();
+            assert!(!plane.light_links().lit_by(light));
+            let group = world.registry.get_by_index(1).unwrap();
+            assert!(group.children()[0].light_links().lit_by(light));
+            assert_eq!(
+                group.light_links().only,
+                Some(vec!["key".to_string(), "rim".to_string()])
+            );
+        }
+    }
+
+    #[test]
+    fn quads_and_discs_are_loaded_and_saved() {
+        let json = r#"{ "objects": [
+            { "type": "quad", "transform": [{ "scale": [2, 1, 2] }] },
+            { "type": "disc", "transform": [{ "translate": [0, 1, 0] }] }
+        ] }"#;
+        let mut world = load_world(json).unwrap();
+        let saved = world.to_scene_description().unwrap();
+        assert!(matches!(saved.objects[0].shape, ShapeDescription::Quad));
+        assert!(matches!(saved.objects[1].shape, ShapeDescription::Disc));
+
+        // Flat bounds still work in the BVH
+        world.build_bvh();
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let xs = world.intersect_world(&Ray::new(Tuple::point(1.5, 5.0, 0.0), down));
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 5.0);
+        let xs = world.intersect_world(&Ray::new(Tuple::point(0.5, 5.0, 0.5), down));
+        assert_eq!(xs.len(), 2);
+    }
+
+    #[test]
+    fn velocities_are_parsed_and_saved() {
+        let json = r#"{ "objects": [{ "type": "group", "velocity": [1, 0, -2],
+                        "children": [{ "type": "sphere" }] }] }"#;
+        let world = load_world(json).unwrap();
+        let saved = load_world(&world.to_scene_description().unwrap().to_json()).unwrap();
+
+        for world in [world, saved] {
+            let group = world.registry.get_by_index(0).unwrap();
+            let velocity = Some(Tuple::vector(1.0, 0.0, -2.0));
+            assert_eq!(group.data().velocity, velocity);
+            assert_eq!(group.children()[0].data().velocity, velocity);
+        }
+    }
+
+    #[test]
+    fn emissive_materials_are_loaded_and_saved() {
+        let json = r#"{ "objects": [
+            { "type": "sphere", "material": { "emissive": [2, 1.5, 1] } },
+            { "type": "plane" }
+        ] }"#;
+        let world = load_world(json).unwrap();
+        let saved = SceneDescription::from_world(&world).unwrap();
+
+        let glow = world.registry.get_by_index(0).unwrap().material().emissive;
+        assert_eq!(glow, Colour::new(2.0, 1.5, 1.0));
+        let materials: Vec<_> = saved
+            .objects
+            .iter()
+            .map(|o| o.material.as_ref().unwrap().emissive)
+            .collect();
+        assert_eq!(materials, vec![Some([2.0, 1.5, 1.0]), None]);
+    }
+
+    #[test]
+    fn objects_can_use_named_materials() {
+        let json = r#"{
+            "materials": {
+                "glass": { "transparency": 0.9, "refractive_index": 1.5, "colour": [1, 1, 1] },
+                "green_glass": { "extends": "glass", "colour": [0.1, 0.4, 0.1] }
+            },
+            "objects": [
+                { "type": "sphere", "material": "glass" },
+                { "type": "group", "children": [
+                    { "type": "sphere", "material": { "extends": "green_glass", "shininess": 300 } }
+                ] }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let glass = world.registry.get_by_index(0).unwrap().material();
+        assert_eq!(glass.refractive_index, 1.5);
+        assert_eq!(glass.colour, Colour::new(1.0, 1.0, 1.0));
+        let group = world.registry.get_by_index(1).unwrap();
+        let green = group.children()[0].material();
+        assert_eq!(green.transparency, 0.9);
+        assert_eq!(green.colour, Colour::new(0.1, 0.4, 0.1));
+        assert_eq!(green.shininess, 300.0);
+    }
+
+    #[test]
+    fn bad_material_names_are_rejected() {
+        let unknown = r#"{ "objects": [{ "type": "sphere", "material": "velvet" }] }"#;
+        assert!(load_world(unknown)
+            .err()
+            .unwrap()
+            .contains("Unknown material 'velvet'"));
+
+        // Reported even though nothing uses them
+        let cycle = r#"{ "materials": {
+            "a": { "extends": "b" },
+            "b": { "extends": "a" }
+        } }"#;
+        assert!(load_world(cycle).err().unwrap().contains("extends itself"));
+    }
+
+    #[test]
+    fn preset_materials_can_be_used_by_name() {
+        let json = r#"{
+            "materials": { "mirror": { "colour": [1, 0, 0] } },
+            "objects": [
+                { "type": "sphere", "material": { "extends": "gold", "reflective": 0 } },
+                { "type": "sphere", "material": "mirror" }
+            ]
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let gold = world.registry.get_by_index(0).unwrap().material();
+        assert_eq!(gold.colour, Material::from_name("gold").unwrap().colour);
+        assert_eq!(gold.reflective, 0.0);
+        // The scene's own mirror replaces the preset
+        let mirror = world.registry.get_by_index(1).unwrap().material();
+        assert_eq!(mirror.colour, Colour::new(1.0, 0.0, 0.0));
+        assert_eq!(mirror.reflective, 0.0);
+    }
+
+    #[test]
+    fn instances_are_loaded_and_saved() {
+        let json = r#"{ "objects": [
+            { "type": "plane" },
+            { "type": "sphere", "transform": [{ "translate": [0, 1, 0] }] },
+            { "type": "instance", "of": 1, "transform": [{ "translate": [3, 0, 0] }],
+              "material": { "colour": [1, 0, 0] } }
+        ] }"#;
+        let world = load_world(json).unwrap();
+        let down = Tuple::vector(0.0, -1.0, 0.0);
+        let xs = world.intersect_world(&Ray::new(Tuple::point(3.0, 5.0, 0.0), down));
+        assert_eq!(xs[0].t, 3.0);
+        assert_eq!(
+            xs[0].object_id,
+            world.registry.get_by_index(2).unwrap().id()
+        );
+
+        let saved = SceneDescription::from_world(&world).unwrap();
+        assert!(matches!(
+            saved.objects[2].shape,
+            ShapeDescription::Instance { of: 1 }
+        ));
+        assert!(load_world(r#"{ "objects": [{ "type": "instance", "of": 0 }] }"#).is_err());
+    }
+
+    #[test]
+    fn instances_of_instances_are_refused() {
+        let json = r#"{ "objects": [
+            { "type": "sphere" },
+            { "type": "instance", "of": 0, "transform": [{ "translate": [3, 0, 0] }] },
+            { "type": "instance", "of": 1, "transform": [{ "translate": [3, 0, 0] }] }
+        ] }"#;
+
+        let err = load_world(json).err().unwrap();
+
+        assert!(
+            err.contains("Object 2") && err.contains("instance"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn worlds_built_in_code_can_be_saved() {
+        let world = World::third_world();
+
+        let saved = world.to_scene_description().unwrap().to_json();
+        let reloaded = load_world(&saved).unwrap();
+
+        assert_eq!(reloaded.registry.len(), world.registry.len());
+        for (a, b) in sample_colours(&world)
+            .into_iter()
+            .zip(sample_colours(&reloaded))
+        {
+            assert_abs_diff_eq!(a, b, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn images_without_a_file_cannot_be_saved() {
+        let mut world = World::new();
+        let mut sphere = Sphere::new();
+        let image = UvImage::from_canvas(&crate::camera::Canvas::new(2, 2));
+        sphere.data.material.pattern = Some(PatternType::TextureMap(TextureMap::image(
+            image,
+            UvMapping::Spherical,
+        )));
+        world.add_object(sphere);
+
+        let err = world.to_scene_description().err().unwrap();
+        assert!(err.contains("Object 0"), "{}", err);
+    }
+
+    #[test]
+    fn animations_are_parsed_and_saved() {
+        let json = r#"{
+            "objects": [{ "type": "sphere", "name": "ball" }],
+            "animation": {
+                "objects": {
+                    "ball": { "interpolation": "cubic", "keys": [
+                        { "time": 0, "translate": [0, 1, 0], "reflective": 0 },
+                        { "time": 2, "translate": [0, 3, 0], "colour": [1, 0, 0], "reflective": 0.8 }
+                    ] }
+                },
+                "camera": { "keys": [{ "time": 1, "from": [0, 0, -5], "to": [0, 0, 0] }] }
+            }
+        }"#;
+        let world = load_world(json).unwrap();
+
+        let ball = world.get_by_name("ball").unwrap();
+        assert_eq!(
+            ball.transform() * Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, 1.0, 0.0)
+        );
+        let later = world.at_time(2.0);
+        let ball = later.get_by_name("ball").unwrap();
+        assert_eq!(ball.material().reflective, 0.8);
+        assert_eq!(ball.material().colour, Colour::new(1.0, 0.0, 0.0));
+
+        let saved = SceneDescription::from_world(&world).unwrap();
+        let animation = saved.animation.unwrap();
+        let keys = &animation.objects["ball"];
+        assert_eq!(keys.interpolation.as_deref(), Some("cubic"));
+        assert_eq!(keys.keys.len(), 2);
+        assert_eq!(keys.keys[1].translate, Some([0.0, 3.0, 0.0]));
+        assert_eq!(keys.keys[1].fields["reflective"], 0.8);
+        assert_eq!(
+            animation.camera.unwrap().keys[0].from,
+            Some([0.0, 0.0, -5.0])
+        );
+
+        let unknown = r#"{ "animation": { "objects": { "cup": { "keys": [] } } } }"#;
+        assert!(load_world(unknown).err().unwrap().contains("cup"));
+        let field = r#"{
+            "objects": [{ "type": "sphere", "name": "ball" }],
+            "animation": { "objects": { "ball": { "keys": [{ "time": 0, "wobble": 1 }] } } }
+        }"#;
+        assert!(load_world(field).err().unwrap().contains("wobble"));
+    }
+
+    #[test]
+    fn children_follow_their_animated_parents() {
+        let json = r#"{
+            "objects": [
+                { "type": "sphere", "name": "hand", "parent": "arm", "transform": [{ "translate": [1, 0, 0] }] },
+                { "type": "sphere", "name": "arm", "transform": [{ "translate": [0, 2, 0] }] }
+            ],
+            "animation": { "objects": { "arm": { "keys": [
+                { "time": 0, "translate": [0, 2, 0] },
+                { "time": 1, "translate": [0, 5, 0] }
+            ] } } }
+        }"#;
+        let world = load_world(json).unwrap();
+        let origin = |world: &World| {
+            world.get_by_name("hand").unwrap().transform() * Tuple::point(0.0, 0.0, 0.0)
+        };
+        assert_eq!(origin(&world), Tuple::point(1.0, 2.0, 0.0));
+        assert_eq!(origin(&world.at_time(1.0)), Tuple::point(1.0, 5.0, 0.0));
+
+        let saved = SceneDescription::from_world(&world).unwrap();
+        assert_eq!(saved.objects[0].parent.as_deref(), Some("arm"));
+        let reloaded = saved.build().unwrap();
+        assert_abs_diff_eq!(
+            origin(&reloaded.at_time(1.0)),
+            Tuple::point(1.0, 5.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+
+        let cycle = r#"{ "objects": [
+            { "type": "sphere", "name": "a", "parent": "b" },
+            { "type": "sphere", "name": "b", "parent": "a" }
+        ] }"#;
+        assert!(load_world(cycle).err().unwrap().contains("Object 1"));
+        let missing = r#"{ "objects": [{ "type": "sphere", "parent": "b" }] }"#;
+        assert!(load_world(missing).err().unwrap().contains("'b'"));
+    }
+
+    #[test]
+    fn parented_children_keep_their_pivot() {
+        let scene = |arm: &str| {
+            format!(
+                r#"{{ "objects": [
+                    {{ "type": "sphere", "name": "hand", "parent": "arm", "pivot": [1, 0, 0],
+                       "transform": [{{ "scale": [2, 2, 2] }}] }},
+                    {{ "type": "sphere", "name": "arm", "transform": {} }}
+                ] }}"#,
+                arm
+            )
+        };
+        let origin = |world: &World| {
+            world.get_by_name("hand").unwrap().transform() * Tuple::point(0.0, 0.0, 0.0)
+        };
+        let world = load_world(&scene(r#"[{ "translate": [0, 2, 0] }]"#)).unwrap();
+        assert_abs_diff_eq!(
+            origin(&world),
+            Tuple::point(-1.0, 2.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+
+        let world = load_world(&scene(r#"[{ "scale": [3, 3, 3] }, { "rotate_z": 1 }]"#)).unwrap();
+        let reloaded = SceneDescription::from_world(&world)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_abs_diff_eq!(origin(&reloaded), origin(&world), epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn shapes_inside_groups_cannot_be_animated() {
+        let json = r#"{
+            "objects": [{ "type": "group", "children": [{ "type": "sphere", "name": "ball" }] }],
+            "animation": { "objects": { "ball": { "keys": [{ "time": 0, "translate": [0, 1, 0] }] } } }
+        }"#;
+        let err = load_world(json).err().unwrap();
+        assert!(err.contains("'ball' is inside a group"), "{}", err);
+    }
+
+    #[test]
+    fn animated_scales_must_be_invertible() {
+        for scale in ["[0, 0, 0]", "[1, 0, 1]"] {
+            let json = format!(
+                r#"{{
+                    "objects": [{{ "type": "sphere", "name": "ball" }}],
+                    "animation": {{ "objects": {{ "ball": {{ "keys": [
+                        {{ "time": 0, "scale": [1, 1, 1] }},
+                        {{ "time": 1, "scale": {} }}
+                    ] }} }} }}
+                }}"#,
+                scale
+            );
+            let err = load_world(&json).err().unwrap();
+            assert!(err.contains("'ball'") && err.contains("scale"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{matrix::Matrix, shape_registry::ShapeRegistry};
+
+// A hierarchy of transforms driving the world's shapes, for articulated
+// scenes: turning a shoulder node swings the upper arm, forearm and hand
+// hanging off it. Unlike a Group, which bakes its transform into its children
+// when they're added, a node can be moved at any time. Changes are only
+// pushed to the world's shapes by update, and only for the nodes they touched.
+//
+// Every world has one, World::scene_graph, which scene files fill in from
+// their objects' "parent" names and which animation moves; see
+// World::update_scene_graph.
+#[derive(Clone, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone)]
+struct Node {
+    // Relative to the parent, or to the world for a root
+    local: Matrix,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // Top-level shape ids, each with its transform relative to the node
+    shapes: Vec<(u32, Matrix)>,
+    // The local transform with all its parents' applied, as of the last update
+    world: Matrix,
+    // Changed since the last update; everything under it moves with it
+    dirty: bool,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph::default()
+    }
+
+    pub fn add_node(&mut self, parent: Option<NodeId>, local: Matrix) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            local,
+            parent,
+            children: Vec::new(),
+            shapes: Vec::new(),
+            world: Matrix::identity(),
+            dirty: true,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        id
+    }
+
+    // Makes a top-level shape follow the node, placed by offset within it
+    pub fn attach(&mut self, node: NodeId, shape_id: u32, offset: Matrix) {
+        self.nodes[node.0].shapes.push((shape_id, offset));
+        self.mark_dirty(node);
+    }
+
+    // The node a shape is attached to, if any
+    pub fn node_of(&self, shape_id: u32) -> Option<NodeId> {
+        (0..self.nodes.len())
+            .map(NodeId)
+            .find(|node| self.shapes(*node).any(|id| id == shape_id))
+    }
+
+    // Ids of the shapes attached to the node
+    pub fn shapes(&self, node: NodeId) -> impl Iterator<Item = u32> + '_ {
+        self.nodes[node.0].shapes.iter().map(|(id, _)| *id)
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node.0].parent
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node.0].children
+    }
+
+    pub fn local_transform(&self, node: NodeId) -> &Matrix {
+        &self.nodes[node.0].local
+    }
+
+    pub fn set_local_transform(&mut self, node: NodeId, local: Matrix) {
+        self.nodes[node.0].local = local;
+        self.mark_dirty(node);
+    }
+
+    // Moves a node and everything under it to a new parent, keeping its local
+    // transform. Fails if the new parent is the node or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        let mut ancestor = parent;
+        while let Some(id) = ancestor {
+            if id == node {
+                return Err("a node can't be moved under itself".to_string());
+            }
+            ancestor = self.nodes[id.0].parent;
+        }
+
+        if let Some(old) = self.nodes[node.0].parent {
+            self.nodes[old.0].children.retain(|&child| child != node);
+        }
+        if let Some(new) = parent {
+            self.nodes[new.0].children.push(node);
+        }
+        self.nodes[node.0].parent = parent;
+        self.mark_dirty(node);
+        Ok(())
+    }
+
+    // The node's local transform with all its parents' applied
+    pub fn world_transform(&self, node: NodeId) -> Matrix {
+        let mut transform = self.nodes[node.0].local.clone();
+        let mut parent = self.nodes[node.0].parent;
+        while let Some(id) = parent {
+            transform = &self.nodes[id.0].local * &transform;
+            parent = self.nodes[id.0].parent;
+        }
+        transform
+    }
+
+    // Whether update has anything to do
+    pub fn is_dirty(&self) -> bool {
+        self.nodes.iter().any(|node| node.dirty)
+    }
+
+    // Gives the shapes under nodes that moved since the last update their new
+    // transforms, returning how many were changed. Only the moved nodes and
+    // those under them are visited; the rest keep their transforms from
+    // earlier updates.
+    pub fn update(&mut self, registry: &mut ShapeRegistry) -> usize {
+        let mut updated = 0;
+        let mut stack: Vec<(NodeId, Matrix)> = (0..self.nodes.len())
+            .map(NodeId)
+            .filter(|&id| self.nodes[id.0].dirty && !self.has_dirty_ancestor(id))
+            .map(|id| {
+                let parent = self.nodes[id.0].parent;
+                let parent_transform = parent.map_or_else(Matrix::identity, |parent| {
+                    self.nodes[parent.0].world.clone()
+                });
+                (id, parent_transform)
+            })
+            .collect();
+        while let Some((id, parent_transform)) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            node.dirty = false;
+            node.world = &parent_transform * &node.local;
+            for (shape_id, offset) in &node.shapes {
+                if registry.set_transform(*shape_id, &node.world * offset) {
+                    updated += 1;
+                }
+            }
+            for &child in &node.children {
+                stack.push((child, node.world.clone()));
+            }
+        }
+        updated
+    }
+
+    fn has_dirty_ancestor(&self, node: NodeId) -> bool {
+        let mut ancestor = self.nodes[node.0].parent;
+        while let Some(id) = ancestor {
+            if self.nodes[id.0].dirty {
+                return true;
+            }
+            ancestor = self.nodes[id.0].parent;
+        }
+        false
+    }
+
+    fn mark_dirty(&mut self, node: NodeId) {
+        self.nodes[node.0].dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::consts::FRAC_PI_2;
+    use crate::scalar::TEST_EPSILON;
+    use crate::shape::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn origin_of(world: &World, id: u32) -> Tuple {
+        world.registry.get(id).unwrap().transform() * Tuple::point(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        let mut world = World::new();
+        let hand = world.add_object(Sphere::new());
+        let mut graph = SceneGraph::new();
+        let shoulder = graph.add_node(None, Matrix::translation(0.0, 2.0, 0.0));
+        let elbow = graph.add_node(Some(shoulder), Matrix::translation(1.0, 0.0, 0.0));
+        graph.attach(elbow, hand, Matrix::translation(1.0, 0.0, 0.0));
+
+        assert_eq!(graph.update(&mut world.registry), 1);
+        assert_abs_diff_eq!(
+            origin_of(&world, hand),
+            Tuple::point(2.0, 2.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+
+        // Raising the arm swings the hand up over the shoulder
+        graph.set_local_transform(
+            shoulder,
+            &Matrix::translation(0.0, 2.0, 0.0) * &Matrix::rotation_z(FRAC_PI_2),
+        );
+        assert!(graph.is_dirty());
+        assert_eq!(graph.update(&mut world.registry), 1);
+        assert_abs_diff_eq!(
+            origin_of(&world, hand),
+            Tuple::point(0.0, 4.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            graph.world_transform(elbow) * Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, 3.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+
+        // Bending the elbow alone starts from the shoulder as last updated
+        graph.set_local_transform(elbow, Matrix::translation(2.0, 0.0, 0.0));
+        assert_eq!(graph.update(&mut world.registry), 1);
+        assert_abs_diff_eq!(
+            origin_of(&world, hand),
+            Tuple::point(0.0, 5.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn update_only_touches_shapes_under_moved_nodes() {
+        let mut world = World::new();
+        let a = world.add_object(Sphere::new());
+        let b = world.add_object(Sphere::new());
+        let mut graph = SceneGraph::new();
+        let left = graph.add_node(None, Matrix::identity());
+        let right = graph.add_node(None, Matrix::identity());
+        graph.attach(left, a, Matrix::identity());
+        graph.attach(right, b, Matrix::identity());
+        graph.update(&mut world.registry);
+
+        assert!(!graph.is_dirty());
+        assert_eq!(graph.update(&mut world.registry), 0);
+        graph.set_local_transform(right, Matrix::translation(0.0, 0.0, 3.0));
+        assert_eq!(graph.update(&mut world.registry), 1);
+        assert_eq!(origin_of(&world, a), Tuple::point(0.0, 0.0, 0.0));
+        assert_eq!(origin_of(&world, b), Tuple::point(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn nodes_can_be_reparented_but_not_under_themselves() {
+        let mut world = World::new();
+        let ball = world.add_object(Sphere::new());
+        let mut graph = SceneGraph::new();
+        let table = graph.add_node(None, Matrix::translation(5.0, 0.0, 0.0));
+        let hand = graph.add_node(None, Matrix::translation(0.0, 1.0, 0.0));
+        let held = graph.add_node(Some(hand), Matrix::identity());
+        graph.attach(held, ball, Matrix::identity());
+        graph.update(&mut world.registry);
+
+        assert!(graph.set_parent(hand, Some(held)).is_err());
+        assert!(graph.set_parent(hand, Some(hand)).is_err());
+
+        graph.set_parent(held, Some(table)).unwrap();
+        assert_eq!(graph.parent(held), Some(table));
+        assert!(graph.children(hand).is_empty());
+        graph.update(&mut world.registry);
+        assert_eq!(origin_of(&world, ball), Tuple::point(5.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{scene::SceneDescription, world::World};
+
+// A fingerprint of a render's inputs, for checking that two renders really
+// used the same scene before comparing their timings or pixels. The world is
+// described as a scene file would save it, so equivalent scenes written
+// differently (a translate step or the same matrix, keys in another order)
+// hash the same. Image textures and meshes loaded from files are identified by
+// their paths, not their contents.
+//
+// The hash is 64-bit FNV-1a over canonical JSON, printed as 16 hex digits. It
+// is the same on every platform and build, unlike std's hashers.
+pub fn scene_hash(world: &World, settings: &impl Serialize) -> Result<String, String> {
+    let inputs = serde_json::json!({
+        "scene": SceneDescription::from_world(world)?,
+        "world": {
+            "secondary_t_min": world.secondary_t_min(),
+            "shadow_bias": world.shadow_bias(),
+            "roulette_threshold": world.settings.roulette_threshold,
+            "roulette_depth": world.settings.roulette_depth,
+            "max_bounces": world.settings.max_bounces,
+            "samples": world.settings.samples,
+            "shadows": world.settings.shadows,
+            "show_bounds": world.settings.show_bounds,
+            "transparent_shadows": world.settings.transparent_shadows,
+            "integrator": world.settings.integrator,
+        },
+        "settings": serde_json::to_value(settings).map_err(|e| e.to_string())?,
+    });
+    Ok(format!(
+        "{:016x}",
+        fnv1a(canonical_json(&inputs).as_bytes())
+    ))
+}
+
+// JSON with object keys sorted and numbers in one fixed format: whole numbers
+// without a fraction, -0 as 0, anything else in Rust's shortest round-trip
+// form. 1, 1.0 and -0.0 therefore all come out as they would for an integer.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&canonical_number(n)),
+        Value::String(s) => out.push_str(&Value::String(s.clone()).to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn canonical_number(n: &serde_json::Number) -> String {
+    if let Some(i) = n.as_i64() {
+        return i.to_string();
+    }
+    if let Some(u) = n.as_u64() {
+        return u.to_string();
+    }
+    let f = n.as_f64().unwrap_or(0.0);
+    // Beyond 2^53 not every integer is a float, so keep the float form
+    if f.fract() == 0.0 && f.abs() < 9007199254740992.0 {
+        (f as i64).to_string()
+    } else {
+        format!("{:?}", f)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{colour::Colour, scene::load_world};
+
+    #[test]
+    fn numbers_and_keys_have_one_canonical_form() {
+        let a: Value = serde_json::from_str(r#"{ "b": [1.0, -0.0, 0.1], "a": 2 }"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"a":2.0,"b":[1,0,1e-1]}"#).unwrap();
+
+        assert_eq!(canonical_json(&a), r#"{"a":2,"b":[1,0,0.1]}"#);
+        assert_eq!(canonical_json(&a), canonical_json(&b));
+    }
+
+    #[test]
+    fn equivalent_scenes_hash_the_same() {
+        let a = load_world(
+            r#"{ "objects": [{ "type": "sphere", "transform": [{ "translate": [1, 0, 0] }],
+                 "material": { "ambient": 0.5 } }] }"#,
+        )
+        .unwrap();
+        let b = load_world(
+            r#"{ "objects": [{ "material": { "ambient": 0.50 }, "type": "sphere",
+                 "transform": [{ "translate": [1.0, 0.0, -0.0] }] }] }"#,
+        )
+        .unwrap();
+
+        let hash = scene_hash(&a, &()).unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, scene_hash(&b, &()).unwrap());
+    }
+
+    #[test]
+    fn any_change_to_the_inputs_changes_the_hash() {
+        let world = World::default_world();
+        let hash = scene_hash(&world, &[400, 300]).unwrap();
+
+        let mut recoloured = World::default_world();
+        let first = recoloured.registry.get_by_index(0).unwrap();
+        let (id, mut material) = (first.id(), first.material().clone());
+        material.colour = Colour::new(0.8, 1.0, 0.61);
+        recoloured
+            .registry
+            .get_mut(id)
+            .unwrap()
+            .set_material(material);
+        let mut thresholded = World::default_world();
+        thresholded.settings.roulette_threshold = 0.5;
+
+        assert_ne!(scene_hash(&recoloured, &[400, 300]).unwrap(), hash);
+        assert_ne!(scene_hash(&thresholded, &[400, 300]).unwrap(), hash);
+        assert_ne!(scene_hash(&world, &[400, 301]).unwrap(), hash);
+        assert_eq!(
+            scene_hash(&World::default_world(), &[400, 300]).unwrap(),
+            hash
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    intersection::Intersection,
+    pattern::uv_pattern::{cubic_map, CubeFace},
+    ray::Ray,
+    scalar::Float,
+    tuple::Tuple,
+    world::World,
+};
+
+// Approximate shadows for draft renders. The distance to the nearest surface
+// is traced once per texel of a cube around the light; afterwards a point is
+// in shadow if something in the map lies closer to the light than it does.
+// Shadow tests then cost a lookup instead of a ray, at the price of blocky
+// edges and missed detail smaller than a texel. The map goes stale as soon as
+// the light or any shape moves.
+#[derive(Clone)]
+pub struct ShadowMap {
+    light_position: Tuple,
+    resolution: usize,
+    // Per face in CubeFace order, rows from v = 0 upwards
+    depths: Vec<Vec<Float>>,
+}
+
+const FACES: [CubeFace; 6] = [
+    CubeFace::Right,
+    CubeFace::Left,
+    CubeFace::Up,
+    CubeFace::Down,
+    CubeFace::Front,
+    CubeFace::Back,
+];
+
+impl ShadowMap {
+    // Traces resolution x resolution rays for each face of the cube
+    pub fn build(world: &World, light_position: Tuple, resolution: usize) -> ShadowMap {
+        let resolution = resolution.max(1);
+        let depths = FACES
+            .iter()
+            .map(|&face| {
+                let mut depths = Vec::with_capacity(resolution * resolution);
+                for j in 0..resolution {
+                    for i in 0..resolution {
+                        let u = (i as Float + 0.5) / resolution as Float;
+                        let v = (j as Float + 0.5) / resolution as Float;
+                        let direction = face_direction(face, u, v).normalise();
+                        let xs = world.intersect_world(&Ray::new(light_position, direction));
+                        depths.push(nearest_caster(world, &xs));
+                    }
+                }
+                depths
+            })
+            .collect();
+
+        ShadowMap {
+            light_position,
+            resolution,
+            depths,
+        }
+    }
+
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    // Whether the map was built for a light at this position
+    pub fn is_for(&self, light_position: Tuple) -> bool {
+        let p = self.light_position;
+        (p.x, p.y, p.z) == (light_position.x, light_position.y, light_position.z)
+    }
+
+    pub fn is_shadowed(&self, point: Tuple) -> bool {
+        let to_point = point - self.light_position;
+        let distance = to_point.magnitude();
+        let largest = to_point.x.abs().max(to_point.y.abs()).max(to_point.z.abs());
+        if largest == 0.0 {
+            return false;
+        }
+
+        let on_cube = Tuple::point(
+            to_point.x / largest,
+            to_point.y / largest,
+            to_point.z / largest,
+        );
+        let (face, u, v) = cubic_map(on_cube);
+        let texel = |t: Float| ((t * self.resolution as Float) as usize).min(self.resolution - 1);
+        let depth = self.depths[face as usize][texel(v) * self.resolution + texel(u)];
+
+        // A texel covers a wider patch further from the light, so the surface
+        // that filled it can sit a little in front of points it also covers
+        let bias = distance * 4.0 / self.resolution as Float;
+        depth < distance - bias
+    }
+}
+
+// Distance to the first surface that casts shadows. Transparent ones count as
+// opaque here, whatever world.transparent_shadows says.
+fn nearest_caster(world: &World, xs: &[Intersection]) -> Float {
+    xs.iter()
+        .filter(|x| x.t >= 0.0)
+        .filter(|x| {
+            world
+                .registry
+                .get(x.object_id)
+                .is_some_and(|shape| shape.visibility().cast_shadows)
+        })
+        .map(|x| x.t)
+        .fold(Float::INFINITY, Float::min)
+}
+
+// Direction from the cube's centre through (u, v) on a face; the inverse of
+// cubic_map
+fn face_direction(face: CubeFace, u: Float, v: Float) -> Tuple {
+    let (a, b) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+    match face {
+        CubeFace::Front => Tuple::vector(a, b, 1.0),
+        CubeFace::Back => Tuple::vector(-a, b, -1.0),
+        CubeFace::Left => Tuple::vector(-1.0, b, a),
+        CubeFace::Right => Tuple::vector(1.0, b, -a),
+        CubeFace::Up => Tuple::vector(a, 1.0, -b),
+        CubeFace::Down => Tuple::vector(a, -1.0, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn face_directions_invert_the_cubic_map() {
+        for face in FACES {
+            let d = face_direction(face, 0.3, 0.8);
+            let (mapped_face, u, v) = cubic_map(Tuple::point(d.x, d.y, d.z));
+
+            assert_eq!(mapped_face, face);
+            assert_abs_diff_eq!(u, 0.3, epsilon = TEST_EPSILON);
+            assert_abs_diff_eq!(v, 0.8, epsilon = TEST_EPSILON);
+        }
+    }
+
+    #[test]
+    fn shadow_map_agrees_with_traced_shadows_away_from_edges() {
+        let w = World::default_world();
+        let light = w.lights[0].position;
+        let map = ShadowMap::build(&w, light, 128);
+
+        // The book's shadow tests: behind the spheres, beside them, and between
+        // the light and the spheres
+        for (point, shadowed) in [
+            (Tuple::point(0.0, 10.0, 0.0), false),
+            (Tuple::point(10.0, -10.0, 10.0), true),
+            (Tuple::point(-20.0, 20.0, -20.0), false),
+            (Tuple::point(-2.0, 2.0, -2.0), false),
+        ] {
+            assert_eq!(map.is_shadowed(point), shadowed, "{:?}", point);
+            assert_eq!(w.is_shadowed(point), shadowed);
+        }
+    }
+}
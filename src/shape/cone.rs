@@ -0,0 +1,328 @@
+use crate::{
+    bounds::BoundingBox,
+    core::constants::{EPSILON, PARALLEL_THRESHOLD},
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scalar::Float,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Double-napped cone with its apex at the origin, where the radius at any y
+// is |y|. Like Cylinder it can be truncated to minimum < y < maximum and
+// capped at those extents.
+#[derive(Clone)]
+pub struct Cone {
+    pub data: ShapeData,
+    pub minimum: Float,
+    pub maximum: Float,
+    pub closed: bool,
+}
+
+impl Default for Cone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cone {
+    pub fn new() -> Cone {
+        let identity = Matrix::identity();
+        Cone {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            minimum: Float::NEG_INFINITY,
+            maximum: Float::INFINITY,
+            closed: false,
+        }
+    }
+
+    pub fn truncated(minimum: Float, maximum: Float, closed: bool) -> Cone {
+        let mut cone = Cone::new();
+        cone.minimum = minimum;
+        cone.maximum = maximum;
+        cone.closed = closed;
+        cone
+    }
+
+    // Checks if the intersection at t is within the cone's radius at the cap
+    // height
+    fn check_cap(ray: &Ray, t: Float, radius: Float) -> bool {
+        let x = ray.origin.x + t * ray.direction.x;
+        let z = ray.origin.z + t * ray.direction.z;
+        (x * x + z * z) <= radius * radius + EPSILON
+    }
+
+    fn intersect_caps(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if !self.closed || ray.direction.y.abs() < PARALLEL_THRESHOLD {
+            return;
+        }
+
+        let t = (self.minimum - ray.origin.y) / ray.direction.y;
+        if Cone::check_cap(ray, t, self.minimum.abs()) {
+            xs.push(Intersection::new(t, self));
+        }
+
+        let t = (self.maximum - ray.origin.y) / ray.direction.y;
+        if Cone::check_cap(ray, t, self.maximum.abs()) {
+            xs.push(Intersection::new(t, self));
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        let (o, d) = (ray.origin, ray.direction);
+
+        let a = d.x * d.x - d.y * d.y + d.z * d.z;
+        let b = 2.0 * o.x * d.x - 2.0 * o.y * d.y + 2.0 * o.z * d.z;
+        let c = o.x * o.x - o.y * o.y + o.z * o.z;
+
+        if a.abs() < PARALLEL_THRESHOLD {
+            // Ray is parallel to one of the nappes, so it can hit the other at
+            // most once
+            if b.abs() >= EPSILON {
+                let t = -c / (2.0 * b);
+                let y = o.y + t * d.y;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(Intersection::new(t, self));
+                }
+            }
+        } else {
+            // Grazing rays can round to just below zero
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < -EPSILON {
+                return;
+            }
+
+            let sqrt_discriminant = discriminant.max(0.0).sqrt();
+            let mut t0 = (-b - sqrt_discriminant) / (2.0 * a);
+            let mut t1 = (-b + sqrt_discriminant) / (2.0 * a);
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            let y0 = o.y + t0 * d.y;
+            if self.minimum < y0 && y0 < self.maximum {
+                xs.push(Intersection::new(t0, self));
+            }
+
+            let y1 = o.y + t1 * d.y;
+            if self.minimum < y1 && y1 < self.maximum {
+                xs.push(Intersection::new(t1, self));
+            }
+        }
+
+        self.intersect_caps(ray, xs);
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        let dist = local_point.x * local_point.x + local_point.z * local_point.z;
+
+        if dist < self.maximum * self.maximum && local_point.y >= self.maximum - EPSILON {
+            return Tuple::vector(0.0, 1.0, 0.0);
+        }
+        if dist < self.minimum * self.minimum && local_point.y <= self.minimum + EPSILON {
+            return Tuple::vector(0.0, -1.0, 0.0);
+        }
+
+        // The surface is degenerate at the apex, so fall back to the cone's axis
+        if dist < EPSILON && local_point.y.abs() < EPSILON {
+            return Tuple::vector(0.0, 1.0, 0.0);
+        }
+
+        let mut y = dist.sqrt();
+        if local_point.y > 0.0 {
+            y = -y;
+        }
+        Tuple::vector(local_point.x, y, local_point.z)
+    }
+
+    // The radius at any height equals |y|, so the widest point is at whichever
+    // end is further out
+    fn bounds(&self) -> BoundingBox {
+        let limit = self.minimum.abs().max(self.maximum.abs());
+        BoundingBox::new(
+            Tuple::point(-limit, self.minimum, -limit),
+            Tuple::point(limit, self.maximum, limit),
+        )
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Cone {
+            minimum: Some(self.minimum).filter(|m| m.is_finite()),
+            maximum: Some(self.maximum).filter(|m| m.is_finite()),
+            closed: self.closed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn intersecting_cone_with_ray() {
+        let shape = Cone::new();
+        let test_cases = [
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                5.0,
+                5.0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(1.0, 1.0, 1.0),
+                8.66025,
+                8.66025,
+            ),
+            (
+                Tuple::point(1.0, 1.0, -5.0),
+                Tuple::vector(-0.5, -1.0, 1.0),
+                4.55006,
+                49.44994,
+            ),
+        ];
+
+        for (origin, direction, t0, t1) in test_cases {
+            let r = Ray::new(origin, direction.normalise());
+            let xs = shape.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, t0, epsilon = 0.0001);
+            assert_abs_diff_eq!(xs[1].t, t1, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn ray_hits_one_nappe_only() {
+        let shape = Cone::truncated(0.0, 2.0, false);
+        let r = Ray::new(Tuple::point(0.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = shape.local_intersect(&r);
+
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.0, epsilon = 0.0001);
+        assert_abs_diff_eq!(xs[1].t, 6.0, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn ray_hits_both_nappes() {
+        let shape = Cone::truncated(-2.0, 2.0, false);
+        let r = Ray::new(Tuple::point(0.0, -5.0, 0.5), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = shape.local_intersect(&r);
+
+        assert_eq!(xs.len(), 2);
+        let (lower, upper) = (r.position(xs[0].t), r.position(xs[1].t));
+        assert_abs_diff_eq!(lower.y, -0.5, epsilon = 0.0001);
+        assert_abs_diff_eq!(upper.y, 0.5, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn intersecting_cone_with_ray_parallel_to_one_of_its_halves() {
+        let shape = Cone::new();
+        let direction = Tuple::vector(0.0, 1.0, 1.0).normalise();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -1.0), direction);
+        let xs = shape.local_intersect(&r);
+
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 0.35355, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn intersecting_cone_end_caps() {
+        let shape = Cone::truncated(-0.5, 0.5, true);
+        let test_cases = [
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -0.25),
+                Tuple::vector(0.0, 1.0, 1.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -0.25),
+                Tuple::vector(0.0, 1.0, 0.0),
+                4,
+            ),
+        ];
+
+        for (origin, direction, count) in test_cases {
+            let r = Ray::new(origin, direction.normalise());
+            let xs = shape.local_intersect(&r);
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn computing_normal_vector_on_cone() {
+        let shape = Cone::new();
+        let test_cases = [
+            (
+                Tuple::point(1.0, 1.0, 1.0),
+                Tuple::vector(1.0, -(Float::sqrt(2.0)), 1.0),
+            ),
+            (Tuple::point(-1.0, -1.0, 0.0), Tuple::vector(-1.0, 1.0, 0.0)),
+        ];
+
+        for (point, normal) in test_cases {
+            assert_abs_diff_eq!(shape.local_normal_at(&point), normal, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn normal_at_apex_is_well_defined() {
+        let mut shape = Cone::new();
+        shape.set_transform(Matrix::translation(0.0, 1.0, 0.0));
+        let n = shape.normal_at(&Tuple::point(0.0, 1.0, 0.0));
+
+        assert_abs_diff_eq!(n, Tuple::vector(0.0, 1.0, 0.0), epsilon = 0.0001);
+    }
+
+    #[test]
+    fn normal_vector_on_cone_end_caps() {
+        let shape = Cone::truncated(-1.0, 2.0, true);
+        let test_cases = [
+            (Tuple::point(0.0, -1.0, 0.5), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.5, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(1.5, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+        ];
+
+        for (point, normal) in test_cases {
+            assert_eq!(shape.local_normal_at(&point), normal);
+        }
+    }
+
+    #[test]
+    fn bounded_cone_has_bounding_box() {
+        let c = Cone::truncated(-5.0, 3.0, false);
+        let b = c.bounds();
+
+        assert_eq!(b.min, Tuple::point(-5.0, -5.0, -5.0));
+        assert_eq!(b.max, Tuple::point(5.0, 3.0, 5.0));
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    bounds::BoundingBox,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scene::{CsgOperationDescription, ObjectDescription, ShapeDescription},
+    shape::{nearest_child_normal, LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    // Decides whether a hit on one child survives, given whether it's on the left
+    // child and whether the ray is currently inside the left and right children
+    pub fn intersection_allowed(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => (left_hit && !in_right) || (!left_hit && !in_left),
+            CsgOperation::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+            CsgOperation::Difference => (left_hit && !in_right) || (!left_hit && in_left),
+        }
+    }
+}
+
+// Combines two child shapes. Children keep their own materials, and their
+// transforms are stored in world space: setting the CSG's transform re-applies
+// it on top of each child's transform.
+#[derive(Clone)]
+pub struct Csg {
+    pub data: ShapeData,
+    pub operation: CsgOperation,
+    pub left: Box<dyn Shape>,
+    pub right: Box<dyn Shape>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+        let identity = Matrix::identity();
+        Csg {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+
+    // Expects xs sorted by t
+    pub fn filter_intersections(&self, mut xs: Vec<Intersection>) -> Vec<Intersection> {
+        self.filter_from(&mut xs, 0);
+        xs
+    }
+
+    // Filters the sorted intersections from start onwards in place, leaving
+    // the ones before it alone
+    fn filter_from(&self, xs: &mut Vec<Intersection>, start: usize) {
+        let mut in_left = false;
+        let mut in_right = false;
+
+        let mut kept = start;
+        for index in start..xs.len() {
+            let left_hit = self.left.includes(xs[index].object_id);
+
+            if self
+                .operation
+                .intersection_allowed(left_hit, in_left, in_right)
+            {
+                xs.swap(kept, index);
+                kept += 1;
+            }
+
+            if left_hit {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+        }
+        xs.truncate(kept);
+    }
+}
+
+impl Shape for Csg {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        // Undo the previous transform on the children before applying the new one
+        let transform = self.data.about_pivot(transform);
+        let delta = &transform * &self.data.inverse_transform;
+        for child in self.children_mut() {
+            let child_transform = child.data().without_pivot(&(&delta * child.transform()));
+            child.set_transform(child_transform);
+        }
+
+        self.data.set_transform(transform);
+    }
+
+    fn children(&self) -> Vec<&dyn Shape> {
+        vec![self.left.as_ref(), self.right.as_ref()]
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn Shape> {
+        vec![self.left.as_mut(), self.right.as_mut()]
+    }
+
+    // Children already carry world-space transforms, so intersect them directly
+    fn intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        let start = xs.len();
+        self.left.intersect_into(ray, xs);
+        self.right.intersect_into(ray, xs);
+        xs[start..].sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+
+        self.filter_from(xs, start);
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, xs: &mut Vec<Intersection>) {
+        self.intersect_into(&local_ray.transform(&self.data.transform), xs)
+    }
+
+    // Hits always reference one of the children, which compute their own
+    // normals, so this is only for callers asking the CSG itself
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        nearest_child_normal(self, local_point)
+    }
+
+    fn has_surface(&self) -> bool {
+        false
+    }
+
+    // Children already carry world-space transforms
+    fn world_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for child in self.children() {
+            bounds.add_box(&child.world_bounds());
+        }
+        bounds
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.world_bounds().transform(&self.data.inverse_transform)
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        let operation = match self.operation {
+            CsgOperation::Union => CsgOperationDescription::Union,
+            CsgOperation::Intersection => CsgOperationDescription::Intersection,
+            CsgOperation::Difference => CsgOperationDescription::Difference,
+        };
+        Ok(ShapeDescription::Csg {
+            operation,
+            left: Box::new(ObjectDescription::from_shape(
+                self.left.as_ref(),
+                &self.data.transform,
+            )?),
+            right: Box::new(ObjectDescription::from_shape(
+                self.right.as_ref(),
+                &self.data.transform,
+            )?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::{Float, TEST_EPSILON};
+    use crate::shape::{plane::Plane, sphere::Sphere};
+    use crate::shape_registry::ShapeRegistry;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn a_csg_asked_for_its_normal_gives_the_nearest_childs() {
+        let mut left = Sphere::new();
+        left.set_transform(Matrix::translation(-3.0, 0.0, 0.0));
+        let mut right = Sphere::new();
+        right.set_transform(Matrix::scaling(2.0, 2.0, 2.0).translate(3.0, 0.0, 0.0));
+        let mut c = Csg::union(Box::new(left), Box::new(right));
+        c.set_transform(Matrix::translation(0.0, 0.0, 10.0));
+
+        assert_abs_diff_eq!(
+            c.normal_at(&Tuple::point(-4.0, 0.0, 10.0)),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            c.normal_at(&Tuple::point(3.0, 2.0, 10.0)),
+            Tuple::vector(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn csg_is_created_with_an_operation_and_two_shapes() {
+        let mut registry = ShapeRegistry::new();
+        let c = Csg::union(Box::new(Sphere::new()), Box::new(Plane::new()));
+        let id = registry.register(c);
+
+        let c = registry.get(id).unwrap();
+        let children = c.children();
+        assert_eq!(children.len(), 2);
+        assert_ne!(children[0].id(), children[1].id());
+        assert!(registry.get(children[0].id()).is_some());
+        assert!(registry.get(children[1].id()).is_some());
+    }
+
+    #[test]
+    fn evaluating_rule_for_csg_operation() {
+        use CsgOperation::*;
+        let test_cases = [
+            (Union, true, true, true, false),
+            (Union, true, true, false, true),
+            (Union, true, false, true, false),
+            (Union, true, false, false, true),
+            (Union, false, true, true, false),
+            (Union, false, true, false, false),
+            (Union, false, false, true, true),
+            (Union, false, false, false, true),
+            (Intersection, true, true, true, true),
+            (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true),
+            (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true),
+            (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false),
+            (Intersection, false, false, false, false),
+            (Difference, true, true, true, false),
+            (Difference, true, true, false, true),
+            (Difference, true, false, true, false),
+            (Difference, true, false, false, true),
+            (Difference, false, true, true, true),
+            (Difference, false, true, false, true),
+            (Difference, false, false, true, false),
+            (Difference, false, false, false, false),
+        ];
+
+        for (op, lhit, inl, inr, expected) in test_cases {
+            assert_eq!(
+                op.intersection_allowed(lhit, inl, inr),
+                expected,
+                "{:?} lhit={} inl={} inr={}",
+                op,
+                lhit,
+                inl,
+                inr
+            );
+        }
+    }
+
+    #[test]
+    fn filtering_list_of_intersections() {
+        let test_cases = [
+            (CsgOperation::Union, 0, 3),
+            (CsgOperation::Intersection, 1, 2),
+            (CsgOperation::Difference, 0, 1),
+        ];
+
+        for (op, x0, x1) in test_cases {
+            let mut c = Csg::new(op, Box::new(Sphere::new()), Box::new(Sphere::new()));
+            c.left.data_mut().set_id(1);
+            c.right.data_mut().set_id(2);
+
+            let xs = vec![
+                Intersection::new(1.0, c.left.as_ref()),
+                Intersection::new(2.0, c.right.as_ref()),
+                Intersection::new(3.0, c.left.as_ref()),
+                Intersection::new(4.0, c.right.as_ref()),
+            ];
+
+            let result = c.filter_intersections(xs.clone());
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0], xs[x0]);
+            assert_eq!(result[1], xs[x1]);
+        }
+    }
+
+    #[test]
+    fn ray_misses_csg_object() {
+        let c = Csg::union(Box::new(Sphere::new()), Box::new(Sphere::new()));
+        let r = Ray::new(Tuple::point(0.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(c.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_hits_csg_object() {
+        let s1 = Sphere::new();
+        let mut s2 = Sphere::new();
+        s2.set_transform(Matrix::translation(0.0, 0.0, 0.5));
+
+        let mut registry = ShapeRegistry::new();
+        let id = registry.register(Csg::union(Box::new(s1), Box::new(s2)));
+        let c = registry.get(id).unwrap();
+        let (left_id, right_id) = (c.children()[0].id(), c.children()[1].id());
+
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = c.intersect(&r);
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[0].object_id, left_id);
+        assert_eq!(xs[1].t, 6.5);
+        assert_eq!(xs[1].object_id, right_id);
+    }
+
+    #[test]
+    fn intersecting_into_a_buffer_leaves_earlier_entries_alone() {
+        let s1 = Sphere::new();
+        let mut s2 = Sphere::new();
+        s2.set_transform(Matrix::translation(0.0, 0.0, 0.5));
+
+        let mut registry = ShapeRegistry::new();
+        let id = registry.register(Csg::intersection(Box::new(s1), Box::new(s2)));
+        let c = registry.get(id).unwrap();
+
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut xs = vec![Intersection::new(100.0, c)];
+        c.intersect_into(&r, &mut xs);
+
+        let ts: Vec<Float> = xs.iter().map(|x| x.t).collect();
+        assert_eq!(ts, vec![100.0, 4.5, 6.0]);
+    }
+
+    #[test]
+    fn transforming_csg_moves_its_children() {
+        let mut right = Sphere::new();
+        right.set_transform(Matrix::translation(0.0, 0.0, 0.5));
+        let mut c = Csg::difference(Box::new(Sphere::new()), Box::new(right));
+
+        c.set_transform(Matrix::translation(5.0, 0.0, 0.0));
+        assert_eq!(*c.left.transform(), Matrix::translation(5.0, 0.0, 0.0));
+        assert_eq!(*c.right.transform(), Matrix::translation(5.0, 0.0, 0.5));
+
+        c.set_transform(Matrix::translation(0.0, 1.0, 0.0));
+        assert_eq!(*c.left.transform(), Matrix::translation(0.0, 1.0, 0.0));
+        assert_eq!(*c.right.transform(), Matrix::translation(0.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn normal_on_transformed_csg_child_is_in_world_space() {
+        let mut c = Csg::union(Box::new(Sphere::new()), Box::new(Sphere::new()));
+        c.set_transform(Matrix::translation(0.0, 2.0, 0.0));
+
+        let n = c.left.normal_at(&Tuple::point(0.0, 3.0, 0.0));
+
+        assert_eq!(n, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn world_shades_hits_on_csg_children() {
+        use crate::{colour::Colour, light::Light, materials::Material, world::World};
+
+        let mut w = World::new();
+        w.lights = vec![Light::point_light(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Colour::white(),
+        )];
+
+        let left = Sphere::new();
+        let mut right = Sphere::new();
+        let mut material = Material::new();
+        material.colour = Colour::new(1.0, 0.0, 0.0);
+        material.specular = 0.0;
+        right.set_material(material);
+        right.set_transform(Matrix::translation(0.0, 0.0, -1.0));
+        w.add_object(Csg::difference(Box::new(left), Box::new(right)));
+
+        // The front of the left sphere has been carved away, so the ray ends up
+        // hitting the inside of the right sphere's surface, shaded with its material
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = w.intersect_world(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 5.0);
+
+        let c = w.colour_at(&r, 0);
+        assert!(c.r > 0.0);
+        assert_eq!(c.g, 0.0);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{
+    bounds::BoundingBox,
+    core::constants::{EPSILON, PARALLEL_THRESHOLD},
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scalar::Float,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Unit-radius cylinder centred on the y axis, optionally truncated to
+// minimum < y < maximum and optionally capped at those extents.
+#[derive(Clone)]
+pub struct Cylinder {
+    pub data: ShapeData,
+    pub minimum: Float,
+    pub maximum: Float,
+    pub closed: bool,
+}
+
+impl Default for Cylinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cylinder {
+    pub fn new() -> Cylinder {
+        let identity = Matrix::identity();
+        Cylinder {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            minimum: Float::NEG_INFINITY,
+            maximum: Float::INFINITY,
+            closed: false,
+        }
+    }
+
+    pub fn truncated(minimum: Float, maximum: Float, closed: bool) -> Cylinder {
+        let mut cylinder = Cylinder::new();
+        cylinder.minimum = minimum;
+        cylinder.maximum = maximum;
+        cylinder.closed = closed;
+        cylinder
+    }
+
+    // Checks if the intersection at t is within the unit radius of the y axis
+    fn check_cap(ray: &Ray, t: Float) -> bool {
+        let x = ray.origin.x + t * ray.direction.x;
+        let z = ray.origin.z + t * ray.direction.z;
+        (x * x + z * z) <= 1.0 + EPSILON
+    }
+
+    fn intersect_caps(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if !self.closed || ray.direction.y.abs() < PARALLEL_THRESHOLD {
+            return;
+        }
+
+        let t = (self.minimum - ray.origin.y) / ray.direction.y;
+        if Cylinder::check_cap(ray, t) {
+            xs.push(Intersection::new(t, self));
+        }
+
+        let t = (self.maximum - ray.origin.y) / ray.direction.y;
+        if Cylinder::check_cap(ray, t) {
+            xs.push(Intersection::new(t, self));
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        let a = ray.direction.x * ray.direction.x + ray.direction.z * ray.direction.z;
+
+        // Rays parallel to the y axis can only hit the caps
+        if a.abs() >= PARALLEL_THRESHOLD {
+            let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
+            let c = ray.origin.x * ray.origin.x + ray.origin.z * ray.origin.z - 1.0;
+
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return;
+            }
+
+            let sqrt_discriminant = discriminant.sqrt();
+            let mut t0 = (-b - sqrt_discriminant) / (2.0 * a);
+            let mut t1 = (-b + sqrt_discriminant) / (2.0 * a);
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            let y0 = ray.origin.y + t0 * ray.direction.y;
+            if self.minimum < y0 && y0 < self.maximum {
+                xs.push(Intersection::new(t0, self));
+            }
+
+            let y1 = ray.origin.y + t1 * ray.direction.y;
+            if self.minimum < y1 && y1 < self.maximum {
+                xs.push(Intersection::new(t1, self));
+            }
+        }
+
+        self.intersect_caps(ray, xs);
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        let dist = local_point.x * local_point.x + local_point.z * local_point.z;
+
+        if dist < 1.0 && local_point.y >= self.maximum - EPSILON {
+            Tuple::vector(0.0, 1.0, 0.0)
+        } else if dist < 1.0 && local_point.y <= self.minimum + EPSILON {
+            Tuple::vector(0.0, -1.0, 0.0)
+        } else {
+            Tuple::vector(local_point.x, 0.0, local_point.z)
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(-1.0, self.minimum, -1.0),
+            Tuple::point(1.0, self.maximum, 1.0),
+        )
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Cylinder {
+            minimum: Some(self.minimum).filter(|m| m.is_finite()),
+            maximum: Some(self.maximum).filter(|m| m.is_finite()),
+            closed: self.closed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn ray_misses_cylinder() {
+        let cyl = Cylinder::new();
+        let test_cases = [
+            (Tuple::point(1.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 0.0, -5.0), Tuple::vector(1.0, 1.0, 1.0)),
+        ];
+
+        for (origin, direction) in test_cases {
+            let r = Ray::new(origin, direction.normalise());
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), 0);
+        }
+    }
+
+    #[test]
+    fn ray_strikes_cylinder() {
+        let cyl = Cylinder::new();
+        let test_cases = [
+            (
+                Tuple::point(1.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                5.0,
+                5.0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, 0.0, -5.0),
+                Tuple::vector(0.1, 1.0, 1.0),
+                6.80798,
+                7.08872,
+            ),
+        ];
+
+        for (origin, direction, t0, t1) in test_cases {
+            let r = Ray::new(origin, direction.normalise());
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, t0, epsilon = 0.0001);
+            assert_abs_diff_eq!(xs[1].t, t1, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn normal_vector_on_cylinder() {
+        let cyl = Cylinder::new();
+        let test_cases = [
+            (Tuple::point(1.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0)),
+            (Tuple::point(0.0, 5.0, -1.0), Tuple::vector(0.0, 0.0, -1.0)),
+            (Tuple::point(0.0, -2.0, 1.0), Tuple::vector(0.0, 0.0, 1.0)),
+            (Tuple::point(-1.0, 1.0, 0.0), Tuple::vector(-1.0, 0.0, 0.0)),
+        ];
+
+        for (point, normal) in test_cases {
+            assert_eq!(cyl.local_normal_at(&point), normal);
+        }
+    }
+
+    #[test]
+    fn default_minimum_and_maximum_for_cylinder() {
+        let cyl = Cylinder::new();
+
+        assert_eq!(cyl.minimum, Float::NEG_INFINITY);
+        assert_eq!(cyl.maximum, Float::INFINITY);
+    }
+
+    #[test]
+    fn default_closed_value_for_cylinder() {
+        let cyl = Cylinder::new();
+
+        assert!(!cyl.closed);
+    }
+
+    #[test]
+    fn intersecting_constrained_cylinder() {
+        let cyl = Cylinder::truncated(1.0, 2.0, false);
+        let test_cases = [
+            (Tuple::point(0.0, 1.5, 0.0), Tuple::vector(0.1, 1.0, 0.0), 0),
+            (
+                Tuple::point(0.0, 3.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 2.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 1.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 1.5, -2.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                2,
+            ),
+        ];
+
+        for (point, direction, count) in test_cases {
+            let r = Ray::new(point, direction.normalise());
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn intersecting_caps_of_closed_cylinder() {
+        let cyl = Cylinder::truncated(1.0, 2.0, true);
+        let test_cases = [
+            (
+                Tuple::point(0.0, 3.0, 0.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 3.0, -2.0),
+                Tuple::vector(0.0, -1.0, 2.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 4.0, -2.0),
+                Tuple::vector(0.0, -1.0, 1.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -2.0),
+                Tuple::vector(0.0, 1.0, 2.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, -1.0, -2.0),
+                Tuple::vector(0.0, 1.0, 1.0),
+                2,
+            ),
+        ];
+
+        for (point, direction, count) in test_cases {
+            let r = Ray::new(point, direction.normalise());
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), count);
+        }
+    }
+
+    #[test]
+    fn normal_vector_on_cylinder_end_caps() {
+        let cyl = Cylinder::truncated(1.0, 2.0, true);
+        let test_cases = [
+            (Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.5, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.0, 1.0, 0.5), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.0, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.5, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 2.0, 0.5), Tuple::vector(0.0, 1.0, 0.0)),
+        ];
+
+        for (point, normal) in test_cases {
+            assert_eq!(cyl.local_normal_at(&point), normal);
+        }
+    }
+
+    #[test]
+    fn transformed_cylinder_normal_on_wall_is_normalised() {
+        let mut cyl = Cylinder::truncated(-1.0, 1.0, true);
+        cyl.set_transform(Matrix::scaling(2.0, 1.0, 2.0));
+        let n = cyl.normal_at(&Tuple::point(2.0, 0.0, 0.0));
+
+        assert_abs_diff_eq!(n, Tuple::vector(1.0, 0.0, 0.0), epsilon = 0.0001);
+    }
+
+    #[test]
+    fn bounded_cylinder_has_bounding_box() {
+        let c = Cylinder::truncated(-5.0, 3.0, false);
+        let b = c.bounds();
+
+        assert_eq!(b.min, Tuple::point(-1.0, -5.0, -1.0));
+        assert_eq!(b.max, Tuple::point(1.0, 3.0, 1.0));
+    }
+}
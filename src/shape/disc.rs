@@ -0,0 +1,107 @@
+use crate::{
+    bounds::BoundingBox,
+    core::constants::PARALLEL_THRESHOLD,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// A flat circle of radius 1 on the xz plane, centred on the origin and
+// facing up
+#[derive(Clone)]
+pub struct Disc {
+    pub data: ShapeData,
+}
+
+impl Default for Disc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Disc {
+    pub fn new() -> Disc {
+        let identity = Matrix::identity();
+        Disc {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+        }
+    }
+}
+
+impl Shape for Disc {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if ray.direction.y.abs() < PARALLEL_THRESHOLD {
+            return;
+        }
+
+        let t = -ray.origin.y / ray.direction.y;
+        let point = ray.position(t);
+        if point.x * point.x + point.z * point.z > 1.0 {
+            return;
+        }
+        xs.push(Intersection::new(t, self));
+    }
+
+    fn local_normal_at(&self, _local_point: &Tuple) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1.0, 0.0, -1.0), Tuple::point(1.0, 0.0, 1.0))
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Disc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn ray_hits_inside_the_circle() {
+        let d = Disc::new();
+        let r = Ray::new(Tuple::point(0.7, -2.0, 0.7), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = d.local_intersect(&r);
+
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 2.0);
+        assert_eq!(
+            d.local_normal_at(&Tuple::point(0.7, 0.0, 0.7)),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn ray_misses_the_corners_of_the_bounding_square() {
+        let d = Disc::new();
+        let r = Ray::new(Tuple::point(0.8, 1.0, 0.8), Tuple::vector(0.0, -1.0, 0.0));
+
+        assert!(d.local_intersect(&r).is_empty());
+    }
+}
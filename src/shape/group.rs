@@ -0,0 +1,333 @@
+use crate::{
+    bounds::BoundingBox,
+    bvh::Bvh,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scene::{ObjectDescription, ShapeDescription},
+    shape::{nearest_child_normal, LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Collection of shapes that are transformed together. As with CSG, children
+// store world-space transforms: adding a child or transforming the group
+// applies the group's transform on top of the child's own.
+#[derive(Clone)]
+pub struct Group {
+    pub data: ShapeData,
+    pub children: Vec<Box<dyn Shape>>,
+    // Optional acceleration structure over the children; see build_bvh
+    bvh: Option<Bvh>,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Group {
+    pub fn new() -> Group {
+        let identity = Matrix::identity();
+        Group {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            children: Vec::new(),
+            bvh: None,
+        }
+    }
+
+    pub fn add_child(&mut self, mut child: Box<dyn Shape>) {
+        let child_transform = &self.data.transform * child.transform();
+        child.set_transform(child.data().without_pivot(&child_transform));
+        self.children.push(child);
+        self.bvh = None;
+    }
+
+    // Worth doing for large groups such as meshes, so rays skip most children
+    pub fn build_bvh(&mut self) {
+        let bounds: Vec<_> = self.children.iter().map(|c| c.bvh_bounds()).collect();
+        self.bvh = Some(Bvh::build(&bounds));
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+impl Shape for Group {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        let transform = self.data.about_pivot(transform);
+        let delta = &transform * &self.data.inverse_transform;
+        for child in self.children_mut() {
+            let child_transform = child.data().without_pivot(&(&delta * child.transform()));
+            child.set_transform(child_transform);
+        }
+
+        self.data.set_transform(transform);
+
+        if self.bvh.is_some() {
+            self.build_bvh();
+        }
+    }
+
+    // Groups have no surface of their own, so a material set on the group is
+    // handed down to everything inside it
+    fn set_material(&mut self, material: Material) {
+        for child in self.children_mut() {
+            child.set_material(material.clone());
+        }
+        self.data.material = material;
+    }
+
+    fn children(&self) -> Vec<&dyn Shape> {
+        self.children.iter().map(|child| child.as_ref()).collect()
+    }
+
+    fn child(&self, index: usize) -> Option<&dyn Shape> {
+        self.children.get(index).map(|child| child.as_ref())
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn Shape> {
+        self.children
+            .iter_mut()
+            .map(|child| child.as_mut() as &mut dyn Shape)
+            .collect()
+    }
+
+    fn intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        let start = xs.len();
+        match &self.bvh {
+            // Children share the group's velocity, so the tree is searched
+            // where they were when the shutter opened
+            Some(bvh) => bvh.traverse(
+                &Ray {
+                    origin: self.data.at_rest(ray.origin, ray.time),
+                    ..*ray
+                },
+                |index| self.children[index].intersect_into(ray, xs),
+            ),
+            None => {
+                for child in &self.children {
+                    child.intersect_into(ray, xs);
+                }
+            }
+        }
+        xs[start..].sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, xs: &mut Vec<Intersection>) {
+        self.intersect_into(&local_ray.transform(&self.data.transform), xs)
+    }
+
+    // Hits reference the child they're on, so this is only for callers asking
+    // the group itself
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        nearest_child_normal(self, local_point)
+    }
+
+    fn has_surface(&self) -> bool {
+        false
+    }
+
+    // Children already carry world-space transforms
+    fn world_bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        for child in self.children() {
+            bounds.add_box(&child.world_bounds());
+        }
+        bounds
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.world_bounds().transform(&self.data.inverse_transform)
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        let children = self
+            .children
+            .iter()
+            .map(|child| ObjectDescription::from_shape(child.as_ref(), &self.data.transform))
+            .collect::<Result<_, _>>()?;
+        Ok(ShapeDescription::Group { children })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Float;
+    use crate::scalar::TEST_EPSILON;
+    use crate::shape::sphere::Sphere;
+    use crate::shape_registry::ShapeRegistry;
+
+    #[test]
+    fn creating_a_new_group() {
+        let g = Group::new();
+
+        assert_eq!(*g.transform(), Matrix::identity());
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    fn intersecting_ray_with_empty_group() {
+        let g = Group::new();
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(g.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn intersecting_ray_with_nonempty_group() {
+        let s1 = Sphere::new();
+        let mut s2 = Sphere::new();
+        s2.set_transform(Matrix::translation(0.0, 0.0, -3.0));
+        let mut s3 = Sphere::new();
+        s3.set_transform(Matrix::translation(5.0, 0.0, 0.0));
+
+        let mut g = Group::new();
+        g.add_child(Box::new(s1));
+        g.add_child(Box::new(s2));
+        g.add_child(Box::new(s3));
+
+        let mut registry = ShapeRegistry::new();
+        let id = registry.register(g);
+        let g = registry.get(id).unwrap();
+        let children = g.children();
+
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = g.intersect(&r);
+
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].object_id, children[1].id());
+        assert_eq!(xs[1].object_id, children[1].id());
+        assert_eq!(xs[2].object_id, children[0].id());
+        assert_eq!(xs[3].object_id, children[0].id());
+    }
+
+    #[test]
+    fn intersecting_transformed_group() {
+        let mut g = Group::new();
+        g.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::translation(5.0, 0.0, 0.0));
+        g.add_child(Box::new(s));
+
+        let r = Ray::new(Tuple::point(10.0, 0.0, -10.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn normal_on_child_object() {
+        use crate::scalar::consts::PI;
+        use approx::assert_abs_diff_eq;
+
+        let mut g1 = Group::new();
+        g1.set_transform(Matrix::rotation_y(PI / 2.0));
+        let mut g2 = Group::new();
+        g2.set_transform(Matrix::scaling(1.0, 2.0, 3.0));
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::translation(5.0, 0.0, 0.0));
+        g2.add_child(Box::new(s));
+        g1.add_child(Box::new(g2));
+
+        let s = g1.children()[0].children()[0];
+        let n = s.normal_at(&Tuple::point(1.7321, 1.1547, -5.5774));
+
+        assert_abs_diff_eq!(n, Tuple::vector(0.2857, 0.4286, -0.8571), epsilon = 0.0001);
+
+        // Asking the groups themselves gives the same
+        let n = g1.normal_at(&Tuple::point(1.7321, 1.1547, -5.5774));
+        assert_abs_diff_eq!(n, Tuple::vector(0.2857, 0.4286, -0.8571), epsilon = 0.0001);
+    }
+
+    #[test]
+    fn material_set_on_group_applies_to_children() {
+        let mut g = Group::new();
+        g.add_child(Box::new(Sphere::new()));
+        let mut m = Material::new();
+        m.ambient = 1.0;
+
+        g.set_material(m);
+
+        assert_eq!(g.children()[0].material().ambient, 1.0);
+    }
+
+    #[test]
+    fn group_bounds_contain_transformed_children() {
+        use approx::assert_abs_diff_eq;
+
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::translation(2.0, 5.0, -3.0) * Matrix::scaling(2.0, 2.0, 2.0));
+        let mut g = Group::new();
+        g.add_child(Box::new(s));
+        g.set_transform(Matrix::translation(1.0, 0.0, 0.0));
+
+        let b = g.world_bounds();
+        assert_abs_diff_eq!(b.min, Tuple::point(1.0, 3.0, -5.0));
+        assert_abs_diff_eq!(b.max, Tuple::point(5.0, 7.0, -1.0));
+    }
+
+    #[test]
+    fn group_with_bvh_finds_the_same_hits() {
+        let mut g = Group::new();
+        for i in 0..20 {
+            let mut s = Sphere::new();
+            s.set_transform(Matrix::translation(0.0, 0.0, i as Float * 3.0));
+            g.add_child(Box::new(s));
+        }
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let expected: Vec<Float> = g.intersect(&r).iter().map(|i| i.t).collect();
+
+        g.build_bvh();
+        let ts: Vec<Float> = g.intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts, expected);
+        assert_eq!(ts.len(), 40);
+
+        // Moving the group keeps the tree in step with the children
+        g.set_transform(Matrix::translation(0.0, 10.0, 0.0));
+        assert!(g.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn children_keep_their_pivot_when_the_group_moves() {
+        use crate::scalar::consts::PI;
+        use approx::assert_abs_diff_eq;
+
+        let mut s = Sphere::new();
+        s.set_pivot(Some(Tuple::point(1.0, 0.0, 0.0)));
+        s.set_transform(Matrix::rotation_z(PI));
+
+        let mut g = Group::new();
+        g.add_child(Box::new(s));
+        g.set_transform(Matrix::translation(0.0, 3.0, 0.0));
+
+        // Spun half a turn about x = 1, then lifted with the group
+        let centre = g.children[0].transform() * Tuple::point(0.0, 0.0, 0.0);
+        assert_abs_diff_eq!(centre, Tuple::point(2.0, 3.0, 0.0), epsilon = TEST_EPSILON);
+    }
+}
@@ -0,0 +1,280 @@
+use std::rc::Rc;
+
+use crate::{
+    bounds::BoundingBox,
+    intersection::Intersection,
+    matrix::Matrix,
+    ray::Ray,
+    scene::ShapeDescription,
+    shape::{first_surface, LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Another copy of a top-level shape, usually a mesh, placed by its own
+// transform on top of the prototype's. The prototype is shared with the
+// registry rather than copied, so a thousand trees cost one tree's triangles.
+//
+// Hits report the instance's id, with the prototype's shape that was hit as
+// part_id for working out normals. The whole instance is drawn in one
+// material: the prototype's first surface's unless set_material overrides it.
+// Instances see the prototype as it was when they were made, and an instance
+// can't itself be a prototype: World::add_instance and scene files refuse one.
+#[derive(Clone)]
+pub struct Instance {
+    pub data: ShapeData,
+    prototype: Rc<dyn Shape>,
+}
+
+impl Instance {
+    pub fn new(prototype: Rc<dyn Shape>) -> Instance {
+        let identity = Matrix::identity();
+        Instance {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: first_surface(prototype.as_ref()).material().clone(),
+            },
+            prototype,
+        }
+    }
+
+    pub fn prototype(&self) -> &dyn Shape {
+        self.prototype.as_ref()
+    }
+}
+
+impl Shape for Instance {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    // The prototype intersects rays in world space, which is the instance's
+    // object space
+    fn local_intersect_into(&self, local_ray: &Ray, xs: &mut Vec<Intersection>) {
+        let start = xs.len();
+        self.prototype.intersect_into(local_ray, xs);
+        for x in &mut xs[start..] {
+            x.part_id = Some(x.object_id);
+            x.object_id = self.data.id;
+        }
+    }
+
+    // Hits carry the part they're on, so this is only for callers asking the
+    // instance itself. The prototype is in the instance's object space.
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        self.prototype.normal_at(local_point)
+    }
+
+    fn has_surface(&self) -> bool {
+        false
+    }
+
+    fn is_instance(&self) -> bool {
+        true
+    }
+
+    // The registry numbers nested shapes depth first, so each child's subtree
+    // holds the ids from its own up to the next child's. The child to descend
+    // into is the last one whose id is no greater than the one looked for.
+    fn part(&self, id: u32) -> Option<&dyn Shape> {
+        let mut shape = self.prototype.as_ref();
+        while shape.id() != id {
+            let at_most = |index: usize| shape.child(index).is_some_and(|c| c.id() <= id);
+            if !at_most(0) {
+                return None;
+            }
+            // Doubling to overshoot, then halving back to the last match
+            let mut high = 1;
+            while at_most(high) {
+                high *= 2;
+            }
+            let (mut low, mut high) = (high / 2, high);
+            while high - low > 1 {
+                let mid = (low + high) / 2;
+                if at_most(mid) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            shape = shape.child(low)?;
+        }
+        Some(shape)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.prototype.bvh_bounds()
+    }
+
+    // The prototype's id; SceneDescription::from_world turns it into the
+    // index scene files use
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Instance {
+            of: self.prototype.id() as usize,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::TEST_EPSILON;
+    use crate::{
+        colour::Colour,
+        intersection::prepare_computations,
+        materials::Material,
+        shape::{group::Group, sphere::Sphere},
+        world::World,
+    };
+    use approx::assert_abs_diff_eq;
+
+    // A group holding a unit sphere at x = 5, and an instance of it moved
+    // back to the origin
+    fn instanced_world() -> (World, u32, u32) {
+        let mut world = World::new();
+        let mut group = Group::new();
+        group.add_child(Box::new(Sphere::new()));
+        group.set_transform(Matrix::translation(5.0, 0.0, 0.0));
+        let prototype = world.add_object(group);
+
+        let instance = world
+            .add_instance(prototype, Matrix::translation(-5.0, 0.0, 0.0))
+            .unwrap();
+        (world, prototype, instance)
+    }
+
+    #[test]
+    fn an_instance_asked_for_its_normal_gives_the_prototypes() {
+        let (world, _, instance) = instanced_world();
+        let copy = world.registry.get(instance).unwrap();
+
+        assert_abs_diff_eq!(
+            copy.normal_at(&Tuple::point(-1.0, 0.0, 0.0)),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn instances_share_the_prototype() {
+        let (world, prototype, instance) = instanced_world();
+        let copy = world.registry.get(instance).unwrap();
+        assert_eq!(copy.children().len(), 0);
+        assert!(std::ptr::addr_eq(
+            world.registry.get_shared(prototype).unwrap().as_ref(),
+            world.registry.get(prototype).unwrap()
+        ));
+
+        let bounds = copy.world_bounds();
+        assert_abs_diff_eq!(bounds.min.x, -1.0, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(bounds.max.x, 1.0, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn hits_on_an_instance_use_its_transform_for_normals() {
+        let (world, prototype, instance) = instanced_world();
+        let sphere = world.registry.get(prototype).unwrap().children()[0].id();
+
+        let ray = Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+        let xs = world.intersect_world(&ray);
+        // The prototype itself is still there at x = 5
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[2].object_id, sphere);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[0].object_id, instance);
+        assert_eq!(xs[0].part_id, Some(sphere));
+
+        let comps = prepare_computations(&xs[0], &ray, &world.registry, Some(&xs)).unwrap();
+        assert_eq!(comps.object.id(), instance);
+        assert_abs_diff_eq!(comps.normalv.x, -1.0, epsilon = TEST_EPSILON);
+    }
+
+    // The normal of the first hit on the instance along the x axis
+    fn instance_normal(world: &World) -> Option<Tuple> {
+        let ray = Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+        let xs = world.intersect_world(&ray);
+        prepare_computations(&xs[0], &ray, &world.registry, Some(&xs)).map(|comps| comps.normalv)
+    }
+
+    #[test]
+    fn instances_outlive_their_prototype() {
+        let (mut world, prototype, _) = instanced_world();
+        assert!(world.registry.remove(prototype));
+
+        assert_abs_diff_eq!(
+            instance_normal(&world).unwrap(),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn instances_keep_the_prototype_as_it_was() {
+        let (mut world, prototype, _) = instanced_world();
+        let group = world.registry.get_mut(prototype).unwrap();
+        group.children_mut()[0].set_transform(Matrix::translation(0.0, 1.0, 0.0));
+
+        // Hit where the instance's untouched sphere is, and shaded as that
+        // sphere rather than the moved one
+        assert_abs_diff_eq!(
+            instance_normal(&world).unwrap(),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn parts_are_found_in_the_prototype_by_id() {
+        let mut group = Group::new();
+        for _ in 0..5 {
+            let mut inner = Group::new();
+            inner.add_child(Box::new(Sphere::new()));
+            inner.add_child(Box::new(Sphere::new()));
+            group.add_child(Box::new(inner));
+        }
+        let mut world = World::new();
+        let prototype = world.add_object(group);
+        let instance = world.add_instance(prototype, Matrix::identity()).unwrap();
+        let copy = world.registry.get(instance).unwrap();
+
+        for id in prototype..=prototype + 15 {
+            assert_eq!(copy.part(id).map(|part| part.id()), Some(id));
+        }
+        assert!(copy.part(instance).is_none());
+    }
+
+    #[test]
+    fn instances_cannot_be_instanced() {
+        let (mut world, _, instance) = instanced_world();
+
+        assert_eq!(world.add_instance(instance, Matrix::identity()), None);
+    }
+
+    #[test]
+    fn instance_material_can_be_overridden() {
+        let mut sphere = Sphere::new();
+        sphere.data.material.colour = Colour::new(0.0, 1.0, 0.0);
+        let mut world = World::new();
+        let prototype = world.add_object(sphere);
+
+        let mut instance = Instance::new(world.registry.get_shared(prototype).unwrap());
+        assert_eq!(instance.material().colour.g, 1.0);
+
+        let mut material = Material::new();
+        material.colour = Colour::new(1.0, 0.0, 0.0);
+        instance.set_material(material);
+        assert_eq!(instance.material().colour.r, 1.0);
+        assert_eq!(instance.prototype().material().colour.g, 1.0);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    bounds::BoundingBox,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scalar::Float,
+    scene::{LodLevelDescription, ObjectDescription, ShapeDescription},
+    shape::{first_surface, sphere::Sphere, LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Level of detail: a detailed shape, usually a mesh, with simpler stand-ins for
+// when it looks small. Each ray picks one by the shape's apparent size from the
+// ray's eye (the camera ray's origin, see Ray::eye), its bounding sphere's
+// diameter over the distance to it. That is roughly the angle it covers, so
+// camera.pixel_size * 20.0 is the size of twenty pixels at the image centre.
+// As with groups, the versions store world-space transforms and transforming
+// the LOD moves them all.
+#[derive(Clone)]
+pub struct Lod {
+    pub data: ShapeData,
+    detail: Box<dyn Shape>,
+    // Sorted by max_size, smallest first
+    levels: Vec<LodLevel>,
+    // Bounding sphere of the detailed shape, used below impostor_below
+    impostor: Option<(Float, Sphere)>,
+    centre: Tuple,
+    radius: Float,
+}
+
+#[derive(Clone)]
+pub struct LodLevel {
+    // Used while the shape looks smaller than this
+    pub max_size: Float,
+    pub shape: Box<dyn Shape>,
+}
+
+impl Lod {
+    pub fn new(detail: Box<dyn Shape>) -> Lod {
+        let identity = Matrix::identity();
+        let mut lod = Lod {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            detail,
+            levels: Vec::new(),
+            impostor: None,
+            centre: Tuple::point(0.0, 0.0, 0.0),
+            radius: 0.0,
+        };
+        lod.update_bounding_sphere();
+        lod
+    }
+
+    // A simplified version to use while the shape looks smaller than max_size.
+    // Add every level before registering the LOD with a world.
+    pub fn add_level(&mut self, max_size: Float, mut shape: Box<dyn Shape>) {
+        let transform = &self.data.transform * shape.transform();
+        shape.set_transform(shape.data().without_pivot(&transform));
+        let index = self
+            .levels
+            .partition_point(|level| level.max_size <= max_size);
+        self.levels.insert(index, LodLevel { max_size, shape });
+    }
+
+    // Below max_size the shape is drawn as its bounding sphere, in the
+    // material of its first surface
+    pub fn set_impostor_below(&mut self, max_size: Float) {
+        let mut sphere = Sphere::new();
+        sphere.set_material(first_surface(self.detail.as_ref()).material().clone());
+        self.impostor = Some((max_size, sphere));
+        self.update_bounding_sphere();
+    }
+
+    pub fn levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    pub fn impostor_below(&self) -> Option<Float> {
+        self.impostor.as_ref().map(|(max_size, _)| *max_size)
+    }
+
+    // How large the shape looks from a point; infinite from inside its
+    // bounding sphere
+    pub fn apparent_size(&self, from: Tuple) -> Float {
+        let distance = (self.centre - from).magnitude();
+        if distance <= self.radius {
+            Float::INFINITY
+        } else {
+            2.0 * self.radius / distance
+        }
+    }
+
+    // The version to draw at a given apparent size
+    pub fn select(&self, size: Float) -> &dyn Shape {
+        if let Some((max_size, sphere)) = &self.impostor {
+            if size < *max_size {
+                return sphere;
+            }
+        }
+        self.levels
+            .iter()
+            .find(|level| size < level.max_size)
+            .map_or(self.detail.as_ref(), |level| level.shape.as_ref())
+    }
+
+    // Keeps the impostor wrapped around the detailed shape
+    fn update_bounding_sphere(&mut self) {
+        let bounds = self.detail.world_bounds();
+        self.centre = bounds.centre();
+        self.radius = (bounds.max - bounds.min).magnitude() / 2.0;
+        if let Some((_, sphere)) = &mut self.impostor {
+            let (c, r) = (self.centre, self.radius);
+            sphere.set_transform(&Matrix::translation(c.x, c.y, c.z) * &Matrix::scaling(r, r, r));
+        }
+    }
+}
+
+impl Shape for Lod {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        let transform = self.data.about_pivot(transform);
+        let delta = &transform * &self.data.inverse_transform;
+        for child in self.children_mut() {
+            let child_transform = child.data().without_pivot(&(&delta * child.transform()));
+            child.set_transform(child_transform);
+        }
+
+        self.data.set_transform(transform);
+        self.update_bounding_sphere();
+    }
+
+    fn set_material(&mut self, material: Material) {
+        for child in self.children_mut() {
+            child.set_material(material.clone());
+        }
+        self.data.material = material;
+    }
+
+    // The detailed shape, then each level, then the impostor
+    fn children(&self) -> Vec<&dyn Shape> {
+        let mut children = vec![self.detail.as_ref()];
+        children.extend(self.levels.iter().map(|level| level.shape.as_ref()));
+        if let Some((_, sphere)) = &self.impostor {
+            children.push(sphere);
+        }
+        children
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn Shape> {
+        let mut children: Vec<&mut dyn Shape> = vec![self.detail.as_mut()];
+        children.extend(
+            self.levels
+                .iter_mut()
+                .map(|level| level.shape.as_mut() as &mut dyn Shape),
+        );
+        if let Some((_, sphere)) = &mut self.impostor {
+            children.push(sphere);
+        }
+        children
+    }
+
+    fn intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        self.select(self.apparent_size(ray.eye))
+            .intersect_into(ray, xs)
+    }
+
+    fn local_intersect_into(&self, local_ray: &Ray, xs: &mut Vec<Intersection>) {
+        self.intersect_into(&local_ray.transform(&self.data.transform), xs)
+    }
+
+    // Hits reference the version they're on. Asked for a normal itself, with
+    // no eye to choose a version by, a LOD gives the detail's.
+    fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
+        let world_point = &self.data.transform * *local_point;
+        // Undoes the inverse transpose normal_at applies to object space normals
+        &self.data.transform.transpose() * self.detail.normal_at(&world_point)
+    }
+
+    fn has_surface(&self) -> bool {
+        false
+    }
+
+    // Simpler versions are expected to fit inside the detailed one
+    fn world_bounds(&self) -> BoundingBox {
+        self.detail.world_bounds()
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.world_bounds().transform(&self.data.inverse_transform)
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        let describe =
+            |shape: &dyn Shape| ObjectDescription::from_shape(shape, &self.data.transform);
+        Ok(ShapeDescription::Lod {
+            detail: Box::new(describe(self.detail.as_ref())?),
+            levels: self
+                .levels
+                .iter()
+                .map(|level| {
+                    Ok(LodLevelDescription {
+                        max_size: level.max_size,
+                        object: describe(level.shape.as_ref())?,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+            impostor_below: self.impostor_below(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    // A unit sphere with a half-size sphere for mid distances and an impostor
+    // for far away
+    fn test_lod() -> Lod {
+        let mut lod = Lod::new(Box::new(Sphere::new()));
+        let mut simple = Sphere::new();
+        simple.set_transform(Matrix::scaling(0.5, 0.5, 0.5));
+        lod.add_level(0.5, Box::new(simple));
+        lod.set_impostor_below(0.01);
+        lod
+    }
+
+    #[test]
+    fn a_lod_asked_for_its_normal_gives_the_details() {
+        let mut lod = test_lod();
+        lod.set_transform(Matrix::translation(0.0, 5.0, 0.0));
+
+        assert_abs_diff_eq!(
+            lod.normal_at(&Tuple::point(0.0, 6.0, 0.0)),
+            Tuple::vector(0.0, 1.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    fn first_hit(lod: &Lod, z: Float) -> Float {
+        let ray = Ray::new(Tuple::point(0.0, 0.0, z), Tuple::vector(0.0, 0.0, 1.0));
+        lod.intersect(&ray)[0].t
+    }
+
+    // This far out, single precision loses most of its digits solving the
+    // sphere's quadratic
+    const FAR_EPSILON: Float = if cfg!(feature = "f32") {
+        0.01
+    } else {
+        TEST_EPSILON
+    };
+
+    #[test]
+    fn rays_see_the_version_for_their_distance() {
+        let lod = test_lod();
+
+        // The bounding sphere has radius sqrt(3), so it looks 0.69 across from
+        // 5 units, 0.069 from 50 and 0.0069 from 500
+        assert_abs_diff_eq!(first_hit(&lod, -5.0), 4.0, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(first_hit(&lod, -50.0), 49.5, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(
+            first_hit(&lod, -500.0),
+            500.0 - Float::sqrt(3.0),
+            epsilon = FAR_EPSILON
+        );
+    }
+
+    #[test]
+    fn shadow_rays_see_the_version_the_camera_sees() {
+        let light = crate::light::Light::point_light(
+            Tuple::point(0.0, 0.0, -60.0),
+            crate::colour::Colour::white(),
+        );
+        let mut lod_world = crate::world::World::new();
+        lod_world.lights = vec![light.clone()];
+        lod_world.add_object(test_lod());
+        let mut plain_world = crate::world::World::new();
+        plain_world.lights = vec![light];
+        let mut simple = Sphere::new();
+        simple.set_transform(Matrix::scaling(0.5, 0.5, 0.5));
+        plain_world.add_object(simple);
+
+        // From here the half-size sphere is drawn, and its surface is inside
+        // the detailed sphere that would otherwise shadow it
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -50.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_abs_diff_eq!(
+            lod_world.colour_at(&ray, 0),
+            plain_world.colour_at(&ray, 0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn levels_are_kept_in_size_order() {
+        let mut lod = Lod::new(Box::new(Sphere::new()));
+        lod.add_level(0.5, Box::new(Sphere::new()));
+        lod.add_level(0.1, Box::new(Sphere::new()));
+
+        let sizes: Vec<Float> = lod.levels().iter().map(|level| level.max_size).collect();
+        assert_eq!(sizes, vec![0.1, 0.5]);
+        assert!(std::ptr::addr_eq(
+            lod.select(0.05),
+            lod.levels()[0].shape.as_ref()
+        ));
+        assert!(std::ptr::addr_eq(
+            lod.select(0.3),
+            lod.levels()[1].shape.as_ref()
+        ));
+        assert!(std::ptr::addr_eq(
+            lod.select(Float::INFINITY),
+            lod.detail.as_ref()
+        ));
+    }
+
+    #[test]
+    fn transforming_the_lod_moves_every_version() {
+        let mut lod = test_lod();
+        lod.set_transform(Matrix::translation(0.0, 0.0, 10.0));
+
+        assert_abs_diff_eq!(first_hit(&lod, 5.0), 4.0, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(first_hit(&lod, -40.0), 49.5, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(
+            first_hit(&lod, -490.0),
+            500.0 - Float::sqrt(3.0),
+            epsilon = FAR_EPSILON
+        );
+    }
+
+    #[test]
+    fn impostor_takes_the_material_of_the_detailed_surface() {
+        let mut sphere = Sphere::new();
+        sphere.data.material.ambient = 0.7;
+        let mut lod = Lod::new(Box::new(sphere));
+        lod.set_impostor_below(0.1);
+
+        assert_eq!(lod.select(0.0).material().ambient, 0.7);
+    }
+}
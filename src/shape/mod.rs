@@ -1,6 +1,17 @@
+#[allow(clippy::module_inception)]
 pub mod shape;
-pub use shape::{Shape, ShapeData};
+pub(crate) use shape::{first_surface, nearest_child_normal};
+pub use shape::{LightLinks, Shape, ShapeClone, ShapeData, Visibility};
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disc;
+pub mod group;
+pub mod instance;
+pub mod lod;
 pub mod plane;
+pub mod quad;
+pub mod smooth_triangle;
 pub mod sphere;
-// Add more shapes here as you implement them, e.g.:
-// pub mod plane;
+pub mod triangle;
+// Add more shapes here as you implement them
@@ -1,9 +1,14 @@
 use crate::{
+    bounds::BoundingBox,
+    core::constants::PARALLEL_THRESHOLD,
     intersection::Intersection,
     materials::Material,
     matrix::Matrix,
+    pattern::uv_pattern::planar_map,
     ray::Ray,
-    shape::{Shape, ShapeData},
+    scalar::Float,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
     tuple::Tuple,
 };
 
@@ -12,6 +17,12 @@ pub struct Plane {
     pub data: ShapeData,
 }
 
+impl Default for Plane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Plane {
     pub fn new() -> Plane {
         let identity = Matrix::identity();
@@ -20,6 +31,12 @@ impl Plane {
                 id: 0,
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
                 material: Material::new(),
             },
         }
@@ -35,18 +52,33 @@ impl Shape for Plane {
         &mut self.data
     }
 
-    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
-        if ray.direction.y.abs() < f64::EPSILON * 50000.0 {
-            return vec![];
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if ray.direction.y.abs() < PARALLEL_THRESHOLD {
+            return;
         }
 
         let t = -ray.origin.y / ray.direction.y;
-        return vec![Intersection::new(t, self)];
+        xs.push(Intersection::new(t, self));
+    }
+
+    fn local_uv_at(&self, local_point: &Tuple) -> Option<(Float, Float)> {
+        Some(planar_map(*local_point))
     }
 
     fn local_normal_at(&self, _local_point: &Tuple) -> Tuple {
         Tuple::vector(0.0, 1.0, 0.0)
     }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(Float::NEG_INFINITY, 0.0, Float::NEG_INFINITY),
+            Tuple::point(Float::INFINITY, 0.0, Float::INFINITY),
+        )
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Plane)
+    }
 }
 
 #[cfg(test)]
@@ -92,6 +124,15 @@ mod tests {
         assert_eq!(xs[0].object_id, p.data.id);
     }
 
+    #[test]
+    fn plane_points_have_planar_uv() {
+        let p = Plane::new();
+        assert_eq!(
+            p.local_uv_at(&Tuple::point(-1.25, 0.0, 2.5)),
+            Some((0.75, 0.5))
+        );
+    }
+
     #[test]
     fn a_ray_intersecting_a_plane_from_below() {
         let p = Plane::new();
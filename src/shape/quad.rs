@@ -0,0 +1,116 @@
+use crate::{
+    bounds::BoundingBox,
+    core::constants::PARALLEL_THRESHOLD,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// A flat square on the xz plane from -1 to 1 on both axes, facing up. Scale
+// it for walls, table tops and the like.
+#[derive(Clone)]
+pub struct Quad {
+    pub data: ShapeData,
+}
+
+impl Default for Quad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Quad {
+    pub fn new() -> Quad {
+        let identity = Matrix::identity();
+        Quad {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+        }
+    }
+}
+
+impl Shape for Quad {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if ray.direction.y.abs() < PARALLEL_THRESHOLD {
+            return;
+        }
+
+        let t = -ray.origin.y / ray.direction.y;
+        let point = ray.position(t);
+        if point.x.abs() > 1.0 || point.z.abs() > 1.0 {
+            return;
+        }
+        xs.push(Intersection::new(t, self));
+    }
+
+    fn local_normal_at(&self, _local_point: &Tuple) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1.0, 0.0, -1.0), Tuple::point(1.0, 0.0, 1.0))
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Quad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn ray_hits_inside_the_square() {
+        let q = Quad::new();
+        let r = Ray::new(Tuple::point(0.9, 1.0, -0.9), Tuple::vector(0.0, -1.0, 0.0));
+        let xs = q.local_intersect(&r);
+
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.0);
+    }
+
+    #[test]
+    fn ray_misses_beyond_the_edges() {
+        let q = Quad::new();
+        for (x, z) in [(1.1, 0.0), (0.0, -1.1), (1.01, 1.01)] {
+            let r = Ray::new(Tuple::point(x, 1.0, z), Tuple::vector(0.0, -1.0, 0.0));
+            assert!(q.local_intersect(&r).is_empty(), "({}, {})", x, z);
+        }
+        let parallel = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(q.local_intersect(&parallel).is_empty());
+    }
+
+    #[test]
+    fn quad_is_bounded() {
+        let mut q = Quad::new();
+        q.set_transform(Matrix::scaling(2.0, 1.0, 3.0));
+        let b = q.world_bounds();
+
+        assert!(b.is_finite());
+        assert_eq!(b.max, Tuple::point(2.0, 0.0, 3.0));
+    }
+}
@@ -1,25 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use crate::bounds::BoundingBox;
+use crate::light::Light;
 use crate::materials::Material;
 use crate::matrix::Matrix;
+use crate::scene::ShapeDescription;
 use crate::tuple::Tuple;
-use crate::{intersection::Intersection, ray::Ray};
+use crate::{intersection::Intersection, ray::Ray, scalar::Float};
 
 #[derive(Clone)]
 pub struct ShapeData {
     pub id: u32,
+    // For finding the shape by something steadier than its id, which depends
+    // on the order shapes were added in; see ShapeRegistry::find_by_name
+    pub name: Option<String>,
     pub transform: Matrix,
     pub inverse_transform: Matrix,
+    // Maps object-space normals to world space; kept in step with transform
+    pub inverse_transpose: Matrix,
+    // Point in object space that set_transform rotates and scales about,
+    // rather than the origin
+    pub pivot: Option<Tuple>,
     pub material: Material,
+    pub visibility: Visibility,
+    pub light_links: LightLinks,
+    // World-space units per second the shape moves while the shutter is open,
+    // from where its transform puts it. See Ray::time.
+    pub velocity: Option<Tuple>,
     // Optionally, add saved_ray for testing
     // pub saved_ray: Option<Ray>,
 }
 
+// Which parts of a render a shape takes part in; everything by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    // Blocks light from reaching other surfaces
+    pub cast_shadows: bool,
+    // When off, the shape is lit as if nothing stood between it and the light
+    pub receive_shadows: bool,
+    // Hit by camera rays. Hidden shapes still cast shadows and show up in
+    // reflections.
+    pub camera_visible: bool,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility {
+            cast_shadows: true,
+            receive_shadows: true,
+            camera_visible: true,
+        }
+    }
+}
+
+// Which light groups reach a shape; see Light::group. By default every light
+// does, e.g. a backdrop can leave out the key light meant for a character.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightLinks {
+    // When given, only lights in these groups reach the shape. Lights with no
+    // group don't either.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub only: Option<Vec<String>>,
+    // Lights in these groups never reach the shape
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl LightLinks {
+    pub fn is_default(&self) -> bool {
+        *self == LightLinks::default()
+    }
+
+    pub fn lit_by(&self, light: &Light) -> bool {
+        match &light.group {
+            Some(group) => {
+                !self.exclude.contains(group)
+                    && self.only.as_ref().is_none_or(|only| only.contains(group))
+            }
+            None => self.only.is_none(),
+        }
+    }
+}
+
 impl ShapeData {
     pub fn set_id(&mut self, id: u32) {
         self.id = id;
     }
+
+    pub fn set_transform(&mut self, transform: Matrix) {
+        self.inverse_transform = transform.inverse();
+        self.inverse_transpose = self.inverse_transform.transpose();
+        self.transform = transform;
+    }
+
+    // Where a world-space point at a ray's time was when the shutter opened.
+    // Moving a ray back this way and intersecting the shape where it rests
+    // gives the same hits as moving the shape.
+    pub fn at_rest(&self, point: Tuple, time: Float) -> Tuple {
+        match self.velocity {
+            Some(velocity) => point - velocity * time,
+            None => point,
+        }
+    }
+
+    // Wraps transform so that it acts about the pivot
+    pub fn about_pivot(&self, transform: Matrix) -> Matrix {
+        match self.pivot {
+            Some(p) => {
+                &(&Matrix::translation(p.x, p.y, p.z) * &transform)
+                    * &Matrix::translation(-p.x, -p.y, -p.z)
+            }
+            None => transform,
+        }
+    }
+
+    // Undoes about_pivot, recovering the transform it was given
+    pub fn without_pivot(&self, transform: &Matrix) -> Matrix {
+        match self.pivot {
+            Some(p) => {
+                &(&Matrix::translation(-p.x, -p.y, -p.z) * transform)
+                    * &Matrix::translation(p.x, p.y, p.z)
+            }
+            None => transform.clone(),
+        }
+    }
+}
+
+// Lets boxed shapes be cloned. Implemented automatically for every Shape that
+// is Clone, so shapes only need #[derive(Clone)].
+pub trait ShapeClone {
+    fn clone_box(&self) -> Box<dyn Shape>;
+}
+
+impl<T: Shape + Clone + 'static> ShapeClone for T {
+    fn clone_box(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
 }
 
-pub trait Shape {
+impl Clone for Box<dyn Shape> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+pub trait Shape: ShapeClone {
     fn id(&self) -> u32 {
         self.data().id
     }
@@ -37,30 +162,234 @@ pub trait Shape {
     }
 
     fn set_transform(&mut self, transform: Matrix) {
-        self.data_mut().inverse_transform = transform.inverse();
-        self.data_mut().transform = transform;
+        let transform = self.data().about_pivot(transform);
+        self.data_mut().set_transform(transform);
+    }
+
+    // Keeps the transform last given to set_transform, now applied about the
+    // new pivot
+    fn set_pivot(&mut self, pivot: Option<Tuple>) {
+        let transform = self.data().without_pivot(self.transform());
+        self.data_mut().pivot = pivot;
+        self.set_transform(transform);
     }
 
     fn set_material(&mut self, material: Material) {
         self.data_mut().material = material;
     }
 
+    fn visibility(&self) -> Visibility {
+        self.data().visibility
+    }
+
+    // Applies to everything inside composite shapes too
+    fn set_visibility(&mut self, visibility: Visibility) {
+        for child in self.children_mut() {
+            child.set_visibility(visibility);
+        }
+        self.data_mut().visibility = visibility;
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.data().name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<String>) {
+        self.data_mut().name = name;
+    }
+
+    fn light_links(&self) -> &LightLinks {
+        &self.data().light_links
+    }
+
+    // Applies to everything inside composite shapes too
+    fn set_light_links(&mut self, light_links: LightLinks) {
+        for child in self.children_mut() {
+            child.set_light_links(light_links.clone());
+        }
+        self.data_mut().light_links = light_links;
+    }
+
+    // Composite shapes pass it on to everything inside them, which move
+    // together
+    fn set_velocity(&mut self, velocity: Option<Tuple>) {
+        for child in self.children_mut() {
+            child.set_velocity(velocity);
+        }
+        self.data_mut().velocity = velocity;
+    }
+
+    // True if this shape or anything inside it has a velocity
+    fn is_moving(&self) -> bool {
+        self.data().velocity.is_some() || self.children().iter().any(|child| child.is_moving())
+    }
+
+    // Composite shapes (e.g. CSG) expose their children so the registry can
+    // give them ids and resolve intersections that reference them
+    fn children(&self) -> Vec<&dyn Shape> {
+        Vec::new()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn Shape> {
+        Vec::new()
+    }
+
+    // True if id is this shape or any shape nested inside it
+    fn includes(&self, id: u32) -> bool {
+        self.id() == id || self.children().iter().any(|child| child.includes(id))
+    }
+
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
-        let local_ray = ray.clone().transform(&self.data().inverse_transform);
+        let mut xs = Vec::new();
+        self.intersect_into(ray, &mut xs);
+        xs
+    }
+
+    // Appends the ray's intersections to xs rather than allocating, so
+    // renders can reuse one buffer across rays
+    fn intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        let data = self.data();
+        let ray = Ray {
+            origin: data.at_rest(ray.origin, ray.time),
+            ..*ray
+        };
+        let local_ray = ray.transform(&data.inverse_transform);
         // self.data_mut().saved_ray = Some(local_ray.clone()); // for testing
-        self.local_intersect(&local_ray)
+        self.local_intersect_into(&local_ray, xs)
+    }
+
+    // True if the ray hits the shape at some t in [0, distance). Shadow rays
+    // only need to know that, so shapes can answer without collecting hits.
+    fn intersects_before(&self, ray: &Ray, distance: Float) -> bool {
+        let data = self.data();
+        let ray = Ray {
+            origin: data.at_rest(ray.origin, ray.time),
+            ..*ray
+        };
+        self.local_intersects_before(&ray.transform(&data.inverse_transform), distance)
+    }
+
+    fn local_intersects_before(&self, local_ray: &Ray, distance: Float) -> bool {
+        self.local_intersect(local_ray)
+            .iter()
+            .any(|x| x.t >= 0.0 && x.t < distance)
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Vec<Intersection> {
+        let mut xs = Vec::new();
+        self.local_intersect_into(local_ray, &mut xs);
+        xs
     }
 
     fn normal_at(&self, world_point: &Tuple) -> Tuple {
-        let object_point = self.data().inverse_transform.clone() * world_point.clone();
+        let object_point = &self.data().inverse_transform * *world_point;
         let object_normal = self.local_normal_at(&object_point);
-        let world_normal = self.data().inverse_transform.transpose() * object_normal;
+        let world_normal = &self.data().inverse_transpose * object_normal;
         Tuple::vector(world_normal.x, world_normal.y, world_normal.z).normalise()
     }
 
+    // Like normal_at, but lets shapes that interpolate normals use the hit's u/v
+    fn normal_at_hit(&self, world_point: &Tuple, hit: &Intersection) -> Tuple {
+        let object_point = &self.data().inverse_transform * *world_point;
+        let object_normal = self.local_normal_at_hit(&object_point, hit);
+        let world_normal = &self.data().inverse_transpose * object_normal;
+        Tuple::vector(world_normal.x, world_normal.y, world_normal.z).normalise()
+    }
+
+    fn local_normal_at_hit(&self, local_point: &Tuple, _hit: &Intersection) -> Tuple {
+        self.local_normal_at(local_point)
+    }
+
+    // A direction along the surface at a point, in object space, that normal
+    // maps take as the direction of increasing u. It only needs to be roughly
+    // right: it's made perpendicular to the normal afterwards. The x axis
+    // matches planar uv mapping.
+    fn local_tangent_at(&self, _local_point: &Tuple) -> Tuple {
+        Tuple::vector(1.0, 0.0, 0.0)
+    }
+
+    // Surface coordinates of a point in object space, for shapes with a uv
+    // mapping of their own. prepare_computations asks for the hit's alone;
+    // triangles give theirs with each intersection instead.
+    fn local_uv_at(&self, _local_point: &Tuple) -> Option<(Float, Float)> {
+        None
+    }
+
+    // Bounds of the shape in world space
+    fn world_bounds(&self) -> BoundingBox {
+        self.bounds().transform(&self.data().transform)
+    }
+
+    // Bounds used to build BVHs. A moving shape could be anywhere along its
+    // path, so it's left unbounded and always tested.
+    fn bvh_bounds(&self) -> BoundingBox {
+        if self.is_moving() {
+            BoundingBox::infinite()
+        } else {
+            self.world_bounds()
+        }
+    }
+
+    // False for shapes made of other shapes, such as groups and CSG, which
+    // have no normals of their own
+    fn has_surface(&self) -> bool {
+        true
+    }
+
+    // True for instances, which can't be the prototype of another instance
+    fn is_instance(&self) -> bool {
+        false
+    }
+
+    // The shape a hit's part_id names. Only instances have parts, found in
+    // their own copy of the prototype rather than the registry's.
+    fn part(&self, _id: u32) -> Option<&dyn Shape> {
+        None
+    }
+
+    // Index-based child lookup, so shapes with many children can avoid
+    // collecting them all just to reach one
+    fn child(&self, index: usize) -> Option<&dyn Shape> {
+        self.children().get(index).copied()
+    }
+
     // Abstract methods
     fn data(&self) -> &ShapeData;
     fn data_mut(&mut self) -> &mut ShapeData;
-    fn local_intersect(&self, local_ray: &Ray) -> Vec<Intersection>;
+    fn local_intersect_into(&self, local_ray: &Ray, xs: &mut Vec<Intersection>);
     fn local_normal_at(&self, local_point: &Tuple) -> Tuple;
+    // Bounds in object space
+    fn bounds(&self) -> BoundingBox;
+    // The shape-specific part of a scene file entry, used to save worlds
+    fn describe(&self) -> Result<ShapeDescription, String>;
+}
+
+// The normal of a group or CSG at a point in its object space, taken from the
+// child whose bounds are nearest the point and returned in the same space.
+// Groups and CSG store their children's transforms in world space, so the
+// point is moved to world space to choose the nearest child. Hits carry the
+// child they're on, so this is only reached by asking the composite for a
+// normal directly. Shapes with no children have no surface, and any normal
+// will do.
+pub(crate) fn nearest_child_normal(shape: &dyn Shape, local_point: &Tuple) -> Tuple {
+    let world_point = shape.transform() * *local_point;
+    let nearest = shape.children().into_iter().min_by(|a, b| {
+        let a = a.world_bounds().distance_to(world_point);
+        let b = b.world_bounds().distance_to(world_point);
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let Some(child) = nearest else {
+        return Tuple::vector(0.0, 1.0, 0.0);
+    };
+    // Undoes the inverse transpose normal_at applies to object space normals
+    &shape.transform().transpose() * child.normal_at(&world_point)
+}
+
+// The first leaf shape inside a shape, or the shape itself if it has no
+// children. Stand-ins for a whole composite shape take its material.
+pub(crate) fn first_surface(shape: &dyn Shape) -> &dyn Shape {
+    match shape.child(0) {
+        Some(child) => first_surface(child),
+        None => shape,
+    }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    bounds::BoundingBox,
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scalar::Float,
+    scene::{triple, ShapeDescription},
+    shape::{
+        triangle::{barycentric, interpolate_texture, moller_trumbore, tangent_in_plane},
+        LightLinks, Shape, ShapeData, Visibility,
+    },
+    tuple::Tuple,
+};
+
+// Triangle with a normal per vertex, interpolated across the face using the
+// hit's barycentric u/v so meshes shade smoothly.
+#[derive(Clone)]
+pub struct SmoothTriangle {
+    pub data: ShapeData,
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub n1: Tuple,
+    pub n2: Tuple,
+    pub n3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    // As for Triangle::texture
+    pub texture: Option<[(Float, Float); 3]>,
+}
+
+impl SmoothTriangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple, n1: Tuple, n2: Tuple, n3: Tuple) -> Self {
+        let identity = Matrix::identity();
+        SmoothTriangle {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1: p2 - p1,
+            e2: p3 - p1,
+            texture: None,
+        }
+    }
+
+    pub fn with_texture(self, texture: [(Float, Float); 3]) -> Self {
+        SmoothTriangle {
+            texture: Some(texture),
+            ..self
+        }
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if let Some((t, u, v)) = moller_trumbore(&self.p1, &self.e1, &self.e2, ray) {
+            xs.push(Intersection::with_uv(t, self, u, v));
+        }
+    }
+
+    // Without a hit there is nothing to interpolate with, so use the face normal
+    fn local_normal_at(&self, _local_point: &Tuple) -> Tuple {
+        self.e2.cross(&self.e1).normalise()
+    }
+
+    fn local_tangent_at(&self, local_point: &Tuple) -> Tuple {
+        tangent_in_plane(&self.local_normal_at(local_point), &self.e1)
+    }
+
+    fn local_uv_at(&self, local_point: &Tuple) -> Option<(Float, Float)> {
+        let weights = barycentric(&self.p1, &self.e1, &self.e2, local_point);
+        self.texture
+            .map(|texture| interpolate_texture(&texture, weights))
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::SmoothTriangle {
+            p1: triple(self.p1),
+            p2: triple(self.p2),
+            p3: triple(self.p3),
+            n1: triple(self.n1),
+            n2: triple(self.n2),
+            n3: triple(self.n3),
+            uv: self.texture.map(|texture| texture.map(|(u, v)| [u, v])),
+        })
+    }
+
+    fn local_normal_at_hit(&self, local_point: &Tuple, hit: &Intersection) -> Tuple {
+        match hit.uv {
+            Some((u, v)) => self.n2 * u + self.n3 * v + self.n1 * (1.0 - u - v),
+            None => self.local_normal_at(local_point),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{intersection::prepare_computations, shape_registry::ShapeRegistry};
+    use approx::assert_abs_diff_eq;
+
+    fn test_smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn constructing_a_smooth_triangle() {
+        let tri = test_smooth_triangle();
+
+        assert_eq!(tri.p1, Tuple::point(0.0, 1.0, 0.0));
+        assert_eq!(tri.p2, Tuple::point(-1.0, 0.0, 0.0));
+        assert_eq!(tri.p3, Tuple::point(1.0, 0.0, 0.0));
+        assert_eq!(tri.n1, Tuple::vector(0.0, 1.0, 0.0));
+        assert_eq!(tri.n2, Tuple::vector(-1.0, 0.0, 0.0));
+        assert_eq!(tri.n3, Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn intersection_with_smooth_triangle_stores_u_v() {
+        let tri = test_smooth_triangle();
+        let r = Ray::new(Tuple::point(-0.2, 0.3, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = tri.local_intersect(&r);
+
+        assert_eq!(xs.len(), 1);
+        let (u, v) = xs[0].uv.unwrap();
+        assert_abs_diff_eq!(u, 0.45, epsilon = 0.0001);
+        assert_abs_diff_eq!(v, 0.25, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn smooth_triangle_uses_u_v_to_interpolate_normal() {
+        let tri = test_smooth_triangle();
+        let i = Intersection::with_uv(1.0, &tri, 0.45, 0.25);
+        let n = tri.normal_at_hit(&Tuple::point(0.0, 0.0, 0.0), &i);
+
+        assert_abs_diff_eq!(n, Tuple::vector(-0.5547, 0.83205, 0.0), epsilon = 0.0001);
+    }
+
+    #[test]
+    fn preparing_normal_on_smooth_triangle() {
+        let tri = test_smooth_triangle();
+        let i = Intersection::with_uv(1.0, &tri, 0.45, 0.25);
+        let r = Ray::new(Tuple::point(-0.2, 0.3, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let mut registry = ShapeRegistry::new();
+        registry.register(tri);
+
+        let comps = prepare_computations(&i, &r, &registry, None).unwrap();
+
+        assert_abs_diff_eq!(
+            comps.normalv,
+            Tuple::vector(-0.5547, 0.83205, 0.0),
+            epsilon = 0.0001
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use crate::{
+    bounds::BoundingBox,
     intersection::Intersection,
     materials::Material,
     matrix::Matrix,
+    pattern::uv_pattern::spherical_map,
     ray::Ray,
-    shape::{Shape, ShapeData},
+    scalar::Float,
+    scene::ShapeDescription,
+    shape::{LightLinks, Shape, ShapeData, Visibility},
     tuple::Tuple,
 };
 
@@ -12,6 +16,12 @@ pub struct Sphere {
     pub data: ShapeData,
 }
 
+impl Default for Sphere {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Sphere {
     pub fn new() -> Sphere {
         let identity = Matrix::identity();
@@ -20,6 +30,12 @@ impl Sphere {
                 id: 0, // Temporary, will be set by registry
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
                 material: Material::new(),
             },
         }
@@ -35,6 +51,12 @@ impl Sphere {
                 id: 0, // Temporary, will be set by registry
                 transform: identity.clone(),
                 inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
                 material: m,
             },
         }
@@ -50,38 +72,99 @@ impl Shape for Sphere {
         &mut self.data
     }
 
-    fn local_intersect(&self, ray: &Ray) -> Vec<Intersection> {
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
         let sphere_to_ray = ray.origin - Tuple::point(0.0, 0.0, 0.0);
         let a = ray.direction.dot(&ray.direction);
         let b = 2.0 * ray.direction.dot(&sphere_to_ray);
         let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
 
         let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return vec![];
-        } else {
+        if discriminant >= 0.0 {
             let sqrt_discriminant = discriminant.sqrt();
             let inv_2a = 1.0 / (2.0 * a);
             let t1 = (-b - sqrt_discriminant) * inv_2a;
             let t2 = (-b + sqrt_discriminant) * inv_2a;
 
-            vec![Intersection::new(t1, self), Intersection::new(t2, self)]
+            xs.push(Intersection::new(t1, self));
+            xs.push(Intersection::new(t2, self));
+        }
+    }
+
+    fn local_intersects_before(&self, ray: &Ray, distance: Float) -> bool {
+        let sphere_to_ray = ray.origin - Tuple::point(0.0, 0.0, 0.0);
+        let a = ray.direction.dot(&ray.direction);
+        let b = 2.0 * ray.direction.dot(&sphere_to_ray);
+        let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
+
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return false;
         }
+        let sqrt_discriminant = discriminant.sqrt();
+        let inv_2a = 1.0 / (2.0 * a);
+        [
+            (-b - sqrt_discriminant) * inv_2a,
+            (-b + sqrt_discriminant) * inv_2a,
+        ]
+        .iter()
+        .any(|&t| t >= 0.0 && t < distance)
     }
 
     fn local_normal_at(&self, local_point: &Tuple) -> Tuple {
-        local_point.clone() - Tuple::point(0.0, 0.0, 0.0)
+        *local_point - Tuple::point(0.0, 0.0, 0.0)
+    }
+
+    fn local_uv_at(&self, local_point: &Tuple) -> Option<(Float, Float)> {
+        Some(spherical_map(*local_point))
+    }
+
+    // Eastwards, the way spherical uv mapping's u increases; any direction
+    // will do at the poles
+    fn local_tangent_at(&self, local_point: &Tuple) -> Tuple {
+        if local_point.x.abs() < 1e-9 && local_point.z.abs() < 1e-9 {
+            Tuple::vector(1.0, 0.0, 0.0)
+        } else {
+            Tuple::vector(-local_point.z, 0.0, local_point.x).normalise()
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Sphere)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::scalar::Float;
+    use crate::scalar::TEST_EPSILON;
     use approx::assert_abs_diff_eq;
 
     use super::*;
+    use crate::intersection::prepare_computations;
     use crate::ray::Ray;
+    use crate::shape_registry::ShapeRegistry;
     use crate::tuple::Tuple;
 
+    #[test]
+    fn sphere_tangents_point_east() {
+        let s = Sphere::new();
+
+        assert_abs_diff_eq!(
+            s.local_tangent_at(&Tuple::point(0.0, 0.0, -1.0)),
+            Tuple::vector(1.0, 0.0, 0.0)
+        );
+        assert_abs_diff_eq!(
+            s.local_tangent_at(&Tuple::point(1.0, 0.0, 0.0)),
+            Tuple::vector(0.0, 0.0, 1.0)
+        );
+        let pole = s.local_tangent_at(&Tuple::point(0.0, 1.0, 0.0));
+        assert_abs_diff_eq!(pole.magnitude(), 1.0);
+    }
+
     #[test]
     fn ray_intersects_sphere_at_two_points() {
         let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
@@ -93,6 +176,43 @@ mod tests {
         assert_eq!(xs[1].object_id, s.data.id);
     }
 
+    #[test]
+    fn intersects_before_checks_the_nearest_hit_in_range() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+
+        assert!(!s.intersects_before(&r, 2.5));
+        assert!(s.intersects_before(&r, 3.5));
+        // From inside, the far side counts
+        let inside = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(s.intersects_before(&inside, 2.5));
+        assert!(!s.intersects_before(&inside, 1.5));
+    }
+
+    #[test]
+    fn sphere_hits_get_spherical_uv() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+        let xs = s.intersect(&r);
+        let mut registry = ShapeRegistry::new();
+        registry.register(s);
+        let uv = |i: &Intersection| {
+            prepare_computations(&i.clone(), &r, &registry, None)
+                .unwrap()
+                .uv
+                .unwrap()
+        };
+
+        // In object space, so the scaling makes no difference
+        let (u, v) = uv(&xs[0]);
+        assert_abs_diff_eq!(u, 0.0, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(v, 0.5, epsilon = TEST_EPSILON);
+        let (u, _) = uv(&xs[1]);
+        assert_abs_diff_eq!(u, 0.5, epsilon = TEST_EPSILON);
+    }
+
     #[test]
     fn ray_intersects_sphere_at_tangent() {
         let r = Ray::new(Tuple::point(0.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
@@ -184,41 +304,81 @@ mod tests {
     #[test]
     fn normal_on_sphere_at_nonaxial_point() {
         let s = Sphere::new();
-        let sqrt_3_div_3 = (3.0_f64).sqrt() / 3.0;
+        let sqrt_3_div_3 = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(&Tuple::point(sqrt_3_div_3, sqrt_3_div_3, sqrt_3_div_3));
 
-        assert_eq!(n, Tuple::vector(sqrt_3_div_3, sqrt_3_div_3, sqrt_3_div_3));
+        assert_abs_diff_eq!(
+            n,
+            Tuple::vector(sqrt_3_div_3, sqrt_3_div_3, sqrt_3_div_3),
+            epsilon = TEST_EPSILON
+        );
     }
 
     #[test]
     fn normal_is_normalized_vector() {
         let s = Sphere::new();
-        let sqrt_3_div_3 = (3.0_f64).sqrt() / 3.0;
+        let sqrt_3_div_3 = Float::sqrt(3.0) / 3.0;
         let n = s.normal_at(&Tuple::point(sqrt_3_div_3, sqrt_3_div_3, sqrt_3_div_3));
 
-        assert_eq!(n, n.normalise());
+        assert_abs_diff_eq!(n, n.normalise(), epsilon = TEST_EPSILON);
     }
 
     #[test]
     fn normal_on_translated_sphere() {
         let mut s = Sphere::new();
         s.set_transform(Matrix::translation(0.0, 1.0, 0.0));
-        let n = s.normal_at(&Tuple::point(0.0, 1.70711, -0.70711));
-
-        assert_abs_diff_eq!(n, Tuple::vector(0.0, 0.70711, -0.70711), epsilon = 0.0001);
+        let frac_1_sqrt_2 = crate::scalar::consts::FRAC_1_SQRT_2;
+        let n = s.normal_at(&Tuple::point(0.0, 1.0 + frac_1_sqrt_2, -frac_1_sqrt_2));
+
+        assert_abs_diff_eq!(
+            n,
+            Tuple::vector(0.0, frac_1_sqrt_2, -frac_1_sqrt_2),
+            epsilon = 0.0001
+        );
     }
 
     #[test]
     fn normal_on_transformed_sphere() {
         let mut s = Sphere::new();
-        let m = Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotation_z(std::f64::consts::PI / 5.0);
+        let m =
+            Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotation_z(crate::scalar::consts::PI / 5.0);
         s.set_transform(m);
-        let sqrt_2_div_2 = (2.0_f64).sqrt() / 2.0;
+        let sqrt_2_div_2 = Float::sqrt(2.0) / 2.0;
         let n = s.normal_at(&Tuple::point(0.0, sqrt_2_div_2, -sqrt_2_div_2));
 
         assert_abs_diff_eq!(n, Tuple::vector(0.0, 0.97014, -0.24254), epsilon = 0.0001);
     }
 
+    #[test]
+    fn set_transform_updates_the_cached_inverse_transpose() {
+        let mut s = Sphere::new();
+        let m = Matrix::translation(1.0, 2.0, 3.0) * Matrix::rotation_x(0.3);
+        s.set_transform(m.clone());
+
+        assert_eq!(s.data.inverse_transpose, m.inverse().transpose());
+    }
+
+    #[test]
+    fn transforms_act_about_the_pivot() {
+        let mut s = Sphere::new();
+        s.set_pivot(Some(Tuple::point(0.0, 1.0, 0.0)));
+        s.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+
+        // The pivot stays put while the sphere grows away from it
+        let pivot = s.transform() * Tuple::point(0.0, 1.0, 0.0);
+        let centre = s.transform() * Tuple::point(0.0, 0.0, 0.0);
+        assert_abs_diff_eq!(pivot, Tuple::point(0.0, 1.0, 0.0), epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(centre, Tuple::point(0.0, -1.0, 0.0), epsilon = TEST_EPSILON);
+
+        // Clearing the pivot keeps the scaling, now about the origin
+        s.set_pivot(None);
+        assert_abs_diff_eq!(
+            s.transform() * Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(0.0, 2.0, 0.0),
+            epsilon = TEST_EPSILON
+        );
+    }
+
     #[test]
     fn sphere_has_default_material() {
         let s = Sphere::new();
@@ -248,4 +408,25 @@ mod tests {
         assert_eq!(s.material().transparency, 1.0);
         assert_eq!(s.material().refractive_index, 1.5);
     }
+
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_ray_time() {
+        let mut s = Sphere::new();
+        s.set_velocity(Some(Tuple::vector(0.0, 4.0, 0.0)));
+        let r = Ray::new(Tuple::point(0.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(s.intersect(&r).is_empty());
+        let xs = s.intersect(&r.with_time(0.5));
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.0);
+
+        // The normal is the moved sphere's
+        let mut w = crate::world::World::new();
+        w.add_object(s);
+        let r = r.with_time(0.5);
+        let xs = w.intersect_world(&r);
+        let comps =
+            crate::intersection::prepare_computations(&xs[0], &r, &w.registry, None).unwrap();
+        assert_abs_diff_eq!(comps.normalv, Tuple::vector(0.0, 0.0, -1.0));
+    }
 }
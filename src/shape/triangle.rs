@@ -0,0 +1,273 @@
+use crate::{
+    bounds::BoundingBox,
+    core::constants::{EPSILON, PARALLEL_THRESHOLD},
+    intersection::Intersection,
+    materials::Material,
+    matrix::Matrix,
+    ray::Ray,
+    scalar::Float,
+    scene::{triple, ShapeDescription},
+    shape::{LightLinks, Shape, ShapeData, Visibility},
+    tuple::Tuple,
+};
+
+// Möller–Trumbore ray/triangle test. Returns (t, u, v) where u and v are the
+// barycentric weights of p2 and p3 respectively.
+pub(crate) fn moller_trumbore(
+    p1: &Tuple,
+    e1: &Tuple,
+    e2: &Tuple,
+    ray: &Ray,
+) -> Option<(Float, Float, Float)> {
+    let dir_cross_e2 = ray.direction.cross(e2);
+    let det = e1.dot(&dir_cross_e2);
+    if det.abs() < PARALLEL_THRESHOLD {
+        return None;
+    }
+
+    let f = 1.0 / det;
+    let p1_to_origin = ray.origin - *p1;
+    let u = f * p1_to_origin.dot(&dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * ray.direction.dot(&origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = f * e2.dot(&origin_cross_e1);
+    Some((t, u, v))
+}
+
+// Barycentric weights of p2 and p3 at a point in the triangle's plane, as
+// moller_trumbore gives them for hits
+pub(crate) fn barycentric(p1: &Tuple, e1: &Tuple, e2: &Tuple, point: &Tuple) -> (Float, Float) {
+    let p = *point - *p1;
+    let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+    let (dp1, dp2) = (p.dot(e1), p.dot(e2));
+    let denominator = d11 * d22 - d12 * d12;
+    (
+        (d22 * dp1 - d12 * dp2) / denominator,
+        (d11 * dp2 - d12 * dp1) / denominator,
+    )
+}
+
+// Texture coordinates given at the corners, blended by barycentric weights
+pub(crate) fn interpolate_texture(
+    texture: &[(Float, Float); 3],
+    (u, v): (Float, Float),
+) -> (Float, Float) {
+    let w = 1.0 - u - v;
+    (
+        texture[0].0 * w + texture[1].0 * u + texture[2].0 * v,
+        texture[0].1 * w + texture[1].1 * u + texture[2].1 * v,
+    )
+}
+
+// The x axis flattened onto a triangle, as planar mapping would lay a normal
+// map over it, or the first edge if the triangle faces along x
+pub(crate) fn tangent_in_plane(normal: &Tuple, e1: &Tuple) -> Tuple {
+    let x = Tuple::vector(1.0, 0.0, 0.0);
+    let tangent = x - *normal * normal.dot(&x);
+    if tangent.magnitude() < EPSILON {
+        e1.normalise()
+    } else {
+        tangent.normalise()
+    }
+}
+
+// Flat-shaded triangle: the same normal is used across the whole face
+#[derive(Clone)]
+pub struct Triangle {
+    pub data: ShapeData,
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    pub e1: Tuple,
+    pub e2: Tuple,
+    pub normal: Tuple,
+    // Texture coordinates at p1, p2 and p3, such as an OBJ file's vt, for
+    // UvMapping::Surface
+    pub texture: Option<[(Float, Float); 3]>,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple) -> Triangle {
+        let identity = Matrix::identity();
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        Triangle {
+            data: ShapeData {
+                id: 0,
+                transform: identity.clone(),
+                inverse_transform: identity.inverse(),
+                inverse_transpose: identity.clone(),
+                pivot: None,
+                name: None,
+                visibility: Visibility::default(),
+                light_links: LightLinks::default(),
+                velocity: None,
+                material: Material::new(),
+            },
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal: e2.cross(&e1).normalise(),
+            texture: None,
+        }
+    }
+
+    pub fn with_texture(self, texture: [(Float, Float); 3]) -> Triangle {
+        Triangle {
+            texture: Some(texture),
+            ..self
+        }
+    }
+}
+
+impl Shape for Triangle {
+    fn data(&self) -> &ShapeData {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut ShapeData {
+        &mut self.data
+    }
+
+    fn local_intersect_into(&self, ray: &Ray, xs: &mut Vec<Intersection>) {
+        if let Some((t, u, v)) = moller_trumbore(&self.p1, &self.e1, &self.e2, ray) {
+            xs.push(Intersection::with_uv(t, self, u, v));
+        }
+    }
+
+    fn local_normal_at(&self, _local_point: &Tuple) -> Tuple {
+        self.normal
+    }
+
+    fn local_tangent_at(&self, _local_point: &Tuple) -> Tuple {
+        tangent_in_plane(&self.normal, &self.e1)
+    }
+
+    fn local_uv_at(&self, local_point: &Tuple) -> Option<(Float, Float)> {
+        let weights = barycentric(&self.p1, &self.e1, &self.e2, local_point);
+        self.texture
+            .map(|texture| interpolate_texture(&texture, weights))
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut bounds = BoundingBox::empty();
+        bounds.add_point(self.p1);
+        bounds.add_point(self.p2);
+        bounds.add_point(self.p3);
+        bounds
+    }
+
+    fn describe(&self) -> Result<ShapeDescription, String> {
+        Ok(ShapeDescription::Triangle {
+            p1: triple(self.p1),
+            p2: triple(self.p2),
+            p3: triple(self.p3),
+            uv: self.texture.map(|texture| texture.map(|(u, v)| [u, v])),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_triangle() -> Triangle {
+        Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn triangle_tangents_lie_in_the_triangle() {
+        let t = test_triangle();
+        let tangent = t.local_tangent_at(&Tuple::point(0.0, 0.5, 0.0));
+        assert_eq!(tangent, Tuple::vector(1.0, 0.0, 0.0));
+
+        // Facing along x, the first edge is used instead
+        let side = Triangle::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(0.0, 0.0, 1.0),
+        );
+        let tangent = side.local_tangent_at(&Tuple::point(0.0, 0.2, 0.2));
+        assert_eq!(tangent, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn constructing_a_triangle() {
+        let t = test_triangle();
+
+        assert_eq!(t.p1, Tuple::point(0.0, 1.0, 0.0));
+        assert_eq!(t.p2, Tuple::point(-1.0, 0.0, 0.0));
+        assert_eq!(t.p3, Tuple::point(1.0, 0.0, 0.0));
+        assert_eq!(t.e1, Tuple::vector(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2, Tuple::vector(1.0, -1.0, 0.0));
+        assert_eq!(t.normal, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn finding_normal_on_a_triangle() {
+        let t = test_triangle();
+        let n1 = t.local_normal_at(&Tuple::point(0.0, 0.5, 0.0));
+        let n2 = t.local_normal_at(&Tuple::point(-0.5, 0.75, 0.0));
+        let n3 = t.local_normal_at(&Tuple::point(0.5, 0.25, 0.0));
+
+        assert_eq!(n1, t.normal);
+        assert_eq!(n2, t.normal);
+        assert_eq!(n3, t.normal);
+    }
+
+    #[test]
+    fn intersecting_ray_parallel_to_triangle() {
+        let t = test_triangle();
+        let r = Ray::new(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p1_p3_edge() {
+        let t = test_triangle();
+        let r = Ray::new(Tuple::point(1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p1_p2_edge() {
+        let t = test_triangle();
+        let r = Ray::new(Tuple::point(-1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_p2_p3_edge() {
+        let t = test_triangle();
+        let r = Ray::new(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_strikes_a_triangle() {
+        let t = test_triangle();
+        let r = Ray::new(Tuple::point(0.0, 0.5, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&r);
+
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 2.0);
+    }
+}
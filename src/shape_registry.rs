@@ -1,43 +1,237 @@
-use crate::shape::Shape;
+use crate::{materials::Material, matrix::Matrix, shape::Shape};
 use std::collections::HashMap;
+use std::rc::Rc;
 
+// Events kept before they're collapsed into one Restored, so a registry
+// nobody takes events from doesn't grow without bound
+const MAX_EVENTS: usize = 1024;
+
+// The maps are shared copy-on-write, so a snapshot is a handful of reference
+// count bumps and later edits only copy what they touch.
 pub struct ShapeRegistry {
-    shapes: HashMap<u32, Box<dyn Shape>>,
-    insertion_order: Vec<u32>, // Track insertion order for indexing
-    next_id: u32,              // Counter for unique shape IDs
+    shapes: Rc<HashMap<u32, Rc<dyn Shape>>>,
+    insertion_order: Rc<Vec<u32>>, // Track insertion order for indexing
+    next_id: u32,                  // Counter for unique shape IDs
+    owners: Rc<HashMap<u32, (u32, Vec<usize>)>>, // Nested shape id -> containing top-level id and child index path
+    events: Vec<RegistryEvent>,                  // Changes since the last take_events
+    bounds_revision: u64,                        // Bumped by every change that can move a shape
+}
+
+// What changed in a registry, for anything caching data derived from its
+// shapes. Ids are of top-level shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryEvent {
+    Added(u32),
+    Removed(u32),
+    Replaced(u32),
+    TransformChanged(u32),
+    MaterialChanged(u32),
+    // Handed out by get_mut, so anything may have changed
+    Modified(u32),
+    // A snapshot was restored, or there were more changes than are kept;
+    // every shape may have changed
+    Restored,
+}
+
+impl RegistryEvent {
+    // Whether shapes may have moved, been resized or been renumbered, making
+    // bounds and BVHs built from the registry stale
+    pub fn changes_bounds(&self) -> bool {
+        !matches!(self, RegistryEvent::MaterialChanged(_))
+    }
+}
+
+// Saved registry state for undo/redo. Shapes are shared with the registry
+// rather than copied, so holding on to many snapshots is cheap.
+#[derive(Clone)]
+pub struct RegistrySnapshot {
+    shapes: Rc<HashMap<u32, Rc<dyn Shape>>>,
+    insertion_order: Rc<Vec<u32>>,
+    next_id: u32,
+    owners: Rc<HashMap<u32, (u32, Vec<usize>)>>,
+}
+
+impl Default for ShapeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ShapeRegistry {
     pub fn new() -> Self {
         ShapeRegistry {
-            shapes: HashMap::new(),
-            insertion_order: Vec::new(),
+            shapes: Rc::new(HashMap::new()),
+            insertion_order: Rc::new(Vec::new()),
             next_id: 0,
+            owners: Rc::new(HashMap::new()),
+            events: Vec::new(),
+            bounds_revision: 0,
         }
     }
 
-    pub fn register<T: Shape + 'static>(&mut self, mut object: T) -> u32 {
+    pub fn register<T: Shape + 'static>(&mut self, object: T) -> u32 {
+        self.register_boxed(Box::new(object))
+    }
+
+    pub fn register_boxed(&mut self, mut object: Box<dyn Shape>) -> u32 {
         let id = self.next_id;
         self.next_id += 1;
         object.data_mut().set_id(id);
-        self.shapes.insert(id, Box::new(object));
-        self.insertion_order.push(id);
+        self.assign_child_ids(object.as_mut(), id, &mut Vec::new());
+        Rc::make_mut(&mut self.shapes).insert(id, Rc::from(object));
+        Rc::make_mut(&mut self.insertion_order).push(id);
+        self.record(RegistryEvent::Added(id));
         id
     }
 
+    // Removes a top-level shape and everything nested in it. Shapes after it
+    // move up one index. Returns false if the id isn't a top-level shape.
+    pub fn remove(&mut self, id: u32) -> bool {
+        if Rc::make_mut(&mut self.shapes).remove(&id).is_none() {
+            return false;
+        }
+        Rc::make_mut(&mut self.insertion_order).retain(|&other| other != id);
+        self.forget_children(id);
+        self.record(RegistryEvent::Removed(id));
+        true
+    }
+
+    // Puts a new shape in place of a top-level one, keeping its id and index.
+    // Shapes nested in the new one get fresh ids.
+    pub fn replace(&mut self, id: u32, mut object: Box<dyn Shape>) -> bool {
+        if !self.shapes.contains_key(&id) {
+            return false;
+        }
+        self.forget_children(id);
+        object.data_mut().set_id(id);
+        self.assign_child_ids(object.as_mut(), id, &mut Vec::new());
+        Rc::make_mut(&mut self.shapes).insert(id, Rc::from(object));
+        self.record(RegistryEvent::Replaced(id));
+        true
+    }
+
+    pub fn set_transform(&mut self, id: u32, transform: Matrix) -> bool {
+        let Some(shape) = self.shape_mut(id) else {
+            return false;
+        };
+        shape.set_transform(transform);
+        self.record(RegistryEvent::TransformChanged(id));
+        true
+    }
+
+    pub fn set_material(&mut self, id: u32, material: Material) -> bool {
+        let Some(shape) = self.shape_mut(id) else {
+            return false;
+        };
+        shape.set_material(material);
+        self.record(RegistryEvent::MaterialChanged(id));
+        true
+    }
+
+    // Changes since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<RegistryEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // Changes whenever shapes may have moved, so a cache built from the
+    // registry can note the revision and check it later
+    pub fn bounds_revision(&self) -> u64 {
+        self.bounds_revision
+    }
+
+    fn record(&mut self, event: RegistryEvent) {
+        if event.changes_bounds() {
+            self.bounds_revision += 1;
+        }
+        if self.events.len() >= MAX_EVENTS {
+            self.events.clear();
+            self.events.push(RegistryEvent::Restored);
+        }
+        self.events.push(event);
+    }
+
+    fn forget_children(&mut self, owner: u32) {
+        if self.owners.values().any(|(other, _)| *other == owner) {
+            Rc::make_mut(&mut self.owners).retain(|_, (other, _)| *other != owner);
+        }
+    }
+
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot {
+            shapes: Rc::clone(&self.shapes),
+            insertion_order: Rc::clone(&self.insertion_order),
+            next_id: self.next_id,
+            owners: Rc::clone(&self.owners),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: RegistrySnapshot) {
+        self.shapes = snapshot.shapes;
+        self.insertion_order = snapshot.insertion_order;
+        self.next_id = snapshot.next_id;
+        self.owners = snapshot.owners;
+        self.record(RegistryEvent::Restored);
+    }
+
+    fn assign_child_ids(&mut self, object: &mut dyn Shape, owner: u32, path: &mut Vec<usize>) {
+        for (index, child) in object.children_mut().into_iter().enumerate() {
+            let id = self.next_id;
+            self.next_id += 1;
+            child.data_mut().set_id(id);
+            path.push(index);
+            Rc::make_mut(&mut self.owners).insert(id, (owner, path.clone()));
+            self.assign_child_ids(child, owner, path);
+            path.pop();
+        }
+    }
+
+    // Looks up top-level shapes and shapes nested inside composites
     pub fn get(&self, id: u32) -> Option<&dyn Shape> {
-        self.shapes.get(&id).map(|s| s.as_ref())
+        if let Some(shape) = self.shapes.get(&id) {
+            return Some(shape.as_ref());
+        }
+
+        let (owner, path) = self.owners.get(&id)?;
+        let mut shape = self.shapes.get(owner)?.as_ref();
+        for &index in path {
+            shape = shape.child(index)?;
+        }
+        Some(shape)
+    }
+
+    // A top-level shape itself rather than a borrow, for instances to share
+    pub fn get_shared(&self, id: u32) -> Option<Rc<dyn Shape>> {
+        self.shapes.get(&id).cloned()
+    }
+
+    // The top-level shape a shape belongs to, or the shape itself if it isn't
+    // nested
+    pub fn root_id(&self, id: u32) -> u32 {
+        self.owners.get(&id).map_or(id, |(owner, _)| *owner)
+    }
+
+    // A shape still referenced by a snapshot is copied before being handed out,
+    // so edits never leak into saved states
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut dyn Shape> {
+        if self.shapes.contains_key(&id) {
+            self.record(RegistryEvent::Modified(id));
+        }
+        self.shape_mut(id)
     }
 
-    pub fn get_mut(&mut self, id: u32) -> Option<&mut Box<dyn Shape>> {
-        self.shapes.get_mut(&id)
+    fn shape_mut(&mut self, id: u32) -> Option<&mut dyn Shape> {
+        let shape = Rc::make_mut(&mut self.shapes).get_mut(&id)?;
+        if Rc::get_mut(shape).is_none() {
+            *shape = Rc::from(shape.clone_box());
+        }
+        Rc::get_mut(shape).map(|s| s as &mut dyn Shape)
     }
 
-    pub fn get_all_spheres(&self) -> Vec<&dyn Shape> {
+    pub fn get_all_shapes(&self) -> Vec<&dyn Shape> {
         self.shapes.values().map(|s| s.as_ref()).collect()
     }
 
-    // Get sphere by insertion order (0-based indexing)
+    // Get shape by insertion order (0-based indexing)
     pub fn get_by_index(&self, index: usize) -> Option<&dyn Shape> {
         self.insertion_order
             .get(index)
@@ -45,7 +239,7 @@ impl ShapeRegistry {
             .map(|s| s.as_ref())
     }
 
-    // Number of spheres in registry
+    // Number of shapes in registry
     pub fn len(&self) -> usize {
         self.shapes.len()
     }
@@ -54,18 +248,33 @@ impl ShapeRegistry {
         self.shapes.is_empty()
     }
 
-    // Find sphere by predicate
-    pub fn find_sphere<F>(&self, predicate: F) -> Option<&dyn Shape>
+    // Find shape by predicate
+    pub fn find_shape<F>(&self, predicate: F) -> Option<&dyn Shape>
     where
         F: Fn(&dyn Shape) -> bool,
     {
         self.shapes
             .values()
             .map(|s| s.as_ref())
-            .find(|sphere| predicate(*sphere))
+            .find(|shape| predicate(*shape))
+    }
+
+    // The first shape with this name, top-level shapes in insertion order
+    // each followed by whatever is nested inside them
+    pub fn find_by_name(&self, name: &str) -> Option<&dyn Shape> {
+        fn search<'a>(shape: &'a dyn Shape, name: &str) -> Option<&'a dyn Shape> {
+            if shape.name() == Some(name) {
+                return Some(shape);
+            }
+            shape
+                .children()
+                .into_iter()
+                .find_map(|child| search(child, name))
+        }
+        self.iter().find_map(|shape| search(shape, name))
     }
 
-    // Iterator over spheres in insertion order
+    // Iterator over shapes in insertion order
     pub fn iter(&self) -> impl Iterator<Item = &dyn Shape> {
         self.insertion_order
             .iter()
@@ -77,7 +286,28 @@ impl ShapeRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::shape::sphere::Sphere;
+    use crate::matrix::Matrix;
+    use crate::shape::{group::Group, sphere::Sphere};
+
+    #[test]
+    fn shapes_can_be_found_by_name() {
+        let mut registry = ShapeRegistry::new();
+        let mut floor = Sphere::new();
+        floor.set_name(Some("floor".to_string()));
+        let mut group = Group::new();
+        let mut ball = Sphere::new();
+        ball.set_name(Some("ball".to_string()));
+        group.add_child(Box::new(ball));
+        registry.register(Sphere::new());
+        let floor_id = registry.register(floor);
+        registry.register(group);
+
+        assert_eq!(registry.find_by_name("floor").unwrap().id(), floor_id);
+        let ball = registry.find_by_name("ball").unwrap();
+        assert_eq!(registry.get(ball.id()).unwrap().name(), Some("ball"));
+        assert_ne!(registry.root_id(ball.id()), ball.id());
+        assert!(registry.find_by_name("wall").is_none());
+    }
 
     #[test]
     fn registry_can_store_and_retrieve_sphere() {
@@ -99,4 +329,131 @@ mod tests {
 
         assert!(result.is_none());
     }
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_edits() {
+        let mut registry = ShapeRegistry::new();
+        let id = registry.register(Sphere::new());
+        let snapshot = registry.snapshot();
+
+        registry.register(Sphere::new());
+        let mut material = registry.get(id).unwrap().material().clone();
+        material.ambient = 1.0;
+        registry.get_mut(id).unwrap().set_material(material);
+
+        registry.restore(snapshot);
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get(id).unwrap().material().ambient, 0.1);
+    }
+
+    #[test]
+    fn restore_can_move_forwards_again() {
+        let mut registry = ShapeRegistry::new();
+        let before = registry.snapshot();
+        registry.register(Sphere::new());
+        let after = registry.snapshot();
+
+        registry.restore(before);
+        assert!(registry.is_empty());
+
+        registry.restore(after);
+        assert_eq!(registry.len(), 1);
+        // Ids handed out after a restore don't collide with restored shapes
+        assert_eq!(registry.register(Sphere::new()), 1);
+    }
+
+    #[test]
+    fn snapshots_share_unchanged_shapes() {
+        let mut registry = ShapeRegistry::new();
+        let a = registry.register(Sphere::new());
+        let b = registry.register(Sphere::new());
+        let snapshot = registry.snapshot();
+
+        registry
+            .get_mut(b)
+            .unwrap()
+            .set_transform(Matrix::translation(1.0, 0.0, 0.0));
+
+        assert!(Rc::ptr_eq(&registry.shapes[&a], &snapshot.shapes[&a]));
+        assert!(!Rc::ptr_eq(&registry.shapes[&b], &snapshot.shapes[&b]));
+    }
+
+    #[test]
+    fn removing_keeps_the_remaining_order() {
+        let mut registry = ShapeRegistry::new();
+        let ids: Vec<u32> = (0..3).map(|_| registry.register(Sphere::new())).collect();
+
+        assert!(registry.remove(ids[1]));
+        assert!(!registry.remove(ids[1]));
+
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.get_by_index(1).unwrap().id(), ids[2]);
+        assert!(registry.get(ids[1]).is_none());
+    }
+
+    #[test]
+    fn replacing_keeps_the_id_and_index_but_renumbers_children() {
+        let mut registry = ShapeRegistry::new();
+        let first = registry.register(Sphere::new());
+        let mut group = Group::new();
+        group.add_child(Box::new(Sphere::new()));
+        let group_id = registry.register(group);
+        let old_child = registry.get(group_id).unwrap().child(0).unwrap().id();
+
+        let mut replacement = Group::new();
+        replacement.add_child(Box::new(Sphere::new()));
+        assert!(registry.replace(group_id, Box::new(replacement)));
+
+        let replaced = registry.get_by_index(1).unwrap();
+        let new_child = replaced.child(0).unwrap().id();
+        assert_eq!(replaced.id(), group_id);
+        assert_ne!(new_child, old_child);
+        assert!(registry.get(old_child).is_none());
+        assert_eq!(registry.root_id(new_child), group_id);
+        assert_eq!(registry.get_by_index(0).unwrap().id(), first);
+        assert!(!registry.replace(new_child, Box::new(Sphere::new())));
+    }
+
+    #[test]
+    fn edits_are_reported_as_events() {
+        let mut registry = ShapeRegistry::new();
+        let id = registry.register(Sphere::new());
+        registry.take_events();
+        let revision = registry.bounds_revision();
+
+        let mut material = registry.get(id).unwrap().material().clone();
+        material.ambient = 0.5;
+        assert!(registry.set_material(id, material));
+        assert_eq!(registry.bounds_revision(), revision);
+        assert_eq!(registry.get(id).unwrap().material().ambient, 0.5);
+
+        assert!(registry.set_transform(id, Matrix::scaling(2.0, 2.0, 2.0)));
+        assert!(registry.remove(id));
+        assert!(!registry.set_transform(id, Matrix::identity()));
+
+        assert_eq!(
+            registry.take_events(),
+            vec![
+                RegistryEvent::MaterialChanged(id),
+                RegistryEvent::TransformChanged(id),
+                RegistryEvent::Removed(id),
+            ]
+        );
+        assert_eq!(registry.bounds_revision(), revision + 2);
+        assert!(registry.take_events().is_empty());
+    }
+
+    #[test]
+    fn unread_events_are_collapsed() {
+        let mut registry = ShapeRegistry::new();
+        for _ in 0..MAX_EVENTS + 10 {
+            registry.register(Sphere::new());
+        }
+
+        let events = registry.take_events();
+        assert_eq!(events.len(), 11);
+        assert_eq!(events[0], RegistryEvent::Restored);
+        assert_eq!(events[10], RegistryEvent::Added(MAX_EVENTS as u32 + 9));
+    }
 }
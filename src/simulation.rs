@@ -1,9 +1,20 @@
-use crate::environment::Environment;
+use crate::intersection::prepare_computations;
 use crate::projectile::Projectile;
+use crate::ray::Ray;
+use crate::tuple::{reflect, Tuple};
+use crate::world::World;
+use crate::{environment::Environment, force::Force, scalar::Float};
+
+// Bounces resolved per step, so a particle wedged in a corner can't keep
+// bouncing within a single step forever
+const MAX_BOUNCES_PER_STEP: usize = 4;
 
 pub struct Simulation {
     environment: Environment,
     projectiles: Vec<Projectile>,
+    forces: Vec<Box<dyn Force>>,
+    // Height of a floor everything bounces off, if any
+    ground: Option<Float>,
 }
 
 impl Simulation {
@@ -11,27 +22,271 @@ impl Simulation {
         Simulation {
             environment,
             projectiles,
+            forces: Vec::new(),
+            ground: None,
         }
     }
 
+    pub fn with_force(mut self, force: impl Force + 'static) -> Self {
+        self.forces.push(Box::new(force));
+        self
+    }
+
+    pub fn with_ground(mut self, height: Float) -> Self {
+        self.ground = Some(height);
+        self
+    }
+
+    // Returns its index, for forces that act on particular projectiles
+    pub fn add_projectile(&mut self, projectile: Projectile) -> usize {
+        self.projectiles.push(projectile);
+        self.projectiles.len() - 1
+    }
+
+    // One step of one unit of time, as in the book's exercise where gravity
+    // and wind are the change in velocity per tick
     pub fn tick(&mut self) {
-        for projectile in &mut self.projectiles {
-            projectile.vel = projectile.vel + self.environment.gravity + self.environment.wind;
-            projectile.pos = projectile.pos + projectile.vel;
-        }
+        self.step(1.0);
+    }
+
+    // Advances dt seconds with gravity and wind as accelerations, plus the
+    // forces divided by each projectile's mass
+    pub fn step(&mut self, dt: Float) {
+        self.advance(None, dt);
+    }
+
+    // As step, also bouncing off the world's shapes. Each particle's path is
+    // traced as a ray from its centre, and it stops where a sphere of its
+    // radius would touch whatever the ray hits, so surfaces that only graze
+    // the side of the ball are missed. Particles don't collide with each other.
+    pub fn step_in(&mut self, world: &World, dt: Float) {
+        self.advance(Some(world), dt);
     }
 
     pub fn get_projectiles(&self) -> &Vec<Projectile> {
         &self.projectiles
     }
+
+    fn advance(&mut self, world: Option<&World>, dt: Float) {
+        // Worked out for everything before anything moves, so the order of
+        // the projectiles doesn't matter
+        let accelerations: Vec<Tuple> = (0..self.projectiles.len())
+            .map(|index| self.acceleration(index))
+            .collect();
+        for (projectile, acceleration) in self.projectiles.iter_mut().zip(accelerations) {
+            projectile.vel = projectile.vel + acceleration * dt;
+            match world {
+                Some(world) => move_through(world, projectile, dt),
+                None => projectile.pos = projectile.pos + projectile.vel * dt,
+            }
+            if let Some(ground) = self.ground {
+                bounce_off_ground(projectile, ground);
+            }
+        }
+    }
+
+    fn acceleration(&self, index: usize) -> Tuple {
+        let force = self
+            .forces
+            .iter()
+            .fold(Tuple::vector(0.0, 0.0, 0.0), |sum, force| {
+                sum + force.force(index, &self.projectiles)
+            });
+        self.environment.gravity + self.environment.wind + force / self.projectiles[index].mass
+    }
+}
+
+fn move_through(world: &World, projectile: &mut Projectile, dt: Float) {
+    let mut remaining = projectile.vel.magnitude() * dt;
+    for _ in 0..MAX_BOUNCES_PER_STEP {
+        if remaining <= 0.0 {
+            break;
+        }
+        let direction = projectile.vel.normalise();
+        match contact(world, projectile.pos, direction, projectile.radius) {
+            Some((distance, normal)) if distance < remaining => {
+                // Lifted off the surface so the next ray can't hit it
+                // straight away at t = 0
+                projectile.pos =
+                    projectile.pos + direction * distance + normal * world.secondary_t_min();
+                projectile.vel = reflect(&projectile.vel, &normal) * projectile.restitution;
+                remaining = (remaining - distance) * projectile.restitution;
+            }
+            _ => {
+                projectile.pos = projectile.pos + direction * remaining;
+                break;
+            }
+        }
+    }
+}
+
+// Anything that sank below the ground this step is put back on it, heading
+// up again with its speed cut by its restitution
+fn bounce_off_ground(projectile: &mut Projectile, ground: Float) {
+    let lowest = ground + projectile.radius;
+    if projectile.pos.y < lowest {
+        projectile.pos.y = lowest;
+        if projectile.vel.y < 0.0 {
+            projectile.vel.y = -projectile.vel.y * projectile.restitution;
+        }
+    }
+}
+
+// How far a sphere centred at from can travel along direction before touching
+// a surface, and the surface normal there
+fn contact(world: &World, from: Tuple, direction: Tuple, radius: Float) -> Option<(Float, Tuple)> {
+    let ray = Ray::new(from, direction);
+    let hit = world.first_hit(&ray, 0.0)?;
+    let comps = prepare_computations(&hit, &ray, &world.registry, None)?;
+    // The normal faces back along the ray, and the ball touches sooner the
+    // more head-on it meets the surface
+    let cos = -direction.dot(&comps.normalv);
+    if cos <= 0.0 {
+        return None;
+    }
+    Some(((hit.t - radius / cos).max(0.0), comps.normalv))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tuple::Tuple;
+    use crate::scalar::TEST_EPSILON;
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        force::{Drag, Spring, SpringEnd},
+        matrix::Matrix,
+        shape::{plane::Plane, Shape},
+    };
 
     use super::*;
 
+    fn falling_ball(restitution: Float) -> Simulation {
+        let environment =
+            Environment::new(Tuple::vector(0.0, -9.8, 0.0), Tuple::vector(0.0, 0.0, 0.0));
+        let mut ball = Projectile::new(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, 0.0, 0.0));
+        ball.radius = 0.5;
+        ball.restitution = restitution;
+        Simulation::new(environment, vec![ball])
+    }
+
+    #[test]
+    fn particles_bounce_off_the_floor() {
+        let mut world = World::new();
+        world.add_object(Plane::new());
+        let mut simulation = falling_ball(0.8);
+
+        let mut heights = Vec::new();
+        for _ in 0..300 {
+            simulation.step_in(&world, 0.01);
+            heights.push(simulation.projectiles[0].pos.y);
+        }
+
+        // It never sinks into the floor, rebounds, and loses height each bounce
+        assert!(heights.iter().all(|&y| y >= 0.5 - 1e-9));
+        let lowest = (0..heights.len())
+            .min_by(|&a, &b| heights[a].total_cmp(&heights[b]))
+            .unwrap();
+        assert!(heights[lowest] < 0.6);
+        let rebound = heights[lowest..].iter().cloned().fold(0.0, Float::max);
+        assert!(rebound > 2.0 && rebound < 5.0, "{}", rebound);
+    }
+
+    #[test]
+    fn particles_bounce_off_walls_at_an_angle() {
+        let mut world = World::new();
+        let mut wall = Plane::new();
+        wall.set_transform(
+            &Matrix::translation(2.0, 0.0, 0.0)
+                * &Matrix::rotation_z(crate::scalar::consts::PI / 2.0),
+        );
+        world.add_object(wall);
+        let environment =
+            Environment::new(Tuple::vector(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 0.0));
+        let ball = Projectile::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 1.0));
+        let mut simulation = Simulation::new(environment, vec![ball]);
+
+        simulation.step_in(&world, 3.0);
+
+        // Reaches the wall at (2, 0, 2), then heads back along x
+        let ball = &simulation.projectiles[0];
+        assert_abs_diff_eq!(
+            ball.vel,
+            Tuple::vector(-1.0, 0.0, 1.0),
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(ball.pos, Tuple::point(1.0, 0.0, 3.0), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn the_ground_bounces_projectiles_without_a_world() {
+        let mut simulation = falling_ball(0.5).with_ground(0.0);
+
+        let mut heights = Vec::new();
+        for _ in 0..300 {
+            simulation.step(0.01);
+            heights.push(simulation.projectiles[0].pos.y);
+        }
+
+        assert!(heights.iter().all(|&y| y >= 0.5));
+        let lowest = (0..heights.len())
+            .min_by(|&a, &b| heights[a].total_cmp(&heights[b]))
+            .unwrap();
+        // Half the speed back is a quarter of the drop
+        let rebound = heights[lowest..].iter().cloned().fold(0.0, Float::max);
+        assert!(rebound > 1.4 && rebound < 1.8, "{}", rebound);
+    }
+
+    #[test]
+    fn forces_move_light_projectiles_further() {
+        let environment =
+            Environment::new(Tuple::vector(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 0.0));
+        let still = Tuple::vector(0.0, 0.0, 0.0);
+        let mut heavy = Projectile::new(Tuple::point(0.0, 0.0, 0.0), still);
+        heavy.mass = 4.0;
+        let light = Projectile::new(Tuple::point(0.0, 0.0, 1.0), still);
+        // The same spring pulls each towards a point 2 units along x
+        let mut simulation = Simulation::new(environment, vec![heavy, light])
+            .with_force(Spring::new(
+                0,
+                SpringEnd::Point(Tuple::point(2.0, 0.0, 0.0)),
+                1.0,
+                0.0,
+            ))
+            .with_force(Spring::new(
+                1,
+                SpringEnd::Point(Tuple::point(2.0, 0.0, 1.0)),
+                1.0,
+                0.0,
+            ));
+
+        simulation.step(0.1);
+
+        let [heavy, light] = &simulation.projectiles[..] else {
+            unreachable!()
+        };
+        assert_abs_diff_eq!(heavy.vel.x, 0.05, epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(light.vel.x, 0.2, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn drag_brings_falling_projectiles_to_a_terminal_velocity() {
+        let environment =
+            Environment::new(Tuple::vector(0.0, -9.8, 0.0), Tuple::vector(0.0, 0.0, 0.0));
+        let mut simulation = Simulation::new(environment, Vec::new()).with_force(Drag::new(0.2));
+        simulation.add_projectile(Projectile::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 0.0, 0.0),
+        ));
+
+        for _ in 0..2000 {
+            simulation.step(0.01);
+        }
+
+        // Where drag, 0.2 v^2, balances gravity
+        let terminal = Float::sqrt(9.8 / 0.2);
+        assert_abs_diff_eq!(simulation.projectiles[0].vel.y, -terminal, epsilon = 1e-3);
+    }
+
     #[test]
     fn test_simulation_tick_moves_projectile() {
         // Set up environment with gravity and wind
@@ -68,7 +323,7 @@ mod tests {
         assert_eq!(final_position.x, 0.99);
         assert_eq!(final_position.y, 1.9);
         assert_eq!(final_position.z, 0.0);
-        assert_eq!(final_position.is_point(), true); // Should remain a point
+        assert!(final_position.is_point()); // Should remain a point
     }
 
     #[test]
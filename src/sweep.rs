@@ -0,0 +1,250 @@
+use crate::{
+    background::Background,
+    camera::{Camera, Canvas},
+    colour::Colour,
+    light::Light,
+    materials::Material,
+    matrix::Matrix,
+    pattern::{checkered::Checkered, PatternType},
+    scalar::Float,
+    shape::{plane::Plane, sphere::Sphere, Shape},
+    tuple::Tuple,
+    world::World,
+};
+
+// Pixels of background left between cells of a contact sheet
+const GAP: usize = 4;
+const GAP_COLOUR: Colour = Colour {
+    r: 0.15,
+    g: 0.15,
+    b: 0.15,
+};
+
+// Material properties a sweep can vary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepParameter {
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Reflective,
+    Transparency,
+    RefractiveIndex,
+    Dispersion,
+}
+
+impl SweepParameter {
+    pub fn from_name(name: &str) -> Option<SweepParameter> {
+        match name.to_ascii_lowercase().as_str() {
+            "ambient" => Some(SweepParameter::Ambient),
+            "diffuse" => Some(SweepParameter::Diffuse),
+            "specular" => Some(SweepParameter::Specular),
+            "shininess" => Some(SweepParameter::Shininess),
+            "reflective" => Some(SweepParameter::Reflective),
+            "transparency" => Some(SweepParameter::Transparency),
+            "refractive_index" => Some(SweepParameter::RefractiveIndex),
+            "dispersion" => Some(SweepParameter::Dispersion),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SweepParameter::Ambient => "ambient",
+            SweepParameter::Diffuse => "diffuse",
+            SweepParameter::Specular => "specular",
+            SweepParameter::Shininess => "shininess",
+            SweepParameter::Reflective => "reflective",
+            SweepParameter::Transparency => "transparency",
+            SweepParameter::RefractiveIndex => "refractive_index",
+            SweepParameter::Dispersion => "dispersion",
+        }
+    }
+
+    pub fn apply(&self, material: &mut Material, value: Float) {
+        match self {
+            SweepParameter::Ambient => material.ambient = value,
+            SweepParameter::Diffuse => material.diffuse = value,
+            SweepParameter::Specular => material.specular = value,
+            SweepParameter::Shininess => material.shininess = value,
+            SweepParameter::Reflective => material.reflective = value,
+            SweepParameter::Transparency => material.transparency = value,
+            SweepParameter::RefractiveIndex => material.refractive_index = value,
+            SweepParameter::Dispersion => material.dispersion = value,
+        }
+    }
+}
+
+// One axis of a contact sheet: `steps` evenly spaced values from `from` to `to`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    pub parameter: SweepParameter,
+    pub from: Float,
+    pub to: Float,
+    pub steps: usize,
+}
+
+impl Sweep {
+    // Parses "name=from:to:steps", e.g. "reflective=0:1:5"
+    pub fn parse(spec: &str) -> Result<Sweep, String> {
+        let invalid = || format!("Invalid sweep '{}', expected name=from:to:steps", spec);
+        let (name, range) = spec.split_once('=').ok_or_else(invalid)?;
+        let parameter = SweepParameter::from_name(name.trim())
+            .ok_or_else(|| format!("Unknown sweep parameter '{}'", name.trim()))?;
+
+        let parts: Vec<&str> = range.split(':').map(str::trim).collect();
+        let [from, to, steps] = parts[..] else {
+            return Err(invalid());
+        };
+        let steps: usize = steps.parse().map_err(|_| invalid())?;
+        if steps == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Sweep {
+            parameter,
+            from: from.parse().map_err(|_| invalid())?,
+            to: to.parse().map_err(|_| invalid())?,
+            steps,
+        })
+    }
+
+    pub fn value(&self, step: usize) -> Float {
+        if self.steps <= 1 {
+            return self.from;
+        }
+        self.from + (self.to - self.from) * step as Float / (self.steps - 1) as Float
+    }
+}
+
+// A sphere with the given material standing on a checkered floor, lit from
+// above left. The sphere sits at (0, 1, 0), in view of the CLI's default camera.
+pub fn material_preview_world(material: Material) -> World {
+    let mut world = World::new();
+    world.lights = vec![Light::point_light(
+        Tuple::point(-10.0, 10.0, -10.0),
+        Colour::white(),
+    )];
+    world.background = Background::gradient(Colour::white(), Colour::new(0.4, 0.6, 0.9));
+
+    let mut floor = Plane::new();
+    let mut floor_material = Material::new();
+    floor_material.specular = 0.0;
+    floor_material.set_pattern(Some(PatternType::Checkered(Checkered::new(
+        Colour::new(0.9, 0.9, 0.9),
+        Colour::new(0.3, 0.3, 0.3),
+    ))));
+    floor.set_material(floor_material);
+    world.add_object(floor);
+
+    let mut sphere = Sphere::new();
+    sphere.set_transform(Matrix::translation(0.0, 1.0, 0.0));
+    sphere.set_material(material);
+    world.add_object(sphere);
+
+    world.build_bvh();
+    world
+}
+
+// Renders `base` once per combination of sweep values into a contact sheet.
+// Columns follow `across` and rows follow `down`; each cell is one camera frame.
+pub fn render_sweep(
+    camera: &Camera,
+    base: &Material,
+    across: &Sweep,
+    down: Option<&Sweep>,
+) -> Canvas {
+    let columns = across.steps;
+    let rows = down.map_or(1, |s| s.steps);
+    let (cell_w, cell_h) = (camera.hsize, camera.vsize);
+    let mut sheet = Canvas::new(
+        columns * cell_w + (columns - 1) * GAP,
+        rows * cell_h + (rows - 1) * GAP,
+    );
+    for y in 0..sheet.height {
+        for x in 0..sheet.width {
+            sheet.write_pixel(x, y, GAP_COLOUR);
+        }
+    }
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut material = base.clone();
+            across.parameter.apply(&mut material, across.value(column));
+            if let Some(down) = down {
+                down.parameter.apply(&mut material, down.value(row));
+            }
+
+            let world = material_preview_world(material);
+            let (left, top) = (column * (cell_w + GAP), row * (cell_h + GAP));
+            camera.render_with(&world, |y, pixels| {
+                for (x, colour) in pixels.iter().enumerate() {
+                    sheet.write_pixel(left + x, top + y, *colour);
+                }
+            });
+        }
+    }
+
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::TEST_EPSILON;
+    use crate::transformations::view_transform;
+    use approx::assert_abs_diff_eq;
+
+    fn cell_camera(size: usize) -> Camera {
+        let mut camera = Camera::new(size, size, crate::scalar::consts::PI / 3.0);
+        camera.set_transform(view_transform(
+            Tuple::point(0.0, 1.5, -5.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+        camera
+    }
+
+    #[test]
+    fn parsing_a_sweep() {
+        let sweep = Sweep::parse("reflective=0:1:5").unwrap();
+
+        assert_eq!(sweep.parameter, SweepParameter::Reflective);
+        assert_eq!(sweep.steps, 5);
+        assert_abs_diff_eq!(sweep.value(0), 0.0);
+        assert_abs_diff_eq!(sweep.value(2), 0.5);
+        assert_abs_diff_eq!(sweep.value(4), 1.0);
+
+        assert!(Sweep::parse("roughness=0:1:5").is_err());
+        assert!(Sweep::parse("reflective=0:1").is_err());
+        assert!(Sweep::parse("reflective=0:1:0").is_err());
+    }
+
+    #[test]
+    fn contact_sheet_has_a_cell_per_combination() {
+        let across = Sweep::parse("ambient=0:1:3").unwrap();
+        let down = Sweep::parse("diffuse=0:1:2").unwrap();
+
+        let sheet = render_sweep(&cell_camera(8), &Material::new(), &across, Some(&down));
+
+        assert_eq!(sheet.width, 3 * 8 + 2 * GAP);
+        assert_eq!(sheet.height, 2 * 8 + GAP);
+        assert_eq!(sheet.pixel_at(8, 0), GAP_COLOUR);
+    }
+
+    #[test]
+    fn cells_follow_the_swept_value() {
+        let across = Sweep::parse("ambient=0:1:2").unwrap();
+        let mut base = Material::new();
+        base.diffuse = 0.0;
+        base.specular = 0.0;
+
+        let sheet = render_sweep(&cell_camera(9), &base, &across, None);
+
+        // The centre of each cell looks at the sphere, which is lit only by ambient
+        let dark = sheet.pixel_at(4, 4);
+        let bright = sheet.pixel_at(9 + GAP + 4, 4);
+        assert_abs_diff_eq!(dark, Colour::black(), epsilon = TEST_EPSILON);
+        assert_abs_diff_eq!(bright, Colour::white(), epsilon = TEST_EPSILON);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::camera::{tiles, Tile};
+
+// Hands out the tiles of one image to a pool of web workers, each with its own
+// RenderContext, and keeps track of which are done. Tiles go out in reading
+// order, so the same calls always give the same assignments.
+//
+// Every tile handed out carries the scheduler's generation. restart() begins a
+// new generation, for when the scene or camera changes mid-render, and
+// results still arriving from the old one are turned away by complete().
+#[wasm_bindgen]
+pub struct TileScheduler {
+    tiles: Vec<Tile>,
+    states: Vec<TileState>,
+    queue: VecDeque<usize>,
+    generation: u32,
+    completed: usize,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TileState {
+    Pending,
+    InFlight,
+    Done,
+}
+
+#[wasm_bindgen]
+impl TileScheduler {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, tile_size: u32) -> TileScheduler {
+        let tiles = tiles(width as usize, height as usize, tile_size as usize);
+        let mut scheduler = TileScheduler {
+            states: vec![TileState::Pending; tiles.len()],
+            tiles,
+            queue: VecDeque::new(),
+            generation: 0,
+            completed: 0,
+            cancelled: false,
+        };
+        scheduler.restart();
+        scheduler
+    }
+
+    // The next tile to render as [index, generation, x, y, width, height], or
+    // nothing once every tile is handed out or the render is cancelled
+    pub fn next_tile(&mut self) -> Option<Vec<u32>> {
+        if self.cancelled {
+            return None;
+        }
+        let index = self.queue.pop_front()?;
+        self.states[index] = TileState::InFlight;
+        let tile = self.tiles[index];
+        Some(vec![
+            index as u32,
+            self.generation,
+            tile.x as u32,
+            tile.y as u32,
+            tile.width as u32,
+            tile.height as u32,
+        ])
+    }
+
+    // Marks a tile as rendered. Returns false if the result should be thrown
+    // away: it's from an earlier generation, the render was cancelled, or the
+    // tile was already done.
+    pub fn complete(&mut self, index: u32, generation: u32) -> bool {
+        if !self.is_current(index, generation) || self.cancelled {
+            return false;
+        }
+        let index = index as usize;
+        if self.states[index] == TileState::Done {
+            return false;
+        }
+        self.states[index] = TileState::Done;
+        self.completed += 1;
+        true
+    }
+
+    // Puts a tile whose worker failed or went away back at the front of the
+    // queue, so it is handed out again next
+    pub fn requeue(&mut self, index: u32, generation: u32) {
+        if self.is_current(index, generation) && self.states[index as usize] == TileState::InFlight
+        {
+            self.states[index as usize] = TileState::Pending;
+            self.queue.push_front(index as usize);
+        }
+    }
+
+    // Stops handing out tiles and ignores results still in flight
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    // Starts a new generation with every tile waiting to be rendered again
+    pub fn restart(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.states.fill(TileState::Pending);
+        self.queue = (0..self.tiles.len()).collect();
+        self.completed = 0;
+        self.cancelled = false;
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn tile_count(&self) -> u32 {
+        self.tiles.len() as u32
+    }
+
+    pub fn completed_count(&self) -> u32 {
+        self.completed as u32
+    }
+
+    pub fn in_flight_count(&self) -> u32 {
+        self.states
+            .iter()
+            .filter(|&&state| state == TileState::InFlight)
+            .count() as u32
+    }
+
+    // Fraction of tiles done, from 0 to 1
+    pub fn get_progress(&self) -> f32 {
+        if self.tiles.is_empty() {
+            1.0
+        } else {
+            self.completed as f32 / self.tiles.len() as f32
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completed == self.tiles.len()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl TileScheduler {
+    fn is_current(&self, index: u32, generation: u32) -> bool {
+        generation == self.generation && (index as usize) < self.tiles.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_all(scheduler: &mut TileScheduler) -> Vec<Vec<u32>> {
+        std::iter::from_fn(|| scheduler.next_tile()).collect()
+    }
+
+    #[test]
+    fn tiles_are_handed_out_once_in_reading_order() {
+        let mut scheduler = TileScheduler::new(10, 7, 4);
+        let handed_out = take_all(&mut scheduler);
+
+        assert_eq!(handed_out.len(), 6);
+        assert_eq!(handed_out[2], vec![2, 1, 8, 0, 2, 4]);
+        assert_eq!(handed_out, take_all(&mut TileScheduler::new(10, 7, 4)));
+        assert_eq!(scheduler.in_flight_count(), 6);
+        assert!(!scheduler.is_finished());
+
+        for tile in &handed_out {
+            assert!(scheduler.complete(tile[0], tile[1]));
+        }
+        assert!(!scheduler.complete(0, 1));
+        assert!(scheduler.is_finished());
+        assert_eq!(scheduler.get_progress(), 1.0);
+    }
+
+    #[test]
+    fn restarting_turns_away_results_from_the_old_generation() {
+        let mut scheduler = TileScheduler::new(8, 8, 4);
+        let old = scheduler.next_tile().unwrap();
+
+        scheduler.restart();
+        assert!(!scheduler.complete(old[0], old[1]));
+        assert_eq!(scheduler.completed_count(), 0);
+        assert_eq!(take_all(&mut scheduler).len(), 4);
+        assert_eq!(scheduler.generation(), 2);
+    }
+
+    #[test]
+    fn cancelling_stops_the_render_and_requeued_tiles_come_back_first() {
+        let mut scheduler = TileScheduler::new(8, 8, 4);
+        let first = scheduler.next_tile().unwrap();
+        let second = scheduler.next_tile().unwrap();
+
+        scheduler.requeue(first[0], first[1]);
+        assert_eq!(scheduler.next_tile().unwrap(), first);
+
+        scheduler.cancel();
+        assert!(scheduler.next_tile().is_none());
+        assert!(!scheduler.complete(second[0], second[1]));
+        assert!(scheduler.is_cancelled());
+    }
+}
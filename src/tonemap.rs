@@ -0,0 +1,203 @@
+use crate::{colour::Colour, lut::ColourLut, scalar::Float};
+use std::rc::Rc;
+
+// How HDR colours are squeezed into [0, 1] before quantising to 8 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapOperator {
+    // Anything brighter than 1.0 is cut off
+    #[default]
+    Clamp,
+    // c / (1 + c): compresses highlights instead of clipping them
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub fn from_name(name: &str) -> Option<ToneMapOperator> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Some(ToneMapOperator::Clamp),
+            "reinhard" => Some(ToneMapOperator::Reinhard),
+            "aces" => Some(ToneMapOperator::Aces),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, v: Float) -> Float {
+        let v = v.max(0.0);
+        match self {
+            ToneMapOperator::Clamp => v,
+            ToneMapOperator::Reinhard => v / (1.0 + v),
+            ToneMapOperator::Aces => (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14),
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+// Converts rendered colours to display bytes. The CLI and the wasm canvas share
+// this so a frame looks the same in the browser as in the saved image.
+#[derive(Debug, Clone)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    // Multiplier applied before the operator, in linear space
+    pub exposure: Float,
+    // 1.0 leaves values as they are; 2.2 approximates sRGB encoding
+    pub gamma: Float,
+    // Encode with the exact sRGB curve instead of gamma, for viewers that
+    // expect sRGB images
+    pub srgb: bool,
+    // Optional colour grade applied to the final display colour
+    pub lut: Option<Rc<ColourLut>>,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::Clamp,
+            exposure: 1.0,
+            gamma: 1.0,
+            srgb: false,
+            lut: None,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: Float, gamma: Float) -> ToneMapping {
+        ToneMapping {
+            operator,
+            exposure,
+            gamma,
+            srgb: false,
+            lut: None,
+        }
+    }
+
+    pub fn map(&self, colour: Colour) -> Colour {
+        let channel = |v: Float| {
+            let mapped = self.operator.apply(v * self.exposure);
+            if self.srgb {
+                srgb_encode(mapped)
+            } else if self.gamma == 1.0 {
+                mapped
+            } else {
+                mapped.powf(1.0 / self.gamma)
+            }
+        };
+        let mapped = Colour::new(channel(colour.r), channel(colour.g), channel(colour.b));
+        match &self.lut {
+            Some(lut) => lut.apply(mapped),
+            None => mapped,
+        }
+    }
+
+    pub fn to_rgba8(&self, colour: Colour) -> [u8; 4] {
+        self.map(colour).to_rgba8()
+    }
+}
+
+// Linear [0, 1] to the sRGB transfer curve: a short linear toe, then a 2.4
+// power that averages out to roughly gamma 2.2
+fn srgb_encode(v: Float) -> Float {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else if v >= 1.0 {
+        // The formula lands a hair under 1.0, which would truncate to 254
+        1.0
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn default_mapping_matches_plain_clamping() {
+        let t = ToneMapping::default();
+
+        assert_eq!(t.to_rgba8(Colour::new(0.5, 1.5, -0.2)), [127, 255, 0, 255]);
+    }
+
+    #[test]
+    fn reinhard_compresses_highlights() {
+        let t = ToneMapping::new(ToneMapOperator::Reinhard, 1.0, 1.0);
+        let c = t.map(Colour::new(1.0, 3.0, 0.0));
+
+        assert_abs_diff_eq!(c.r, 0.5);
+        assert_abs_diff_eq!(c.g, 0.75);
+        assert_abs_diff_eq!(c.b, 0.0);
+    }
+
+    #[test]
+    fn aces_stays_in_range() {
+        let t = ToneMapping::new(ToneMapOperator::Aces, 1.0, 1.0);
+
+        for v in [0.0, 0.18, 1.0, 10.0, 1000.0] {
+            let c = t.map(Colour::new(v, v, v));
+            assert!((0.0..=1.0).contains(&c.r), "{} -> {}", v, c.r);
+        }
+    }
+
+    #[test]
+    fn exposure_scales_before_mapping() {
+        let t = ToneMapping::new(ToneMapOperator::Clamp, 2.0, 1.0);
+
+        assert_abs_diff_eq!(t.map(Colour::new(0.25, 0.0, 0.0)).r, 0.5);
+    }
+
+    #[test]
+    fn gamma_brightens_midtones() {
+        let t = ToneMapping::new(ToneMapOperator::Clamp, 1.0, 2.0);
+
+        assert_abs_diff_eq!(t.map(Colour::new(0.25, 0.0, 1.0)).r, 0.5);
+        assert_abs_diff_eq!(t.map(Colour::new(0.25, 0.0, 1.0)).b, 1.0);
+    }
+
+    #[test]
+    fn srgb_encoding_follows_the_standard_curve() {
+        let mut t = ToneMapping::new(ToneMapOperator::Clamp, 1.0, 1.0);
+        t.srgb = true;
+
+        // Linear 0.18 grey encodes to 0.461; the toe keeps darks linear
+        assert_eq!(t.to_rgba8(Colour::new(0.18, 1.0, 0.0)), [117, 255, 0, 255]);
+        assert_abs_diff_eq!(t.map(Colour::new(0.001, 0.0, 0.0)).r, 0.01292);
+    }
+
+    #[test]
+    fn srgb_encoding_replaces_gamma() {
+        let mut t = ToneMapping::new(ToneMapOperator::Reinhard, 1.0, 2.2);
+        t.srgb = true;
+
+        // Reinhard maps 1.0 to 0.5, which sRGB encodes as 0.735
+        assert_abs_diff_eq!(t.map(Colour::new(1.0, 0.0, 0.0)).r, 0.7354, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn lut_is_applied_after_tone_mapping() {
+        let lut = ColourLut::parse_cube(
+            "LUT_3D_SIZE 2\n1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n",
+        )
+        .unwrap();
+        let mut t = ToneMapping::new(ToneMapOperator::Clamp, 1.0, 1.0);
+        t.lut = Some(Rc::new(lut));
+
+        // The LUT inverts, and sees the clamped value rather than 3.0
+        assert_eq!(t.to_rgba8(Colour::new(3.0, 0.0, 1.0)), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn operators_are_looked_up_by_name() {
+        assert_eq!(
+            ToneMapOperator::from_name("Reinhard"),
+            Some(ToneMapOperator::Reinhard)
+        );
+        assert_eq!(
+            ToneMapOperator::from_name("aces"),
+            Some(ToneMapOperator::Aces)
+        );
+        assert_eq!(ToneMapOperator::from_name("unknown"), None);
+    }
+}
@@ -1,25 +1,36 @@
+use crate::scalar::Float;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
+// Laid out like [x, y, z, w], so it converts to and from an array for free
 #[derive(Debug, Copy, Clone)]
+#[repr(C)]
 pub struct Tuple {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub w: f64, // 1 for point, 0 for vector
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub w: Float, // 1 for point, 0 for vector
 }
 
 impl Tuple {
-    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Tuple {
+    pub fn new(x: Float, y: Float, z: Float, w: Float) -> Tuple {
         Tuple { x, y, z, w }
     }
 
-    pub fn point(x: f64, y: f64, z: f64) -> Tuple {
+    pub fn point(x: Float, y: Float, z: Float) -> Tuple {
         Tuple { x, y, z, w: 1.0 }
     }
-    pub fn vector(x: f64, y: f64, z: f64) -> Tuple {
+    pub fn vector(x: Float, y: Float, z: Float) -> Tuple {
         Tuple { x, y, z, w: 0.0 }
     }
 
+    pub fn from_array([x, y, z, w]: [Float; 4]) -> Tuple {
+        Tuple { x, y, z, w }
+    }
+
+    pub fn to_array(self) -> [Float; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+
     pub fn is_point(&self) -> bool {
         self.w == 1.0
     }
@@ -29,7 +40,7 @@ impl Tuple {
     }
 
     #[inline]
-    pub fn magnitude(&self) -> f64 {
+    pub fn magnitude(&self) -> Float {
         (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
     }
 
@@ -45,7 +56,7 @@ impl Tuple {
     }
 
     #[inline]
-    pub fn dot(&self, other: &Tuple) -> f64 {
+    pub fn dot(&self, other: &Tuple) -> Float {
         self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
     }
 
@@ -61,7 +72,7 @@ impl Tuple {
 }
 
 pub fn reflect(dir: &Tuple, normal: &Tuple) -> Tuple {
-    dir.clone() - normal.clone() * 2.0 * dir.dot(normal)
+    *dir - *normal * 2.0 * dir.dot(normal)
 }
 
 impl Add for Tuple {
@@ -100,9 +111,9 @@ impl Neg for Tuple {
     }
 }
 
-impl Mul<f64> for Tuple {
+impl Mul<Float> for Tuple {
     type Output = Tuple;
-    fn mul(self, scalar: f64) -> Self::Output {
+    fn mul(self, scalar: Float) -> Self::Output {
         Tuple {
             x: self.x * scalar,
             y: self.y * scalar,
@@ -112,9 +123,9 @@ impl Mul<f64> for Tuple {
     }
 }
 
-impl Div<f64> for Tuple {
+impl Div<Float> for Tuple {
     type Output = Tuple;
-    fn div(self, scalar: f64) -> Self::Output {
+    fn div(self, scalar: Float) -> Self::Output {
         Tuple {
             x: self.x / scalar,
             y: self.y / scalar,
@@ -127,6 +138,7 @@ impl Div<f64> for Tuple {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scalar::TEST_EPSILON;
     use approx::{assert_abs_diff_eq, AbsDiffEq};
 
     impl PartialEq for Tuple {
@@ -136,32 +148,32 @@ mod tests {
     }
 
     impl AbsDiffEq for Tuple {
-        type Epsilon = f64;
+        type Epsilon = Float;
 
         fn default_epsilon() -> Self::Epsilon {
-            f64::EPSILON
+            Float::EPSILON
         }
 
         fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
-            f64::abs_diff_eq(&self.x, &other.x, epsilon)
-                && f64::abs_diff_eq(&self.y, &other.y, epsilon)
-                && f64::abs_diff_eq(&self.z, &other.z, epsilon)
-                && f64::abs_diff_eq(&self.w, &other.w, epsilon)
+            Float::abs_diff_eq(&self.x, &other.x, epsilon)
+                && Float::abs_diff_eq(&self.y, &other.y, epsilon)
+                && Float::abs_diff_eq(&self.z, &other.z, epsilon)
+                && Float::abs_diff_eq(&self.w, &other.w, epsilon)
         }
     }
 
     #[test]
     fn tuple_point_is_point() {
         let tuple = Tuple::new(1.0, 2.0, 3.0, 1.0);
-        assert_eq!(tuple.is_point(), true);
-        assert_eq!(tuple.is_vector(), false);
+        assert!(tuple.is_point());
+        assert!(!tuple.is_vector());
     }
 
     #[test]
     fn tuple_vector_is_vector() {
         let tuple = Tuple::new(1.0, 2.0, 3.0, 0.0);
-        assert_eq!(tuple.is_point(), false);
-        assert_eq!(tuple.is_vector(), true);
+        assert!(!tuple.is_point());
+        assert!(tuple.is_vector());
     }
 
     #[test]
@@ -190,7 +202,7 @@ mod tests {
         let point2 = Tuple::point(5.0, 6.0, 7.0);
         let vector = point1 - point2;
         assert_abs_diff_eq!(vector, Tuple::new(-2.0, -4.0, -6.0, 0.0));
-        assert_eq!(vector.is_vector(), true);
+        assert!(vector.is_vector());
     }
 
     #[test]
@@ -199,7 +211,7 @@ mod tests {
         let vector = Tuple::vector(5.0, 6.0, 7.0);
         let result = point - vector;
         assert_abs_diff_eq!(result, Tuple::new(-2.0, -4.0, -6.0, 1.0));
-        assert_eq!(result.is_point(), true);
+        assert!(result.is_point());
     }
 
     #[test]
@@ -208,7 +220,7 @@ mod tests {
         let vector2 = Tuple::vector(5.0, 6.0, 7.0);
         let vector = vector1 - vector2;
         assert_abs_diff_eq!(vector, Tuple::new(-2.0, -4.0, -6.0, 0.0));
-        assert_eq!(vector.is_vector(), true);
+        assert!(vector.is_vector());
     }
 
     #[test]
@@ -217,7 +229,7 @@ mod tests {
         let vector = Tuple::vector(1.0, -2.0, 3.0);
         let result = zero - vector;
         assert_abs_diff_eq!(result, Tuple::new(-1.0, 2.0, -3.0, 0.0));
-        assert_eq!(result.is_vector(), true);
+        assert!(result.is_vector());
     }
 
     #[test]
@@ -254,8 +266,8 @@ mod tests {
             (Tuple::vector(1.0, 0.0, 0.0), 1.0),
             (Tuple::vector(0.0, 1.0, 0.0), 1.0),
             (Tuple::vector(0.0, 0.0, 1.0), 1.0),
-            (Tuple::vector(1.0, 2.0, 3.0), 14.0_f64.sqrt()),
-            (Tuple::vector(-1.0, -2.0, -3.0), 14.0_f64.sqrt()),
+            (Tuple::vector(1.0, 2.0, 3.0), Float::sqrt(14.0)),
+            (Tuple::vector(-1.0, -2.0, -3.0), Float::sqrt(14.0)),
         ];
 
         for (vector, expected) in test_cases {
@@ -282,7 +294,7 @@ mod tests {
     fn magnitude_of_normalised_vector() {
         let vector = Tuple::vector(1.0, 2.0, 3.0);
         let normalised = vector.normalise();
-        assert_eq!(normalised.magnitude(), 1.0)
+        assert_abs_diff_eq!(normalised.magnitude(), 1.0, epsilon = TEST_EPSILON)
     }
 
     #[test]
@@ -311,7 +323,7 @@ mod tests {
     #[test]
     fn reflecting_vector_off_slanted_surface() {
         let v = Tuple::vector(0.0, -1.0, 0.0);
-        let sqrt_2_div_2 = (2.0_f64).sqrt() / 2.0;
+        let sqrt_2_div_2 = Float::sqrt(2.0) / 2.0;
         let n = Tuple::vector(sqrt_2_div_2, sqrt_2_div_2, 0.0);
         let r = reflect(&v, &n);
         assert_abs_diff_eq!(r, Tuple::vector(1.0, 0.0, 0.0), epsilon = 0.0001);
@@ -1,37 +1,334 @@
 use crate::{
+    animation::Animation,
+    background::Background,
+    bounds_overlay::overlay_bounds,
+    bvh::Bvh,
     colour::Colour,
-    intersection::{hit, prepare_computations, Intersection, PreComputedData},
+    core::constants::SHADOW_BIAS,
+    intersection::{
+        hit, hit_after, prepare_computations_with_bias, schlick, Intersection, PreComputedData,
+    },
     light::Light,
-    materials::lighting,
+    light_sampler::LightSampler,
+    materials::{direct_lighting, lighting},
+    matrix::Matrix,
     pattern::{
         checkered::Checkered, gradient::Gradient, ring::Ring, striped::Striped, Pattern,
         PatternType,
     },
     ray::Ray,
-    shape::{plane::Plane, sphere::Sphere, Shape},
+    render_stats::StatsCounters,
+    scalar::{to_f64, Float},
+    scene::SceneDescription,
+    scene_graph::SceneGraph,
+    scene_hash,
+    shadow_map::ShadowMap,
+    shape::{instance::Instance, plane::Plane, sphere::Sphere, Shape},
     shape_registry::ShapeRegistry,
     tuple::Tuple,
 };
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Default number of reflections followed from each camera ray
+pub const DEFAULT_MAX_BOUNCES: u32 = 5;
+
+// Default minimum distance along shadow and reflection rays before a hit counts
+pub const DEFAULT_SECONDARY_T_MIN: Float = 1e-4;
+
+// Reflection chains whose accumulated reflectivity falls below a threshold
+// can be ended at random, with survivors weighted up so the average colour is
+// unchanged. It's off by default, as it adds noise to full renders.
+pub const DEFAULT_ROULETTE_THRESHOLD: Float = 0.0;
+
+// Cap on the chance of a bounce surviving depth roulette, so even chains of
+// perfect mirrors or clear glass end
+const MAX_ROULETTE_SURVIVAL: Float = 0.95;
+
+thread_local! {
+    // Intersection buffers for World::with_intersections to reuse
+    static SCRATCH: RefCell<Vec<Vec<Intersection>>> = const { RefCell::new(Vec::new()) };
+    // Camera and bounce rays traced on this thread, for World::bounce_count
+    static RAYS_CAST: Cell<u64> = const { Cell::new(0) };
+}
+
+// How much light transport is traced for each camera ray
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrator {
+    // Reflections, transparent shadows and everything else the world supports
+    #[default]
+    Full,
+    // Direct lighting with hard shadows only: one shadow ray per light at each
+    // hit, for interactive views and quick checks of a scene
+    Preview,
+    // Monte Carlo path tracing: light bounces diffusely between surfaces and
+    // emissive materials light their surroundings. Ambient terms are ignored.
+    // Each sample follows one random path, so it needs many samples per pixel.
+    Path,
+}
+
+impl Integrator {
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        match name.to_ascii_lowercase().as_str() {
+            "full" => Some(Integrator::Full),
+            "preview" => Some(Integrator::Preview),
+            "path" => Some(Integrator::Path),
+            _ => None,
+        }
+    }
+}
 
-pub(crate) const MAX_BOUNCES: i32 = 5;
+// What a shape does to a shadow ray crossing it
+enum ShadowFilter {
+    // Casts no shadow
+    None,
+    Opaque,
+    // Lets through this much light
+    Tint(Colour),
+}
+
+// How a world is rendered, as opposed to what's in it. Kept as one value so it
+// can be carried over when the scene is swapped out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RenderSettings {
+    // Hits closer than this along secondary rays are ignored, on top of starting
+    // those rays from the over point. Raise it if heavily scaled shapes show acne.
+    // None for DEFAULT_SECONDARY_T_MIN metres in the world's units; see
+    // World::secondary_t_min.
+    pub secondary_t_min: Option<Float>,
+    // Distance hit points are lifted off surfaces before secondary rays leave
+    // them. None for SHADOW_BIAS metres in the world's units, which suits
+    // double precision scenes of ordinary size; see World::shadow_bias.
+    pub shadow_bias: Option<Float>,
+    // Product of reflectivities below which reflection rays play Russian
+    // roulette; 0.0 always recurses to the bounce limit
+    pub roulette_threshold: Float,
+    // From this many bounces deep, each reflection or refraction survives
+    // with a chance equal to its reflectivity or transparency and is weighted
+    // up to make up for the ones ended. Deep mirror and glass chains then end
+    // on their own, so max_bounces can be raised and no longer darkens them.
+    pub roulette_depth: Option<u32>,
+    // Reflections followed from each camera ray; 0 gives no reflections at all
+    pub max_bounces: u32,
+    // Rays averaged per pixel for anti-aliasing; 1 traces through pixel
+    // centres. Where they fall is up to the camera's sampling mode.
+    pub samples: usize,
+    // When off, every surface is lit as if nothing stood between it and the
+    // light. Quicker, and handy for checking a scene's layout.
+    pub shadows: bool,
+    // Debug overlay of shape bounding boxes on camera rays
+    pub show_bounds: bool,
+    // Let light through transparent materials, tinted by their colour, instead
+    // of treating every shadow caster as opaque
+    pub transparent_shadows: bool,
+    pub integrator: Integrator,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            secondary_t_min: None,
+            shadow_bias: None,
+            roulette_threshold: DEFAULT_ROULETTE_THRESHOLD,
+            roulette_depth: None,
+            max_bounces: DEFAULT_MAX_BOUNCES,
+            samples: 1,
+            shadows: true,
+            show_bounds: false,
+            transparent_shadows: false,
+            integrator: Integrator::Full,
+        }
+    }
+}
 
 pub struct World {
     pub registry: ShapeRegistry,
-    pub light: Option<Light>,
+    // Full and preview renders add up every light; the path integrator picks
+    // one per hit with a LightSampler.
+    pub lights: Vec<Light>,
+    pub background: Background,
+    pub settings: RenderSettings,
+    // Length of one scene unit in metres. Set it with set_unit_scale so that
+    // distance-based defaults follow the scene's scale.
+    pub unit_scale: Float,
+    // Scene time in seconds, used to evaluate time-varying lights
+    pub time: Float,
+    // Keyed changes over time, applied by at_time
+    pub animation: Option<Animation>,
+    // Parented transforms of top-level shapes; see update_scene_graph
+    pub scene_graph: SceneGraph,
+    // Every call to intersect_world counts, including shadow and reflection rays
+    rays_traced: AtomicU64,
+    // Built on demand by build_bvh, with the registry's bounds revision at the
+    // time. It's ignored once shapes are added, removed or moved.
+    bvh: Option<(Bvh, u64)>,
+    // Approximate shadows for draft renders, one per light, used in place of
+    // shadow rays when present
+    shadow_maps: Vec<ShadowMap>,
+    // Work counts for RenderStats, recorded with the "stats" feature
+    stats: StatsCounters,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
     pub fn new() -> Self {
         World {
             registry: ShapeRegistry::new(),
-            light: Option::None,
+            lights: Vec::new(),
+            background: Background::default(),
+            settings: RenderSettings::default(),
+            unit_scale: 1.0,
+            time: 0.0,
+            animation: None,
+            scene_graph: SceneGraph::new(),
+            rays_traced: AtomicU64::new(0),
+            bvh: None,
+            shadow_maps: Vec::new(),
+            stats: StatsCounters::default(),
         }
     }
 
     pub fn add_object<T: Shape + 'static>(&mut self, object: T) -> u32 {
+        self.bvh = None;
         self.registry.register(object)
     }
 
+    // See ShapeRegistry::find_by_name
+    pub fn get_by_name(&self, name: &str) -> Option<&dyn Shape> {
+        self.registry.find_by_name(name)
+    }
+
+    pub fn add_boxed_object(&mut self, object: Box<dyn Shape>) -> u32 {
+        self.bvh = None;
+        self.registry.register_boxed(object)
+    }
+
+    // Adds another copy of a top-level shape, sharing its geometry; see
+    // Instance. None if there's no such shape or it's an instance itself.
+    pub fn add_instance(&mut self, prototype: u32, transform: Matrix) -> Option<u32> {
+        let prototype = self.registry.get_shared(prototype)?;
+        if prototype.is_instance() {
+            return None;
+        }
+        let mut instance = Instance::new(prototype);
+        instance.set_transform(transform);
+        Some(self.add_object(instance))
+    }
+
+    // Partitions the top-level shapes so rays only test those whose bounds they
+    // cross. Call again after editing shapes through the registry.
+    pub fn build_bvh(&mut self) {
+        let bounds: Vec<_> = self.registry.iter().map(|s| s.bvh_bounds()).collect();
+        self.bvh = Some((Bvh::build(&bounds), self.registry.bounds_revision()));
+    }
+
+    // Declares how many metres one scene unit represents, e.g. 0.01 for a scene
+    // modelled in centimetres. Only the scale is stored; distance defaults are
+    // converted from metres when they're read, by secondary_t_min, shadow_bias
+    // and metres_to_units.
+    pub fn set_unit_scale(&mut self, metres_per_unit: Float) {
+        self.unit_scale = metres_per_unit;
+    }
+
+    pub fn metres_to_units(&self, metres: Float) -> Float {
+        metres / self.unit_scale
+    }
+
+    // settings.secondary_t_min, or the default for the world's unit scale
+    pub fn secondary_t_min(&self) -> Float {
+        self.settings
+            .secondary_t_min
+            .unwrap_or_else(|| self.metres_to_units(DEFAULT_SECONDARY_T_MIN))
+    }
+
+    // settings.shadow_bias, or the default for the world's unit scale
+    pub fn shadow_bias(&self) -> Float {
+        self.settings
+            .shadow_bias
+            .unwrap_or_else(|| self.metres_to_units(SHADOW_BIAS))
+    }
+
+    // Everything needed to rebuild this world from a scene file; see
+    // SceneDescription::to_json
+    pub fn to_scene_description(&self) -> Result<SceneDescription, String> {
+        SceneDescription::from_world(self)
+    }
+
+    // Identifies the world together with render settings such as the camera
+    // and resolution; see scene_hash
+    pub fn scene_hash(&self, settings: &impl Serialize) -> Result<String, String> {
+        scene_hash::scene_hash(self, settings)
+    }
+
+    // A copy of the world at `time` seconds with its animation applied, for
+    // rendering a frame. Shapes are shared with this world until the animation
+    // changes them. A BVH is rebuilt if this world has one; shadow maps aren't
+    // carried over.
+    pub fn at_time(&self, time: Float) -> World {
+        let mut registry = ShapeRegistry::new();
+        registry.restore(self.registry.snapshot());
+        let mut world = World {
+            registry,
+            lights: self.lights.clone(),
+            background: self.background.clone(),
+            settings: self.settings,
+            unit_scale: self.unit_scale,
+            time,
+            animation: self.animation.clone(),
+            scene_graph: self.scene_graph.clone(),
+            rays_traced: AtomicU64::new(0),
+            bvh: None,
+            shadow_maps: Vec::new(),
+            stats: StatsCounters::default(),
+        };
+        if let Some(animation) = &self.animation {
+            animation.apply(&mut world, time);
+        }
+        if self.has_bvh() {
+            world.build_bvh();
+        }
+        world
+    }
+
+    // Moves the shapes attached to scene graph nodes that changed since the
+    // last call, returning how many were moved
+    pub fn update_scene_graph(&mut self) -> usize {
+        self.scene_graph.update(&mut self.registry)
+    }
+
+    // False once the registry has changed since build_bvh
+    pub fn has_bvh(&self) -> bool {
+        self.current_bvh().is_some()
+    }
+
+    fn current_bvh(&self) -> Option<&Bvh> {
+        self.bvh
+            .as_ref()
+            .filter(|(_, revision)| *revision == self.registry.bounds_revision())
+            .map(|(bvh, _)| bvh)
+    }
+
+    // Built-in scenes, as selected by name from the CLI and the wasm demo
+    pub fn from_name(name: &str) -> Option<World> {
+        match name {
+            "default" => Some(World::default_world()),
+            "test" => Some(World::test_world()),
+            "third" => Some(World::third_world()),
+            _ => None,
+        }
+        .map(|mut world: World| {
+            world.build_bvh();
+            world
+        })
+    }
+
     pub fn default_world() -> Self {
         use crate::{colour::Colour, materials::Material, matrix::Matrix, tuple::Tuple};
 
@@ -54,7 +351,17 @@ impl World {
 
         let mut world = World {
             registry: ShapeRegistry::new(),
-            light: Some(light),
+            lights: vec![light],
+            background: Background::default(),
+            settings: RenderSettings::default(),
+            unit_scale: 1.0,
+            time: 0.0,
+            animation: None,
+            scene_graph: SceneGraph::new(),
+            rays_traced: AtomicU64::new(0),
+            bvh: None,
+            shadow_maps: Vec::new(),
+            stats: StatsCounters::default(),
         };
 
         world.add_object(s1);
@@ -64,8 +371,8 @@ impl World {
     }
 
     pub fn test_world() -> Self {
+        use crate::scalar::consts::PI;
         use crate::{colour::Colour, materials::Material, matrix::Matrix, tuple::Tuple};
-        use std::f64::consts::PI;
 
         // Create light source
         let light_position = Tuple::point(-10.0, 10.0, -10.0);
@@ -74,7 +381,17 @@ impl World {
 
         let mut world = World {
             registry: ShapeRegistry::new(),
-            light: Some(light),
+            lights: vec![light],
+            background: Background::default(),
+            settings: RenderSettings::default(),
+            unit_scale: 1.0,
+            time: 0.0,
+            animation: None,
+            scene_graph: SceneGraph::new(),
+            rays_traced: AtomicU64::new(0),
+            bvh: None,
+            shadow_maps: Vec::new(),
+            stats: StatsCounters::default(),
         };
 
         // 1. Floor - extremely flattened sphere with matte texture
@@ -150,8 +467,8 @@ impl World {
     }
 
     pub fn third_world() -> Self {
+        use crate::scalar::consts::PI;
         use crate::{colour::Colour, materials::Material, matrix::Matrix, tuple::Tuple};
-        use std::f64::consts::PI;
 
         // Create light source positioned above and to the left
         let light_position = Tuple::point(-10.0, 5.0, -10.0);
@@ -160,7 +477,17 @@ impl World {
 
         let mut world = World {
             registry: ShapeRegistry::new(),
-            light: Some(light),
+            lights: vec![light],
+            background: Background::default(),
+            settings: RenderSettings::default(),
+            unit_scale: 1.0,
+            time: 0.0,
+            animation: None,
+            scene_graph: SceneGraph::new(),
+            rays_traced: AtomicU64::new(0),
+            bvh: None,
+            shadow_maps: Vec::new(),
+            stats: StatsCounters::default(),
         };
 
         // 1. Floor - a plane at y=0 with a matte finish
@@ -249,87 +576,674 @@ impl World {
 
     pub fn intersect_world(&self, ray: &Ray) -> Vec<Intersection> {
         let mut intersections = Vec::new();
-        for sphere in self.registry.iter() {
-            let mut object_intersections = sphere.intersect(ray);
-            intersections.append(&mut object_intersections);
-        }
+        self.intersect_world_into(ray, &mut intersections);
+        intersections
+    }
 
+    // As intersect_world, but refills a caller's buffer so its allocation can
+    // be reused from ray to ray
+    pub fn intersect_world_into(&self, ray: &Ray, intersections: &mut Vec<Intersection>) {
+        self.collect_intersections(ray, intersections);
         intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
-        intersections
     }
 
-    pub fn shade_hit(&self, comps: &PreComputedData, bounces_remaining: i32) -> Colour {
-        let shadowed = self.is_shadowed(comps.over_point);
+    // The nearest intersection at or beyond t_min, for callers that only need
+    // the hit. It's picked with a running minimum rather than by sorting.
+    pub fn first_hit(&self, ray: &Ray, t_min: Float) -> Option<Intersection> {
+        let mut xs = SCRATCH
+            .with(|pool| pool.borrow_mut().pop())
+            .unwrap_or_default();
+        self.collect_intersections(ray, &mut xs);
+        let nearest = hit_after(&xs, t_min).cloned();
+        SCRATCH.with(|pool| pool.borrow_mut().push(xs));
+        nearest
+    }
 
-        let surface = match self.light.clone() {
-            Some(light) => lighting(
-                comps.object.material().clone(),
-                &Sphere::new(),
-                light,
-                comps.point.clone(),
-                comps.eyev.clone(),
-                comps.normalv.clone(),
-                shadowed,
+    // Every intersection along the ray, in no particular order
+    fn collect_intersections(&self, ray: &Ray, intersections: &mut Vec<Intersection>) {
+        self.rays_traced.fetch_add(1, Ordering::Relaxed);
+        RAYS_CAST.with(|count| count.set(count.get() + 1));
+        self.stats.ray_cast();
+
+        intersections.clear();
+        match self.current_bvh() {
+            Some(bvh) => bvh.traverse(ray, |index| {
+                if let Some(shape) = self.registry.get_by_index(index) {
+                    self.stats.intersection_tests(1);
+                    shape.intersect_into(ray, intersections);
+                }
+            }),
+            None => {
+                self.stats.intersection_tests(self.registry.len());
+                for shape in self.registry.iter() {
+                    shape.intersect_into(ray, intersections);
+                }
+            }
+        }
+    }
+
+    // Intersects a ray with the world using one of this thread's scratch
+    // buffers, and hands the sorted intersections to f. Shading recurses
+    // while its caller's intersections are still in use, so buffers are kept
+    // in a pool with one per level of recursion.
+    fn with_intersections<R>(&self, ray: &Ray, f: impl FnOnce(&[Intersection]) -> R) -> R {
+        let mut xs = SCRATCH
+            .with(|pool| pool.borrow_mut().pop())
+            .unwrap_or_default();
+        self.intersect_world_into(ray, &mut xs);
+        let result = f(&xs);
+        SCRATCH.with(|pool| pool.borrow_mut().push(xs));
+        result
+    }
+
+    // Every light in the world that reaches the shape
+    pub fn lights_for<'a>(&'a self, shape: &'a dyn Shape) -> impl Iterator<Item = &'a Light> {
+        self.lights
+            .iter()
+            .filter(move |light| shape.light_links().lit_by(light))
+    }
+
+    pub fn shade_hit(&self, comps: &PreComputedData, bounces_remaining: u32) -> Colour {
+        self.shade_hit_weighted(comps, bounces_remaining, 1.0)
+    }
+
+    // throughput is the product of reflectivities along the path to this hit
+    fn shade_hit_weighted(
+        &self,
+        comps: &PreComputedData,
+        bounces_remaining: u32,
+        throughput: Float,
+    ) -> Colour {
+        // No light, or none that reaches the shape = black
+        let mut surface = Colour::black();
+        for light in self.lights_for(comps.object) {
+            let light = light.at_time(self.time);
+            let transmission = if comps.object.visibility().receive_shadows {
+                self.light_transmission_from(
+                    light.position,
+                    comps.over_point,
+                    comps.time,
+                    comps.eye,
+                )
+            } else {
+                Colour::white()
+            };
+            surface = surface
+                + lighting(
+                    comps.object.material(),
+                    comps.object,
+                    &light,
+                    comps.point,
+                    comps.eyev,
+                    comps.normalv,
+                    transmission,
+                );
+        }
+
+        let (reflected, refracted) = match self.settings.integrator {
+            Integrator::Full | Integrator::Path => (
+                self.reflected_colour_weighted(comps, bounces_remaining, throughput),
+                self.refracted_colour_weighted(comps, bounces_remaining, throughput),
             ),
-            None => Colour::new(0.0, 0.0, 0.0), // No light = black
+            Integrator::Preview => (Colour::black(), Colour::black()),
         };
 
-        let reflected = self.reflected_colour(comps, bounces_remaining);
+        let material = comps.object.material();
+        if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(comps);
+            return material.emissive
+                + surface
+                + reflected * reflectance
+                + refracted * (1.0 - reflectance);
+        }
+        material.emissive + surface + reflected + refracted
+    }
 
-        surface + reflected
+    // One sample of the light along a ray by path tracing, whatever the
+    // integrator setting; see Integrator::Path
+    pub fn colour_at_path(&self, ray: &Ray, bounces_remaining: u32) -> Colour {
+        self.with_intersections(ray, |xs| self.trace_path(ray, xs, bounces_remaining, 0.0))
+    }
+
+    // Direct light from one of the lights plus one bounce, picked at random:
+    // a mirror reflection with probability equal to the reflectivity, or else
+    // a cosine-weighted diffuse bounce. Weights keep the average unbiased.
+    fn trace_path(
+        &self,
+        ray: &Ray,
+        xs: &[Intersection],
+        bounces_remaining: u32,
+        t_min: Float,
+    ) -> Colour {
+        let Some(hit) = hit_after(xs, t_min) else {
+            return self.background.colour_at(ray);
+        };
+        let Some(comps) =
+            prepare_computations_with_bias(hit, ray, &self.registry, Some(xs), self.shadow_bias())
+        else {
+            return Colour::black();
+        };
+        let material = comps.object.material();
+
+        // Each frame and sample makes its own choices
+        let seed = ray.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ to_f64(self.time).to_bits();
+        let draw = |salt| path_draw(comps.over_point, ray.direction, seed, salt);
+        let mut colour = material.emissive;
+        // One light, picked by how much it's likely to add here and weighted
+        // by how often it's picked
+        let lights: Vec<Light> = self
+            .lights_for(comps.object)
+            .map(|light| light.at_time(self.time))
+            .collect();
+        if let Some(sample) = LightSampler::new(&lights).sample(comps.point, draw(3)) {
+            let light = &lights[sample.index];
+            let transmission = if comps.object.visibility().receive_shadows {
+                self.light_transmission_from(
+                    light.position,
+                    comps.over_point,
+                    comps.time,
+                    comps.eye,
+                )
+            } else {
+                Colour::white()
+            };
+            let direct = direct_lighting(
+                material,
+                comps.object,
+                light,
+                comps.point,
+                comps.eyev,
+                comps.normalv,
+                transmission,
+            );
+            colour = colour + direct * (1.0 / sample.pdf);
+        }
+        if bounces_remaining == 0 {
+            return colour;
+        }
+
+        let (direction, weight) = if draw(0) < material.reflective {
+            (comps.reflectv, Colour::white())
+        } else {
+            let albedo = material.colour_at(comps.object, comps.point);
+            let weight = albedo * (material.diffuse / (1.0 - material.reflective));
+            (cosine_sample(comps.normalv, draw(1), draw(2)), weight)
+        };
+
+        let bounce = Ray::new(comps.over_point, direction)
+            .with_time(comps.time)
+            .with_eye(comps.eye)
+            .with_channel(comps.channel)
+            .with_seed(ray.seed);
+        let incoming = self.with_intersections(&bounce, |xs| {
+            self.trace_path(&bounce, xs, bounces_remaining - 1, self.secondary_t_min())
+        });
+        colour + incoming * weight
+    }
+
+    pub fn colour_at(&self, ray: &Ray, bounces_remaining: u32) -> Colour {
+        // Preview shading only looks at the hit, so it can skip sorting. A hit
+        // on a shape hidden from the camera needs the shapes behind it, so
+        // those rays take the usual route.
+        if self.settings.integrator == Integrator::Preview && !self.settings.show_bounds {
+            let Some(hit) = self.first_hit(ray, 0.0) else {
+                return self.background.colour_at(ray);
+            };
+            let visible = self
+                .registry
+                .get(hit.object_id)
+                .is_some_and(|shape| shape.visibility().camera_visible);
+            if visible {
+                return match prepare_computations_with_bias(
+                    &hit,
+                    ray,
+                    &self.registry,
+                    None,
+                    self.shadow_bias(),
+                ) {
+                    Some(comps) => self.shade_hit(&comps, bounces_remaining),
+                    None => Colour::black(),
+                };
+            }
+        }
+        self.with_intersections(ray, |xs| {
+            self.colour_from_intersections(ray, xs, bounces_remaining)
+        })
     }
 
-    pub fn colour_at(&self, ray: &Ray, bounces_remaining: i32) -> Colour {
-        let xs = self.intersect_world(ray);
-        let hit = hit(&xs);
-        match hit {
+    // How many reflection, refraction and path bounces shading the camera ray
+    // traced, not counting shadow rays
+    pub fn bounce_count(&self, ray: &Ray) -> u64 {
+        let before = RAYS_CAST.with(Cell::get);
+        self.colour_at(ray, self.settings.max_bounces);
+        (RAYS_CAST.with(Cell::get) - before).saturating_sub(1)
+    }
+
+    // Shades a ray whose intersections have already been found, sorted by t
+    pub fn colour_from_intersections(
+        &self,
+        ray: &Ray,
+        xs: &[Intersection],
+        bounces_remaining: u32,
+    ) -> Colour {
+        // Shapes hidden from the camera are only dropped here, so they still
+        // show up in reflections
+        let hidden = |x: &Intersection| {
+            self.registry
+                .get(x.object_id)
+                .is_some_and(|shape| !shape.visibility().camera_visible)
+        };
+        let visible: Vec<Intersection>;
+        let xs = if xs.iter().any(hidden) {
+            visible = xs.iter().filter(|x| !hidden(x)).cloned().collect();
+            &visible
+        } else {
+            xs
+        };
+
+        let colour = match self.settings.integrator {
+            Integrator::Path => self.trace_path(ray, xs, bounces_remaining, 0.0),
+            Integrator::Full | Integrator::Preview => {
+                self.shade_intersections(ray, xs, bounces_remaining, 0.0, 1.0)
+            }
+        };
+        if self.settings.show_bounds {
+            overlay_bounds(self, ray, colour, hit(xs).map(|h| h.t))
+        } else {
+            colour
+        }
+    }
+
+    fn shade_intersections(
+        &self,
+        ray: &Ray,
+        xs: &[Intersection],
+        bounces_remaining: u32,
+        t_min: Float,
+        throughput: Float,
+    ) -> Colour {
+        match hit_after(xs, t_min) {
             Some(hit) => {
-                let comp = prepare_computations(hit, ray, &self.registry, Some(&xs));
+                let comp = prepare_computations_with_bias(
+                    hit,
+                    ray,
+                    &self.registry,
+                    Some(xs),
+                    self.shadow_bias(),
+                );
                 match comp {
-                    Some(comp) => self.shade_hit(&comp, bounces_remaining),
+                    Some(comp) => self.shade_hit_weighted(&comp, bounces_remaining, throughput),
                     None => Colour::black(),
                 }
             }
-            None => Colour::black(),
+            None => self.background.colour_at(ray),
         }
     }
 
+    pub fn rays_traced(&self) -> u64 {
+        self.rays_traced.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_rays_traced(&self) {
+        self.rays_traced.store(0, Ordering::Relaxed);
+    }
+
+    // Counts since the last Camera render started
+    #[cfg(feature = "stats")]
+    pub fn render_stats(&self) -> crate::render_stats::RenderStats {
+        self.stats.snapshot()
+    }
+
+    pub fn reset_render_stats(&self) {
+        self.stats.reset();
+    }
+
+    // Replaces ray-traced shadow tests with lookups into depth maps traced
+    // from each light. They're not kept up to date, so rebuild them after
+    // moving shapes or lights.
+    pub fn build_shadow_map(&mut self, resolution: usize) {
+        let maps = self
+            .lights
+            .iter()
+            .map(|light| ShadowMap::build(self, light.position, resolution))
+            .collect();
+        self.shadow_maps = maps;
+    }
+
+    pub fn clear_shadow_map(&mut self) {
+        self.shadow_maps.clear();
+    }
+
+    pub fn has_shadow_map(&self) -> bool {
+        !self.shadow_maps.is_empty()
+    }
+
+    // Whether every light is blocked from the point. A world without lights
+    // has nothing to cast shadows from.
     pub fn is_shadowed(&self, point: Tuple) -> bool {
-        let v = self.light.as_ref().unwrap().position - point.clone();
-        let distance = v.clone().magnitude();
+        let transmission = self.light_transmission(point);
+        transmission.r.max(transmission.g).max(transmission.b) <= 0.0
+    }
+
+    // How much of the light reaches a point, per channel, averaged over the
+    // lights: white when nothing is in the way and black behind an opaque
+    // shadow caster. With transparent_shadows, each transparent surface
+    // crossed filters the light by its transparency and colour, so a glass
+    // sphere filters it twice.
+    pub fn light_transmission(&self, point: Tuple) -> Colour {
+        self.light_transmission_at(point, 0.0)
+    }
+
+    // As light_transmission, with shadow casters where they are `time` seconds
+    // after the shutter opened
+    pub fn light_transmission_at(&self, point: Tuple, time: Float) -> Colour {
+        if self.lights.is_empty() {
+            return Colour::white();
+        }
+        let total = self
+            .lights
+            .iter()
+            .map(|light| self.light_transmission_from(light.position, point, time, point))
+            .fold(Colour::black(), |total, transmission| total + transmission);
+        total * (1.0 / self.lights.len() as Float)
+    }
+
+    // As light_transmission_at, for a light at any position and with
+    // level-of-detail shapes drawn as seen from `eye`; see Ray::eye. A shadow
+    // map is only used for the light it was built for.
+    pub fn light_transmission_from(
+        &self,
+        light_position: Tuple,
+        point: Tuple,
+        time: Float,
+        eye: Tuple,
+    ) -> Colour {
+        if !self.settings.shadows {
+            return Colour::white();
+        }
+        if let Some(map) = self
+            .shadow_maps
+            .iter()
+            .find(|map| map.is_for(light_position))
+        {
+            return if map.is_shadowed(point) {
+                Colour::black()
+            } else {
+                Colour::white()
+            };
+        }
+
+        let v = light_position - point;
+        let distance = v.magnitude();
         let direction = v.normalise();
 
-        let r = Ray::new(point, direction);
-        let xs = self.intersect_world(&r);
+        // Starting secondary_t_min along the ray, rather than ignoring hits
+        // before it, lets shapes test for hits in [0, distance)
+        let t_min = self.secondary_t_min();
+        let r = Ray::new(point + direction * t_min, direction)
+            .with_time(time)
+            .with_eye(eye);
+        self.stats.shadow_ray();
+        self.shadow_transmission(&r, distance - t_min)
+    }
+
+    // The light let through by everything the ray crosses before `distance`,
+    // as for light_transmission. Order doesn't matter for that, so hits are
+    // never sorted, and the search ends at the first opaque shadow caster.
+    pub fn shadow_transmission(&self, ray: &Ray, distance: Float) -> Colour {
+        self.rays_traced.fetch_add(1, Ordering::Relaxed);
+        self.stats.ray_cast();
+
+        let mut xs = SCRATCH
+            .with(|pool| pool.borrow_mut().pop())
+            .unwrap_or_default();
+        let mut transmission = Colour::white();
+        let mut blocks = |shape: &dyn Shape| {
+            self.stats.intersection_tests(1);
+            // A lone shape's material covers every hit on it, and if it's
+            // opaque any hit will do
+            if shape.child(0).is_none() {
+                match self.shadow_filter(shape) {
+                    ShadowFilter::None => return false,
+                    ShadowFilter::Opaque => return shape.intersects_before(ray, distance),
+                    ShadowFilter::Tint(_) => {}
+                }
+            }
+            xs.clear();
+            shape.intersect_into(ray, &mut xs);
+            for x in xs.iter().filter(|x| x.t >= 0.0 && x.t < distance) {
+                let Some(shape) = self.registry.get(x.object_id) else {
+                    continue;
+                };
+                match self.shadow_filter(shape) {
+                    ShadowFilter::None => {}
+                    ShadowFilter::Opaque => return true,
+                    ShadowFilter::Tint(tint) => transmission = transmission * tint,
+                }
+            }
+            false
+        };
+
+        let blocked = match self.current_bvh() {
+            Some(bvh) => bvh.traverse_until(ray, |index| {
+                self.registry.get_by_index(index).is_some_and(&mut blocks)
+            }),
+            None => self.registry.iter().any(&mut blocks),
+        };
+        SCRATCH.with(|pool| pool.borrow_mut().push(xs));
+        if blocked {
+            Colour::black()
+        } else {
+            transmission
+        }
+    }
+
+    // What a shape does to shadow rays crossing its surface
+    fn shadow_filter(&self, shape: &dyn Shape) -> ShadowFilter {
+        if !shape.visibility().cast_shadows {
+            return ShadowFilter::None;
+        }
+        let material = shape.material();
+        let filtered =
+            self.settings.transparent_shadows && self.settings.integrator == Integrator::Full;
+        if !filtered || material.transparency <= 0.0 {
+            return ShadowFilter::Opaque;
+        }
+        ShadowFilter::Tint(material.colour * material.transparency)
+    }
+
+    pub fn refracted_colour(&self, comps: &PreComputedData, bounces_remaining: u32) -> Colour {
+        self.refracted_colour_weighted(comps, bounces_remaining, 1.0)
+    }
+
+    // With dispersion each colour channel is traced along its own ray, bent
+    // by that channel's refractive index here and at every later refraction
+    fn refracted_colour_weighted(
+        &self,
+        comps: &PreComputedData,
+        bounces_remaining: u32,
+        throughput: Float,
+    ) -> Colour {
+        let transparency = comps.object.material().transparency;
+        if bounces_remaining == 0 || transparency == 0.0 {
+            return Colour::black();
+        }
+
+        let mut weight = transparency;
+        let mut throughput = throughput * transparency;
+        let depth = self.settings.max_bounces.saturating_sub(bounces_remaining);
+        if self.settings.roulette_depth.is_some_and(|min| depth >= min) {
+            let survival = transparency.min(MAX_ROULETTE_SURVIVAL);
+            if roulette_draw(comps.under_point, -comps.eyev) >= survival {
+                return Colour::black();
+            }
+            weight /= survival;
+            throughput /= survival;
+        }
+
+        let (d1, d2) = comps.dispersion;
+        let trace = |channel: Option<usize>| {
+            let spread = channel.map_or(0.0, |channel| channel as Float - 1.0);
+            let (n1, n2) = (comps.n1 + spread * d1, comps.n2 + spread * d2);
+            self.trace_refraction(comps, n1, n2, channel, bounces_remaining, throughput)
+        };
+        // A ray that was split already carries one channel on
+        let c = match comps.channel {
+            None if d1 != 0.0 || d2 != 0.0 => {
+                Colour::new(trace(Some(0)).r, trace(Some(1)).g, trace(Some(2)).b)
+            }
+            channel => trace(channel),
+        };
+
+        c * weight
+    }
 
-        let hit = hit(&xs);
-        match hit {
-            Some(hit) => hit.t < distance,
-            None => false,
+    // The light arriving along the ray refracted through the surface, going
+    // from refractive index n1 to n2. Black under total internal reflection.
+    fn trace_refraction(
+        &self,
+        comps: &PreComputedData,
+        n1: Float,
+        n2: Float,
+        channel: Option<usize>,
+        bounces_remaining: u32,
+        throughput: Float,
+    ) -> Colour {
+        let n_ratio = n1 / n2;
+        let cos_i = comps.eyev.dot(&comps.normalv);
+        let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return Colour::black();
         }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
+        let refract_ray = Ray::new(comps.under_point, direction)
+            .with_time(comps.time)
+            .with_eye(comps.eye)
+            .with_channel(channel);
+        self.with_intersections(&refract_ray, |xs| {
+            self.shade_intersections(
+                &refract_ray,
+                xs,
+                bounces_remaining - 1,
+                self.secondary_t_min(),
+                throughput,
+            )
+        })
     }
 
-    pub fn reflected_colour(&self, comps: &PreComputedData, bounces_remaining: i32) -> Colour {
-        if bounces_remaining <= 0 {
+    pub fn reflected_colour(&self, comps: &PreComputedData, bounces_remaining: u32) -> Colour {
+        self.reflected_colour_weighted(comps, bounces_remaining, 1.0)
+    }
+
+    fn reflected_colour_weighted(
+        &self,
+        comps: &PreComputedData,
+        bounces_remaining: u32,
+        throughput: Float,
+    ) -> Colour {
+        if bounces_remaining == 0 {
             return Colour::black();
         }
 
-        if comps.object.material().reflective == 0.0 {
+        let reflective = comps.object.material().reflective;
+        if reflective == 0.0 {
             return Colour::black();
         }
 
-        let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
-        let c = self.colour_at(&reflect_ray, bounces_remaining - 1);
+        let mut weight = reflective;
+        let mut throughput = throughput * reflective;
+        let depth = self.settings.max_bounces.saturating_sub(bounces_remaining);
+        let survival = if self.settings.roulette_depth.is_some_and(|min| depth >= min) {
+            reflective.min(MAX_ROULETTE_SURVIVAL)
+        } else if throughput < self.settings.roulette_threshold {
+            throughput / self.settings.roulette_threshold
+        } else {
+            1.0
+        };
+        if survival < 1.0 {
+            if roulette_draw(comps.over_point, comps.reflectv) >= survival {
+                return Colour::black();
+            }
+            weight /= survival;
+            throughput /= survival;
+        }
+
+        let reflect_ray = Ray::new(comps.over_point, comps.reflectv)
+            .with_time(comps.time)
+            .with_eye(comps.eye)
+            .with_channel(comps.channel);
+        self.stats.reflection_ray(depth);
+        let c = self.with_intersections(&reflect_ray, |xs| {
+            self.shade_intersections(
+                &reflect_ray,
+                xs,
+                bounces_remaining - 1,
+                self.secondary_t_min(),
+                throughput,
+            )
+        });
+
+        c * weight
+    }
+}
+
+// Hashes the reflection ray to a value in [0, 1), so renders stay repeatable
+fn roulette_draw(origin: Tuple, direction: Tuple) -> Float {
+    path_draw(origin, direction, 0, 0)
+}
 
-        c * comps.object.material().reflective
+// As roulette_draw, with seed and salt giving independent values for the same
+// ray
+fn path_draw(origin: Tuple, direction: Tuple, seed: u64, salt: u64) -> Float {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325 ^ salt;
+    h = (h ^ seed).wrapping_mul(0x0000_0100_0000_01b3);
+    h ^= h >> 29;
+    for v in [
+        origin.x,
+        origin.y,
+        origin.z,
+        direction.x,
+        direction.y,
+        direction.z,
+    ] {
+        h = (h ^ to_f64(v).to_bits()).wrapping_mul(0x0000_0100_0000_01b3);
+        h ^= h >> 29;
     }
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 32;
+    // Only as many bits as Float holds exactly, so the result stays below 1
+    let bits = Float::MANTISSA_DIGITS;
+    (h >> (64 - bits)) as Float / (1u64 << bits) as Float
+}
+
+// A direction in the hemisphere around normal, more likely the closer it is to
+// the normal, in proportion to the cosine between them. u1 and u2 are in [0, 1).
+fn cosine_sample(normal: Tuple, u1: Float, u2: Float) -> Tuple {
+    let helper = if normal.x.abs() > 0.9 {
+        Tuple::vector(0.0, 1.0, 0.0)
+    } else {
+        Tuple::vector(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(&normal).normalise();
+    let bitangent = normal.cross(&tangent);
+
+    let r = u1.sqrt();
+    let phi = 2.0 * crate::scalar::consts::PI * u2;
+    (tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - u1).sqrt())
+        .normalise()
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::scalar::TEST_EPSILON;
     use approx::assert_abs_diff_eq;
 
-    use crate::{colour::Colour, ray::Ray, tuple::Tuple};
+    use crate::{
+        colour::Colour, intersection::prepare_computations, ray::Ray, shape::Visibility,
+        tuple::Tuple,
+    };
 
     use super::*;
 
@@ -338,7 +1252,7 @@ mod tests {
         let world = World::new();
 
         assert_eq!(world.registry.len(), 0);
-        assert!(world.light.is_none());
+        assert!(world.lights.is_empty());
     }
 
     #[test]
@@ -346,8 +1260,8 @@ mod tests {
         let world = World::default_world();
 
         // Check light
-        assert!(world.light.is_some());
-        let light = world.light.unwrap();
+        assert_eq!(world.lights.len(), 1);
+        let light = &world.lights[0];
         assert_eq!(light.position, Tuple::point(-10.0, 10.0, -10.0));
         assert_eq!(light.intensity, Colour::new(1.0, 1.0, 1.0));
 
@@ -382,6 +1296,109 @@ mod tests {
         assert_eq!(xs[3].t, 6.0);
     }
 
+    #[test]
+    fn intersecting_into_a_buffer_replaces_its_contents() {
+        let w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let miss = Ray::new(Tuple::point(0.0, 5.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let mut xs = Vec::new();
+        w.intersect_world_into(&r, &mut xs);
+        assert_eq!(xs, w.intersect_world(&r));
+        let capacity = xs.capacity();
+
+        w.intersect_world_into(&miss, &mut xs);
+        assert!(xs.is_empty());
+        assert_eq!(xs.capacity(), capacity);
+    }
+
+    #[test]
+    fn first_hit_is_the_nearest_intersection_past_t_min() {
+        let w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let miss = Ray::new(Tuple::point(0.0, 5.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = w.intersect_world(&r);
+        assert_eq!(w.first_hit(&r, 0.0).as_ref(), hit(&xs));
+        assert_eq!(w.first_hit(&r, 4.2).map(|x| x.t), Some(4.5));
+        assert_eq!(w.first_hit(&r, 7.0), None);
+        assert_eq!(w.first_hit(&miss, 0.0), None);
+    }
+
+    #[test]
+    fn at_time_moves_a_copy_and_leaves_the_world_alone() {
+        use crate::animation::{Interpolation, ObjectAnimation, Track};
+
+        let mut world = World::default_world();
+        let first = world.registry.iter().next().unwrap().id();
+        world
+            .registry
+            .get_mut(first)
+            .unwrap()
+            .set_name(Some("ball".to_string()));
+        let mut animation = Animation::default();
+        animation.objects.insert(
+            "ball".to_string(),
+            ObjectAnimation {
+                translation: Some(
+                    Track::new(Interpolation::Linear).key(1.0, Tuple::vector(0.0, 0.0, 10.0)),
+                ),
+                ..ObjectAnimation::default()
+            },
+        );
+        world.animation = Some(animation);
+        world.build_bvh();
+
+        let frame = world.at_time(1.0);
+
+        let origin = Tuple::point(0.0, 0.0, 0.0);
+        assert_eq!(frame.time, 1.0);
+        assert!(frame.has_bvh());
+        assert_eq!(
+            frame.get_by_name("ball").unwrap().transform() * origin,
+            Tuple::point(0.0, 0.0, 10.0)
+        );
+        assert_eq!(
+            world.get_by_name("ball").unwrap().transform() * origin,
+            origin
+        );
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_abs_diff_eq!(
+            frame.first_hit(&ray, 0.0).unwrap().t,
+            4.5,
+            epsilon = TEST_EPSILON
+        );
+    }
+
+    #[test]
+    fn preview_shading_sees_past_hidden_shapes() {
+        let mut w = World::default_world();
+        w.settings.integrator = Integrator::Preview;
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = w.intersect_world(&r);
+        let expected = w.colour_from_intersections(&r, &xs, DEFAULT_MAX_BOUNCES);
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), expected);
+
+        edit_visibility(&mut w, 0, |v| v.camera_visible = false);
+
+        let xs = w.intersect_world(&r);
+        let expected = w.colour_from_intersections(&r, &xs, DEFAULT_MAX_BOUNCES);
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), expected);
+        assert_ne!(expected, w.background.colour_at(&r));
+    }
+
+    #[test]
+    fn shading_reuses_scratch_buffers() {
+        let w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let first = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+        let pooled = SCRATCH.with(|pool| pool.borrow().len());
+        assert!(pooled > 0);
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), first);
+        assert_eq!(SCRATCH.with(|pool| pool.borrow().len()), pooled);
+    }
+
     #[test]
     fn shading_an_intersection() {
         let w = World::default_world();
@@ -390,30 +1407,62 @@ mod tests {
         let i = crate::intersection::Intersection {
             t: 4.0,
             object_id: shape.id(),
+            uv: None,
+            part_id: None,
         };
 
         let comps = crate::intersection::prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let c = w.shade_hit(&comps, MAX_BOUNCES);
+        let c = w.shade_hit(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_abs_diff_eq!(c, Colour::new(0.38066, 0.47583, 0.2855), epsilon = 0.0001);
     }
 
+    #[test]
+    fn shading_follows_the_hit_shapes_transform() {
+        use crate::{
+            matrix::Matrix,
+            pattern::{striped::Striped, PatternType},
+        };
+
+        // Stripes are one unit wide in object space, so two units once scaled
+        let mut w = World::new();
+        w.lights = vec![Light::point_light(
+            Tuple::point(0.0, 0.0, -10.0),
+            Colour::white(),
+        )];
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
+        s.data.material.ambient = 1.0;
+        s.data.material.diffuse = 0.0;
+        s.data.material.specular = 0.0;
+        s.data.material.pattern = Some(PatternType::Striped(Striped::new(
+            Colour::white(),
+            Colour::black(),
+        )));
+        w.add_object(s);
+
+        let r = Ray::new(Tuple::point(1.5, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), Colour::white());
+    }
+
     #[test]
     fn shading_an_intersection_from_the_inside() {
         let mut w = World::default_world();
-        w.light = Some(Light::point_light(
+        w.lights = vec![Light::point_light(
             Tuple::point(0.0, 0.25, 0.0),
             Colour::new(1.0, 1.0, 1.0),
-        ));
+        )];
         let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
         let shape = w.registry.get_by_index(1).unwrap(); // second object in w
         let i = crate::intersection::Intersection {
             t: 0.5,
             object_id: shape.id(),
+            uv: None,
+            part_id: None,
         };
 
         let comps = crate::intersection::prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let c = w.shade_hit(&comps, MAX_BOUNCES);
+        let c = w.shade_hit(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_abs_diff_eq!(c, Colour::new(0.90498, 0.90498, 0.90498), epsilon = 0.0001);
     }
@@ -423,17 +1472,28 @@ mod tests {
         let w = World::default_world();
         let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
 
-        let c = w.colour_at(&r, MAX_BOUNCES);
+        let c = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
 
         assert_eq!(c, Colour::new(0.0, 0.0, 0.0));
     }
 
+    #[test]
+    fn color_when_ray_misses_uses_background() {
+        let mut w = World::default_world();
+        w.background = Background::gradient(Colour::white(), Colour::new(0.0, 0.0, 1.0));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        let c = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+
+        assert_eq!(c, Colour::new(0.0, 0.0, 1.0));
+    }
+
     #[test]
     fn color_when_ray_hits() {
         let w = World::default_world();
         let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
 
-        let c = w.colour_at(&r, MAX_BOUNCES);
+        let c = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
 
         assert_abs_diff_eq!(c, Colour::new(0.38066, 0.47583, 0.2855), epsilon = 0.0001);
     }
@@ -441,10 +1501,10 @@ mod tests {
     #[test]
     fn color_with_intersection_behind_ray() {
         let mut w = World::new();
-        w.light = Some(Light::point_light(
+        w.lights = vec![Light::point_light(
             Tuple::point(-10.0, 10.0, -10.0),
             Colour::new(1.0, 1.0, 1.0),
-        ));
+        )];
 
         // Create spheres with ambient = 1.0
         let mut s1 = Sphere::new();
@@ -465,7 +1525,7 @@ mod tests {
         w.add_object(s2);
 
         let r = Ray::new(Tuple::point(0.0, 0.0, 0.75), Tuple::vector(0.0, 0.0, -1.0));
-        let c = w.colour_at(&r, MAX_BOUNCES);
+        let c = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
 
         // The color should be the inner object's material color
         let inner_color = w.registry.get_by_index(1).unwrap().material().colour;
@@ -488,6 +1548,25 @@ mod tests {
         assert!(w.is_shadowed(p));
     }
 
+    #[test]
+    fn shadows_are_tested_against_every_light() {
+        let mut w = World::default_world();
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        w.lights.push(Light::point_light(
+            Tuple::point(10.0, 10.0, 10.0),
+            Colour::white(),
+        ));
+
+        // Only the first light is blocked
+        assert!(!w.is_shadowed(p));
+        let half = Colour::new(0.5, 0.5, 0.5);
+        assert_abs_diff_eq!(w.light_transmission(p), half, epsilon = TEST_EPSILON);
+
+        w.build_shadow_map(64);
+        assert_eq!(w.shadow_maps.len(), 2);
+        assert_abs_diff_eq!(w.light_transmission(p), half, epsilon = TEST_EPSILON);
+    }
+
     #[test]
     fn no_shadow_when_object_behind_light() {
         let w = World::default_world();
@@ -504,13 +1583,120 @@ mod tests {
         assert!(!w.is_shadowed(p));
     }
 
+    fn edit_materials(w: &mut World, edit: impl Fn(&mut crate::materials::Material)) {
+        let ids: Vec<u32> = w.registry.iter().map(|s| s.id()).collect();
+        for id in ids {
+            let shape = w.registry.get_mut(id).unwrap();
+            let mut material = shape.material().clone();
+            edit(&mut material);
+            shape.set_material(material);
+        }
+    }
+
+    #[test]
+    fn transparent_shadow_casters_tint_the_light() {
+        let mut w = World::default_world();
+        edit_materials(&mut w, |m| m.transparency = 0.5);
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        assert!(w.is_shadowed(p));
+
+        w.settings.transparent_shadows = true;
+
+        // Through both sides of each sphere; only the outer one is coloured
+        let expected = Colour::new(0.8 * 0.8, 1.0, 0.6 * 0.6) * 0.0625;
+        assert_abs_diff_eq!(w.light_transmission(p), expected, epsilon = TEST_EPSILON);
+        assert!(!w.is_shadowed(p));
+    }
+
+    #[test]
+    fn shapes_can_opt_out_of_casting_shadows() {
+        let mut w = World::default_world();
+        for index in 0..2 {
+            edit_visibility(&mut w, index, |v| v.cast_shadows = false);
+        }
+
+        assert!(!w.is_shadowed(Tuple::point(10.0, -10.0, 10.0)));
+    }
+
+    fn edit_visibility(w: &mut World, index: usize, edit: impl Fn(&mut Visibility)) {
+        let id = w.registry.get_by_index(index).unwrap().id();
+        let shape = w.registry.get_mut(id).unwrap();
+        let mut visibility = shape.visibility();
+        edit(&mut visibility);
+        shape.set_visibility(visibility);
+    }
+
+    #[test]
+    fn shape_flags_control_shadows() {
+        let mut w = World::default_world();
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        edit_visibility(&mut w, 0, |v| v.cast_shadows = false);
+        assert!(w.is_shadowed(p));
+
+        edit_visibility(&mut w, 1, |v| v.cast_shadows = false);
+        assert!(!w.is_shadowed(p));
+
+        // The inner sphere is inside the outer one, so normally unlit
+        let mut w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shadowed = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+        edit_visibility(&mut w, 1, |v| v.receive_shadows = false);
+        assert!(w.colour_at(&r, DEFAULT_MAX_BOUNCES).r > shadowed.r);
+    }
+
+    #[test]
+    fn camera_rays_pass_through_hidden_shapes() {
+        let mut w = World::default_world();
+        edit_visibility(&mut w, 0, |v| v.camera_visible = false);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let inner = w.registry.get_by_index(1).unwrap();
+        let comps = prepare_computations(&Intersection::new(4.5, inner), &r, &w.registry, None);
+        let expected = w.shade_hit(&comps.unwrap(), DEFAULT_MAX_BOUNCES);
+
+        assert_abs_diff_eq!(
+            w.colour_at(&r, DEFAULT_MAX_BOUNCES),
+            expected,
+            epsilon = TEST_EPSILON
+        );
+        // It still casts its shadow
+        assert!(w.is_shadowed(Tuple::point(10.0, -10.0, 10.0)));
+    }
+
+    #[test]
+    fn partly_transmitted_light_only_scales_diffuse_and_specular() {
+        let mut w = World::default_world();
+        edit_materials(&mut w, |m| m.transparency = 0.5);
+        let mut floor = Plane::new();
+        floor.set_transform(crate::matrix::Matrix::translation(0.0, -10.0, 0.0));
+        w.add_object(floor);
+        let r = Ray::new(Tuple::point(9.0, 0.0, 9.0), Tuple::vector(0.0, -1.0, 0.0));
+        let shade = |w: &World| w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+
+        let dark = shade(&w);
+        w.settings.transparent_shadows = true;
+        let filtered = shade(&w);
+        for index in 0..2 {
+            edit_visibility(&mut w, index, |v| v.cast_shadows = false);
+        }
+        let lit = shade(&w);
+        assert!(lit.r > dark.r);
+
+        let transmission = Colour::new(0.8 * 0.8, 1.0, 0.6 * 0.6) * 0.0625;
+        assert_abs_diff_eq!(
+            filtered,
+            dark + (lit - dark) * transmission,
+            epsilon = TEST_EPSILON
+        );
+    }
+
     #[test]
     fn shade_hit_is_given_an_intersection_in_shadow() {
         let mut w = World::new();
-        w.light = Some(Light::point_light(
+        w.lights = vec![Light::point_light(
             Tuple::point(0.0, 0.0, -10.0),
             Colour::new(1.0, 1.0, 1.0),
-        ));
+        )];
 
         let s1 = Sphere::new();
         w.add_object(s1);
@@ -523,10 +1709,12 @@ mod tests {
         let i = Intersection {
             t: 4.0,
             object_id: s2_id,
+            uv: None,
+            part_id: None,
         };
 
         let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let c = w.shade_hit(&comps, MAX_BOUNCES);
+        let c = w.shade_hit(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_eq!(c, Colour::new(0.1, 0.1, 0.1));
     }
@@ -543,9 +1731,9 @@ mod tests {
         mat.ambient = 1.0;
         shape.set_material(mat);
 
-        let i = Intersection::new(1.0, &*w.registry.get(shape_id).unwrap());
+        let i = Intersection::new(1.0, w.registry.get(shape_id).unwrap());
         let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let color = w.reflected_colour(&comps, MAX_BOUNCES);
+        let color = w.reflected_colour(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_eq!(color, Colour::new(0.0, 0.0, 0.0));
     }
@@ -565,16 +1753,16 @@ mod tests {
             Tuple::point(0.0, 0.0, -3.0),
             Tuple::vector(
                 0.0,
-                -std::f64::consts::SQRT_2 / 2.0,
-                std::f64::consts::SQRT_2 / 2.0,
+                -crate::scalar::consts::SQRT_2 / 2.0,
+                crate::scalar::consts::SQRT_2 / 2.0,
             ),
         );
         let i = Intersection::new(
-            std::f64::consts::SQRT_2,
-            &*w.registry.get(shape_id).unwrap(),
+            crate::scalar::consts::SQRT_2,
+            w.registry.get(shape_id).unwrap(),
         );
         let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let colour = w.reflected_colour(&comps, MAX_BOUNCES);
+        let colour = w.reflected_colour(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_abs_diff_eq!(colour.r, 0.19032, epsilon = 0.0001);
         assert_abs_diff_eq!(colour.g, 0.2379, epsilon = 0.0001);
@@ -596,29 +1784,61 @@ mod tests {
             Tuple::point(0.0, 0.0, -3.0),
             Tuple::vector(
                 0.0,
-                -std::f64::consts::SQRT_2 / 2.0,
-                std::f64::consts::SQRT_2 / 2.0,
+                -crate::scalar::consts::SQRT_2 / 2.0,
+                crate::scalar::consts::SQRT_2 / 2.0,
             ),
         );
         let i = Intersection::new(
-            std::f64::consts::SQRT_2,
-            &*w.registry.get(shape_id).unwrap(),
+            crate::scalar::consts::SQRT_2,
+            w.registry.get(shape_id).unwrap(),
         );
         let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
-        let colour = w.shade_hit(&comps, MAX_BOUNCES);
+        let colour = w.shade_hit(&comps, DEFAULT_MAX_BOUNCES);
 
         assert_abs_diff_eq!(colour.r, 0.87677, epsilon = 0.0001);
         assert_abs_diff_eq!(colour.g, 0.92436, epsilon = 0.0001);
         assert_abs_diff_eq!(colour.b, 0.82918, epsilon = 0.0001);
     }
 
+    #[test]
+    fn preview_integrator_leaves_out_reflections() {
+        let mut w = World::default_world();
+        w.settings.integrator = Integrator::Preview;
+        let mut shape = Plane::new();
+        let mut mat = shape.material().clone();
+        mat.reflective = 0.5;
+        shape.set_material(mat);
+        shape.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        w.add_object(shape);
+
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(
+                0.0,
+                -crate::scalar::consts::SQRT_2 / 2.0,
+                crate::scalar::consts::SQRT_2 / 2.0,
+            ),
+        );
+        w.reset_rays_traced();
+        let colour = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+
+        // shade_hit_with_reflective_material less the reflected colour, from
+        // one camera ray and one shadow ray
+        assert_abs_diff_eq!(
+            colour,
+            Colour::new(0.68645, 0.68646, 0.68644),
+            epsilon = 0.0001
+        );
+        assert_eq!(w.rays_traced(), 2);
+    }
+
     #[test]
     fn color_at_with_mutually_reflective_surfaces() {
         let mut w = World::new();
-        w.light = Some(Light::point_light(
+        w.lights = vec![Light::point_light(
             Tuple::point(0.0, 0.0, 0.0),
             Colour::new(1.0, 1.0, 1.0),
-        ));
+        )];
 
         let mut lower = Plane::new();
         let mut lower_mat = lower.material().clone();
@@ -637,7 +1857,7 @@ mod tests {
         let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
 
         // This should terminate successfully without infinite recursion
-        let _colour = w.colour_at(&r, MAX_BOUNCES);
+        let _colour = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
     }
 
     #[test]
@@ -655,13 +1875,13 @@ mod tests {
             Tuple::point(0.0, 0.0, -3.0),
             Tuple::vector(
                 0.0,
-                -std::f64::consts::SQRT_2 / 2.0,
-                std::f64::consts::SQRT_2 / 2.0,
+                -crate::scalar::consts::SQRT_2 / 2.0,
+                crate::scalar::consts::SQRT_2 / 2.0,
             ),
         );
         let i = Intersection::new(
-            std::f64::consts::SQRT_2,
-            &*w.registry.get(shape_id).unwrap(),
+            crate::scalar::consts::SQRT_2,
+            w.registry.get(shape_id).unwrap(),
         );
         let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
 
@@ -669,4 +1889,585 @@ mod tests {
 
         assert_eq!(color, Colour::black());
     }
+
+    #[test]
+    fn intersect_world_with_bvh_matches_linear_search() {
+        let mut w = World::new();
+        for i in 0..50 {
+            let mut s = Sphere::new();
+            s.set_transform(crate::matrix::Matrix::translation(
+                i as Float * 3.0,
+                0.0,
+                0.0,
+            ));
+            w.add_object(s);
+        }
+        w.add_object(Plane::new());
+
+        let rays = [
+            Ray::new(Tuple::point(30.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0)),
+            Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0)),
+            Ray::new(
+                Tuple::point(0.0, 5.0, -5.0),
+                Tuple::vector(0.0, -1.0, 1.0).normalise(),
+            ),
+        ];
+        let expected: Vec<Vec<Float>> = rays
+            .iter()
+            .map(|r| w.intersect_world(r).iter().map(|i| i.t).collect())
+            .collect();
+
+        w.build_bvh();
+        assert!(w.has_bvh());
+        for (r, expected) in rays.iter().zip(expected) {
+            let ts: Vec<Float> = w.intersect_world(r).iter().map(|i| i.t).collect();
+            assert_eq!(ts, expected);
+        }
+    }
+
+    #[test]
+    fn unit_scale_converts_distance_tolerances() {
+        let mut w = World::new();
+        assert_eq!(w.secondary_t_min(), DEFAULT_SECONDARY_T_MIN);
+        assert_eq!(w.shadow_bias(), SHADOW_BIAS);
+
+        // Centimetres: the same physical distance is 100 times as many units
+        w.set_unit_scale(0.01);
+
+        assert_abs_diff_eq!(w.secondary_t_min(), DEFAULT_SECONDARY_T_MIN * 100.0);
+        assert_abs_diff_eq!(w.shadow_bias(), SHADOW_BIAS * 100.0);
+        assert_abs_diff_eq!(w.metres_to_units(2.0), 200.0);
+
+        // Settings given explicitly are in scene units already
+        w.settings.secondary_t_min = Some(0.5);
+        w.settings.shadow_bias = Some(0.25);
+        w.set_unit_scale(0.001);
+        assert_eq!(w.secondary_t_min(), 0.5);
+        assert_eq!(w.shadow_bias(), 0.25);
+    }
+
+    #[test]
+    fn adding_an_object_discards_the_bvh() {
+        let mut w = World::default_world();
+        w.build_bvh();
+
+        w.add_object(Sphere::new());
+
+        assert!(!w.has_bvh());
+    }
+
+    #[test]
+    fn moving_or_removing_shapes_makes_the_bvh_stale() {
+        use crate::matrix::Matrix;
+
+        let mut w = World::default_world();
+        let outer = w.registry.get_by_index(0).unwrap().id();
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        w.build_bvh();
+        let material = w.registry.get(outer).unwrap().material().clone();
+        w.registry.set_material(outer, material);
+        assert!(w.has_bvh());
+
+        w.registry
+            .set_transform(outer, Matrix::translation(0.0, 10.0, 0.0));
+        assert!(!w.has_bvh());
+        // The inner sphere is still hit, where the stale BVH would have said
+        assert_eq!(w.intersect_world(&ray)[0].t, 4.5);
+
+        w.build_bvh();
+        w.registry.remove(outer);
+        assert!(!w.has_bvh());
+        assert_eq!(w.intersect_world(&ray).len(), 2);
+    }
+
+    #[test]
+    fn light_links_pick_the_lights_that_reach_a_shape() {
+        let mut w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let lit = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+        let id = w.registry.get_by_index(0).unwrap().id();
+        let links = |only: Option<&str>, exclude: &[&str]| crate::shape::LightLinks {
+            only: only.map(|group| vec![group.to_string()]),
+            exclude: exclude.iter().map(|group| group.to_string()).collect(),
+        };
+
+        // Ungrouped lights reach everything that doesn't ask for a group
+        w.registry
+            .get_mut(id)
+            .unwrap()
+            .set_light_links(links(None, &["key"]));
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), lit);
+        w.registry
+            .get_mut(id)
+            .unwrap()
+            .set_light_links(links(Some("key"), &[]));
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), Colour::black());
+
+        w.lights = vec![w.lights[0].clone().with_group("key")];
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), lit);
+        w.registry
+            .get_mut(id)
+            .unwrap()
+            .set_light_links(links(None, &["key"]));
+        assert_eq!(w.colour_at(&r, DEFAULT_MAX_BOUNCES), Colour::black());
+    }
+
+    #[test]
+    fn shadows_can_be_turned_off() {
+        let mut w = World::default_world();
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        assert!(w.is_shadowed(p));
+
+        w.settings.shadows = false;
+        assert!(!w.is_shadowed(p));
+        assert_eq!(w.light_transmission(p), Colour::white());
+    }
+
+    #[test]
+    fn refracted_colour_of_an_opaque_surface_is_black() {
+        let w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = w.intersect_world(&r);
+        let comps = prepare_computations(&xs[0], &r, &w.registry, Some(&xs)).unwrap();
+
+        assert_eq!(w.refracted_colour(&comps, 5), Colour::black());
+    }
+
+    #[test]
+    fn refracted_colour_under_total_internal_reflection_is_black() {
+        let mut w = World::default_world();
+        edit_materials(&mut w, |m| {
+            m.transparency = 1.0;
+            m.refractive_index = 1.5;
+        });
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let r = Ray::new(Tuple::point(0.0, 0.0, half), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = w.intersect_world(&r);
+        // Leaving the outer sphere from inside, where xs holds the inner one's
+        // hits too
+        let exit = xs.iter().rposition(|x| x.t > 0.0).unwrap();
+        let comps = prepare_computations(&xs[exit], &r, &w.registry, Some(&xs)).unwrap();
+
+        assert_eq!(w.refracted_colour(&comps, 5), Colour::black());
+        assert_eq!(w.refracted_colour(&comps, 0), Colour::black());
+    }
+
+    // A transparent floor above a red ball, seen at 45 degrees
+    fn glass_floor_world(reflective: Float) -> (World, Ray) {
+        let mut w = World::default_world();
+        let mut floor = Plane::new();
+        floor.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        floor.data.material.reflective = reflective;
+        floor.data.material.transparency = 0.5;
+        floor.data.material.refractive_index = 1.5;
+        w.add_object(floor);
+        let mut ball = Sphere::new();
+        ball.set_transform(crate::matrix::Matrix::translation(0.0, -3.5, -0.5));
+        ball.data.material.colour = Colour::new(1.0, 0.0, 0.0);
+        ball.data.material.ambient = 0.5;
+        w.add_object(ball);
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -half, half),
+        );
+        (w, r)
+    }
+
+    #[test]
+    fn shading_sees_through_transparent_surfaces() {
+        let (w, r) = glass_floor_world(0.0);
+        let expected = Colour::new(0.93642, 0.68642, 0.68642);
+        assert_abs_diff_eq!(w.colour_at(&r, 5), expected, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn shading_weights_reflection_and_refraction_by_schlick() {
+        let (w, r) = glass_floor_world(0.5);
+        let expected = Colour::new(0.93391, 0.69643, 0.69243);
+        assert_abs_diff_eq!(w.colour_at(&r, 5), expected, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn dispersion_refracts_each_channel_by_its_own_index() {
+        let mut w = World::new();
+        w.background = crate::background::Background::gradient(Colour::black(), Colour::white());
+        let mut glass = Sphere::glass();
+        glass.data.material.reflective = 0.0;
+        w.add_object(glass);
+        let r = Ray::new(Tuple::point(0.0, -0.6, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let with_index = |w: &mut World, index: Float, dispersion: Float| {
+            edit_materials(w, |m| {
+                m.refractive_index = index;
+                m.dispersion = dispersion;
+            });
+            w.colour_at(&r, 5)
+        };
+
+        let split = with_index(&mut w, 1.5, 0.05);
+        assert_abs_diff_eq!(
+            split.r,
+            with_index(&mut w, 1.45, 0.0).r,
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            split.g,
+            with_index(&mut w, 1.5, 0.0).g,
+            epsilon = TEST_EPSILON
+        );
+        assert_abs_diff_eq!(
+            split.b,
+            with_index(&mut w, 1.55, 0.0).b,
+            epsilon = TEST_EPSILON
+        );
+        // Blue bends most, so leaves the sphere heading furthest up the gradient
+        assert!(split.b > split.r);
+    }
+
+    #[test]
+    fn dispersed_rays_are_split_only_once() {
+        let mut w = World::new();
+        let mut glass = Sphere::glass();
+        glass.data.material.reflective = 0.0;
+        glass.data.material.dispersion = 0.05;
+        w.add_object(glass);
+        let r = Ray::new(Tuple::point(0.0, 0.1, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        w.colour_at(&r, 5);
+
+        // The camera ray, three rays into the sphere and one out of it each
+        assert_eq!(w.rays_traced(), 7);
+    }
+
+    #[test]
+    fn max_bounces_limits_reflections() {
+        let mut w = World::default_world();
+        let mut mirror = Plane::new();
+        mirror.data.material.reflective = 0.5;
+        mirror.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        w.add_object(mirror);
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
+        );
+
+        let reflected = w.colour_at(&r, w.settings.max_bounces);
+        w.settings.max_bounces = 0;
+        let flat = w.colour_at(&r, w.settings.max_bounces);
+        assert!(reflected.r > flat.r);
+    }
+
+    #[test]
+    fn shadow_rays_ignore_hits_closer_than_t_min() {
+        let mut w = World::default_world();
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        assert!(w.is_shadowed(p));
+
+        // The spheres are roughly 17 units along the shadow ray
+        w.settings.secondary_t_min = Some(20.0);
+
+        assert!(!w.is_shadowed(p));
+    }
+
+    #[test]
+    fn shadow_transmission_only_counts_hits_before_the_distance() {
+        let w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // The outer sphere is hit at t = 4 and 6
+        assert_eq!(w.shadow_transmission(&r, 3.9), Colour::white());
+        assert_eq!(w.shadow_transmission(&r, 4.1), Colour::black());
+    }
+
+    #[test]
+    fn shadow_rays_agree_with_and_without_a_bvh() {
+        let mut w = World::default_world();
+        let mut group = crate::shape::group::Group::new();
+        let mut s = Sphere::new();
+        s.set_transform(crate::matrix::Matrix::translation(5.0, 0.0, 5.0));
+        group.add_child(Box::new(s));
+        w.add_object(group);
+        let points = [
+            Tuple::point(10.0, -10.0, 10.0),
+            Tuple::point(-2.0, 2.0, -2.0),
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::point(6.2, -0.8, 6.3),
+        ];
+        let linear: Vec<bool> = points.iter().map(|&p| w.is_shadowed(p)).collect();
+
+        w.build_bvh();
+
+        let with_bvh: Vec<bool> = points.iter().map(|&p| w.is_shadowed(p)).collect();
+        assert_eq!(linear, with_bvh);
+        assert_eq!(linear, [true, false, false, true]);
+    }
+
+    #[test]
+    fn reflection_rays_ignore_hits_closer_than_t_min() {
+        let mut w = World::default_world();
+        let mut floor = Plane::new();
+        let mut m = crate::materials::Material::new();
+        m.reflective = 0.5;
+        floor.set_material(m);
+        floor.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        w.add_object(floor);
+
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -half, half),
+        );
+        let xs = w.intersect_world(&r);
+        let comps = prepare_computations(&xs[0], &r, &w.registry, Some(&xs)).unwrap();
+        assert!(w.reflected_colour(&comps, DEFAULT_MAX_BOUNCES).r > 0.0);
+
+        // Pushing t_min past the spheres leaves only the black background
+        w.settings.secondary_t_min = Some(100.0);
+        assert_eq!(
+            w.reflected_colour(&comps, DEFAULT_MAX_BOUNCES),
+            Colour::black()
+        );
+    }
+
+    #[test]
+    fn flickering_light_is_evaluated_at_world_time() {
+        use crate::light::Flicker;
+
+        let mut w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let steady = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+
+        let flicker = Flicker::new(0.5, 1.0, 0);
+        w.lights = vec![w.lights[0].clone().with_flicker(flicker)];
+        w.time = 0.25;
+        let flickering = w.colour_at(&r, DEFAULT_MAX_BOUNCES);
+
+        // Every lighting term scales with the light's intensity
+        let factor = flicker.factor_at(0.25);
+        assert_ne!(factor, 1.0);
+        assert_abs_diff_eq!(flickering, steady * factor, epsilon = 0.0001);
+    }
+
+    #[test]
+    fn deep_reflections_play_roulette_by_reflectivity() {
+        let mut w = World::default_world();
+        let mut mirror = Plane::new();
+        mirror.data.material.reflective = 0.5;
+        mirror.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        let mirror_id = w.add_object(mirror);
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let hits = |w: &World, x: Float| {
+            let r = Ray::new(Tuple::point(x, 0.0, -3.0), Tuple::vector(0.0, -half, half));
+            let i = Intersection::new(
+                crate::scalar::consts::SQRT_2,
+                w.registry.get(mirror_id).unwrap(),
+            );
+            let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
+            w.reflected_colour(&comps, w.settings.max_bounces)
+        };
+
+        // The only reflection is shallower than roulette_depth, so untouched
+        w.settings.roulette_depth = Some(1);
+        let exact = hits(&w, 0.0);
+        assert!(exact.r > 0.0);
+
+        // Each reflection survives half the time and counts double
+        w.settings.roulette_depth = Some(0);
+        let outcomes: Vec<Colour> = (0..40).map(|i| hits(&w, i as Float * 1e-6)).collect();
+        let survivors = outcomes.iter().filter(|c| c.r > 0.0).count();
+        assert!((10..=30).contains(&survivors), "{}", survivors);
+        for c in outcomes.iter().filter(|c| c.r > 0.0) {
+            assert_abs_diff_eq!(*c, exact * 2.0, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn dim_reflections_are_ended_or_weighted_up_by_roulette() {
+        let mut w = World::default_world();
+        let mut shape = Plane::new();
+        shape.data.material.reflective = 0.02;
+        shape.set_transform(crate::matrix::Matrix::translation(0.0, -1.0, 0.0));
+        let shape_id = w.add_object(shape);
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let hits = |w: &World, x: Float| {
+            let r = Ray::new(Tuple::point(x, 0.0, -3.0), Tuple::vector(0.0, -half, half));
+            let i = Intersection::new(
+                crate::scalar::consts::SQRT_2,
+                w.registry.get(shape_id).unwrap(),
+            );
+            let comps = prepare_computations(&i, &r, &w.registry, None).unwrap();
+            w.reflected_colour(&comps, DEFAULT_MAX_BOUNCES)
+        };
+
+        w.settings.roulette_threshold = 0.0;
+        let exact = hits(&w, 0.0);
+        assert!(exact.r > 0.0);
+
+        // Survives with probability 0.02 / 0.05, so survivors are scaled by 2.5
+        w.settings.roulette_threshold = 0.05;
+        let outcomes: Vec<Colour> = (0..100).map(|i| hits(&w, i as Float * 1e-6)).collect();
+        let survivors = outcomes.iter().filter(|c| c.r > 0.0).count();
+        assert!((25..=55).contains(&survivors), "{}", survivors);
+        for c in outcomes.iter().filter(|c| c.r > 0.0) {
+            assert_abs_diff_eq!(*c, exact * 2.5, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn deep_refractions_play_roulette_by_transparency() {
+        // Two clear panes above a red ball, so the second refraction is one
+        // bounce deep
+        let mut w = World::default_world();
+        for y in [-1.0, -2.0] {
+            let mut pane = Plane::new();
+            pane.set_transform(crate::matrix::Matrix::translation(0.0, y, 0.0));
+            pane.data.material.transparency = 0.5;
+            pane.data.material.refractive_index = 1.0;
+            w.add_object(pane);
+        }
+        let mut ball = Sphere::new();
+        ball.set_transform(crate::matrix::Matrix::translation(0.0, -3.5, -0.5));
+        ball.data.material.colour = Colour::new(1.0, 0.0, 0.0);
+        ball.data.material.ambient = 0.5;
+        w.add_object(ball);
+        let half = crate::scalar::consts::FRAC_1_SQRT_2;
+        let through_top_pane = |w: &World, x: Float, bounces: u32| {
+            let r = Ray::new(Tuple::point(x, 0.0, -3.0), Tuple::vector(0.0, -half, half));
+            let xs = w.intersect_world(&r);
+            let first = xs.iter().position(|x| x.t > 0.0).unwrap();
+            let comps = prepare_computations(&xs[first], &r, &w.registry, Some(&xs)).unwrap();
+            w.refracted_colour(&comps, bounces)
+        };
+
+        let max = w.settings.max_bounces;
+        let exact = through_top_pane(&w, 0.0, max);
+        // Only the lower pane's surface, with nothing seen through it
+        let ended = through_top_pane(&w, 0.0, 1);
+        assert!(exact.r > ended.r);
+
+        // The top pane's refraction is untouched; the lower pane's survives
+        // half the time and counts double
+        w.settings.roulette_depth = Some(1);
+        let outcomes: Vec<Colour> = (0..40)
+            .map(|i| through_top_pane(&w, i as Float * 1e-6, max))
+            .collect();
+        let survivors = outcomes.iter().filter(|c| c.r > ended.r + 1e-4).count();
+        assert!((10..=30).contains(&survivors), "{}", survivors);
+        let survived = ended + (exact - ended) * 2.0;
+        for c in outcomes {
+            if c.r > ended.r + 1e-4 {
+                assert_abs_diff_eq!(c, survived, epsilon = 0.0001);
+            } else {
+                assert_abs_diff_eq!(c, ended, epsilon = 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn path_tracing_lets_emissive_shapes_light_the_scene() {
+        let mut w = World::new();
+        w.add_object(Plane::new());
+        let mut lamp = Sphere::new();
+        lamp.set_transform(crate::matrix::Matrix::translation(0.0, 2.0, 0.0));
+        lamp.data.material.emissive = Colour::new(4.0, 4.0, 4.0);
+        w.add_object(lamp);
+
+        // Looking down at the floor beside the lamp
+        let floor_colour = |w: &World, i: usize| {
+            let origin = Tuple::point(2.0 + i as Float * 1e-4, 5.0, 0.0);
+            w.colour_at(&Ray::new(origin, Tuple::vector(0.0, -1.0, 0.0)), 5)
+        };
+        assert_eq!(floor_colour(&w, 0), Colour::black());
+
+        w.settings.integrator = Integrator::Path;
+        let lit = (0..200).filter(|&i| floor_colour(&w, i).r > 0.0).count();
+        assert!(lit > 0 && lit < 200, "{}", lit);
+
+        // The lamp itself glows under either integrator
+        let at_lamp = Ray::new(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        assert_eq!(w.colour_at(&at_lamp, 0), Colour::new(4.0, 4.0, 4.0));
+        w.settings.integrator = Integrator::Full;
+        assert_eq!(w.colour_at(&at_lamp, 0), Colour::new(4.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn every_light_adds_to_full_shading() {
+        let mut w = World::default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let one_light = w.colour_at(&r, 0);
+
+        w.lights.push(w.lights[0].clone());
+
+        assert_abs_diff_eq!(w.colour_at(&r, 0), one_light * 2.0, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn path_tracing_samples_one_light_and_weights_it() {
+        let mut w = World::default_world();
+        w.settings.integrator = Integrator::Path;
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let one_light = w.colour_at_path(&r, 0);
+
+        // Either copy is picked half the time, so counts double
+        w.lights.push(w.lights[0].clone());
+        assert_abs_diff_eq!(
+            w.colour_at_path(&r, 0),
+            one_light * 2.0,
+            epsilon = TEST_EPSILON
+        );
+
+        // A dark light is never picked
+        w.lights[0].intensity = Colour::black();
+        assert_abs_diff_eq!(w.colour_at_path(&r, 0), one_light, epsilon = TEST_EPSILON);
+    }
+
+    #[test]
+    fn path_samples_along_the_same_ray_differ_by_seed_and_frame() {
+        let mut w = World::default_world();
+        w.settings.integrator = Integrator::Path;
+        // So that bounces pick up different light in different directions
+        w.background = Background::gradient(Colour::black(), Colour::white());
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let samples = |w: &World| {
+            (0..8)
+                .map(|seed| w.colour_at_path(&r.with_seed(seed), 5))
+                .collect::<Vec<_>>()
+        };
+
+        let first = samples(&w);
+        assert_eq!(first, samples(&w));
+        assert!(first.iter().any(|colour| *colour != first[0]));
+        w.time = 1.0;
+        assert_ne!(first, samples(&w));
+    }
+
+    #[test]
+    fn cosine_samples_stay_above_the_surface() {
+        let normal = Tuple::vector(0.0, 0.6, 0.8);
+        let mut mean_cos = 0.0;
+        for i in 0..1000 {
+            let origin = Tuple::point(i as Float, 0.0, 0.0);
+            let d = cosine_sample(
+                normal,
+                path_draw(origin, normal, 0, 1),
+                path_draw(origin, normal, 0, 2),
+            );
+            assert_abs_diff_eq!(d.magnitude(), 1.0, epsilon = TEST_EPSILON);
+            assert!(d.dot(&normal) >= 0.0);
+            mean_cos += d.dot(&normal) / 1000.0;
+        }
+        // The average cosine of a cosine-weighted hemisphere is 2/3
+        assert_abs_diff_eq!(mean_cos, 2.0 / 3.0, epsilon = 0.03);
+    }
+
+    #[test]
+    fn roulette_draws_are_spread_evenly() {
+        let direction = Tuple::vector(0.0, 1.0, 0.0);
+        let below = (0..10000)
+            .map(|i| Tuple::point(i as Float * 0.013, 0.0, -(i as Float) * 0.007))
+            .filter(|&origin| roulette_draw(origin, direction) < 0.4)
+            .count();
+        assert_abs_diff_eq!(below as Float / 10000.0, 0.4, epsilon = 0.02);
+    }
 }